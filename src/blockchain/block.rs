@@ -23,10 +23,16 @@ use std::fmt;
 use blockchain::header::BlockHeader;
 use blockchain::body::BlockBody;
 use blockchain::transactions::Transaction;
+#[cfg(test)]
+use blockchain::transactions::TxPayload;
+use blockchain::transactions::TxId;
 use blockchain::transactions::TxIndex;
 use blockchain::errors::VerificationError;
 use blockchain::errors::VerificationErrorReason::InvalidContentHash;
+use blockchain::errors::BinFormatError;
+use blockchain::errors::BinFormatErrorReason;
 use blockchain::traits::Hashable;
+use blockchain::traits::BinFormat;
 
 /// `BlockId` is equivalent to the sha3 hash of the block header
 
@@ -48,13 +54,22 @@ pub struct BlockId(pub [u8; 32]);
 ///  * `IdCollision`: The supplied block id collides
 ///         with another block id. This is used as
 ///         as safeguard for hash collisions.
-
+///  * `OrphanedBlock`: The block has no valid predecessor
+///         in the storage.
+///  * `StorageFailure`: The storage backend failed to
+///         save the block. Wraps a description of the
+///         underlying problem.
+///  * `InvalidTransaction`: A transaction of the block
+///         failed verification. Wraps the id of the
+///         transaction and the verification error.
 
 #[derive(Debug)]
 pub enum BlockErrorReason{
     UnknownBlockId(BlockId),
     IdCollision(BlockId),
-    OrphanedBlock(BlockId)
+    OrphanedBlock(BlockId),
+    StorageFailure(String),
+    InvalidTransaction(TxId, VerificationError)
 }
 
 impl fmt::Display for BlockErrorReason {
@@ -66,6 +81,10 @@ impl fmt::Display for BlockErrorReason {
                 write!(f, "BlockId collided: {:?}.", block_id),
             BlockErrorReason::OrphanedBlock(ref block_id) =>
                 write!(f, "Block with id {:?} would be an orphan", block_id),
+            BlockErrorReason::StorageFailure(ref description) =>
+                write!(f, "Storage failure: {}", description),
+            BlockErrorReason::InvalidTransaction(ref tx_id, ref err) =>
+                write!(f, "Transaction {:?} is invalid: {}", tx_id, err.reason),
         }
     }
 }
@@ -181,14 +200,31 @@ impl Block{
         self.body.get_transaction(index as usize)
     }
 
+    /// Gets the number of transactions in the block
+
+    pub fn get_transaction_count(&self) -> usize {
+        self.body.transactions.len()
+    }
+
     /// Verifies the internal consistency of the block.
     /// This includes:
     /// * Verification of issuer signature
     /// * Verification of merkle hash tree
+    /// * Verification of transaction signatures
 
-    fn verify_internal(&self) -> Result<(), VerificationError> {
+    pub fn verify_internal(&self) -> Result<(), VerificationError> {
 
         self.header.verify_internal()?;
+        self.verify_content_hash()?;
+        self.verify_tx_signatures()?;
+        Ok(())
+
+    }
+
+    /// Verifies that the content hash of the header
+    /// matches the merkle root of the transactions
+
+    pub fn verify_content_hash(&self) -> Result<(), VerificationError> {
 
         if self.header.content_hash != self.body.merkle_root_hash(){
             let err = VerificationError::new(InvalidContentHash);
@@ -198,6 +234,17 @@ impl Block{
 
     }
 
+    /// Verifies the signatures of all transactions
+
+    pub fn verify_tx_signatures(&self) -> Result<(), VerificationError> {
+
+        for transaction in &self.body.transactions{
+            transaction.verify_signatures()?;
+        }
+        Ok(())
+
+    }
+
     /// Verifies that this block is the successor
     /// of the supplied block
     /// * `prev_block`: The preceding block
@@ -220,6 +267,48 @@ impl Block{
 
 }
 
+impl BinFormat<Block> for Block{
+
+    // The byte format of a block is the byte format of
+    // its header followed by the byte format of its body:
+
+    //    field            length
+    //  .------------------------.
+    //  | header          | 178  |
+    //  |------------------------|
+    //  | body            | n    |
+    //  '------------------------'
+
+    /// Returns the complete Block as an u8 vector
+
+    fn as_bytes(&self) -> Vec<u8>{
+        [self.header.as_bytes(), self.body.as_bytes()].concat()
+    }
+
+    /// Creates a new Block from a byte vector.
+    ///
+    /// * `bytes`: A byte vector
+
+    fn from_bytes(bytes: Vec<u8>) -> Result<Block, BinFormatError>{
+
+        if bytes.len() < 178{
+            let reason = BinFormatErrorReason::InvalidDataSize;
+            return Err(BinFormatError::new(reason));
+        }
+
+        let body_bytes = bytes[178..].to_vec();
+        let mut header_bytes = bytes;
+        header_bytes.truncate(178);
+
+        let header = BlockHeader::from_bytes(header_bytes)?;
+        let body = BlockBody::from_bytes(body_bytes)?;
+
+        Ok(Block{header: header, body: body})
+
+    }
+
+}
+
 #[test]
 fn test_verify_internal(){
 
@@ -240,11 +329,10 @@ fn test_verify_internal(){
 
     // First subtest
     // -------------
-    // Create a block with a dummy transaction. Dummy transactions have
-    // an all zero hash, which means the merkle tree root will be
-    // sha3_256(0....0 + 0x80...0)
+    // Create a block with a dummy transaction. Dummy transactions
+    // don't have any signers, so they pass the signature check
 
-    let mut block = Block::new(public_key, None, 0, vec![Transaction::Dummy]);
+    let mut block = Block::new(public_key, None, 0, vec![Transaction::dummy()]);
 
     block.sign(&secret_key);
 
@@ -259,7 +347,7 @@ fn test_verify_internal(){
     let wrong_content_hash = [4; 32];
     let mut block_header = BlockHeader::new(public_key, None, 0, 0, wrong_content_hash);
 
-    let body = BlockBody{transactions: vec![Transaction::Dummy]};
+    let body = BlockBody{transactions: vec![Transaction::dummy()]};
     let mut block = Block{header: block_header, body: body};
 
     block.sign(&secret_key);
@@ -275,6 +363,26 @@ fn test_verify_internal(){
     assert!(block.verify_internal().is_err(), "Wrong content hash, but block was
                                                classified as valid");
 
+    // Third subtest
+    // -------------
+    // create a correctly signed block containing
+    // a transaction that lacks its signature
+
+    let unsigned = Transaction::new(TxPayload::Dummy, vec![public_key]);
+    let mut block = Block::new(public_key, None, 0, vec![unsigned.clone()]);
+    block.sign(&secret_key);
+
+    assert!(block.verify_internal().is_err(), "Unsigned transaction, but block was
+                                               classified as valid");
+
+    let mut signed = unsigned;
+    signed.sign(&secret_key);
+    let mut block = Block::new(public_key, None, 0, vec![signed]);
+    block.sign(&secret_key);
+
+    assert!(block.verify_internal().is_ok(), "Signed transaction, but block was
+                                              classified as invalid");
+
 }
 
 #[test]
@@ -287,8 +395,8 @@ fn test_chain_link_validation(){
 
     // create two subsequent blocks
 
-    let first_block = Block::new(public_key, None, 0, vec![Transaction::Dummy]);
-    let second_block = Block::new(public_key, Some(&first_block), 1, vec![Transaction::Dummy]);
+    let first_block = Block::new(public_key, None, 0, vec![Transaction::dummy()]);
+    let second_block = Block::new(public_key, Some(&first_block), 1, vec![Transaction::dummy()]);
 
     assert!(second_block.verify_chain_link(&first_block).is_ok(), "Correctly linked blocks classified as invalid");
     assert!(first_block.verify_chain_link(&second_block).is_err(), "Incorrectly linked blocks classified as valid");
@@ -297,9 +405,61 @@ fn test_chain_link_validation(){
     // bock has a timestamp <= the timestamp of the
     // preceding one
 
-    let first_block = Block::new(public_key, None, 0, vec![Transaction::Dummy]);
-    let second_block = Block::new(public_key, Some(&first_block), 0, vec![Transaction::Dummy]);
+    let first_block = Block::new(public_key, None, 0, vec![Transaction::dummy()]);
+    let second_block = Block::new(public_key, Some(&first_block), 0, vec![Transaction::dummy()]);
 
     assert!(second_block.verify_chain_link(&first_block).is_err(), "Incorrectly timestamped block pair classified as valid");
 
 }
+
+#[test]
+fn test_to_bytes_from_bytes(){
+
+    let public_key = [0xE3, 0x70, 0x07, 0x9D, 0x71, 0xD0, 0x59, 0x6F,
+                      0xE6, 0x48, 0x71, 0x85, 0x2A, 0x8E, 0xF0, 0x0C,
+                      0x75, 0xDD, 0x13, 0x79, 0xFD, 0x87, 0xCF, 0xBB,
+                      0x5B, 0xB7, 0x72, 0xBE, 0x90, 0xC6, 0x1E, 0xD3];
+
+    let first_block = Block::new(public_key, None, 1, vec![]);
+    let block = Block::new(public_key, Some(&first_block), 2,
+                           vec![Transaction::dummy(), Transaction::dummy()]);
+
+    let as_bytes = block.as_bytes();
+    let rebuild = Block::from_bytes(as_bytes.clone());
+
+    if let Ok(rebuild) = rebuild{
+
+        assert_eq!(rebuild.get_id(), block.get_id());
+        assert_eq!(rebuild.get_transaction_count(), 2);
+        assert!(rebuild.body.merkle_root_hash() == block.header.content_hash);
+        assert_eq!(rebuild.as_bytes(), as_bytes);
+
+    }else{
+        assert!(false, "Valid binary representation of Block \
+                        could not be deserialized into an object");
+    }
+
+    // a block without a complete header is rejected
+
+    let result = Block::from_bytes(as_bytes[..100].to_vec());
+    match result{
+        Err(err) => match err.reason{
+            BinFormatErrorReason::InvalidDataSize => {},
+            _ => assert!(false, "Truncated header was rejected for the wrong reason")
+        },
+        Ok(_) => assert!(false, "Block with truncated header was accepted")
+    }
+
+    // a block with a truncated body is rejected
+
+    let len = as_bytes.len();
+    let result = Block::from_bytes(as_bytes[..len - 1].to_vec());
+    match result{
+        Err(err) => match err.reason{
+            BinFormatErrorReason::InvalidDataSize => {},
+            _ => assert!(false, "Truncated body was rejected for the wrong reason")
+        },
+        Ok(_) => assert!(false, "Block with truncated body was accepted")
+    }
+
+}
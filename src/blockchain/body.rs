@@ -19,7 +19,20 @@
 //
 
 use blockchain::utils::sha3_256;
+use blockchain::utils::u16_to_u8le;
+use blockchain::utils::u32_to_u8le;
+use blockchain::utils::BinReader;
 use blockchain::traits::Hashable;
+use blockchain::traits::BinFormat;
+use blockchain::transactions::Transaction;
+use blockchain::errors::BinFormatError;
+use blockchain::errors::BinFormatErrorReason;
+
+/// The maximum number of transactions in a block body. Since
+/// transactions are addressed by an u16 `TxIndex`, we can't
+/// store more transactions than an u16 can count.
+
+pub const MAX_TRANSACTIONS: usize = 0xFFFF;
 
 #[derive(Clone)]
 pub struct BlockBody<T: Clone>{
@@ -29,11 +42,14 @@ pub struct BlockBody<T: Clone>{
 
 impl<T: Hashable + Clone> BlockBody<T>{
     
-    /// Creates a new BlockBody
+    /// Creates a new BlockBody. Panics if the number of
+    /// transactions exceeds MAX_TRANSACTIONS.
     ///
     /// * `transactions`: A vec of transactions in the block body
 
     pub fn new(transactions: Vec<T>) -> BlockBody<T>{
+        assert!(transactions.len() <= MAX_TRANSACTIONS,
+                "Block body exceeds the maximum number of transactions");
         BlockBody{transactions: transactions}
     }
 
@@ -114,6 +130,81 @@ impl<T: Hashable + Clone> BlockBody<T>{
     
 }
 
+impl BinFormat<BlockBody<Transaction>> for BlockBody<Transaction>{
+
+    // This section implements (de)serialization methods for
+    // BlockBody. The current (version 0x0) byte format is:
+
+    //    field            length
+    //  .------------------------.
+    //  | version         | 2    |
+    //  |------------------------|
+    //  | tx_count        | 2    |
+    //  |------------------------|
+    //  | tx_len          | 4    |  <-.
+    //  |------------------------|    | repeated
+    //  | transaction     | n    |  <-' tx_count times
+    //  '------------------------'
+
+    /// Returns the BlockBody as an u8 vector
+
+    fn as_bytes(&self) -> Vec<u8>{
+
+        let version_u8le = u16_to_u8le(0x0);
+        let tx_count_u8le = u16_to_u8le(self.transactions.len() as u16);
+
+        let mut bytes = [&version_u8le[..], &tx_count_u8le[..]].concat();
+
+        for transaction in &self.transactions{
+            let tx_bytes = transaction.as_bytes();
+            let tx_len_u8le = u32_to_u8le(tx_bytes.len() as u32);
+            bytes.extend_from_slice(&tx_len_u8le);
+            bytes.extend(tx_bytes);
+        }
+
+        bytes
+
+    }
+
+    /// Creates a new BlockBody from a byte vector.
+    ///
+    /// * `bytes`: A byte vector
+
+    fn from_bytes(bytes: Vec<u8>)
+                    -> Result<BlockBody<Transaction>, BinFormatError>{
+
+        let mut reader = BinReader::new(&bytes);
+
+        if reader.read_u16()? != 0x0{
+            let reason = BinFormatErrorReason::UnsupportedVersion;
+            return Err(BinFormatError::new(reason));
+        }
+
+        let tx_count = reader.read_u16()?;
+
+        // every transaction needs at least its length field,
+        // so we can reject bogus counts before allocating
+
+        if bytes.len() - 4 < tx_count as usize * 4{
+            let reason = BinFormatErrorReason::InvalidDataSize;
+            return Err(BinFormatError::new(reason));
+        }
+
+        let mut transactions = Vec::with_capacity(tx_count as usize);
+
+        for _i in 0..tx_count{
+            let tx_len = reader.read_u32()? as usize;
+            let tx_bytes = reader.read_bytes(tx_len)?.to_vec();
+            transactions.push(Transaction::from_bytes(tx_bytes)?);
+        }
+
+        reader.finish()?;
+        Ok(BlockBody{transactions: transactions})
+
+    }
+
+}
+
 #[test]
 fn test_merkle_tree(){
 
@@ -167,3 +258,101 @@ fn test_merkle_tree(){
     assert!(root_hash == assumed);
 
 }
+
+#[test]
+fn test_to_bytes_from_bytes(){
+
+    let block_body = BlockBody::new(vec![Transaction::dummy(),
+                                         Transaction::dummy(),
+                                         Transaction::dummy()]);
+
+    let as_bytes = block_body.as_bytes();
+
+    // version, count and three length prefixed dummies
+
+    assert_eq!(as_bytes.len(), 2 + 2 + 3 * (4 + 8));
+    assert_eq!(&as_bytes[0..4], &[0x00, 0x00, 0x03, 0x00]);
+
+    let rebuild = BlockBody::from_bytes(as_bytes.clone());
+
+    if let Ok(rebuild) = rebuild{
+        assert_eq!(rebuild.transactions.len(), 3);
+        assert!(rebuild.merkle_root_hash() == block_body.merkle_root_hash());
+    }else{
+        assert!(false, "Valid binary representation of BlockBody \
+                        could not be deserialized into an object");
+    }
+
+    // an empty body is valid as well
+
+    let empty_body: BlockBody<Transaction> = BlockBody::new(vec![]);
+    let rebuild = BlockBody::from_bytes(empty_body.as_bytes());
+    assert!(rebuild.is_ok(), "Empty BlockBody could not be deserialized");
+
+    // unsupported version
+
+    let mut faulty = as_bytes.clone();
+    faulty[0] = 0x01;
+    match BlockBody::from_bytes(faulty){
+        Err(err) => match err.reason{
+            BinFormatErrorReason::UnsupportedVersion => {},
+            _ => assert!(false, "Unknown version was rejected for the wrong reason")
+        },
+        Ok(_) => assert!(false, "BlockBody with unknown version was accepted")
+    }
+
+    // transaction count exceeds the supplied data
+
+    let mut faulty = as_bytes.clone();
+    faulty[2] = 0xFF;
+    faulty[3] = 0xFF;
+    match BlockBody::from_bytes(faulty){
+        Err(err) => match err.reason{
+            BinFormatErrorReason::InvalidDataSize => {},
+            _ => assert!(false, "Bogus transaction count was rejected \
+                                 for the wrong reason")
+        },
+        Ok(_) => assert!(false, "BlockBody with bogus transaction count \
+                                 was accepted")
+    }
+
+    // transaction length exceeds the supplied data
+
+    let mut faulty = as_bytes.clone();
+    faulty[4] = 0xFF;
+    match BlockBody::from_bytes(faulty){
+        Err(err) => match err.reason{
+            BinFormatErrorReason::InvalidDataSize => {},
+            _ => assert!(false, "Bogus transaction length was rejected \
+                                 for the wrong reason")
+        },
+        Ok(_) => assert!(false, "BlockBody with bogus transaction length \
+                                 was accepted")
+    }
+
+    // trailing data
+
+    let mut faulty = as_bytes.clone();
+    faulty.push(0x00);
+    match BlockBody::from_bytes(faulty){
+        Err(err) => match err.reason{
+            BinFormatErrorReason::InvalidDataSize => {},
+            _ => assert!(false, "Trailing data was rejected for the wrong reason")
+        },
+        Ok(_) => assert!(false, "BlockBody with trailing data was accepted")
+    }
+
+    // faulty transaction data is reported by the transaction
+
+    let mut faulty = as_bytes;
+    faulty[10] = 0xFF;
+    match BlockBody::from_bytes(faulty){
+        Err(err) => match err.reason{
+            BinFormatErrorReason::InvalidFieldData(_) => {},
+            _ => assert!(false, "Faulty transaction was rejected for \
+                                 the wrong reason")
+        },
+        Ok(_) => assert!(false, "BlockBody with faulty transaction was accepted")
+    }
+
+}
@@ -0,0 +1,411 @@
+//
+//    Stachanov
+//    Copyright (C) 2017 Stachanov Developer Collective
+//
+//    This file is part of Stachanov.
+//
+//    This program is free software: you can redistribute it and/or
+//    modify it under the terms of the GNU Affero General Public
+//    License, version 3, as published by the Free Software Foundation.
+//
+//    This program is distributed in the hope that it will be useful,
+//    but WITHOUT ANY WARRANTY; without even the implied warranty of
+//    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//    GNU Affero General Public License for more details.
+//
+//    You should have received a copy of the
+//               GNU Affero General Public License
+//    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+// This module implements the value transfer of (composite) means
+// of production. A product P=(T, E) used as mean of production
+// transfers the following value between the times k1 < k2 (the
+// age of the product in minutes):
+//
+//  δ(t, k)       = max(d(t) - k, 0)
+//  ρ(t, k1, k2)  = (δ(t, k1) - δ(t, k2)) / d(t)
+//  θ(P, k1, k2)  = Σ ρ(t, k1, k2) * v(t)   for all t in T
+//
+// All computations use exact rational arithmetic, so summing
+// the transfers of many elements doesn't accumulate rounding
+// errors. Values are only rounded down to whole minutes when
+// they are transferred to an output.
+
+use std::cmp::Ordering;
+use blockchain::traits::ChainStorage;
+use blockchain::txtypes::product::ProductRegistry;
+
+/// `Rational` is a non-negative fraction, which is always
+/// kept in lowest terms. All operations return None on
+/// overflow instead of losing precision.
+
+#[derive(Clone)]
+#[derive(Copy)]
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Eq)]
+pub struct Rational{
+    numer: u128,
+    denom: u128
+}
+
+impl Rational{
+
+    /// Creates a new `Rational` in lowest terms
+    ///
+    /// # Arguments
+    /// * `numer`: The numerator
+    /// * `denom`: The denominator (must not be 0)
+
+    pub fn new(numer: u128, denom: u128) -> Rational{
+
+        assert!(denom != 0, "Denominator of a rational is 0");
+
+        let divisor = gcd(numer, denom);
+        Rational{numer: numer / divisor, denom: denom / divisor}
+
+    }
+
+    /// Creates a new `Rational` from an integer
+
+    pub fn from_integer(integer: u64) -> Rational{
+        Rational{numer: integer as u128, denom: 1}
+    }
+
+    /// Gets the numerator
+
+    pub fn get_numer(&self) -> u128{
+        self.numer
+    }
+
+    /// Gets the denominator
+
+    pub fn get_denom(&self) -> u128{
+        self.denom
+    }
+
+    /// Adds two rationals
+    ///
+    /// # Arguments
+    /// * `other`: The summand
+
+    pub fn checked_add(&self, other: Rational) -> Option<Rational>{
+
+        let divisor = gcd(self.denom, other.denom);
+        let denom = (self.denom / divisor).checked_mul(other.denom)?;
+        let numer = self.numer.checked_mul(denom / self.denom)?
+                              .checked_add(other.numer.checked_mul(denom / other.denom)?)?;
+        Some(Rational::new(numer, denom))
+
+    }
+
+    /// Multiplies the rational with an integer
+    ///
+    /// # Arguments
+    /// * `factor`: The factor
+
+    pub fn checked_mul_integer(&self, factor: u64) -> Option<Rational>{
+
+        let divisor = gcd(factor as u128, self.denom);
+        let numer = self.numer.checked_mul(factor as u128 / divisor)?;
+        Some(Rational::new(numer, self.denom / divisor))
+
+    }
+
+    /// Divides the rational by an integer
+    ///
+    /// # Arguments
+    /// * `divisor`: The divisor (None is returned if it is 0)
+
+    pub fn checked_div_integer(&self, divisor: u64) -> Option<Rational>{
+
+        if divisor == 0{
+            return None
+        }
+
+        let common = gcd(divisor as u128, self.numer);
+        let denom = self.denom.checked_mul(divisor as u128 / common)?;
+        Some(Rational::new(self.numer / common, denom))
+
+    }
+
+    /// Returns the rational rounded down to the next integer
+    /// or None if it doesn't fit into an u64
+
+    pub fn floor(&self) -> Option<u64>{
+        let integer = self.numer / self.denom;
+        if integer > u64::MAX as u128{
+            return None
+        }
+        Some(integer as u64)
+    }
+
+}
+
+impl PartialOrd for Rational{
+    fn partial_cmp(&self, other: &Rational) -> Option<Ordering>{
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Rational{
+
+    // the products can't overflow for rationals
+    // built from u64 numerators and denominators,
+    // larger ones are compared by their continued
+    // fraction expansions
+
+    fn cmp(&self, other: &Rational) -> Ordering{
+        match (self.numer.checked_mul(other.denom), other.numer.checked_mul(self.denom)){
+            (Some(left), Some(right)) => left.cmp(&right),
+            _ => cmp_fractions(self.numer, self.denom, other.numer, other.denom)
+        }
+    }
+
+}
+
+/// Compares the fractions a/b and c/d exactly without
+/// multiplying, by comparing their integer parts and then
+/// the reciprocals of their remainders (in reverse order)
+
+fn cmp_fractions(mut a: u128, mut b: u128, mut c: u128, mut d: u128) -> Ordering{
+
+    let mut reversed = false;
+
+    loop{
+
+        let (quotient_a, quotient_c) = (a / b, c / d);
+        let (remainder_a, remainder_c) = (a % b, c % d);
+
+        let ordering = if quotient_a != quotient_c{
+            quotient_a.cmp(&quotient_c)
+        }else{
+            match (remainder_a, remainder_c){
+                (0, 0) => Ordering::Equal,
+                (0, _) => Ordering::Less,
+                (_, 0) => Ordering::Greater,
+                _ => {
+                    a = b;
+                    b = remainder_a;
+                    c = d;
+                    d = remainder_c;
+                    reversed = !reversed;
+                    continue
+                }
+            }
+        };
+
+        if reversed{
+            return ordering.reverse()
+        }
+        return ordering
+
+    }
+
+}
+
+/// Returns the greatest common divisor of two integers
+
+fn gcd(mut a: u128, mut b: u128) -> u128{
+
+    while b != 0{
+        let remainder = a % b;
+        a = b;
+        b = remainder;
+    }
+
+    // gcd(0, 0) is defined as 1, so
+    // dividing by it is always safe
+
+    if a == 0{
+        return 1
+    }
+    a
+
+}
+
+// ------------------------------------------------------------------------
+
+/// Returns the remaining durability δ(t, k) of a product
+/// element at the age k
+///
+/// # Arguments
+/// * `durability`: The durability d(t) in minutes
+/// * `k`: The age of the product in minutes
+
+pub fn delta(durability: u64, k: u64) -> u64{
+    durability.saturating_sub(k)
+}
+
+/// Returns the share ρ(t, k1, k2) of its value a product
+/// element transfers between the ages k1 and k2
+///
+/// # Arguments
+/// * `durability`: The durability d(t) in minutes
+/// * `k1`: The age at the allocation in minutes
+/// * `k2`: The age at the deallocation in minutes
+
+pub fn rho(durability: u64, k1: u64, k2: u64) -> Rational{
+
+    if durability == 0 || k2 <= k1{
+        return Rational::from_integer(0)
+    }
+
+    let transferred = delta(durability, k1) - delta(durability, k2);
+    Rational::new(transferred as u128, durability as u128)
+
+}
+
+/// Returns the value transfer θ(P, k1, k2) in minutes of a
+/// registered product type used as mean of production
+/// between the ages k1 and k2. The transfer is summed over
+/// the product type and all its subelements.
+///
+/// Returns None if the product type (or one of its
+/// subelements) is unknown or the sum overflows
+///
+/// # Arguments
+/// * `registry`: The product type registry
+/// * `product_type`: The id of the product type
+/// * `k1`: The age at the allocation in minutes
+/// * `k2`: The age at the deallocation in minutes
+
+pub fn theta<C>(registry: &ProductRegistry<C>,
+                product_type: [u8; 32],
+                k1: u64,
+                k2: u64) -> Option<Rational> where C: ChainStorage{
+
+    let mut elements = registry.get_subelements(product_type)?;
+    elements.insert(product_type);
+
+    let mut transfer = Rational::from_integer(0);
+    for element in elements{
+        let durability = registry.get_durability(element)?;
+        let value = registry.get_value(element)?;
+        let element_transfer = rho(durability, k1, k2).checked_mul_integer(value)?;
+        transfer = transfer.checked_add(element_transfer)?;
+    }
+    Some(transfer)
+
+}
+
+// ------------------------------------------------------------------------
+
+#[test]
+fn test_rational(){
+
+    let third = Rational::new(2, 6);
+    assert_eq!(third.get_numer(), 1);
+    assert_eq!(third.get_denom(), 3);
+
+    let sum = third.checked_add(Rational::new(1, 6)).unwrap();
+    assert_eq!(sum, Rational::new(1, 2));
+    assert_eq!(sum.checked_mul_integer(6).unwrap(), Rational::from_integer(3));
+    assert_eq!(sum.checked_div_integer(3).unwrap(), Rational::new(1, 6));
+    assert_eq!(Rational::from_integer(0).checked_div_integer(7).unwrap(), Rational::from_integer(0));
+    assert!(sum.checked_div_integer(0).is_none());
+    assert_eq!(Rational::new(7, 2).floor(), Some(3));
+    assert_eq!(Rational::new(0, 5), Rational::from_integer(0));
+    assert!(Rational::new(1, 3) < Rational::new(1, 2));
+
+    let huge = Rational::new(u128::MAX, 1);
+    assert!(huge.checked_add(Rational::from_integer(1)).is_none());
+    assert!(huge.floor().is_none());
+
+    // large rationals are compared exactly, even where
+    // floating point numbers can't tell them apart
+
+    let above_one = Rational::new(u128::MAX, u128::MAX - 1);
+    let further_above_one = Rational::new(u128::MAX - 1, u128::MAX - 2);
+    assert!(above_one < further_above_one);
+    assert!(further_above_one > above_one);
+    assert_eq!(above_one.cmp(&above_one), Ordering::Equal);
+    assert!(Rational::new(u128::MAX - 1, 3) > Rational::new(u128::MAX - 2, 3));
+
+}
+
+#[test]
+fn test_delta_rho(){
+
+    assert_eq!(delta(100, 30), 70);
+    assert_eq!(delta(100, 130), 0);
+
+    assert_eq!(rho(120, 0, 10), Rational::new(1, 12));
+    assert_eq!(rho(120, 110, 130), Rational::new(1, 12));
+    assert_eq!(rho(120, 130, 140), Rational::from_integer(0));
+    assert_eq!(rho(120, 10, 10), Rational::from_integer(0));
+
+}
+
+#[test]
+fn test_theta(){
+
+    use blockchain::transactions::TxPayload;
+    use blockchain::txtypes::product::ProductTypeDefinition;
+    use blockchain::txtypes::TestChain;
+    use blockchain::txtypes::signed_transaction;
+    use blockchain::txtypes::register_collective;
+    use blockchain::txtypes::keypair;
+
+    let (secret_a, _) = keypair(0x0A);
+
+    let mut chain = TestChain::new();
+    chain.append(1, vec![register_collective(&secret_a)]).unwrap();
+
+    const Y: u64 = 365 * 24 * 60;
+
+    let mut timestamp = 1;
+    let mut define = |durability, value, children| {
+        let definition = ProductTypeDefinition::new(String::from("part"), durability, value, children);
+        let product_type = definition.get_product_type();
+        timestamp += 1;
+        chain.append(timestamp,
+                     vec![signed_transaction(TxPayload::ProductTypeDefinition(definition),
+                                             &[&secret_a])]).unwrap();
+        product_type
+    };
+
+    let c = define(12 * Y, 200, vec![]);
+    let m = define(12 * Y, 600, vec![]);
+    let p = define(9 * Y, 2500, vec![]);
+    let r = define(8 * Y, 700, vec![]);
+    let h = define(5 * Y, 400, vec![]);
+    let x = define(5 * Y, 30, vec![]);
+    let y = define(10 * Y, 10, vec![]);
+    let m_p = define(0, 10, vec![x, p, m]);
+    let m_r = define(0, 1, vec![m, r]);
+    let c_h = define(0, 2, vec![c, h]);
+    let m_r_p = define(0, 0, vec![m_p, m_r]);
+    let computer = define(0, 15, vec![m_r_p, c_h, y]);
+
+    let registry = ProductRegistry::new(&chain.storage, &chain.indexes.products);
+
+    // the value transfer of the computer in its second year
+    // (the proposal rounds every summand and gets roughly 530)
+
+    let transfer = theta(&registry, computer, Y, 2 * Y).unwrap();
+    assert_eq!(transfer, Rational::new(188809, 360));
+    assert_eq!(transfer.floor(), Some(524));
+
+    // in its sixth year only the case, mainboard, CPU,
+    // RAM, cable and the RAM assembly transfer value
+
+    let transfer = theta(&registry, computer, 5 * Y, 6 * Y).unwrap();
+    let expected = Rational::new(200, 12).checked_add(Rational::new(600, 12))
+                                         .and_then(|sum| sum.checked_add(Rational::new(2500, 9)))
+                                         .and_then(|sum| sum.checked_add(Rational::new(700, 8)))
+                                         .and_then(|sum| sum.checked_add(Rational::new(10, 10)))
+                                         .and_then(|sum| sum.checked_add(Rational::new(1, 8)))
+                                         .unwrap();
+    assert_eq!(transfer, expected);
+
+    // over the whole lifetime the complete
+    // aggregated value is transferred
+
+    let transfer = theta(&registry, computer, 0, 12 * Y).unwrap();
+    assert_eq!(transfer, Rational::from_integer(4468));
+
+    assert!(theta(&registry, [0x01; 32], 0, Y).is_none());
+
+}
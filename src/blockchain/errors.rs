@@ -20,15 +20,119 @@
 
 use std::error::Error;
 use std::fmt;
+use blockchain::transactions::TxId;
+use blockchain::transactions::BadClaimReason;
 
 /// `VerificationErrorReason` is an enum used to denote the type
-/// of verification error
+/// of verification error. Besides the structural errors of blocks
+/// it covers the errors of the chain and of the transaction layer:
+///
+/// * `InvalidTimestamp`: The timestamp of a block is not greater
+///         than the timestamp of its predecessor
+/// * `InvalidIndex`: The index of a block doesn't follow the
+///         index of its predecessor (or isn't 0 for the
+///         first block)
+/// * `InvalidTxSigners`: The signers of a transaction don't match
+///         the signers required by its transaction type
+/// * `InvalidTxRef`: A referenced transaction doesn't exist (in
+///         one of the preceding blocks) or has the wrong type
+/// * `BadClaim`: A relationship of a referenced transaction
+///         could not be claimed
+/// * `DuplicateCollective`: The collective is already registered
+/// * `UnknownCollective`: The collective is not registered
+/// * `CollectiveCycle`: The parent relationship would
+///         turn the collective tree into a cycle
+/// * `InvalidTrust`: Trust can't be established between a
+///         collective and itself or between collectives
+///         that already established strong trust
+/// * `MissingTrust`: The collectives didn't establish the
+///         trust required by the transaction
+/// * `InvalidAmount`: An amount or value is out of range
+/// * `OrderRejected`: The referenced order was rejected
+/// * `InvalidOwner`: The referenced resource isn't owned
+///         by the collective
+/// * `ResourceInUse`: The referenced resource is allocated
+///         as a mean of production, dispatched or already consumed
+/// * `MopAllocated`: The production can't be finished while
+///         means of production are allocated to it
+/// * `ValueImbalance`: The declared value of a production
+///         output (first) differs from the value transferred
+///         by its allocations (second)
+/// * `DuplicateProductType`: The product type is already registered
+/// * `UnknownProductType`: The product type is not registered
+/// * `InvalidProductType`: The product type has duplicate
+///         children or declares a durability although it is
+///         composite (or none although it is simple)
+/// * `InvalidRevisionPeriod`: The period of a final revision
+///         isn't aligned or hasn't ended yet
+/// * `OverlappingRevision`: The period was already revised
+/// * `InvalidRevision`: The sums of a final revision don't
+///         match the transactions of its period
+/// * `WriteOffLimitExceeded`: The write-offs of a period
+///         exceed the limit relative to its workloads
+/// * `WorkloadNotRevised`: The workload wasn't registered in
+///         the period of the referenced final revision
+/// * `InsufficientBalance`: The wallet doesn't hold enough
+///         coupons of the collective
+/// * `InvalidLabel`: The commons label of a transaction doesn't
+///         match the label of the referenced order or production
+///         (or a mean of production is needlessly flagged as
+///         repurposed)
+/// * `SeparationViolation`: A commons production consumes an
+///         exclusive resource or allocates an exclusive mean of
+///         production that isn't flagged as repurposed
+/// * `NotC2I`: The collective isn't a C2I collective
+/// * `InsufficientStock`: Fewer units of the resource are
+///         left than dispatched
+/// * `PendingSettlement`: The subsystems of the collectives
+///         haven't settled since a trust cancellation split them
+/// * `DuplicateBallot`: The collective already cast a ballot
+/// * `VoteNotPassed`: The value estimation needs a passed
+///         production vote
+/// * `InvalidActivation`: The activation height isn't after
+///         the block holding the proposal
+/// * `VotingClosed`: The vote doesn't accept ballots anymore
 
 #[derive(Debug)]
 pub enum VerificationErrorReason{
     InvalidIssuerSignature,
     InvalidContentHash,
-    InvalidChainLink
+    InvalidChainLink,
+    InvalidTimestamp,
+    InvalidIndex,
+    InvalidTxSignature,
+    InvalidTxSigners,
+    InvalidTxRef(TxId),
+    BadClaim(BadClaimReason),
+    DuplicateCollective,
+    UnknownCollective,
+    CollectiveCycle,
+    InvalidTrust,
+    MissingTrust,
+    InvalidAmount,
+    OrderRejected,
+    InvalidOwner,
+    ResourceInUse,
+    MopAllocated,
+    ValueImbalance(u64, u64),
+    DuplicateProductType,
+    UnknownProductType,
+    InvalidProductType,
+    InvalidRevisionPeriod,
+    OverlappingRevision,
+    InvalidRevision,
+    WriteOffLimitExceeded,
+    WorkloadNotRevised,
+    InsufficientBalance,
+    InvalidLabel,
+    SeparationViolation,
+    NotC2I,
+    InsufficientStock,
+    PendingSettlement,
+    DuplicateBallot,
+    VoteNotPassed,
+    InvalidActivation,
+    VotingClosed
 }
 
 impl fmt::Display for VerificationErrorReason {
@@ -36,7 +140,44 @@ impl fmt::Display for VerificationErrorReason {
         match *self {
             VerificationErrorReason::InvalidIssuerSignature => write!(f, "Block header signature doesn't match issuer"),
             VerificationErrorReason::InvalidContentHash => write!(f, "Block header content hash doesn't match transaction merkle tree root"),
-            VerificationErrorReason::InvalidChainLink => write!(f, "Chain link is invalid (prev_block_hash, timestamp or index incorrect)")
+            VerificationErrorReason::InvalidChainLink => write!(f, "Chain link is invalid (prev_block_hash, timestamp or index incorrect)"),
+            VerificationErrorReason::InvalidTimestamp => write!(f, "Block timestamp is not greater than the timestamp of its predecessor"),
+            VerificationErrorReason::InvalidIndex => write!(f, "Block index doesn't follow the index of its predecessor"),
+            VerificationErrorReason::InvalidTxSignature => write!(f, "Transaction signature doesn't match signer"),
+            VerificationErrorReason::InvalidTxSigners => write!(f, "Transaction signers don't match the transaction type"),
+            VerificationErrorReason::InvalidTxRef(ref tx_id) => write!(f, "Invalid transaction reference {:?}", tx_id),
+            VerificationErrorReason::BadClaim(ref reason) => write!(f, "Bad relationship claim: {}", reason),
+            VerificationErrorReason::DuplicateCollective => write!(f, "Collective is already registered"),
+            VerificationErrorReason::UnknownCollective => write!(f, "Collective is not registered"),
+            VerificationErrorReason::CollectiveCycle => write!(f, "Parent relationship would create a cycle"),
+            VerificationErrorReason::InvalidTrust => write!(f, "Trust is already established or invalid"),
+            VerificationErrorReason::MissingTrust => write!(f, "Collectives didn't establish the required trust"),
+            VerificationErrorReason::InvalidAmount => write!(f, "Amount or value is out of range"),
+            VerificationErrorReason::OrderRejected => write!(f, "Order was rejected"),
+            VerificationErrorReason::InvalidOwner => write!(f, "Resource isn't owned by the collective"),
+            VerificationErrorReason::ResourceInUse => write!(f, "Resource is allocated or consumed"),
+            VerificationErrorReason::MopAllocated => write!(f, "Means of production are still allocated"),
+            VerificationErrorReason::ValueImbalance(declared, transferred) => {
+                write!(f, "Declared value {} differs from transferred value {}", declared, transferred)
+            },
+            VerificationErrorReason::DuplicateProductType => write!(f, "Product type is already registered"),
+            VerificationErrorReason::UnknownProductType => write!(f, "Product type is not registered"),
+            VerificationErrorReason::InvalidProductType => write!(f, "Product type definition is invalid"),
+            VerificationErrorReason::InvalidRevisionPeriod => write!(f, "Revision period is invalid"),
+            VerificationErrorReason::OverlappingRevision => write!(f, "Period was already revised"),
+            VerificationErrorReason::InvalidRevision => write!(f, "Revision doesn't match its period"),
+            VerificationErrorReason::WriteOffLimitExceeded => write!(f, "Write-offs exceed the limit"),
+            VerificationErrorReason::WorkloadNotRevised => write!(f, "Workload is not part of the revised period"),
+            VerificationErrorReason::InsufficientBalance => write!(f, "Wallet balance is insufficient"),
+            VerificationErrorReason::InvalidLabel => write!(f, "Commons label doesn't match the production"),
+            VerificationErrorReason::SeparationViolation => write!(f, "Commons production can't use exclusive resources"),
+            VerificationErrorReason::NotC2I => write!(f, "Collective isn't a C2I collective"),
+            VerificationErrorReason::InsufficientStock => write!(f, "Too few units of the resource are left"),
+            VerificationErrorReason::PendingSettlement => write!(f, "Settlement between the subsystems is pending"),
+            VerificationErrorReason::DuplicateBallot => write!(f, "Collective already cast a ballot"),
+            VerificationErrorReason::VoteNotPassed => write!(f, "Production vote didn't pass"),
+            VerificationErrorReason::InvalidActivation => write!(f, "Activation height isn't in the future"),
+            VerificationErrorReason::VotingClosed => write!(f, "Voting is closed")
         }
     }
 }
@@ -45,7 +186,7 @@ impl fmt::Display for VerificationErrorReason {
 
 #[derive(Debug)]
 pub struct VerificationError{
-    reason: VerificationErrorReason
+    pub reason: VerificationErrorReason
 }
 
 impl VerificationError{
@@ -95,7 +236,7 @@ impl fmt::Display for BinFormatErrorReason {
 
 #[derive(Debug)]
 pub struct BinFormatError{
-    reason: BinFormatErrorReason
+    pub reason: BinFormatErrorReason
 }
 
 impl BinFormatError{
@@ -373,7 +373,7 @@ fn test_blockheader_signature_validity(){
 
     // create block header
 
-    let mut block = BlockHeader::new(public_key, None, 0, 0xDEADBEEF, [4; 32]);
+    let mut block = BlockHeader::new(public_key, None, 0, 0xBEEF, [4; 32]);
 
     // check for signature validity
 
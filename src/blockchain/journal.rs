@@ -0,0 +1,206 @@
+//
+//    Stachanov
+//    Copyright (C) 2017 Stachanov Developer Collective
+//
+//    This file is part of Stachanov.
+//
+//    This program is free software: you can redistribute it and/or
+//    modify it under the terms of the GNU Affero General Public
+//    License, version 3, as published by the Free Software Foundation.
+//
+//    This program is distributed in the hope that it will be useful,
+//    but WITHOUT ANY WARRANTY; without even the implied warranty of
+//    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//    GNU Affero General Public License for more details.
+//
+//    You should have received a copy of the
+//               GNU Affero General Public License
+//    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+// Indexes derived from the chain are updated incrementally
+// with every applied block. To roll them back together with
+// the chain, every change of an index is journaled together
+// with what it replaced, grouped by the block that caused it.
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::hash::Hash;
+
+/// Undoes a single change of an index of type `T`
+
+pub type Undo<T> = Box<dyn FnOnce(&mut T)>;
+
+/// `Journal` records how to undo the changes of an index,
+/// one list of undos per applied block
+
+pub struct Journal<T>{
+    blocks: Vec<Vec<Undo<T>>>
+}
+
+impl<T> Journal<T>{
+
+    /// Creates a new, empty `Journal`
+
+    pub fn new() -> Journal<T>{
+        Journal{blocks: vec![]}
+    }
+
+    /// Starts recording the changes of a new block
+
+    pub fn begin_block(&mut self){
+        self.blocks.push(vec![]);
+    }
+
+    /// Removes the undos of the last block
+    /// in the order they were recorded
+
+    pub fn pop_block(&mut self) -> Vec<Undo<T>>{
+        self.blocks.pop().unwrap_or_default()
+    }
+
+    /// Records how to undo a change of the current block.
+    /// Changes made outside of a block aren't journaled.
+    ///
+    /// # Arguments
+    /// * `undo`: Reverts the change
+
+    pub fn record<F>(&mut self, undo: F) where F: FnOnce(&mut T) + 'static{
+        if let Some(undos) = self.blocks.last_mut(){
+            undos.push(Box::new(undo));
+        }
+    }
+
+    /// Records the value an entry of a map had
+    /// before it was changed
+    ///
+    /// # Arguments
+    /// * `map`: Selects the map from the index
+    /// * `key`: The key of the entry
+    /// * `previous`: The replaced value or None, if the entry was added
+
+    pub fn record_entry<K, V>(&mut self,
+                              map: fn(&mut T) -> &mut HashMap<K, V>,
+                              key: K,
+                              previous: Option<V>)
+        where T: 'static,
+              K: Eq + Hash + 'static,
+              V: 'static{
+        self.record(move |index| restore(map(index), key, previous));
+    }
+
+    /// Records if a set contained a member
+    /// before it was changed
+    ///
+    /// # Arguments
+    /// * `set`: Selects the set from the index
+    /// * `member`: The inserted or removed member
+    /// * `was_member`: Whether the set contained the member
+
+    pub fn record_member<M>(&mut self,
+                            set: fn(&mut T) -> &mut HashSet<M>,
+                            member: M,
+                            was_member: bool)
+        where T: 'static,
+              M: Eq + Hash + 'static{
+        self.record(move |index| {
+            if was_member{
+                set(index).insert(member);
+            }else{
+                set(index).remove(&member);
+            }
+        });
+    }
+
+}
+
+/// Restores an entry of a map to its previous value
+///
+/// # Arguments
+/// * `map`: The map holding the entry
+/// * `key`: The key of the entry
+/// * `previous`: The previous value or None, if the entry didn't exist
+
+pub fn restore<K, V>(map: &mut HashMap<K, V>, key: K, previous: Option<V>) where K: Eq + Hash{
+    match previous{
+        Some(value) => map.insert(key, value),
+        None => map.remove(&key)
+    };
+}
+
+/// `Journaled` is implemented by indexes that journal their
+/// changes, so they can be rolled back block by block
+
+pub trait Journaled: Sized{
+
+    /// Returns the journal of the index
+
+    fn get_journal(&mut self) -> &mut Journal<Self>;
+
+    /// Starts journaling the changes of a new block
+
+    fn begin_block(&mut self){
+        self.get_journal().begin_block();
+    }
+
+    /// Undoes the changes of the last journaled block
+
+    fn rollback_block(&mut self){
+        let undos = self.get_journal().pop_block();
+        for undo in undos.into_iter().rev(){
+            undo(self);
+        }
+    }
+
+}
+
+#[test]
+fn test_journal(){
+
+    struct Index{
+        entries: HashMap<u8, u64>,
+        members: HashSet<u8>,
+        journal: Journal<Index>
+    }
+
+    impl Journaled for Index{
+        fn get_journal(&mut self) -> &mut Journal<Index>{
+            &mut self.journal
+        }
+    }
+
+    let mut index = Index{entries: HashMap::new(), members: HashSet::new(), journal: Journal::new()};
+
+    // changes outside of a block can't be undone
+
+    index.entries.insert(1, 10);
+    index.journal.record_entry(|index| &mut index.entries, 1, None);
+    index.rollback_block();
+    assert_eq!(index.entries.get(&1), Some(&10));
+
+    index.begin_block();
+    let previous = index.entries.insert(1, 11);
+    index.journal.record_entry(|index| &mut index.entries, 1, previous);
+    let was_member = !index.members.insert(2);
+    index.journal.record_member(|index| &mut index.members, 2, was_member);
+
+    index.begin_block();
+    let previous = index.entries.insert(1, 12);
+    index.journal.record_entry(|index| &mut index.entries, 1, previous);
+    let previous = index.entries.insert(3, 30);
+    index.journal.record_entry(|index| &mut index.entries, 3, previous);
+    let was_member = index.members.remove(&2);
+    index.journal.record_member(|index| &mut index.members, 2, was_member);
+
+    // blocks are undone in reverse order
+
+    index.rollback_block();
+    assert_eq!(index.entries.get(&1), Some(&11));
+    assert_eq!(index.entries.get(&3), None);
+    assert!(index.members.contains(&2));
+
+    index.rollback_block();
+    assert_eq!(index.entries.get(&1), Some(&10));
+    assert!(index.members.is_empty());
+
+}
@@ -24,5 +24,12 @@ pub mod traits;
 pub mod block;
 pub mod errors;
 pub mod transactions;
-mod storages;
+pub mod reorg;
+pub mod txtypes;
+pub mod processor;
+pub mod validator;
+pub mod storages;
+pub mod depreciation;
+pub mod params;
+pub mod journal;
 mod utils;
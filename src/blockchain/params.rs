@@ -0,0 +1,66 @@
+//
+//    Stachanov
+//    Copyright (C) 2017 Stachanov Developer Collective
+//
+//    This file is part of Stachanov.
+//
+//    This program is free software: you can redistribute it and/or
+//    modify it under the terms of the GNU Affero General Public
+//    License, version 3, as published by the Free Software Foundation.
+//
+//    This program is distributed in the hope that it will be useful,
+//    but WITHOUT ANY WARRANTY; without even the implied warranty of
+//    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//    GNU Affero General Public License for more details.
+//
+//    You should have received a copy of the
+//               GNU Affero General Public License
+//    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+// This module holds the consensus parameters of a chain. All
+// nodes of a chain must agree on them, since transactions are
+// verified against them.
+
+/// `ConsensusParams` holds the configurable limits that
+/// transactions are verified against:
+///
+/// * `write_off_limit_divisor`: The value of the exclusive
+///         write-offs of a revision period must be less than
+///         the workloads of the period divided by the divisor
+/// * `vote_threshold`: Value estimations (in minutes) above
+///         the threshold need a passed production vote
+/// * `vote_quorum_percent`: The minimum share (in percent) of
+///         the collectives of the subsystem, that must cast
+///         a ballot
+/// * `vote_approval_percent`: The share (in percent) of the
+///         ballots, that must be exceeded by the approving
+///         ballots
+
+#[derive(Clone)]
+#[derive(Copy)]
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Eq)]
+pub struct ConsensusParams{
+    pub write_off_limit_divisor: u64,
+    pub vote_threshold: u64,
+    pub vote_quorum_percent: u64,
+    pub vote_approval_percent: u64
+}
+
+impl Default for ConsensusParams{
+
+    /// Returns the parameters of the proposal. The vote
+    /// threshold is about ten work years.
+
+    fn default() -> ConsensusParams{
+        ConsensusParams{
+            write_off_limit_divisor: 10,
+            vote_threshold: 1_000_000,
+            vote_quorum_percent: 50,
+            vote_approval_percent: 50
+        }
+    }
+
+}
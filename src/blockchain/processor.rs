@@ -0,0 +1,462 @@
+//
+//    Stachanov
+//    Copyright (C) 2017 Stachanov Developer Collective
+//
+//    This file is part of Stachanov.
+//
+//    This program is free software: you can redistribute it and/or
+//    modify it under the terms of the GNU Affero General Public
+//    License, version 3, as published by the Free Software Foundation.
+//
+//    This program is distributed in the hope that it will be useful,
+//    but WITHOUT ANY WARRANTY; without even the implied warranty of
+//    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//    GNU Affero General Public License for more details.
+//
+//    You should have received a copy of the
+//               GNU Affero General Public License
+//    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+use std::collections::HashMap;
+use blockchain::block::Block;
+use blockchain::block::BlockId;
+use blockchain::block::BlockError;
+use blockchain::block::BlockErrorReason;
+use blockchain::header::BlockHeader;
+use blockchain::params::ConsensusParams;
+use blockchain::journal::Journaled;
+use blockchain::reorg;
+use blockchain::traits::ChainStorage;
+use blockchain::traits::TreeStorage;
+use blockchain::transactions::Transaction;
+use blockchain::transactions::TxId;
+use blockchain::transactions::TxIndex;
+use blockchain::txtypes::TxContext;
+use blockchain::txtypes::verify_payload;
+use blockchain::txtypes::initial_state;
+use blockchain::txtypes::collective::CollectiveIndex;
+use blockchain::txtypes::trust::TrustGraph;
+use blockchain::txtypes::production::ProductionIndex;
+use blockchain::txtypes::product::ProductTypeIndex;
+use blockchain::txtypes::revision::RevisionIndex;
+use blockchain::txtypes::coupon::WalletIndex;
+use blockchain::txtypes::resource::OwnershipIndex;
+use blockchain::txtypes::inventory::InventoryIndex;
+use blockchain::txtypes::settlement::SettlementIndex;
+use blockchain::txtypes::vote::VoteIndex;
+use blockchain::txtypes::governance::ClassificationIndex;
+
+/// `ChainIndexes` bundles all indexes derived from the
+/// transactions of the chain together with the consensus
+/// parameters the transactions are verified against. Indexes
+/// are only ever updated with verified transactions, so
+/// updating them can't fail and they can be rebuilt by
+/// replaying the chain.
+///
+/// The indexes journal their changes block by block, so they
+/// can be rolled back together with the chain.
+
+pub struct ChainIndexes{
+    pub params: ConsensusParams,
+    pub collectives: CollectiveIndex,
+    pub trust: TrustGraph,
+    pub production: ProductionIndex,
+    pub products: ProductTypeIndex,
+    pub revisions: RevisionIndex,
+    pub wallets: WalletIndex,
+    pub ownership: OwnershipIndex,
+    pub inventory: InventoryIndex,
+    pub settlements: SettlementIndex,
+    pub votes: VoteIndex,
+    pub classifications: ClassificationIndex,
+    blocks: Vec<BlockId>
+}
+
+impl ChainIndexes{
+
+    /// Creates a new set of empty indexes
+    /// with the default consensus parameters
+
+    pub fn new() -> ChainIndexes{
+        ChainIndexes::with_params(ConsensusParams::default())
+    }
+
+    /// Creates a new set of empty indexes
+    ///
+    /// # Arguments
+    /// * `params`: The consensus parameters of the chain
+
+    pub fn with_params(params: ConsensusParams) -> ChainIndexes{
+        ChainIndexes{
+            params: params,
+            collectives: CollectiveIndex::new(),
+            trust: TrustGraph::new(),
+            production: ProductionIndex::new(),
+            products: ProductTypeIndex::new(),
+            revisions: RevisionIndex::new(),
+            wallets: WalletIndex::new(),
+            ownership: OwnershipIndex::new(),
+            inventory: InventoryIndex::new(),
+            settlements: SettlementIndex::new(),
+            votes: VoteIndex::new(),
+            classifications: ClassificationIndex::new(),
+            blocks: vec![]
+        }
+    }
+
+    /// Rebuilds the indexes by replaying all
+    /// transactions of the supplied chain
+    ///
+    /// # Arguments
+    /// * `storage`: The storage holding the chain
+    /// * `params`: The consensus parameters of the chain
+
+    pub fn rebuild<C>(storage: &C, params: ConsensusParams) -> ChainIndexes where C: ChainStorage{
+
+        // walk back from the tail to find the
+        // order of the blocks in the chain
+
+        let mut blocks = vec![];
+        let mut current = storage.get_tail_block();
+        while let Some(block) = current{
+            current = match block.get_previous_id(){
+                Some(previous_id) => storage.get_block(previous_id),
+                None => None
+            };
+            blocks.push(block);
+        }
+
+        let mut indexes = ChainIndexes::with_params(params);
+        for block in blocks.iter().rev(){
+            indexes.apply_block(block);
+        }
+        indexes.inventory.notify_subscribers();
+        indexes
+
+    }
+
+    /// Updates all indexes with a verified transaction
+    ///
+    /// # Arguments
+    /// * `tx_id`: The id of the transaction
+    /// * `transaction`: The verified transaction
+    /// * `header`: The header of the block holding the transaction
+
+    pub fn apply_transaction(&mut self, tx_id: TxId, transaction: &Transaction, header: &BlockHeader){
+        self.collectives.apply_transaction(tx_id, transaction);
+        self.trust.apply_transaction(tx_id, transaction);
+        self.production.apply_transaction(tx_id, transaction);
+        self.products.apply_transaction(tx_id, transaction);
+        self.revisions.apply_transaction(tx_id, transaction, header.get_timestamp());
+        self.wallets.apply_transaction(tx_id, transaction);
+
+        // the inventory needs the owners
+        // before the transaction
+
+        self.inventory.apply_transaction(tx_id, transaction, &self.collectives, &self.ownership);
+        self.ownership.apply_transaction(tx_id, transaction);
+        self.settlements.apply_transaction(tx_id, transaction, &self.trust, &self.wallets, &self.inventory);
+        self.votes.apply_transaction(tx_id, transaction, &self.trust);
+        self.classifications.apply_transaction(tx_id, transaction);
+    }
+
+    /// Rolls the indexes back until the supplied block is the
+    /// last block they were updated with. The changes of the
+    /// rolled back blocks are reported to the subscribers of
+    /// the inventory with the next notification.
+    ///
+    /// # Arguments
+    /// * `block_id`: The id of the block or None, to roll back all blocks
+
+    pub fn rollback_to(&mut self, block_id: Option<BlockId>){
+        while !self.blocks.is_empty() && self.blocks.last().cloned() != block_id{
+            self.rollback_block();
+        }
+    }
+
+    /// Starts journaling the changes of a new block
+
+    fn begin_block(&mut self, block_id: BlockId){
+        self.blocks.push(block_id);
+        self.collectives.begin_block();
+        self.trust.begin_block();
+        self.production.begin_block();
+        self.products.begin_block();
+        self.revisions.begin_block();
+        self.wallets.begin_block();
+        self.ownership.begin_block();
+        self.inventory.begin_block();
+        self.settlements.begin_block();
+        self.votes.begin_block();
+        self.classifications.begin_block();
+    }
+
+    /// Undoes the changes of the last block
+
+    fn rollback_block(&mut self){
+        self.blocks.pop();
+        self.collectives.rollback_block();
+        self.trust.rollback_block();
+        self.production.rollback_block();
+        self.products.rollback_block();
+        self.revisions.rollback_block();
+        self.wallets.rollback_block();
+        self.ownership.rollback_block();
+        self.inventory.rollback_block();
+        self.settlements.rollback_block();
+        self.votes.rollback_block();
+        self.classifications.rollback_block();
+    }
+
+    /// Undoes the changes of a rejected block, which
+    /// mustn't be reported to the subscribers
+
+    fn reject_block(&mut self){
+        self.rollback_block();
+        self.inventory.discard_changes();
+    }
+
+    /// Updates all indexes with the transactions of a verified block
+
+    fn apply_block(&mut self, block: &Block){
+
+        let block_id = block.get_id();
+        self.begin_block(block_id);
+
+        for index in 0..block.get_transaction_count(){
+            let tx_index = TxIndex(index as u16);
+            if let Some(transaction) = block.get_transaction(tx_index){
+                let tx_id = TxId::new(block_id, tx_index);
+                self.apply_transaction(tx_id, &transaction, block.get_header_ref());
+            }
+        }
+
+    }
+
+}
+
+/// Verifies the transactions of a block against the chain and
+/// appends the block. The states of the new transactions are
+/// initialized, the relationships they claim are updated and
+/// the indexes are updated with the new transactions.
+///
+/// The block itself (issuer signature, content hash, chain
+/// link) must be verified beforehand. If a transaction is
+/// invalid, neither the storage nor the indexes are changed
+/// and a BlockError with reason InvalidTransaction is returned.
+///
+/// If the chain was rolled back since the indexes were last
+/// updated, the indexes are rolled back to the tail of the
+/// chain first.
+///
+/// # Arguments
+/// * `storage`: The storage holding the chain
+/// * `indexes`: The indexes of the chain
+/// * `block`: The block that should be appended
+
+pub fn apply_block<C>(storage: &mut C,
+                      indexes: &mut ChainIndexes,
+                      block: Block)
+                      -> Result<(), BlockError> where C: ChainStorage{
+
+    let block_id = block.get_id();
+    let header = *block.get_header_ref();
+
+    if indexes.blocks.last().cloned() != block.get_previous_id(){
+        let tail_id = storage.get_tail_block().map(|tail| tail.get_id());
+        indexes.rollback_to(tail_id);
+        indexes.inventory.notify_subscribers();
+    }
+
+    // the indexes are updated in place and
+    // journaled, so a rejected block can be undone
+
+    indexes.begin_block(block_id);
+
+    let mut claimed_states = HashMap::new();
+    let mut new_states = vec![];
+
+    for index in 0..block.get_transaction_count(){
+
+        let tx_index = TxIndex(index as u16);
+        let tx_id = TxId::new(block_id, tx_index);
+        let transaction = match block.get_transaction(tx_index){
+            Some(transaction) => transaction,
+            None => break
+        };
+
+        let verified = transaction.verify_signatures().and_then(|_| {
+            let mut context = TxContext::new(storage,
+                                             indexes,
+                                             &header,
+                                             tx_id,
+                                             &mut claimed_states);
+            verify_payload(&transaction, &mut context)
+        });
+
+        if let Err(err) = verified{
+            indexes.reject_block();
+            let reason = BlockErrorReason::InvalidTransaction(tx_id, err);
+            return Err(BlockError::new(reason))
+        }
+
+        new_states.push((tx_id, initial_state(&transaction)));
+        indexes.apply_transaction(tx_id, &transaction, &header);
+
+    }
+
+    let previous_id = block.get_previous_id();
+    if let Err(err) = storage.append_verified_block(block){
+        indexes.reject_block();
+        return Err(err)
+    }
+
+    let states = new_states.into_iter().chain(claimed_states);
+
+    for (tx_id, tx_state) in states{
+
+        if let Err(err) = storage.set_transaction_state(tx_id, tx_state){
+
+            // undo the append, so the block
+            // is either applied fully or not at all
+
+            indexes.reject_block();
+            if let Some(previous_id) = previous_id{
+                storage.rollback_to(previous_id)?;
+            }else{
+                storage.reset();
+            }
+
+            let reason = BlockErrorReason::StorageFailure(err.to_string());
+            return Err(BlockError::new(reason))
+
+        }
+
+    }
+
+    indexes.inventory.notify_subscribers();
+    Ok(())
+
+}
+
+/// Switches the chain consensus to the branch of the tree
+/// storage ending with the supplied block. The chain is
+/// rolled back and the blocks of the branch are verified and
+/// applied as in `reorg::switch_to_branch`. The indexes follow
+/// the chain, whether the switch succeeds or the chain is
+/// restored.
+///
+/// # Arguments
+/// * `storage`: The storage holding the chain
+/// * `indexes`: The indexes of the chain
+/// * `tree`: The storage holding the competing forks
+/// * `tip`: The id of the last block of the new branch
+
+pub fn switch_to_branch<C, T>(storage: &mut C,
+                              indexes: &mut ChainIndexes,
+                              tree: &T,
+                              tip: BlockId)
+                              -> Result<(), BlockError>
+    where C: ChainStorage,
+          T: TreeStorage{
+
+    let result = reorg::switch_to_branch(storage, tree, tip, |storage, block| {
+        apply_block(storage, indexes, block)
+    });
+
+    // the chain is rolled back without applying a
+    // block, if the branch is part of the chain
+
+    let tail_id = storage.get_tail_block().map(|tail| tail.get_id());
+    indexes.rollback_to(tail_id);
+    indexes.inventory.notify_subscribers();
+    result
+
+}
+
+#[test]
+fn test_switch_to_branch(){
+
+    use blockchain::traits::BlockStorage;
+    use blockchain::storages::memory::MemoryChainStorage;
+    use blockchain::storages::tree::MemoryTreeStorage;
+    use blockchain::transactions::TxRelId;
+    use blockchain::transactions::TxRel;
+    use blockchain::txtypes::register_collective;
+    use blockchain::txtypes::keypair;
+
+    let (secret_a, pubkey_a) = keypair(0x0A);
+    let (secret_b, pubkey_b) = keypair(0x0B);
+    let (secret_c, pubkey_c) = keypair(0x0C);
+
+    // collective b is registered on the chain, c on the
+    // fork, which also holds a faulty block registering a
+    // again
+
+    //                 .---------.
+    //            .--o | block a1|
+    //  .-------. |    '---------'      .---------.
+    //  |genesis| o                .--o | block b2|
+    //  '-------' |    .---------. |    '---------'
+    //            '--o | block b1| o
+    //                 '---------' |    .---------.
+    //                             '--o | faulty  |
+    //                                  '---------'
+
+    let genesis = Block::new([0; 32], None, 0, vec![register_collective(&secret_a)]);
+    let block_a1 = Block::new([0; 32], Some(&genesis), 1, vec![register_collective(&secret_b)]);
+    let block_b1 = Block::new([0; 32], Some(&genesis), 2, vec![register_collective(&secret_c)]);
+    let block_b2 = Block::new([0; 32], Some(&block_b1), 3, vec![]);
+    let faulty = Block::new([0; 32], Some(&block_b1), 4, vec![register_collective(&secret_a)]);
+
+    let genesis_tx_id = TxId::new(genesis.get_id(), TxIndex(0));
+    let block_a1_id = block_a1.get_id();
+    let block_b2_id = block_b2.get_id();
+    let faulty_id = faulty.get_id();
+
+    let mut storage = MemoryChainStorage::new();
+    let mut indexes = ChainIndexes::new();
+    let mut tree = MemoryTreeStorage::new();
+
+    for block in [genesis.clone(), block_a1.clone(), block_b1, block_b2, faulty]{
+        tree.append_verified_block(block).unwrap();
+    }
+    apply_block(&mut storage, &mut indexes, genesis).unwrap();
+    apply_block(&mut storage, &mut indexes, block_a1).unwrap();
+
+    // 1. a faulty branch leaves the chain and the indexes untouched
+
+    let result = switch_to_branch(&mut storage, &mut indexes, &tree, faulty_id);
+    assert!(result.is_err(), "Faulty branch was applied");
+    assert_eq!(storage.get_tail_block().unwrap().get_id(), block_a1_id);
+    assert!(indexes.collectives.get_registration(pubkey_b).is_some(),
+            "Collective of the chain was rolled back");
+    assert!(indexes.collectives.get_registration(pubkey_c).is_none(),
+            "Collective of the faulty branch was kept");
+
+    // 2. switching to a valid branch replaces
+    //    the collectives of the abandoned block
+
+    switch_to_branch(&mut storage, &mut indexes, &tree, block_b2_id).unwrap();
+    assert_eq!(storage.get_tail_block().unwrap().get_id(), block_b2_id);
+    assert!(indexes.collectives.get_registration(pubkey_a).is_some());
+    assert!(indexes.collectives.get_registration(pubkey_b).is_none(),
+            "Collective of the abandoned block was kept");
+    assert!(indexes.collectives.get_registration(pubkey_c).is_some(),
+            "Collective of the new branch is missing");
+
+    match *storage.get_transaction_state(genesis_tx_id).unwrap()
+                  .get_rel(TxRelId::CollectiveParent).unwrap(){
+        TxRel::OneToOne(None) => {},
+        _ => panic!("Registration of the genesis block was claimed")
+    }
+
+    // 3. the indexes follow a branch that ends in the chain
+
+    switch_to_branch(&mut storage, &mut indexes, &tree, genesis_tx_id.block_id).unwrap();
+    assert!(indexes.collectives.get_registration(pubkey_a).is_some());
+    assert!(indexes.collectives.get_registration(pubkey_c).is_none(),
+            "Collective of the rolled back block was kept");
+
+}
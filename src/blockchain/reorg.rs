@@ -0,0 +1,192 @@
+//
+//    Stachanov
+//    Copyright (C) 2017 Stachanov Developer Collective
+//
+//    This file is part of Stachanov.
+//
+//    This program is free software: you can redistribute it and/or
+//    modify it under the terms of the GNU Affero General Public
+//    License, version 3, as published by the Free Software Foundation.
+//
+//    This program is distributed in the hope that it will be useful,
+//    but WITHOUT ANY WARRANTY; without even the implied warranty of
+//    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//    GNU Affero General Public License for more details.
+//
+//    You should have received a copy of the
+//               GNU Affero General Public License
+//    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+
+use blockchain::block::Block;
+use blockchain::block::BlockId;
+use blockchain::block::BlockError;
+use blockchain::block::BlockErrorReason;
+use blockchain::traits::ChainStorage;
+use blockchain::traits::TreeStorage;
+
+/// Switches the chain consensus to the branch of the tree
+/// storage ending with the supplied block. The chain is
+/// rolled back to the common ancestor of the branch and the
+/// chain, then the blocks of the branch are applied one by
+/// one.
+///
+/// Applying a block is delegated to `apply_block`, which is
+/// responsible for appending the block and for updating the
+/// transaction states. If one of the branch blocks can't be
+/// applied, the chain is restored to its former state and
+/// the error is returned. Chains with indexes are switched
+/// with `processor::switch_to_branch`, which keeps the
+/// indexes in line with the chain.
+///
+/// Returns a BlockError with reason UnknownBlockId when the
+/// tip is unknown or its branch doesn't share a block with
+/// the chain.
+///
+/// # Arguments
+/// * `chain`: The storage holding the chain consensus
+/// * `tree`: The storage holding the competing forks
+/// * `tip`: The id of the last block of the new branch
+/// * `apply_block`: Appends a single block to the chain
+
+pub fn switch_to_branch<C, T, F>(chain: &mut C,
+                                 tree: &T,
+                                 tip: BlockId,
+                                 mut apply_block: F)
+                                 -> Result<(), BlockError>
+    where C: ChainStorage,
+          T: TreeStorage,
+          F: FnMut(&mut C, Block) -> Result<(), BlockError>{
+
+    let (ancestor_id, branch) = match (tree.get_common_ancestor(tip, chain),
+                                       tree.get_branch(tip, chain)){
+        (Some(ancestor_id), Some(branch)) => (ancestor_id, branch),
+        _ => {
+            let reason = BlockErrorReason::UnknownBlockId(tip);
+            return Err(BlockError::new(reason))
+        }
+    };
+
+    // remember the abandoned blocks, so we
+    // can restore them if the branch is faulty
+
+    let mut abandoned = vec![];
+    let mut current = chain.get_after(ancestor_id);
+    while let Some(block) = current{
+        current = chain.get_after(block.get_id());
+        abandoned.push(block);
+    }
+
+    chain.rollback_to(ancestor_id)?;
+
+    for block in branch{
+
+        if let Err(err) = apply_block(chain, block){
+
+            chain.rollback_to(ancestor_id)?;
+            for block in abandoned{
+                apply_block(chain, block)?;
+            }
+            return Err(err)
+
+        }
+
+    }
+
+    Ok(())
+
+}
+
+#[test]
+fn test_switch_to_branch(){
+
+    use blockchain::traits::BlockStorage;
+    use blockchain::storages::memory::MemoryChainStorage;
+    use blockchain::storages::tree::MemoryTreeStorage;
+    use blockchain::transactions::TxId;
+    use blockchain::transactions::TxIndex;
+    use blockchain::transactions::TxRel;
+    use blockchain::transactions::TxRelId;
+    use blockchain::transactions::TxState;
+    use blockchain::transactions::TxTotalRelState;
+    use blockchain::transactions::Transaction;
+
+    // the chain consists of genesis and block a1, tx 1 of
+    // the genesis block is claimed by the transaction of a1
+
+    //                 .---------.
+    //            .--o | block a1|
+    //  .-------. |    '---------'
+    //  |genesis| o
+    //  '-------' |    .---------.      .---------.
+    //            '--o | block b1| o--o | block b2|
+    //                 '---------'      '---------'
+
+    let genesis = Block::new([0; 32], None, 0, vec![Transaction::dummy()]);
+    let block_a1 = Block::new([0; 32], Some(&genesis), 1, vec![Transaction::dummy()]);
+    let block_b1 = Block::new([0; 32], Some(&genesis), 2, vec![]);
+    let block_b2 = Block::new([0; 32], Some(&block_b1), 3, vec![]);
+
+    let genesis_tx_id = TxId::new(genesis.get_id(), TxIndex(0));
+    let block_a1_tx_id = TxId::new(block_a1.get_id(), TxIndex(0));
+    let block_a1_id = block_a1.get_id();
+    let block_b2_id = block_b2.get_id();
+
+    let mut chain = MemoryChainStorage::new();
+    let mut tree = MemoryTreeStorage::new();
+
+    for block in [genesis.clone(), block_a1.clone(), block_b1, block_b2]{
+        tree.append_verified_block(block).unwrap();
+    }
+
+    chain.append_verified_block(genesis).unwrap();
+    let mut tx_state = TxState::new(TxTotalRelState::Claimable);
+    tx_state.add_one_to_one_rel(TxRelId::Dummy).unwrap();
+    chain.set_transaction_state(genesis_tx_id, tx_state.clone()).unwrap();
+
+    chain.append_verified_block(block_a1).unwrap();
+    tx_state.claim_rel(TxRelId::Dummy, block_a1_tx_id).unwrap();
+    chain.set_transaction_state(genesis_tx_id, tx_state).unwrap();
+
+    // 1. a faulty branch leaves the chain untouched
+
+    let result = switch_to_branch(&mut chain, &tree, block_b2_id, |chain, block| {
+        if block.get_id() == block_b2_id{
+            let reason = BlockErrorReason::OrphanedBlock(block_b2_id);
+            return Err(BlockError::new(reason))
+        }
+        chain.append_verified_block(block)
+    });
+
+    assert!(result.is_err(), "Faulty branch was applied");
+    assert_eq!(chain.get_tail_block().unwrap().get_id(), block_a1_id,
+               "Chain was not restored after applying a faulty branch");
+
+    // 2. switch to the longer fork
+
+    let result = switch_to_branch(&mut chain, &tree, block_b2_id, |chain, block| {
+        chain.append_verified_block(block)
+    });
+
+    assert!(result.is_ok(), "Could not switch to a valid branch");
+    assert_eq!(chain.get_tail_block().unwrap().get_id(), block_b2_id,
+               "Tip of the branch is not the tail after switching");
+    assert!(chain.get_block(block_a1_id).is_none(),
+            "Abandoned block is still part of the chain");
+
+    let tx_state = chain.get_transaction_state(genesis_tx_id).unwrap();
+    match *tx_state.get_rel(TxRelId::Dummy).unwrap(){
+        TxRel::OneToOne(None) => {},
+        _ => assert!(false, "Claim of an abandoned transaction was \
+                             not rolled back when switching branches")
+    }
+
+    // 3. unknown tips are rejected
+
+    let result = switch_to_branch(&mut chain, &tree, BlockId([0; 32]), |chain, block| {
+        chain.append_verified_block(block)
+    });
+    assert!(result.is_err(), "Switched to an unknown branch");
+
+}
@@ -0,0 +1,702 @@
+//
+//    Stachanov
+//    Copyright (C) 2017 Stachanov Developer Collective
+//
+//    This file is part of Stachanov.
+//
+//    This program is free software: you can redistribute it and/or
+//    modify it under the terms of the GNU Affero General Public
+//    License, version 3, as published by the Free Software Foundation.
+//
+//    This program is distributed in the hope that it will be useful,
+//    but WITHOUT ANY WARRANTY; without even the implied warranty of
+//    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//    GNU Affero General Public License for more details.
+//
+//    You should have received a copy of the
+//               GNU Affero General Public License
+//    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::fs;
+use std::fs::File;
+use std::fs::OpenOptions;
+use std::io;
+use std::io::BufReader;
+use std::io::Read;
+use std::io::Seek;
+use std::io::SeekFrom;
+use std::io::Write;
+use std::path::Path;
+use blockchain::block::Block;
+use blockchain::block::BlockId;
+use blockchain::block::BlockError;
+use blockchain::block::BlockErrorReason;
+use blockchain::header::BlockHeader;
+use blockchain::traits::BinFormat;
+use blockchain::traits::BlockStorage;
+use blockchain::traits::ChainStorage;
+use blockchain::transactions::TxId;
+use blockchain::transactions::TxState;
+#[cfg(test)]
+use blockchain::transactions::TxIndex;
+#[cfg(test)]
+use blockchain::transactions::TxRel;
+#[cfg(test)]
+use blockchain::transactions::TxRelId;
+#[cfg(test)]
+use blockchain::transactions::TxTotalRelState;
+use blockchain::transactions::TxProgError;
+use blockchain::transactions::TxProgErrorReason;
+use blockchain::transactions::Transaction;
+use blockchain::storages::utils::verify_tx_state_refs;
+use blockchain::utils::u32_to_u8le;
+use blockchain::utils::u8le_to_u32;
+use blockchain::utils::sha3_256;
+
+const SEGMENT_FILE_NAME: &str = "chain.seg";
+
+const RECORD_BLOCK: u8 = 0x01;
+const RECORD_TX_STATE: u8 = 0x02;
+
+// The segment file is a plain sequence of records. Records
+// are only ever appended, a later TxState record for the
+// same transaction supersedes the earlier ones:
+
+//    field            length
+//  .------------------------.
+//  | kind            | 1    |
+//  |------------------------|
+//  | payload_len     | 4    |
+//  |------------------------|
+//  | payload         | n    |
+//  |------------------------|
+//  | checksum        | 32   |  (sha3_256 of the payload)
+//  '------------------------'
+
+const RECORD_HEAD_LEN: u64 = 5;
+const RECORD_CHECKSUM_LEN: u64 = 32;
+
+/// `FileChainStorage` is a `ChainStorage` that persists blocks
+/// and transaction states in an append-only segment file. The
+/// storage only keeps an index from ids to file offsets in
+/// memory, which is rebuilt from the segment file on startup.
+///
+/// Since later records never influence earlier ones, a rollback
+/// simply cuts off the segment file at the record of the first
+/// removed block.
+///
+/// If the node crashed in the middle of a write, the segment
+/// file ends with an incomplete record. Such a record is
+/// detected by its size or its checksum and is discarded
+/// when the storage is opened again.
+
+pub struct FileChainStorage{
+    file: File,
+    file_len: u64,
+    index: SegmentIndex
+}
+
+/// `SegmentIndex` maps block ids, heights, timestamps and
+/// transaction ids to record offsets in the segment file
+
+struct SegmentIndex{
+    block_offsets: HashMap<BlockId, u64>,
+    heights: HashMap<BlockId, usize>,
+    chain: Vec<BlockId>,
+    timestamps: BTreeMap<u64, usize>,
+    tx_state_offsets: HashMap<TxId, u64>
+}
+
+impl FileChainStorage{
+
+    /// Opens the storage in the supplied directory. The
+    /// directory and the segment file are created if they
+    /// don't exist yet.
+    ///
+    /// # Arguments
+    /// * `dir`: The directory holding the segment file
+
+    pub fn open(dir: &Path) -> io::Result<FileChainStorage>{
+
+        fs::create_dir_all(dir)?;
+
+        let file = OpenOptions::new().read(true)
+                                     .write(true)
+                                     .create(true)
+                                     .truncate(false)
+                                     .open(dir.join(SEGMENT_FILE_NAME))?;
+
+        let mut storage = FileChainStorage{
+            file: file,
+            file_len: 0,
+            index: SegmentIndex::new()
+        };
+
+        storage.rebuild_index()?;
+        Ok(storage)
+
+    }
+
+    /// Rebuilds the in-memory index by scanning the whole
+    /// segment file. Everything from the first invalid record
+    /// on is cut off the file.
+
+    fn rebuild_index(&mut self) -> io::Result<()>{
+
+        self.index.clear();
+        self.file.seek(SeekFrom::Start(0))?;
+
+        let mut offset = 0;
+
+        {
+            let mut reader = BufReader::new(&self.file);
+            while let Some((kind, payload)) = read_record(&mut reader)?{
+
+                let record_len = RECORD_HEAD_LEN + payload.len() as u64
+                                 + RECORD_CHECKSUM_LEN;
+
+                if !self.index.add_record(kind, payload, offset){
+                    break;
+                }
+
+                offset += record_len;
+
+            }
+        }
+
+        // discard the torn tail (if any)
+
+        if self.file.metadata()?.len() != offset{
+            self.file.set_len(offset)?;
+            self.file.sync_all()?;
+        }
+
+        self.file_len = offset;
+        Ok(())
+
+    }
+
+    /// Appends a record to the end of the segment file and
+    /// returns its offset. If writing fails, the file is
+    /// truncated to its former length.
+    ///
+    /// # Arguments
+    /// * `kind`: The record kind
+    /// * `payload`: The serialized record data
+
+    fn append_record(&mut self, kind: u8, payload: &[u8]) -> io::Result<u64>{
+
+        let offset = self.file_len;
+        let payload_len_u8le = u32_to_u8le(payload.len() as u32);
+        let checksum = sha3_256(payload);
+
+        let record = [&[kind][..],
+                      &payload_len_u8le[..],
+                      payload,
+                      &checksum[..]].concat();
+
+        let result = self.file.seek(SeekFrom::Start(offset))
+                         .and_then(|_| self.file.write_all(&record))
+                         .and_then(|_| self.file.sync_data());
+
+        if let Err(err) = result{
+            let _ = self.file.set_len(offset);
+            return Err(err)
+        }
+
+        self.file_len += record.len() as u64;
+        Ok(offset)
+
+    }
+
+    /// Reads the payload of the record at the supplied offset.
+    /// Returns None if the record can't be read or has a
+    /// different kind.
+    ///
+    /// # Arguments
+    /// * `offset`: The offset of the record in the segment file
+    /// * `kind`: The expected record kind
+
+    fn read_payload(&self, offset: u64, kind: u8) -> Option<Vec<u8>>{
+
+        let mut file = &self.file;
+        file.seek(SeekFrom::Start(offset)).ok()?;
+
+        let mut reader = BufReader::new(file);
+        match read_record(&mut reader){
+            Ok(Some((read_kind, payload))) if read_kind == kind => Some(payload),
+            _ => None
+        }
+
+    }
+
+    /// Returns the block at the supplied height
+    /// or None, if the chain is shorter
+    /// * `height`: The position in the chain, starting at 0
+
+    fn get_at_height(&self, height: usize) -> Option<Block>{
+        let block_id = self.index.chain.get(height)?;
+        self.get_block(*block_id)
+    }
+
+}
+
+impl BlockStorage for FileChainStorage{
+
+    fn get_block(&self, block_id: BlockId) -> Option<Block>{
+        let offset = self.index.block_offsets.get(&block_id)?;
+        let payload = self.read_payload(*offset, RECORD_BLOCK)?;
+        Block::from_bytes(payload).ok()
+    }
+
+    fn get_header(&self, block_id: BlockId) -> Option<BlockHeader>{
+        let block = self.get_block(block_id)?;
+        Some(*block.get_header_ref())
+    }
+
+    fn append_verified_block(&mut self, block: Block)
+            -> Result<(), BlockError>{
+
+        let block_id = block.get_id();
+
+        if self.index.block_offsets.contains_key(&block_id){
+            let reason = BlockErrorReason::IdCollision(block_id);
+            return Err(BlockError::new(reason))
+        }
+
+        if !self.index.is_linked(&block){
+            let reason = BlockErrorReason::OrphanedBlock(block_id);
+            return Err(BlockError::new(reason))
+        }
+
+        let offset = match self.append_record(RECORD_BLOCK, &block.as_bytes()){
+            Ok(offset) => offset,
+            Err(err) => {
+                let reason = BlockErrorReason::StorageFailure(err.to_string());
+                return Err(BlockError::new(reason))
+            }
+        };
+
+        self.index.add_block(&block, offset);
+
+        Ok(())
+
+    }
+
+    fn get_transaction(&self, tx_id: TxId)
+            -> Option<Transaction>{
+        let block = self.get_block(tx_id.block_id)?;
+        block.get_transaction(tx_id.tx_index)
+    }
+
+    fn reset(&mut self){
+
+        self.index.clear();
+        self.file_len = 0;
+
+        let result = self.file.set_len(0)
+                         .and_then(|_| self.file.sync_all());
+
+        if let Err(err) = result{
+            panic!("Could not reset segment file: {}", err);
+        }
+
+    }
+
+}
+
+impl ChainStorage for FileChainStorage{
+
+    fn get_after(&self, block_id: BlockId) -> Option<Block>{
+        let height = self.index.heights.get(&block_id)?;
+        self.get_at_height(height + 1)
+    }
+
+    fn get_after_timestamp(&self, timestamp: u64) -> Option<Block>{
+        let first_after = timestamp.checked_add(1)?;
+        let (_, height) = self.index.timestamps.range(first_after..).next()?;
+        self.get_at_height(*height)
+    }
+
+    fn get_first_block(&self) -> Option<Block>{
+        self.get_at_height(0)
+    }
+
+    fn get_tail_block(&self) -> Option<Block>{
+        let height = self.index.chain.len().checked_sub(1)?;
+        self.get_at_height(height)
+    }
+
+    fn get_transaction_state(&self, tx_id: TxId) -> Option<TxState>{
+        let offset = self.index.tx_state_offsets.get(&tx_id)?;
+        let payload = self.read_payload(*offset, RECORD_TX_STATE)?;
+        let (_, tx_state) = decode_tx_state(&payload)?;
+        Some(tx_state)
+    }
+
+    fn set_transaction_state(&mut self,
+                             tx_id: TxId,
+                             tx_state: TxState) -> Result<(), TxProgError>{
+
+        verify_tx_state_refs(self, tx_id, &tx_state)?;
+
+        let payload = encode_tx_state(tx_id, &tx_state);
+
+        match self.append_record(RECORD_TX_STATE, &payload){
+            Ok(offset) => {
+                self.index.tx_state_offsets.insert(tx_id, offset);
+                Ok(())
+            },
+            Err(err) => {
+                let reason = TxProgErrorReason::StorageFailure(err.to_string());
+                Err(TxProgError::new(reason))
+            }
+        }
+
+    }
+
+    fn rollback_to(&mut self, block_id: BlockId) -> Result<(), BlockError>{
+
+        let height = match self.index.heights.get(&block_id){
+            Some(height) => *height,
+            None => {
+                let reason = BlockErrorReason::UnknownBlockId(block_id);
+                return Err(BlockError::new(reason))
+            }
+        };
+
+        let first_removed_id = match self.index.chain.get(height + 1){
+            Some(first_removed_id) => *first_removed_id,
+            None => return Ok(())
+        };
+
+        let offset = self.index.block_offsets[&first_removed_id];
+
+        let result = self.file.set_len(offset)
+                         .and_then(|_| self.file.sync_all())
+                         .and_then(|_| self.rebuild_index());
+
+        if let Err(err) = result{
+            let reason = BlockErrorReason::StorageFailure(err.to_string());
+            return Err(BlockError::new(reason))
+        }
+
+        Ok(())
+
+    }
+
+}
+
+// ------------------------------------------------------------------------
+
+/// Reads the next record from the reader. Returns None if the
+/// reader is exhausted or the record is incomplete or corrupted
+///
+/// # Arguments
+/// * `reader`: A reader positioned at the start of a record
+
+fn read_record<R>(reader: &mut R) -> io::Result<Option<(u8, Vec<u8>)>> where R: Read{
+
+    let mut head = [0; 5];
+    if !read_exact_or_eof(reader, &mut head)?{
+        return Ok(None)
+    }
+
+    let kind = head[0];
+    let mut payload_len_u8le = [0; 4];
+    payload_len_u8le.copy_from_slice(&head[1..5]);
+    let payload_len = u8le_to_u32(payload_len_u8le) as u64;
+
+    // don't trust the length field of a possibly torn
+    // record, read at most as much as the file provides
+
+    let mut payload = vec![];
+    reader.take(payload_len).read_to_end(&mut payload)?;
+    if payload.len() as u64 != payload_len{
+        return Ok(None)
+    }
+
+    let mut checksum = [0; 32];
+    if !read_exact_or_eof(reader, &mut checksum)?{
+        return Ok(None)
+    }
+
+    if checksum != sha3_256(&payload){
+        return Ok(None)
+    }
+
+    Ok(Some((kind, payload)))
+
+}
+
+/// Fills the buffer from the reader. Returns false if the
+/// reader ended before the buffer could be filled.
+
+fn read_exact_or_eof<R>(reader: &mut R, buf: &mut [u8]) -> io::Result<bool> where R: Read{
+
+    match reader.read_exact(buf){
+        Ok(()) => Ok(true),
+        Err(ref err) if err.kind() == io::ErrorKind::UnexpectedEof => Ok(false),
+        Err(err) => Err(err)
+    }
+
+}
+
+impl SegmentIndex{
+
+    /// Creates a new, empty `SegmentIndex`
+
+    fn new() -> SegmentIndex{
+        SegmentIndex{
+            block_offsets: HashMap::new(),
+            heights: HashMap::new(),
+            chain: vec![],
+            timestamps: BTreeMap::new(),
+            tx_state_offsets: HashMap::new()
+        }
+    }
+
+    /// Removes all entries from the index
+
+    fn clear(&mut self){
+        self.block_offsets.clear();
+        self.heights.clear();
+        self.chain.clear();
+        self.timestamps.clear();
+        self.tx_state_offsets.clear();
+    }
+
+    /// Checks if the block can be appended to the chain
+    /// * `block`: The block that should be appended
+
+    fn is_linked(&self, block: &Block) -> bool{
+        match (block.get_previous_id(), self.chain.last()){
+            (None, None) => true,
+            (Some(prev_block_id), Some(tail_block_id)) => prev_block_id == *tail_block_id,
+            _ => false
+        }
+    }
+
+    /// Adds a block to the index
+    ///
+    /// # Arguments
+    /// * `block`: The appended block
+    /// * `offset`: The offset of the block record
+
+    fn add_block(&mut self, block: &Block, offset: u64){
+
+        let block_id = block.get_id();
+        let height = self.chain.len();
+
+        // since timestamps are strictly increasing in a valid
+        // chain, we only keep the first block for a timestamp
+
+        self.timestamps.entry(block.get_timestamp()).or_insert(height);
+        self.heights.insert(block_id, height);
+        self.chain.push(block_id);
+        self.block_offsets.insert(block_id, offset);
+
+    }
+
+    /// Adds a record read from the segment file to the index.
+    /// Returns false if the record is invalid.
+    ///
+    /// # Arguments
+    /// * `kind`: The record kind
+    /// * `payload`: The record data
+    /// * `offset`: The offset of the record
+
+    fn add_record(&mut self, kind: u8, payload: Vec<u8>, offset: u64) -> bool{
+
+        match kind{
+
+            RECORD_BLOCK => {
+
+                let block = match Block::from_bytes(payload){
+                    Ok(block) => block,
+                    Err(_) => return false
+                };
+
+                if self.block_offsets.contains_key(&block.get_id()) ||
+                   !self.is_linked(&block){
+                    return false
+                }
+
+                self.add_block(&block, offset);
+                true
+
+            },
+
+            RECORD_TX_STATE => {
+
+                match decode_tx_state(&payload){
+                    Some((tx_id, _)) => {
+                        self.tx_state_offsets.insert(tx_id, offset);
+                        true
+                    },
+                    None => false
+                }
+
+            },
+
+            _ => false
+
+        }
+
+    }
+
+}
+
+// ------------------------------------------------------------------------
+
+// The payload of a TxState record is the encoded id of the
+// transaction followed by the canonical encoding of its state
+// (see the BinFormat implementations in transactions.rs):
+
+//    field            length
+//  .------------------------.
+//  | tx_id           | 34   |
+//  |------------------------|
+//  | tx_state        | n    |
+//  '------------------------'
+
+fn encode_tx_state(tx_id: TxId, tx_state: &TxState) -> Vec<u8>{
+    [&tx_id.as_bytes()[..], &tx_state.as_bytes()[..]].concat()
+}
+
+fn decode_tx_state(bytes: &[u8]) -> Option<(TxId, TxState)>{
+
+    if bytes.len() < 34{
+        return None
+    }
+
+    let tx_id = TxId::from_bytes(bytes[0..34].to_vec()).ok()?;
+    let tx_state = TxState::from_bytes(bytes[34..].to_vec()).ok()?;
+    Some((tx_id, tx_state))
+
+}
+
+// ------------------------------------------------------------------------
+
+#[cfg(test)]
+fn test_dir(name: &str) -> ::std::path::PathBuf{
+
+    let dir_name = format!("stachanov-{}-{}", name, ::std::process::id());
+    let dir = ::std::env::temp_dir().join(dir_name);
+    let _ = fs::remove_dir_all(&dir);
+    dir
+
+}
+
+#[test]
+fn test_file_chain_storage(){
+
+    use blockchain::storages::tests::basic::test_chain_storage;
+
+    let dir = test_dir("file-chain-storage");
+    let mut storage = FileChainStorage::open(&dir).unwrap();
+    test_chain_storage(&mut storage);
+
+    let _ = fs::remove_dir_all(&dir);
+
+}
+
+#[test]
+fn test_file_chain_storage_reopen(){
+
+    let dir = test_dir("file-chain-storage-reopen");
+
+    let first_block = Block::new([0; 32], None, 1, vec![Transaction::dummy()]);
+    let second_block = Block::new([0; 32], Some(&first_block), 2,
+                                  vec![Transaction::dummy()]);
+
+    let first_tx_id = TxId::new(first_block.get_id(), TxIndex(0));
+    let second_tx_id = TxId::new(second_block.get_id(), TxIndex(0));
+    let second_block_id = second_block.get_id();
+
+    {
+        let mut storage = FileChainStorage::open(&dir).unwrap();
+        storage.append_verified_block(first_block).unwrap();
+        storage.append_verified_block(second_block).unwrap();
+
+        let mut tx_state = TxState::new(TxTotalRelState::Claimable);
+        tx_state.add_one_to_one_rel(TxRelId::Dummy).unwrap();
+        storage.set_transaction_state(first_tx_id, tx_state).unwrap();
+
+        // a later state supersedes the earlier one
+
+        let mut tx_state = TxState::new(TxTotalRelState::Claimable);
+        tx_state.add_one_to_one_rel(TxRelId::Dummy).unwrap();
+        tx_state.claim_rel(TxRelId::Dummy, second_tx_id).unwrap();
+        storage.set_transaction_state(first_tx_id, tx_state).unwrap();
+    }
+
+    let storage = FileChainStorage::open(&dir).unwrap();
+
+    let tail_block = storage.get_tail_block().unwrap();
+    assert_eq!(tail_block.get_id(), second_block_id,
+               "Tail block was not restored after reopening the storage");
+
+    let tx_state = storage.get_transaction_state(first_tx_id).unwrap();
+    match *tx_state.get_rel(TxRelId::Dummy).unwrap(){
+        TxRel::OneToOne(Some(claimer_tx_id)) => {
+            assert_eq!(claimer_tx_id, second_tx_id,
+                       "Restored relationship has the wrong claimer");
+        },
+        _ => assert!(false, "Latest TxState was not restored after \
+                             reopening the storage")
+    }
+
+    let _ = fs::remove_dir_all(&dir);
+
+}
+
+#[test]
+fn test_file_chain_storage_torn_tail(){
+
+    let dir = test_dir("file-chain-storage-torn-tail");
+
+    let first_block = Block::new([0; 32], None, 1, vec![Transaction::dummy()]);
+    let second_block = Block::new([0; 32], Some(&first_block), 2,
+                                  vec![Transaction::dummy()]);
+    let first_block_id = first_block.get_id();
+    let second_block_id = second_block.get_id();
+
+    {
+        let mut storage = FileChainStorage::open(&dir).unwrap();
+        storage.append_verified_block(first_block).unwrap();
+        storage.append_verified_block(second_block.clone()).unwrap();
+    }
+
+    // simulate a crash in the middle of writing the second block
+
+    let path = dir.join(SEGMENT_FILE_NAME);
+    let file_len = fs::metadata(&path).unwrap().len();
+    let file = OpenOptions::new().write(true).open(&path).unwrap();
+    file.set_len(file_len - 10).unwrap();
+
+    let mut storage = FileChainStorage::open(&dir).unwrap();
+
+    let tail_block = storage.get_tail_block().unwrap();
+    assert_eq!(tail_block.get_id(), first_block_id,
+               "Torn tail record was not discarded");
+    assert!(storage.get_block(second_block_id).is_none(),
+            "Block of the torn tail record could be fetched");
+
+    // the storage must be writable after recovery
+
+    let result = storage.append_verified_block(second_block);
+    assert!(result.is_ok(), "Could not append block after recovery");
+
+    let storage = FileChainStorage::open(&dir).unwrap();
+    let tail_block = storage.get_tail_block().unwrap();
+    assert_eq!(tail_block.get_id(), second_block_id,
+               "Block appended after recovery was not persisted");
+
+    let _ = fs::remove_dir_all(&dir);
+
+}
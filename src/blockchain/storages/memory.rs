@@ -0,0 +1,237 @@
+//
+//    Stachanov
+//    Copyright (C) 2017 Stachanov Developer Collective
+//
+//    This file is part of Stachanov.
+//
+//    This program is free software: you can redistribute it and/or
+//    modify it under the terms of the GNU Affero General Public
+//    License, version 3, as published by the Free Software Foundation.
+//
+//    This program is distributed in the hope that it will be useful,
+//    but WITHOUT ANY WARRANTY; without even the implied warranty of
+//    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//    GNU Affero General Public License for more details.
+//
+//    You should have received a copy of the
+//               GNU Affero General Public License
+//    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use blockchain::block::Block;
+use blockchain::block::BlockId;
+use blockchain::block::BlockError;
+use blockchain::block::BlockErrorReason;
+use blockchain::header::BlockHeader;
+use blockchain::traits::BlockStorage;
+use blockchain::traits::ChainStorage;
+use blockchain::transactions::TxId;
+use blockchain::transactions::TxState;
+use blockchain::transactions::TxProgError;
+use blockchain::transactions::Transaction;
+use blockchain::storages::utils::verify_tx_state_refs;
+
+/// `MemoryChainStorage` is a `ChainStorage` that keeps the
+/// whole chain in memory. Blocks are indexed by their
+/// `BlockId`, by their height and by their timestamp.
+/// Nothing is persisted, so this storage is mainly useful
+/// for testing and as a cache in front of other storages.
+///
+/// To support rollbacks, every `TxState` update is journaled
+/// together with the replaced state under the height of the
+/// tail block at the time of the update.
+
+pub struct MemoryChainStorage{
+    blocks: HashMap<BlockId, Block>,
+    heights: HashMap<BlockId, usize>,
+    chain: Vec<BlockId>,
+    timestamps: BTreeMap<u64, usize>,
+    tx_states: HashMap<TxId, TxState>,
+    journal: Vec<Vec<(TxId, Option<TxState>)>>
+}
+
+impl MemoryChainStorage{
+
+    /// Creates a new, empty `MemoryChainStorage`
+
+    pub fn new() -> MemoryChainStorage{
+        MemoryChainStorage{
+            blocks: HashMap::new(),
+            heights: HashMap::new(),
+            chain: vec![],
+            timestamps: BTreeMap::new(),
+            tx_states: HashMap::new(),
+            journal: vec![]
+        }
+    }
+
+    /// Returns the block at the supplied height
+    /// or None, if the chain is shorter
+    /// * `height`: The position in the chain, starting at 0
+
+    fn get_at_height(&self, height: usize) -> Option<Block>{
+        let block_id = self.chain.get(height)?;
+        self.blocks.get(block_id).cloned()
+    }
+
+}
+
+impl BlockStorage for MemoryChainStorage{
+
+    fn get_block(&self, block_id: BlockId) -> Option<Block>{
+        self.blocks.get(&block_id).cloned()
+    }
+
+    fn get_header(&self, block_id: BlockId) -> Option<BlockHeader>{
+        let block = self.blocks.get(&block_id)?;
+        Some(*block.get_header_ref())
+    }
+
+    fn append_verified_block(&mut self, block: Block)
+            -> Result<(), BlockError>{
+
+        let block_id = block.get_id();
+
+        if self.blocks.contains_key(&block_id){
+            let reason = BlockErrorReason::IdCollision(block_id);
+            return Err(BlockError::new(reason))
+        }
+
+        // a block can only be appended to the tail of the
+        // chain, or as first block to an empty chain
+
+        let is_linked = match (block.get_previous_id(), self.chain.last()){
+            (None, None) => true,
+            (Some(prev_block_id), Some(tail_block_id)) => prev_block_id == *tail_block_id,
+            _ => false
+        };
+
+        if !is_linked{
+            let reason = BlockErrorReason::OrphanedBlock(block_id);
+            return Err(BlockError::new(reason))
+        }
+
+        // since timestamps are strictly increasing in a valid
+        // chain, we only keep the first block for a timestamp
+
+        let height = self.chain.len();
+        self.timestamps.entry(block.get_timestamp()).or_insert(height);
+        self.heights.insert(block_id, height);
+        self.chain.push(block_id);
+        self.journal.push(vec![]);
+        self.blocks.insert(block_id, block);
+
+        Ok(())
+
+    }
+
+    fn get_transaction(&self, tx_id: TxId)
+            -> Option<Transaction>{
+        let block = self.blocks.get(&tx_id.block_id)?;
+        block.get_transaction(tx_id.tx_index)
+    }
+
+    fn reset(&mut self){
+        self.blocks.clear();
+        self.heights.clear();
+        self.chain.clear();
+        self.timestamps.clear();
+        self.tx_states.clear();
+        self.journal.clear();
+    }
+
+}
+
+impl ChainStorage for MemoryChainStorage{
+
+    fn get_after(&self, block_id: BlockId) -> Option<Block>{
+        let height = self.heights.get(&block_id)?;
+        self.get_at_height(height + 1)
+    }
+
+    fn get_after_timestamp(&self, timestamp: u64) -> Option<Block>{
+        let first_after = timestamp.checked_add(1)?;
+        let (_, height) = self.timestamps.range(first_after..).next()?;
+        self.get_at_height(*height)
+    }
+
+    fn get_first_block(&self) -> Option<Block>{
+        self.get_at_height(0)
+    }
+
+    fn get_tail_block(&self) -> Option<Block>{
+        let height = self.chain.len().checked_sub(1)?;
+        self.get_at_height(height)
+    }
+
+    fn get_transaction_state(&self, tx_id: TxId) -> Option<TxState>{
+        self.tx_states.get(&tx_id).cloned()
+    }
+
+    fn set_transaction_state(&mut self,
+                             tx_id: TxId,
+                             tx_state: TxState) -> Result<(), TxProgError>{
+
+        verify_tx_state_refs(self, tx_id, &tx_state)?;
+
+        // the chain can't be empty, since the
+        // transaction was found in a block
+
+        let replaced = self.tx_states.insert(tx_id, tx_state);
+        if let Some(entries) = self.journal.last_mut(){
+            entries.push((tx_id, replaced));
+        }
+
+        Ok(())
+
+    }
+
+    fn rollback_to(&mut self, block_id: BlockId) -> Result<(), BlockError>{
+
+        let height = match self.heights.get(&block_id){
+            Some(height) => *height,
+            None => {
+                let reason = BlockErrorReason::UnknownBlockId(block_id);
+                return Err(BlockError::new(reason))
+            }
+        };
+
+        while self.chain.len() > height + 1{
+
+            // undo the state updates in reverse order
+
+            if let Some(entries) = self.journal.pop(){
+                for (tx_id, replaced) in entries.into_iter().rev(){
+                    match replaced{
+                        Some(tx_state) => self.tx_states.insert(tx_id, tx_state),
+                        None => self.tx_states.remove(&tx_id)
+                    };
+                }
+            }
+
+            if let Some(removed_id) = self.chain.pop(){
+                self.heights.remove(&removed_id);
+                self.blocks.remove(&removed_id);
+            }
+
+        }
+
+        self.timestamps.retain(|_, block_height| *block_height <= height);
+        Ok(())
+
+    }
+
+}
+
+#[test]
+fn test_memory_chain_storage(){
+
+    use blockchain::storages::tests::basic::test_chain_storage;
+
+    let mut storage = MemoryChainStorage::new();
+    test_chain_storage(&mut storage);
+
+}
@@ -18,4 +18,8 @@
 //    along with this program.  If not, see <http://www.gnu.org/licenses/>.
 //
 
+pub mod memory;
+pub mod file;
+pub mod tree;
+mod utils;
 mod tests;
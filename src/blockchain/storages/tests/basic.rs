@@ -19,8 +19,10 @@
 //
 
 use blockchain::traits::ChainStorage;
+use blockchain::traits::TreeStorage;
 use blockchain::storages::tests::blockstorage;
 use blockchain::storages::tests::chainstorage;
+use blockchain::storages::tests::treestorage;
 
 /// Checks basic functionality of a struct implementing
 /// the `ChainStorage` trait
@@ -34,6 +36,8 @@ pub fn test_chain_storage<T>(mut storage: &mut T) where T: ChainStorage{
 
     blockstorage::test_fetch_block::<T>(&mut storage);
     storage.reset();
+    blockstorage::test_fetch_header::<T>(&mut storage);
+    storage.reset();
     blockstorage::test_block_id_collision::<T>(&mut storage);
     storage.reset();
     blockstorage::test_append_orphaned::<T>(&mut storage);
@@ -61,4 +65,37 @@ pub fn test_chain_storage<T>(mut storage: &mut T) where T: ChainStorage{
     chainstorage::txstates::test_txstate_one_to_many_rel::<T>(&mut storage);
     storage.reset();
 
+    chainstorage::rollback::test_rollback_blocks::<T>(&mut storage);
+    storage.reset();
+    chainstorage::rollback::test_rollback_tx_states::<T>(&mut storage);
+    storage.reset();
+
+}
+
+/// Checks basic functionality of a struct implementing
+/// the `TreeStorage` trait
+///
+/// # Arguments
+/// * `storage`: A struct implementing `TreeStorage`
+
+pub fn test_tree_storage<T>(mut storage: &mut T) where T: TreeStorage{
+
+    storage.reset();
+
+    blockstorage::test_fetch_block::<T>(&mut storage);
+    storage.reset();
+    blockstorage::test_fetch_header::<T>(&mut storage);
+    storage.reset();
+    blockstorage::test_block_id_collision::<T>(&mut storage);
+    storage.reset();
+    blockstorage::test_append_orphaned::<T>(&mut storage);
+    storage.reset();
+    blockstorage::test_fetch_dummy_transaction::<T>(&mut storage);
+    storage.reset();
+
+    treestorage::test_tips::<T>(&mut storage);
+    storage.reset();
+    treestorage::test_common_ancestor::<T>(&mut storage);
+    storage.reset();
+
 }
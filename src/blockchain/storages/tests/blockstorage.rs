@@ -26,6 +26,7 @@ use blockchain::block::BlockErrorReason;
 use blockchain::transactions::TxId;
 use blockchain::transactions::TxIndex;
 use blockchain::transactions::Transaction;
+use blockchain::transactions::TxPayload;
 
 /// Tests if a storage appends and fetches
 /// `Block`s correctly
@@ -184,7 +185,8 @@ pub fn test_append_orphaned<T>(storage: &mut T) where T: BlockStorage{
 
 pub fn test_fetch_dummy_transaction<T>(storage: &mut T) where T: BlockStorage{
 
-    let block = Block::new([0; 32], None, 0, vec![Transaction::Dummy]);
+    let dummy = Transaction::new(TxPayload::Dummy, vec![[0x01; 32]]);
+    let block = Block::new([0; 32], None, 0, vec![dummy.clone()]);
     let block_id = block.get_id();
 
     let result = storage.append_verified_block(block);
@@ -197,10 +199,10 @@ pub fn test_fetch_dummy_transaction<T>(storage: &mut T) where T: BlockStorage{
 
     if let Some(transaction) = fetched_transaction{
 
-        // dummy transactions have a
-        // hash value of all zeroes
+        // the fetched transaction must be the
+        // one that was stored in the block
 
-        assert_eq!(transaction.to_sha3_hash(), [0; 32],
+        assert_eq!(transaction.to_sha3_hash(), dummy.to_sha3_hash(),
                    "Fetched dummy transaction, but
                     hashes don't match");
 
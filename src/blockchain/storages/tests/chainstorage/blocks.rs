@@ -21,7 +21,8 @@
 use blockchain::traits::ChainStorage;
 use blockchain::block::Block;
 
-/// Tests if a storage implements `get_after` correctly
+/// Tests if a storage implements `get_after` and
+/// `get_first_block` correctly
 ///
 /// # Arguments
 /// * `storage`: A storage object that implements
@@ -29,6 +30,9 @@ use blockchain::block::Block;
 
 pub fn test_get_after<T>(storage: &mut T) where T: ChainStorage{
 
+    assert!(storage.get_first_block().is_none(),
+            "empty storage has a first block");
+
     // append a chain of different blocks (all unverified)
 
     let mut blocks = vec![];
@@ -52,6 +56,10 @@ pub fn test_get_after<T>(storage: &mut T) where T: ChainStorage{
 
     }
 
+    let first_id = storage.get_first_block().map(|block| block.get_id());
+    assert_eq!(first_id, Some(blocks[0].get_id()),
+               "get_first_block doesn't fetch the genesis block");
+
     // check if they get returned in the same
     // order by get_after
 
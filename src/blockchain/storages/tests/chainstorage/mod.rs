@@ -19,4 +19,5 @@
 //
 
 pub mod blocks;
+pub mod rollback;
 pub mod txstates;
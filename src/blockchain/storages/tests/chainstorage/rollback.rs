@@ -0,0 +1,204 @@
+//
+//    Stachanov
+//    Copyright (C) 2017 Stachanov Developer Collective
+//
+//    This file is part of Stachanov.
+//
+//    This program is free software: you can redistribute it and/or
+//    modify it under the terms of the GNU Affero General Public
+//    License, version 3, as published by the Free Software Foundation.
+//
+//    This program is distributed in the hope that it will be useful,
+//    but WITHOUT ANY WARRANTY; without even the implied warranty of
+//    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//    GNU Affero General Public License for more details.
+//
+//    You should have received a copy of the
+//               GNU Affero General Public License
+//    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+
+use blockchain::traits::ChainStorage;
+use blockchain::block::Block;
+use blockchain::block::BlockId;
+use blockchain::block::BlockErrorReason;
+use blockchain::transactions::TxId;
+use blockchain::transactions::TxIndex;
+use blockchain::transactions::TxRel;
+use blockchain::transactions::TxRelId;
+use blockchain::transactions::TxState;
+use blockchain::transactions::TxTotalRelState;
+use blockchain::transactions::Transaction;
+
+/// Appends a chain of `len` blocks (each holding a single
+/// dummy transaction) to the storage and returns the blocks
+
+fn append_chain<T>(storage: &mut T, len: u64) -> Vec<Block> where T: ChainStorage{
+
+    let mut blocks: Vec<Block> = vec![];
+
+    for i in 0..len{
+
+        let block = Block::new([0; 32], blocks.last(), i + 1,
+                               vec![Transaction::dummy()]);
+
+        let result = storage.append_verified_block(block.clone());
+        assert!(result.is_ok(), "Could not append block");
+
+        blocks.push(block);
+
+    }
+
+    blocks
+
+}
+
+/// Tests if a storage removes blocks on
+/// rollback correctly
+///
+/// # Arguments
+/// * `storage`: A storage object that implements
+///              the `ChainStorage` trait
+
+pub fn test_rollback_blocks<T>(storage: &mut T) where T: ChainStorage{
+
+    let blocks = append_chain(storage, 5);
+
+    // rolling back to the tail doesn't change anything
+
+    let result = storage.rollback_to(blocks[4].get_id());
+    assert!(result.is_ok(), "Rollback to the tail block failed");
+    assert_eq!(storage.get_tail_block().unwrap().get_id(), blocks[4].get_id(),
+               "Rollback to the tail block changed the tail");
+
+    // roll back the last three blocks
+
+    let result = storage.rollback_to(blocks[1].get_id());
+    assert!(result.is_ok(), "Rollback to an existing block failed");
+
+    assert_eq!(storage.get_tail_block().unwrap().get_id(), blocks[1].get_id(),
+               "Rollback target is not the tail block after rollback");
+    assert!(storage.get_after(blocks[1].get_id()).is_none(),
+            "Rollback target still has a successor after rollback");
+    assert!(storage.get_after_timestamp(2).is_none(),
+            "get_after_timestamp returned a removed block");
+
+    for block in &blocks[2..]{
+        assert!(storage.get_block(block.get_id()).is_none(),
+                "Removed block could still be fetched after rollback");
+    }
+
+    // the chain can be extended again
+
+    let result = storage.append_verified_block(blocks[2].clone());
+    assert!(result.is_ok(), "Could not append block after rollback");
+
+    // rolling back to an unknown block fails
+
+    let result = storage.rollback_to(BlockId([0; 32]));
+    assert!(result.is_err(), "Rollback to an unknown block succeeded");
+
+    if let Err(err) = result{
+        match err.reason{
+            BlockErrorReason::UnknownBlockId(block_id) => {
+                assert_eq!(block_id, BlockId([0; 32]),
+                           "BlockErrorReason::UnknownBlockId wrapped \
+                            the wrong block id");
+            },
+            _ => {
+                assert!(false, "Storage returned a wrong error when \
+                                rolling back to an unknown block");
+            }
+        }
+    }
+
+}
+
+/// Tests if a storage restores `TxState`s
+/// (including 1:1 and 1:n claims) on rollback
+///
+/// # Arguments
+/// * `storage`: A storage object that implements
+///              the `ChainStorage` trait
+
+pub fn test_rollback_tx_states<T>(storage: &mut T) where T: ChainStorage{
+
+    // three blocks with one transaction each. The 1:1 and
+    // the 1:n relationship of tx 1 are claimed by tx 2 and
+    // tx 3, the state of tx 2 is set after block 3 is added
+
+    //  .---------.      .---------.      .---------.
+    //  | block 1 | o--o | block 2 | o--o | block 3 |
+    //  |---------|      |---------|      |---------|
+    //  |  tx 1   | <--- |  tx 2   | <--- |  tx 3   |
+    //  '---------'      '---------'      '---------'
+
+    let blocks = append_chain(storage, 1);
+    let tx_id_1 = TxId::new(blocks[0].get_id(), TxIndex(0));
+
+    let one_to_one_rel = TxRelId::Dummy;
+
+    let mut tx_state = TxState::new(TxTotalRelState::Claimable);
+    tx_state.add_one_to_one_rel(one_to_one_rel.clone()).unwrap();
+    storage.set_transaction_state(tx_id_1, tx_state.clone()).unwrap();
+
+    let block_2 = Block::new([0; 32], Some(&blocks[0]), 2, vec![Transaction::dummy()]);
+    let tx_id_2 = TxId::new(block_2.get_id(), TxIndex(0));
+    storage.append_verified_block(block_2.clone()).unwrap();
+
+    tx_state.claim_rel(one_to_one_rel.clone(), tx_id_2).unwrap();
+    storage.set_transaction_state(tx_id_1, tx_state.clone()).unwrap();
+
+    let block_3 = Block::new([0; 32], Some(&block_2), 3, vec![Transaction::dummy()]);
+    let tx_id_3 = TxId::new(block_3.get_id(), TxIndex(0));
+    storage.append_verified_block(block_3).unwrap();
+
+    let mut tx_state_2 = TxState::new(TxTotalRelState::Claimable);
+    tx_state_2.add_one_to_many_rel(TxRelId::Dummy).unwrap();
+    tx_state_2.claim_rel(TxRelId::Dummy, tx_id_3).unwrap();
+    storage.set_transaction_state(tx_id_2, tx_state_2).unwrap();
+
+    tx_state.set_total_rel_state(TxTotalRelState::Finalized(tx_id_3));
+    storage.set_transaction_state(tx_id_1, tx_state).unwrap();
+
+    // 1. roll back block 3: tx 1 must be claimable again, but
+    //    its 1:1 relationship must still be claimed by tx 2.
+    //    tx 2 must have no state, since its state was set after
+    //    block 3 was appended
+
+    storage.rollback_to(block_2.get_id()).unwrap();
+
+    let fetched_tx_state = storage.get_transaction_state(tx_id_1).unwrap();
+    match *fetched_tx_state.get_total_rel_state(){
+        TxTotalRelState::Claimable => {},
+        _ => assert!(false, "Total relationship state was not \
+                             restored on rollback")
+    }
+
+    match *fetched_tx_state.get_rel(one_to_one_rel.clone()).unwrap(){
+        TxRel::OneToOne(Some(claimer_tx_id)) => {
+            assert_eq!(claimer_tx_id, tx_id_2,
+                       "1:1 relationship claimed before the rollback \
+                        target has the wrong claimer after rollback");
+        },
+        _ => assert!(false, "1:1 relationship claimed before the \
+                             rollback target was reset on rollback")
+    }
+
+    assert!(storage.get_transaction_state(tx_id_2).is_none(),
+            "TxState set after the rollback target was not \
+             removed on rollback");
+
+    // 2. roll back block 2: the 1:1 relationship of
+    //    tx 1 must be unclaimed again
+
+    storage.rollback_to(blocks[0].get_id()).unwrap();
+
+    let fetched_tx_state = storage.get_transaction_state(tx_id_1).unwrap();
+    match *fetched_tx_state.get_rel(one_to_one_rel).unwrap(){
+        TxRel::OneToOne(None) => {},
+        _ => assert!(false, "1:1 claim was not removed on rollback")
+    }
+
+}
@@ -80,7 +80,7 @@ pub fn test_txstate_claimable<T>(storage: &mut T) where T: ChainStorage{
 
     // append block with one transaction
 
-    let block = Block::new([0; 32], None, 0, vec![Transaction::Dummy]);
+    let block = Block::new([0; 32], None, 0, vec![Transaction::dummy()]);
     let block_id = block.get_id();
     let tx_id = TxId::new(block_id, TxIndex(0));
 
@@ -133,7 +133,7 @@ pub fn test_txstate_unclaimable<T>(storage: &mut T) where T: ChainStorage{
 
     // append block with one transaction
 
-    let block = Block::new([0; 32], None, 0, vec![Transaction::Dummy]);
+    let block = Block::new([0; 32], None, 0, vec![Transaction::dummy()]);
     let block_id = block.get_id();
     let tx_id = TxId::new(block_id, TxIndex(0));
 
@@ -195,15 +195,15 @@ pub fn test_txstate_finalized<T>(storage: &mut T) where T: ChainStorage{
     //  '---------'
 
     let first_block = Block::new([0; 32], None, 0,
-                                 vec![Transaction::Dummy,
-                                      Transaction::Dummy]);
+                                 vec![Transaction::dummy(),
+                                      Transaction::dummy()]);
 
     let first_block_id = first_block.get_id();
     let first_block_tx_id = TxId::new(first_block_id, TxIndex(0));
     let first_block_tx_id2 = TxId::new(first_block_id, TxIndex(1));
 
     let second_block = Block::new([0; 32], Some(&first_block), 0,
-                                  vec![Transaction::Dummy]);
+                                  vec![Transaction::dummy()]);
 
     let second_block_id = second_block.get_id();
     let second_block_tx_id = TxId::new(second_block_id, TxIndex(0));
@@ -411,15 +411,15 @@ pub fn test_txstate_one_to_one_rel<T>(storage: &mut T) where T: ChainStorage{
     //  '---------'
 
     let first_block = Block::new([0; 32], None, 0,
-                                 vec![Transaction::Dummy,
-                                      Transaction::Dummy]);
+                                 vec![Transaction::dummy(),
+                                      Transaction::dummy()]);
 
     let first_block_id = first_block.get_id();
     let first_block_tx_id = TxId::new(first_block_id, TxIndex(0));
     let first_block_tx_id2 = TxId::new(first_block_id, TxIndex(1));
 
     let second_block = Block::new([0; 32], Some(&first_block), 0,
-                                  vec![Transaction::Dummy]);
+                                  vec![Transaction::dummy()]);
 
     let second_block_id = second_block.get_id();
     let second_block_tx_id = TxId::new(second_block_id, TxIndex(0));
@@ -665,21 +665,21 @@ pub fn test_txstate_one_to_many_rel<T>(storage: &mut T) where T: ChainStorage{
 
 
     let first_block = Block::new([0; 32], None, 0,
-                                 vec![Transaction::Dummy]);
+                                 vec![Transaction::dummy()]);
 
     let first_block_id = first_block.get_id();
     let first_block_tx_id = TxId::new(first_block_id, TxIndex(0));
 
     let second_block = Block::new([0; 32], Some(&first_block), 0,
-                                  vec![Transaction::Dummy,
-                                       Transaction::Dummy]);
+                                  vec![Transaction::dummy(),
+                                       Transaction::dummy()]);
 
     let second_block_id = second_block.get_id();
     let second_block_tx_id = TxId::new(second_block_id, TxIndex(0));
     let second_block_tx_id2 = TxId::new(second_block_id, TxIndex(1));
 
     let third_block = Block::new([0; 32], Some(&second_block), 0,
-                                  vec![Transaction::Dummy]);
+                                  vec![Transaction::dummy()]);
 
     let third_block_id = third_block.get_id();
     let third_block_tx_id = TxId::new(third_block_id, TxIndex(0));
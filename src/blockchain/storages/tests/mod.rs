@@ -21,3 +21,4 @@
 pub mod basic;
 mod blockstorage;
 mod chainstorage;
+mod treestorage;
@@ -0,0 +1,173 @@
+//
+//    Stachanov
+//    Copyright (C) 2017 Stachanov Developer Collective
+//
+//    This file is part of Stachanov.
+//
+//    This program is free software: you can redistribute it and/or
+//    modify it under the terms of the GNU Affero General Public
+//    License, version 3, as published by the Free Software Foundation.
+//
+//    This program is distributed in the hope that it will be useful,
+//    but WITHOUT ANY WARRANTY; without even the implied warranty of
+//    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//    GNU Affero General Public License for more details.
+//
+//    You should have received a copy of the
+//               GNU Affero General Public License
+//    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+
+use blockchain::traits::BlockStorage;
+use blockchain::traits::TreeStorage;
+use blockchain::block::Block;
+use blockchain::storages::memory::MemoryChainStorage;
+use blockchain::transactions::Transaction;
+
+/// Tests if a storage keeps track of the tips of
+/// all forks as well as their heights and their
+/// cumulative transaction counts
+///
+/// # Arguments
+/// * `storage`: A storage object that implements
+///              the `TreeStorage` trait
+
+pub fn test_tips<T>(storage: &mut T) where T: TreeStorage{
+
+    // create two competing forks with
+    // the following layout
+
+    //                 .---------.      .---------.
+    //            .--o | block a1| o--o | block a2|
+    //  .-------. |    |  1 tx   |      |  2 tx   |
+    //  |genesis| o    '---------'      '---------'
+    //  | 1 tx  | |    .---------.
+    //  '-------' '--o | block b1|
+    //                 |  0 tx   |
+    //                 '---------'
+
+    let genesis = Block::new([0; 32], None, 0, vec![Transaction::dummy()]);
+    let block_a1 = Block::new([0; 32], Some(&genesis), 1,
+                              vec![Transaction::dummy()]);
+    let block_a2 = Block::new([0; 32], Some(&block_a1), 2,
+                              vec![Transaction::dummy(),
+                                   Transaction::dummy()]);
+    let block_b1 = Block::new([0; 32], Some(&genesis), 3, vec![]);
+
+    let genesis_id = genesis.get_id();
+    let block_a1_id = block_a1.get_id();
+    let block_a2_id = block_a2.get_id();
+    let block_b1_id = block_b1.get_id();
+
+    for block in [genesis, block_a1, block_b1, block_a2]{
+        let result = storage.append_verified_block(block);
+        assert!(result.is_ok(), "Could not append block with known predecessor");
+    }
+
+    let tips = storage.get_tips();
+    assert_eq!(tips, vec![block_a2_id, block_b1_id],
+               "Storage didn't return the tips of both forks \
+                ordered by descending height");
+
+    assert_eq!(storage.get_height(genesis_id), Some(0),
+               "Root block doesn't have height 0");
+    assert_eq!(storage.get_height(block_a2_id), Some(2),
+               "Storage returned a wrong height for a tip");
+    assert_eq!(storage.get_height(block_b1_id), Some(1),
+               "Storage returned a wrong height for a tip");
+
+    assert_eq!(storage.get_cumulative_tx_count(block_a1_id), Some(2),
+               "Storage returned a wrong cumulative transaction count");
+    assert_eq!(storage.get_cumulative_tx_count(block_a2_id), Some(4),
+               "Storage returned a wrong cumulative transaction count");
+    assert_eq!(storage.get_cumulative_tx_count(block_b1_id), Some(1),
+               "Storage returned a wrong cumulative transaction count");
+
+    // a second root must be rejected
+
+    let second_genesis = Block::new([0; 32], None, 4, vec![]);
+    let result = storage.append_verified_block(second_genesis);
+    assert!(result.is_err(), "Storage accepted a second root block");
+
+}
+
+/// Tests if a storage finds the common ancestor of
+/// a fork with the chain consensus and fetches the
+/// blocks of the fork in chain order
+///
+/// # Arguments
+/// * `storage`: A storage object that implements
+///              the `TreeStorage` trait
+
+pub fn test_common_ancestor<T>(storage: &mut T) where T: TreeStorage{
+
+    // the chain consists of genesis and block a1,
+    // the tree additionally holds two fork blocks
+
+    //                 .---------.      .---------.
+    //            .--o | block a1| o--o | block a2|
+    //  .-------. |    '---------'      '---------'
+    //  |genesis| o
+    //  '-------' |    .---------.      .---------.
+    //            '--o | block b1| o--o | block b2|
+    //                 '---------'      '---------'
+
+    let genesis = Block::new([0; 32], None, 0, vec![]);
+    let block_a1 = Block::new([0; 32], Some(&genesis), 1, vec![]);
+    let block_a2 = Block::new([0; 32], Some(&block_a1), 2, vec![]);
+    let block_b1 = Block::new([0; 32], Some(&genesis), 3, vec![]);
+    let block_b2 = Block::new([0; 32], Some(&block_b1), 4, vec![]);
+
+    let genesis_id = genesis.get_id();
+    let block_a1_id = block_a1.get_id();
+    let block_a2_id = block_a2.get_id();
+    let block_b1_id = block_b1.get_id();
+    let block_b2_id = block_b2.get_id();
+
+    let mut chain = MemoryChainStorage::new();
+    chain.append_verified_block(genesis.clone()).unwrap();
+    chain.append_verified_block(block_a1.clone()).unwrap();
+
+    for block in [genesis, block_a1, block_a2, block_b1, block_b2]{
+        let result = storage.append_verified_block(block);
+        assert!(result.is_ok(), "Could not append block with known predecessor");
+    }
+
+    // fork b branches off at the genesis block
+
+    let ancestor = storage.get_common_ancestor(block_b2_id, &chain);
+    assert_eq!(ancestor, Some(genesis_id),
+               "Storage returned a wrong common ancestor");
+
+    let branch = storage.get_branch(block_b2_id, &chain).unwrap();
+    let branch_ids: Vec<_> = branch.iter().map(|block| block.get_id()).collect();
+    assert_eq!(branch_ids, vec![block_b1_id, block_b2_id],
+               "Storage didn't return the fork blocks in chain order");
+
+    // fork a extends the chain
+
+    let ancestor = storage.get_common_ancestor(block_a2_id, &chain);
+    assert_eq!(ancestor, Some(block_a1_id),
+               "Storage returned a wrong common ancestor \
+                for a block extending the chain");
+
+    let branch = storage.get_branch(block_a2_id, &chain).unwrap();
+    assert_eq!(branch.len(), 1,
+               "Branch of a block extending the chain has \
+                the wrong length");
+
+    // blocks of the chain have no fork blocks
+
+    let branch = storage.get_branch(block_a1_id, &chain).unwrap();
+    assert!(branch.is_empty(),
+            "Branch of a block in the chain is not empty");
+
+    // unknown blocks have no common ancestor
+
+    let unknown_block = Block::new([0; 32], None, 5, vec![]);
+    let ancestor = storage.get_common_ancestor(unknown_block.get_id(), &chain);
+    assert!(ancestor.is_none(),
+            "Storage returned a common ancestor for an unknown block");
+
+}
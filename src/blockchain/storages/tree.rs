@@ -0,0 +1,208 @@
+//
+//    Stachanov
+//    Copyright (C) 2017 Stachanov Developer Collective
+//
+//    This file is part of Stachanov.
+//
+//    This program is free software: you can redistribute it and/or
+//    modify it under the terms of the GNU Affero General Public
+//    License, version 3, as published by the Free Software Foundation.
+//
+//    This program is distributed in the hope that it will be useful,
+//    but WITHOUT ANY WARRANTY; without even the implied warranty of
+//    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//    GNU Affero General Public License for more details.
+//
+//    You should have received a copy of the
+//               GNU Affero General Public License
+//    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+use blockchain::block::Block;
+use blockchain::block::BlockId;
+use blockchain::block::BlockError;
+use blockchain::block::BlockErrorReason;
+use blockchain::header::BlockHeader;
+use blockchain::traits::BlockStorage;
+use blockchain::traits::ChainStorage;
+use blockchain::traits::TreeStorage;
+use blockchain::transactions::TxId;
+use blockchain::transactions::Transaction;
+
+/// `TreeNode` wraps a block in the tree together with
+/// data aggregated over the block and its predecessors
+
+struct TreeNode{
+    block: Block,
+    height: u64,
+    cumulative_tx_count: u64
+}
+
+/// `MemoryTreeStorage` is a `TreeStorage` that keeps the whole
+/// block tree in memory. The tree starts with a single root
+/// block (the genesis block), every other block must have a
+/// known predecessor.
+
+pub struct MemoryTreeStorage{
+    nodes: HashMap<BlockId, TreeNode>,
+    tips: HashSet<BlockId>
+}
+
+impl MemoryTreeStorage{
+
+    /// Creates a new, empty `MemoryTreeStorage`
+
+    pub fn new() -> MemoryTreeStorage{
+        MemoryTreeStorage{
+            nodes: HashMap::new(),
+            tips: HashSet::new()
+        }
+    }
+
+}
+
+impl BlockStorage for MemoryTreeStorage{
+
+    fn get_block(&self, block_id: BlockId) -> Option<Block>{
+        let node = self.nodes.get(&block_id)?;
+        Some(node.block.clone())
+    }
+
+    fn get_header(&self, block_id: BlockId) -> Option<BlockHeader>{
+        let node = self.nodes.get(&block_id)?;
+        Some(*node.block.get_header_ref())
+    }
+
+    fn append_verified_block(&mut self, block: Block)
+            -> Result<(), BlockError>{
+
+        let block_id = block.get_id();
+
+        if self.nodes.contains_key(&block_id){
+            let reason = BlockErrorReason::IdCollision(block_id);
+            return Err(BlockError::new(reason))
+        }
+
+        // the root can only be added to an empty tree, all
+        // other blocks need a predecessor inside the tree
+
+        let tx_count = block.get_transaction_count() as u64;
+
+        let (height, cumulative_tx_count) = match block.get_previous_id(){
+            None if self.nodes.is_empty() => (0, tx_count),
+            Some(prev_block_id) if self.nodes.contains_key(&prev_block_id) => {
+                let parent = &self.nodes[&prev_block_id];
+                (parent.height + 1, parent.cumulative_tx_count + tx_count)
+            },
+            _ => {
+                let reason = BlockErrorReason::OrphanedBlock(block_id);
+                return Err(BlockError::new(reason))
+            }
+        };
+
+        if let Some(prev_block_id) = block.get_previous_id(){
+            self.tips.remove(&prev_block_id);
+        }
+
+        self.tips.insert(block_id);
+        self.nodes.insert(block_id, TreeNode{
+            block: block,
+            height: height,
+            cumulative_tx_count: cumulative_tx_count
+        });
+
+        Ok(())
+
+    }
+
+    fn get_transaction(&self, tx_id: TxId)
+            -> Option<Transaction>{
+        let node = self.nodes.get(&tx_id.block_id)?;
+        node.block.get_transaction(tx_id.tx_index)
+    }
+
+    fn reset(&mut self){
+        self.nodes.clear();
+        self.tips.clear();
+    }
+
+}
+
+impl TreeStorage for MemoryTreeStorage{
+
+    fn get_tips(&self) -> Vec<BlockId>{
+
+        let mut tips: Vec<BlockId> = self.tips.iter().cloned().collect();
+
+        // order by descending height, ties are broken by the
+        // block id, so the order is the same on every node
+
+        tips.sort_by(|a, b| {
+            let BlockId(a_hash) = *a;
+            let BlockId(b_hash) = *b;
+            self.nodes[b].height.cmp(&self.nodes[a].height)
+                                .then(a_hash.cmp(&b_hash))
+        });
+
+        tips
+
+    }
+
+    fn get_height(&self, block_id: BlockId) -> Option<u64>{
+        let node = self.nodes.get(&block_id)?;
+        Some(node.height)
+    }
+
+    fn get_cumulative_tx_count(&self, block_id: BlockId) -> Option<u64>{
+        let node = self.nodes.get(&block_id)?;
+        Some(node.cumulative_tx_count)
+    }
+
+    fn get_common_ancestor<C>(&self, block_id: BlockId, chain: &C)
+            -> Option<BlockId> where C: ChainStorage{
+
+        let mut current_id = block_id;
+
+        loop{
+            let node = self.nodes.get(&current_id)?;
+            if chain.get_header(current_id).is_some(){
+                return Some(current_id)
+            }
+            current_id = node.block.get_previous_id()?;
+        }
+
+    }
+
+    fn get_branch<C>(&self, block_id: BlockId, chain: &C)
+            -> Option<Vec<Block>> where C: ChainStorage{
+
+        let ancestor_id = self.get_common_ancestor(block_id, chain)?;
+
+        let mut branch = vec![];
+        let mut current_id = block_id;
+
+        while current_id != ancestor_id{
+            let node = self.nodes.get(&current_id)?;
+            branch.push(node.block.clone());
+            current_id = node.block.get_previous_id()?;
+        }
+
+        branch.reverse();
+        Some(branch)
+
+    }
+
+}
+
+#[test]
+fn test_memory_tree_storage(){
+
+    use blockchain::storages::tests::basic::test_tree_storage;
+
+    let mut storage = MemoryTreeStorage::new();
+    test_tree_storage(&mut storage);
+
+}
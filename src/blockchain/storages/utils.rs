@@ -0,0 +1,108 @@
+//
+//    Stachanov
+//    Copyright (C) 2017 Stachanov Developer Collective
+//
+//    This file is part of Stachanov.
+//
+//    This program is free software: you can redistribute it and/or
+//    modify it under the terms of the GNU Affero General Public
+//    License, version 3, as published by the Free Software Foundation.
+//
+//    This program is distributed in the hope that it will be useful,
+//    but WITHOUT ANY WARRANTY; without even the implied warranty of
+//    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//    GNU Affero General Public License for more details.
+//
+//    You should have received a copy of the
+//               GNU Affero General Public License
+//    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+
+use blockchain::traits::ChainStorage;
+use blockchain::transactions::TxId;
+use blockchain::transactions::TxRel;
+use blockchain::transactions::TxState;
+use blockchain::transactions::TxTotalRelState;
+use blockchain::transactions::TxProgError;
+use blockchain::transactions::TxProgErrorReason;
+
+/// Checks if a `TxState` may be saved for a transaction in
+/// the supplied storage. This is meant as a common safeguard
+/// for all `ChainStorage` implementations.
+///
+/// Returns a TxProgError with reason UnknownTx, if either the
+/// transaction itself or one of the transactions referenced
+/// in the state (finalizer or claimers) does not exist, and
+/// a TxProgError with reason RefOrderError if one of the
+/// referenced transactions does not live in a subsequent block.
+///
+/// # Arguments
+/// * `storage`: A storage object that implements
+///              the `ChainStorage` trait
+/// * `tx_id`: The id of the transaction the state belongs to
+/// * `tx_state`: The transaction state that should be checked
+
+pub fn verify_tx_state_refs<T>(storage: &T,
+                               tx_id: TxId,
+                               tx_state: &TxState)
+                               -> Result<(), TxProgError> where T: ChainStorage{
+
+    let tx_height = match get_tx_height(storage, tx_id){
+        Some(height) => height,
+        None => {
+            let reason = TxProgErrorReason::UnknownTx(tx_id);
+            return Err(TxProgError::new(reason))
+        }
+    };
+
+    // collect all transactions the state refers to
+
+    let mut ref_tx_ids = vec![];
+
+    if let TxTotalRelState::Finalized(fin_tx_id) = *tx_state.get_total_rel_state(){
+        ref_tx_ids.push(fin_tx_id);
+    }
+
+    for relationship in tx_state.get_rel_map().values(){
+        match *relationship{
+            TxRel::OneToOne(Some(claimer_tx_id)) => ref_tx_ids.push(claimer_tx_id),
+            TxRel::OneToOne(None) => {},
+            TxRel::OneToMany(ref claimer_tx_ids) => ref_tx_ids.extend(claimer_tx_ids)
+        }
+    }
+
+    // referenced transactions must exist and
+    // must live in one of the subsequent blocks
+
+    for ref_tx_id in ref_tx_ids{
+
+        let ref_height = match get_tx_height(storage, ref_tx_id){
+            Some(height) => height,
+            None => {
+                let reason = TxProgErrorReason::UnknownTx(ref_tx_id);
+                return Err(TxProgError::new(reason))
+            }
+        };
+
+        if ref_height <= tx_height{
+            let reason = TxProgErrorReason::RefOrderError;
+            return Err(TxProgError::new(reason))
+        }
+
+    }
+
+    Ok(())
+
+}
+
+/// Returns the index of the block holding the transaction
+/// or None, if the transaction doesn't exist in the storage
+
+fn get_tx_height<T>(storage: &T, tx_id: TxId) -> Option<u64> where T: ChainStorage{
+
+    let header = storage.get_header(tx_id.block_id)?;
+    storage.get_transaction(tx_id)?;
+    Some(header.get_index())
+
+}
@@ -104,6 +104,11 @@ pub trait ChainStorage: BlockStorage {
 
     fn get_after_timestamp(&self, timestamp: u64) -> Option<Block>;
 
+    /// Fetches the first block (the genesis block)
+    /// in the whole chain
+
+    fn get_first_block(&self) -> Option<Block>;
+
     /// Fetches the last block in the whole chain
 
     fn get_tail_block(&self) -> Option<Block>;
@@ -126,6 +131,71 @@ pub trait ChainStorage: BlockStorage {
                              tx_id: TxId,
                              tx_state: TxState) -> Result<(), TxProgError>;
 
+    /// Removes all blocks after the supplied block from the
+    /// chain and restores every `TxState` to the state it had
+    /// before the first removed block was appended. States of
+    /// transactions in removed blocks are removed as well.
+    ///
+    /// Returns a BlockError with reason UnknownBlockId when
+    /// the block is not part of the chain.
+    ///
+    /// * `block_id`: The id of the new tail block
+
+    fn rollback_to(&mut self, block_id: BlockId) -> Result<(), BlockError>;
+
+}
+
+/// The `TreeStorage` trait must be implemented by all storage
+/// backends saving blocks that do not (yet) belong to the chain
+/// consensus. In contrast to `ChainStorage`, blocks can have
+/// multiple successors, so the blocks form a tree of competing
+/// forks. A block can be appended as soon as its predecessor
+/// is known to the storage.
+
+pub trait TreeStorage: BlockStorage {
+
+    /// Fetches the ids of all blocks that don't have
+    /// a successor (yet), ordered by descending height
+
+    fn get_tips(&self) -> Vec<BlockId>;
+
+    /// Fetches the height of a block, that is the number of
+    /// its predecessors in the tree
+    /// * `block_id`: The block identifier (equivalent to the
+    ///               the sha3 hash of the block header)
+
+    fn get_height(&self, block_id: BlockId) -> Option<u64>;
+
+    /// Fetches the number of transactions in the block and
+    /// all of its predecessors
+    /// * `block_id`: The block identifier (equivalent to the
+    ///               the sha3 hash of the block header)
+
+    fn get_cumulative_tx_count(&self, block_id: BlockId) -> Option<u64>;
+
+    /// Fetches the id of the latest predecessor of a block
+    /// (or the block itself) that is part of the supplied chain
+    /// storage. Returns None if the block is unknown or the
+    /// branch doesn't share any block with the chain.
+    ///
+    /// # Arguments
+    /// * `block_id`: The block identifier (typically a tip)
+    /// * `chain`: The storage holding the chain consensus
+
+    fn get_common_ancestor<C>(&self, block_id: BlockId, chain: &C)
+            -> Option<BlockId> where C: ChainStorage;
+
+    /// Fetches all blocks of the branch ending with the supplied
+    /// block, that don't belong to the chain storage (in chain
+    /// order, starting with the successor of the common ancestor)
+    ///
+    /// # Arguments
+    /// * `block_id`: The block identifier (typically a tip)
+    /// * `chain`: The storage holding the chain consensus
+
+    fn get_branch<C>(&self, block_id: BlockId, chain: &C)
+            -> Option<Vec<Block>> where C: ChainStorage;
+
 }
 
 /// `BinFormat` defines an interface for serializing
@@ -18,24 +18,563 @@
 //    along with this program.  If not, see <http://www.gnu.org/licenses/>.
 //
 
+extern crate crypto;
+use self::crypto::ed25519;
 use std::collections::HashMap;
 use std::error::Error;
 use std::fmt;
 use blockchain::traits::Hashable;
+use blockchain::traits::BinFormat;
 use blockchain::block::BlockId;
+use blockchain::txtypes::collective::CollectiveRegistration;
+use blockchain::txtypes::trust::TrustEstablish;
+use blockchain::txtypes::trust::TrustCancel;
+use blockchain::txtypes::production::Order;
+use blockchain::txtypes::production::OrderRejection;
+use blockchain::txtypes::production::ValueEstimation;
+use blockchain::txtypes::production::ProductionStart;
+use blockchain::txtypes::production::ProductionOutput;
+use blockchain::txtypes::allocation::WorkloadAllocation;
+use blockchain::txtypes::allocation::ConsumptiveResourceAllocation;
+use blockchain::txtypes::allocation::MopAllocation;
+use blockchain::txtypes::allocation::MopDeallocation;
+use blockchain::txtypes::product::ProductTypeDefinition;
+use blockchain::txtypes::revision::FinalRevision;
+use blockchain::txtypes::coupon::CouponClaim;
+use blockchain::txtypes::coupon::Appropriation;
+use blockchain::txtypes::resource::Collectivization;
+use blockchain::txtypes::resource::OwnershipTransfer;
+use blockchain::txtypes::resource::WriteOff;
+use blockchain::txtypes::inventory::Dispatch;
+use blockchain::txtypes::inventory::InventoryReconciliation;
+use blockchain::txtypes::settlement::Settlement;
+use blockchain::txtypes::vote::ProductionVote;
+use blockchain::txtypes::vote::Ballot;
+use blockchain::txtypes::governance::ClassificationProposal;
+use blockchain::errors::BinFormatError;
+use blockchain::errors::BinFormatErrorReason;
+use blockchain::errors::VerificationError;
+use blockchain::errors::VerificationErrorReason::InvalidTxSignature;
+use blockchain::utils::u16_to_u8le;
+use blockchain::utils::u32_to_u8le;
+use blockchain::utils::sha3_256;
+use blockchain::utils::BinReader;
+
+/// `MAX_SIGNERS` is the maximum number of signers of a
+/// single transaction (the count is encoded as u8)
+
+pub const MAX_SIGNERS: usize = 0xFF;
+
+/// `TxPayload` holds the type specific content of a transaction.
+/// Every variant corresponds to exactly one transaction type,
+/// so the type tag of a transaction is derived from its payload.
+///
+/// * `Dummy`: A transaction without any content. It doesn't
+///         carry any meaning and is only used as filler
+///         (e.g. in tests)
+/// * `CollectiveRegistration`: Registers a collective or
+///         attaches it to a parent collective
+/// * `TrustEstablish`: Establishes strong trust between
+///         two collectives
+/// * `TrustCancel`: Cancels strong trust between
+///         two collectives
+/// * `Order`: Orders products from a collective
+/// * `OrderRejection`: Rejects an order
+/// * `ValueEstimation`: Accepts an order and estimates
+///         the value of the ordered products
+/// * `ProductionStart`: Starts the production of an estimated order
+/// * `WorkloadAllocation`: Allocates working time to a production
+/// * `ConsumptiveResourceAllocation`: Consumes a resource in a production
+/// * `MopAllocation`: Allocates a mean of production
+/// * `MopDeallocation`: Deallocates a mean of production
+/// * `ProductionOutput`: Finalizes a production with its output
+/// * `ProductTypeDefinition`: Registers a product type
+/// * `FinalRevision`: Fixes the tax rate of a period
+/// * `CouponClaim`: Transforms a workload into labor coupons
+/// * `Appropriation`: Deletes coupons used at a dispatcher
+/// * `Collectivization`: Brings a resource into the chain
+/// * `OwnershipTransfer`: Passes a resource to another collective
+/// * `WriteOff`: Removes a lost or broken resource
+/// * `Dispatch`: Hands products of a C2I collective to consumers
+/// * `InventoryReconciliation`: Corrects a C2I stock after a physical inventory
+/// * `Settlement`: Settles a subsystem split by a trust cancellation
+/// * `ProductionVote`: Opens the vote on a large value estimation
+/// * `Ballot`: Approves or rejects a production vote or proposal
+/// * `ClassificationProposal`: Proposes a commons classification
+
+#[derive(Clone)]
+#[derive(Debug)]
+pub enum TxPayload{
+    Dummy,
+    CollectiveRegistration(CollectiveRegistration),
+    TrustEstablish(TrustEstablish),
+    TrustCancel(TrustCancel),
+    Order(Order),
+    OrderRejection(OrderRejection),
+    ValueEstimation(ValueEstimation),
+    ProductionStart(ProductionStart),
+    WorkloadAllocation(WorkloadAllocation),
+    ConsumptiveResourceAllocation(ConsumptiveResourceAllocation),
+    MopAllocation(MopAllocation),
+    MopDeallocation(MopDeallocation),
+    ProductionOutput(ProductionOutput),
+    ProductTypeDefinition(ProductTypeDefinition),
+    FinalRevision(FinalRevision),
+    CouponClaim(CouponClaim),
+    Appropriation(Appropriation),
+    Collectivization(Collectivization),
+    OwnershipTransfer(OwnershipTransfer),
+    WriteOff(WriteOff),
+    Dispatch(Dispatch),
+    InventoryReconciliation(InventoryReconciliation),
+    Settlement(Settlement),
+    ProductionVote(ProductionVote),
+    Ballot(Ballot),
+    ClassificationProposal(ClassificationProposal)
+}
+
+impl TxPayload{
+
+    /// Returns the type tag of the payload:
+    ///
+    /// * 0x00: `Dummy`
+    /// * 0x01: `CollectiveRegistration`
+    /// * 0x02: `TrustEstablish`
+    /// * 0x03: `TrustCancel`
+    /// * 0x04: `Order`
+    /// * 0x05: `OrderRejection`
+    /// * 0x06: `ValueEstimation`
+    /// * 0x07: `ProductionStart`
+    /// * 0x08: `WorkloadAllocation`
+    /// * 0x09: `ConsumptiveResourceAllocation`
+    /// * 0x0A: `MopAllocation`
+    /// * 0x0B: `MopDeallocation`
+    /// * 0x0C: `ProductionOutput`
+    /// * 0x0D: `ProductTypeDefinition`
+    /// * 0x0E: `FinalRevision`
+    /// * 0x0F: `CouponClaim`
+    /// * 0x10: `Appropriation`
+    /// * 0x11: `Collectivization`
+    /// * 0x12: `OwnershipTransfer`
+    /// * 0x13: `WriteOff`
+    /// * 0x14: `Dispatch`
+    /// * 0x15: `InventoryReconciliation`
+    /// * 0x16: `Settlement`
+    /// * 0x17: `ProductionVote`
+    /// * 0x18: `Ballot`
+    /// * 0x19: `ClassificationProposal`
+
+    pub fn get_type(&self) -> u8{
+        match *self{
+            TxPayload::Dummy => 0x00,
+            TxPayload::CollectiveRegistration(_) => 0x01,
+            TxPayload::TrustEstablish(_) => 0x02,
+            TxPayload::TrustCancel(_) => 0x03,
+            TxPayload::Order(_) => 0x04,
+            TxPayload::OrderRejection(_) => 0x05,
+            TxPayload::ValueEstimation(_) => 0x06,
+            TxPayload::ProductionStart(_) => 0x07,
+            TxPayload::WorkloadAllocation(_) => 0x08,
+            TxPayload::ConsumptiveResourceAllocation(_) => 0x09,
+            TxPayload::MopAllocation(_) => 0x0A,
+            TxPayload::MopDeallocation(_) => 0x0B,
+            TxPayload::ProductionOutput(_) => 0x0C,
+            TxPayload::ProductTypeDefinition(_) => 0x0D,
+            TxPayload::FinalRevision(_) => 0x0E,
+            TxPayload::CouponClaim(_) => 0x0F,
+            TxPayload::Appropriation(_) => 0x10,
+            TxPayload::Collectivization(_) => 0x11,
+            TxPayload::OwnershipTransfer(_) => 0x12,
+            TxPayload::WriteOff(_) => 0x13,
+            TxPayload::Dispatch(_) => 0x14,
+            TxPayload::InventoryReconciliation(_) => 0x15,
+            TxPayload::Settlement(_) => 0x16,
+            TxPayload::ProductionVote(_) => 0x17,
+            TxPayload::Ballot(_) => 0x18,
+            TxPayload::ClassificationProposal(_) => 0x19
+        }
+    }
+
+    /// Returns the payload as an u8 vector
+
+    pub fn as_bytes(&self) -> Vec<u8>{
+        match *self{
+            TxPayload::Dummy => vec![],
+            TxPayload::CollectiveRegistration(ref payload) => payload.as_bytes(),
+            TxPayload::TrustEstablish(ref payload) => payload.as_bytes(),
+            TxPayload::TrustCancel(ref payload) => payload.as_bytes(),
+            TxPayload::Order(ref payload) => payload.as_bytes(),
+            TxPayload::OrderRejection(ref payload) => payload.as_bytes(),
+            TxPayload::ValueEstimation(ref payload) => payload.as_bytes(),
+            TxPayload::ProductionStart(ref payload) => payload.as_bytes(),
+            TxPayload::WorkloadAllocation(ref payload) => payload.as_bytes(),
+            TxPayload::ConsumptiveResourceAllocation(ref payload) => payload.as_bytes(),
+            TxPayload::MopAllocation(ref payload) => payload.as_bytes(),
+            TxPayload::MopDeallocation(ref payload) => payload.as_bytes(),
+            TxPayload::ProductionOutput(ref payload) => payload.as_bytes(),
+            TxPayload::ProductTypeDefinition(ref payload) => payload.as_bytes(),
+            TxPayload::FinalRevision(ref payload) => payload.as_bytes(),
+            TxPayload::CouponClaim(ref payload) => payload.as_bytes(),
+            TxPayload::Appropriation(ref payload) => payload.as_bytes(),
+            TxPayload::Collectivization(ref payload) => payload.as_bytes(),
+            TxPayload::OwnershipTransfer(ref payload) => payload.as_bytes(),
+            TxPayload::WriteOff(ref payload) => payload.as_bytes(),
+            TxPayload::Dispatch(ref payload) => payload.as_bytes(),
+            TxPayload::InventoryReconciliation(ref payload) => payload.as_bytes(),
+            TxPayload::Settlement(ref payload) => payload.as_bytes(),
+            TxPayload::ProductionVote(ref payload) => payload.as_bytes(),
+            TxPayload::Ballot(ref payload) => payload.as_bytes(),
+            TxPayload::ClassificationProposal(ref payload) => payload.as_bytes()
+        }
+    }
+
+    /// Creates a new payload from a type tag and a byte vector
+    ///
+    /// * `tx_type`: The type tag of the transaction
+    /// * `bytes`: A byte vector
 
-// NOTE: This module currently only consists of
-// a dummy implementation
+    pub fn from_bytes(tx_type: u8, bytes: Vec<u8>) -> Result<TxPayload, BinFormatError>{
+
+        match tx_type{
+            0x00 => {
+                BinReader::new(&bytes).finish()?;
+                Ok(TxPayload::Dummy)
+            },
+            0x01 => {
+                let payload = CollectiveRegistration::from_bytes(bytes)?;
+                Ok(TxPayload::CollectiveRegistration(payload))
+            },
+            0x02 => {
+                let payload = TrustEstablish::from_bytes(bytes)?;
+                Ok(TxPayload::TrustEstablish(payload))
+            },
+            0x03 => {
+                let payload = TrustCancel::from_bytes(bytes)?;
+                Ok(TxPayload::TrustCancel(payload))
+            },
+            0x04 => {
+                let payload = Order::from_bytes(bytes)?;
+                Ok(TxPayload::Order(payload))
+            },
+            0x05 => {
+                let payload = OrderRejection::from_bytes(bytes)?;
+                Ok(TxPayload::OrderRejection(payload))
+            },
+            0x06 => {
+                let payload = ValueEstimation::from_bytes(bytes)?;
+                Ok(TxPayload::ValueEstimation(payload))
+            },
+            0x07 => {
+                let payload = ProductionStart::from_bytes(bytes)?;
+                Ok(TxPayload::ProductionStart(payload))
+            },
+            0x08 => {
+                let payload = WorkloadAllocation::from_bytes(bytes)?;
+                Ok(TxPayload::WorkloadAllocation(payload))
+            },
+            0x09 => {
+                let payload = ConsumptiveResourceAllocation::from_bytes(bytes)?;
+                Ok(TxPayload::ConsumptiveResourceAllocation(payload))
+            },
+            0x0A => {
+                let payload = MopAllocation::from_bytes(bytes)?;
+                Ok(TxPayload::MopAllocation(payload))
+            },
+            0x0B => {
+                let payload = MopDeallocation::from_bytes(bytes)?;
+                Ok(TxPayload::MopDeallocation(payload))
+            },
+            0x0C => {
+                let payload = ProductionOutput::from_bytes(bytes)?;
+                Ok(TxPayload::ProductionOutput(payload))
+            },
+            0x0D => {
+                let payload = ProductTypeDefinition::from_bytes(bytes)?;
+                Ok(TxPayload::ProductTypeDefinition(payload))
+            },
+            0x0E => {
+                let payload = FinalRevision::from_bytes(bytes)?;
+                Ok(TxPayload::FinalRevision(payload))
+            },
+            0x0F => {
+                let payload = CouponClaim::from_bytes(bytes)?;
+                Ok(TxPayload::CouponClaim(payload))
+            },
+            0x10 => {
+                let payload = Appropriation::from_bytes(bytes)?;
+                Ok(TxPayload::Appropriation(payload))
+            },
+            0x11 => {
+                let payload = Collectivization::from_bytes(bytes)?;
+                Ok(TxPayload::Collectivization(payload))
+            },
+            0x12 => {
+                let payload = OwnershipTransfer::from_bytes(bytes)?;
+                Ok(TxPayload::OwnershipTransfer(payload))
+            },
+            0x13 => {
+                let payload = WriteOff::from_bytes(bytes)?;
+                Ok(TxPayload::WriteOff(payload))
+            },
+            0x14 => {
+                let payload = Dispatch::from_bytes(bytes)?;
+                Ok(TxPayload::Dispatch(payload))
+            },
+            0x15 => {
+                let payload = InventoryReconciliation::from_bytes(bytes)?;
+                Ok(TxPayload::InventoryReconciliation(payload))
+            },
+            0x16 => {
+                let payload = Settlement::from_bytes(bytes)?;
+                Ok(TxPayload::Settlement(payload))
+            },
+            0x17 => {
+                let payload = ProductionVote::from_bytes(bytes)?;
+                Ok(TxPayload::ProductionVote(payload))
+            },
+            0x18 => {
+                let payload = Ballot::from_bytes(bytes)?;
+                Ok(TxPayload::Ballot(payload))
+            },
+            0x19 => {
+                let payload = ClassificationProposal::from_bytes(bytes)?;
+                Ok(TxPayload::ClassificationProposal(payload))
+            },
+            _ => {
+                let field_name = String::from("type");
+                let reason = BinFormatErrorReason::InvalidFieldData(field_name);
+                Err(BinFormatError::new(reason))
+            }
+        }
+
+    }
+
+}
+
+/// `Transaction` is the envelope around a payload. Besides
+/// the payload it holds the public keys of all parties that
+/// have to sign the transaction and their ed25519 signatures
+/// (in the same order as the public keys). Which parties have
+/// to sign depends on the type of the transaction and is not
+/// checked in here.
 
 #[derive(Clone)]
-pub enum Transaction{
-    Dummy
+#[derive(Debug)]
+pub struct Transaction{
+    version: u16,
+    payload: TxPayload,
+    signer_pubkeys: Vec<[u8; 32]>,
+    signatures: Vec<[u8; 64]>
 }
 
+// the hash of a transaction covers the complete signed
+// content (everything but the signatures), so it can
+// be used for the merkle tree and signed at once.
+
 impl Hashable for Transaction{
 
     fn to_sha3_hash(&self) -> [u8; 32]{
-        [0; 32]
+        sha3_256(&self.message_as_bytes())
+    }
+
+}
+
+impl Transaction{
+
+    /// Creates a new unsigned Transaction
+    ///
+    /// * `payload`: The type specific content
+    /// * `signer_pubkeys`: The public keys of all parties
+    ///                     that have to sign the transaction
+
+    pub fn new(payload: TxPayload, signer_pubkeys: Vec<[u8; 32]>) -> Transaction{
+
+        assert!(signer_pubkeys.len() <= MAX_SIGNERS,
+                "Transaction exceeds the maximum number of signers");
+
+        let signatures = vec![[0; 64]; signer_pubkeys.len()];
+
+        Transaction{
+            version: 0x0,
+            payload: payload,
+            signer_pubkeys: signer_pubkeys,
+            signatures: signatures
+        }
+
+    }
+
+    /// Creates a new unsigned dummy Transaction
+    /// without any signers
+
+    pub fn dummy() -> Transaction{
+        Transaction::new(TxPayload::Dummy, vec![])
+    }
+
+    /// Gets the version of the transaction
+
+    pub fn get_version(&self) -> u16{
+        self.version
+    }
+
+    /// Gets the type tag of the transaction
+
+    pub fn get_type(&self) -> u8{
+        self.payload.get_type()
+    }
+
+    /// Gets a reference to the payload
+
+    pub fn get_payload(&self) -> &TxPayload{
+        &self.payload
+    }
+
+    /// Gets the public keys of all signers
+
+    pub fn get_signer_pubkeys(&self) -> &Vec<[u8; 32]>{
+        &self.signer_pubkeys
+    }
+
+    /// Returns the message segment of the Transaction
+    /// as u8 vector. The message segment is all data
+    /// without the trailing signatures
+
+    fn message_as_bytes(&self) -> Vec<u8>{
+
+        let version_u8le = u16_to_u8le(self.version);
+        let payload = self.payload.as_bytes();
+        let payload_len_u8le = u32_to_u8le(payload.len() as u32);
+
+        let mut bytes = [&version_u8le[..],
+                         &[self.payload.get_type()],
+                         &payload_len_u8le[..],
+                         &payload[..],
+                         &[self.signer_pubkeys.len() as u8]].concat();
+
+        for signer_pubkey in &self.signer_pubkeys{
+            bytes.extend_from_slice(signer_pubkey);
+        }
+
+        bytes
+
+    }
+
+    /// Verifies the signatures of all signers
+
+    pub fn verify_signatures(&self) -> Result<(), VerificationError>{
+
+        let message = self.message_as_bytes();
+
+        for (pubkey, signature) in self.signer_pubkeys.iter().zip(&self.signatures){
+            if !ed25519::verify(&message, pubkey, signature){
+                let err = VerificationError::new(InvalidTxSignature);
+                return Err(err)
+            }
+        }
+        Ok(())
+
+    }
+
+    /// Signs the Transaction. The public key belonging
+    /// to the secret key must be one of the signer keys.
+    ///
+    /// * `secret_key`: The ed25519 secret key
+    ///                 (non-detached as 64 byte array)
+
+    pub fn sign(&mut self, secret_key: &[u8]){
+
+        // the non-detached secret key contains
+        // the public key in its second half
+
+        let position = self.signer_pubkeys.iter()
+                                          .position(|pubkey| pubkey[..] == secret_key[32..]);
+
+        let index = position.expect("Secret key doesn't belong to a signer");
+        let message = self.message_as_bytes();
+        self.signatures[index] = ed25519::signature(&message, secret_key);
+
+    }
+
+}
+
+impl BinFormat<Transaction> for Transaction{
+
+    // This section implements (de)serialization methods for
+    // Transaction. The current (version 0x0) byte format is:
+
+    //    field            length
+    //  .------------------------.
+    //  | version         | 2    |
+    //  |------------------------|
+    //  | type            | 1    |  see TxPayload::get_type
+    //  |------------------------|
+    //  | payload_len     | 4    |
+    //  |------------------------|
+    //  | payload         | n    |
+    //  |------------------------|
+    //  | signer_count    | 1    |
+    //  |------------------------|
+    //  | signer_pubkey   | 32   |  <-- repeated signer_count times
+    //  |------------------------|
+    //  | signature       | 64   |  <-- repeated signer_count times
+    //  '------------------------'
+
+    /// Returns the complete Transaction (including signatures)
+    /// as an u8 vector
+
+    fn as_bytes(&self) -> Vec<u8>{
+
+        let mut bytes = self.message_as_bytes();
+
+        for signature in &self.signatures{
+            bytes.extend_from_slice(signature);
+        }
+
+        bytes
+
+    }
+
+    /// Creates a new Transaction from a byte vector.
+    ///
+    /// * `bytes`: A byte vector
+
+    fn from_bytes(bytes: Vec<u8>)
+                    -> Result<Transaction, BinFormatError>{
+
+        let mut reader = BinReader::new(&bytes);
+
+        let version = reader.read_u16()?;
+
+        if version != 0x0{
+            let reason = BinFormatErrorReason::UnsupportedVersion;
+            return Err(BinFormatError::new(reason));
+        }
+
+        let tx_type = reader.read_u8()?;
+        let payload_len = reader.read_u32()? as usize;
+        let payload_bytes = reader.read_bytes(payload_len)?.to_vec();
+        let payload = TxPayload::from_bytes(tx_type, payload_bytes)?;
+
+        let signer_count = reader.read_u8()?;
+        let mut signer_pubkeys = vec![];
+        let mut signatures = vec![];
+
+        for _i in 0..signer_count{
+            signer_pubkeys.push(reader.read_hash()?);
+        }
+
+        for _i in 0..signer_count{
+            signatures.push(reader.read_signature()?);
+        }
+
+        reader.finish()?;
+
+        Ok(Transaction{
+            version: version,
+            payload: payload,
+            signer_pubkeys: signer_pubkeys,
+            signatures: signatures
+        })
+
     }
 
 }
@@ -88,6 +627,40 @@ impl TxId{
 /// ways to each other. For example workloads can be used
 /// to create coupons, orders are in a relationship to a
 /// production output, etc
+///
+/// * `Dummy`: Used for testing purposes only
+/// * `CollectiveParent`: The 1:1 parent relationship of a
+///         collective registration
+/// * `TrustCancellation`: The 1:1 relationship of a trust
+///         establishment claimed by its cancellation
+/// * `OrderEstimation`: The 1:1 relationship of an order
+///         claimed by its value estimation
+/// * `OrderRejection`: The 1:1 relationship of an order
+///         claimed by its rejection
+/// * `OrderSubOrders`: The 1:n relationship of an order
+///         claimed by orders serving it
+/// * `EstimationProductionStart`: The 1:1 relationship of a value
+///         estimation claimed by its production start
+/// * `ProductionWorkloads`: The 1:n relationship of a production
+///         start claimed by workload allocations
+/// * `ProductionResources`: The 1:n relationship of a production
+///         start claimed by consumptive resource allocations
+/// * `ProductionMopAllocations`: The 1:n relationship of a production
+///         start claimed by allocations of means of production
+/// * `ProductionMopDeallocations`: The 1:n relationship of a production
+///         start claimed by deallocations of means of production
+/// * `MopDeallocation`: The 1:1 relationship of an allocation
+///         of a mean of production claimed by its deallocation
+/// * `ResourceConsumption`: The 1:1 relationship of a resource
+///         claimed by its consumptive allocation
+/// * `WorkloadCoupon`: The 1:1 relationship of a workload
+///         claimed by its coupon claim
+/// * `ResourceTransfer`: The 1:1 relationship of a resource (or
+///         of its latest transfer) claimed by its next transfer
+/// * `EstimationVote`: The 1:1 relationship of a value
+///         estimation claimed by its production vote
+/// * `VoteBallots`: The 1:n relationship of a production vote
+///         or a classification proposal claimed by its ballots
 
 #[derive(Eq)]
 #[derive(PartialEq)]
@@ -95,7 +668,23 @@ impl TxId{
 #[derive(Clone)]
 #[derive(Debug)]
 pub enum TxRelId{
-    Dummy
+    Dummy,
+    CollectiveParent,
+    TrustCancellation,
+    OrderEstimation,
+    OrderRejection,
+    OrderSubOrders,
+    EstimationProductionStart,
+    ProductionWorkloads,
+    ProductionResources,
+    ProductionMopAllocations,
+    ProductionMopDeallocations,
+    MopDeallocation,
+    ResourceConsumption,
+    WorkloadCoupon,
+    ResourceTransfer,
+    EstimationVote,
+    VoteBallots
 }
 
 /// `TxRel` denotes the state of a 1:1 or 1:n relationship
@@ -181,6 +770,9 @@ pub struct TxState{
 ///         of TxState is set to Finalized, meaning that it can
 ///         not be changed anymore. The wrapped transaction id
 ///         points to the transaction that finalized the state.
+/// * `RelFull` is used when a 1:n relationship already has
+///         the maximum number of claimers that can be stored
+///         (u16::MAX). The value wraps the relationship id.
 /// * `UnknownRelId` happens when the transaction layer tries
 ///         to claim a relationship that simply doesn't exist.
 ///         Note, that this is a programming error, not an
@@ -196,6 +788,7 @@ pub enum BadClaimReason{
     RelClaimed(TxRelId, TxId),
     TxUnclaimable,
     TxFinalized(TxId),
+    RelFull(TxRelId),
     UnknownRelId(TxRelId),
 }
 
@@ -208,6 +801,8 @@ impl fmt::Display for BadClaimReason {
                 write!(f, "Transaction is unclaimable"),
             BadClaimReason::TxFinalized(ref fin_tx_id) =>
                 write!(f, "Transaction was finalized by transaction {:?}", fin_tx_id),
+            BadClaimReason::RelFull(ref tx_rel_id) =>
+                write!(f, "Relationship {:?} can't be claimed by more transactions", tx_rel_id),
             BadClaimReason::UnknownRelId(ref tx_rel_id) =>
                 write!(f, "Transaction has no relationship {:?}.", tx_rel_id),
         }
@@ -262,13 +857,17 @@ impl fmt::Display for BadClaim{
 ///         of the blockchain in which a later transaction
 ///         is claimed by an earlier transaction or a
 ///         transaction in the same block.
+/// * `StorageFailure`: The storage backend failed to save
+///         the transaction state. Wraps a description of
+///         the underlying problem.
 
 #[derive(Debug)]
 pub enum TxProgErrorReason{
     UnknownRelId(TxRelId),
     RelIdExists(TxRelId),
     UnknownTx(TxId),
-    RefOrderError
+    RefOrderError,
+    StorageFailure(String)
 }
 
 impl fmt::Display for TxProgErrorReason {
@@ -282,6 +881,8 @@ impl fmt::Display for TxProgErrorReason {
                 write!(f, "Unknown transaction. Requested id was {:?}.", tx_id),
             TxProgErrorReason::RefOrderError =>
                 write!(f, "The reference order of the transactions is illegal."),
+            TxProgErrorReason::StorageFailure(ref description) =>
+                write!(f, "Storage failure: {}", description),
         }
     }
 }
@@ -487,6 +1088,15 @@ impl TxState{
                 },
 
                 TxRel::OneToMany(ref mut tx_ids) => {
+
+                    // the number of claimers is
+                    // serialized as u16
+
+                    if tx_ids.len() >= u16::MAX as usize{
+                        let reason = BadClaimReason::RelFull(tx_rel_id);
+                        let err = BadClaim::new(reason);
+                        return Err(err)
+                    }
                     tx_ids.push(tx_id);
                     return Ok(())
                 }
@@ -506,6 +1116,325 @@ impl TxState{
 
 }
 
+// ------------------------------------------------------------------------
+
+// This section implements (de)serialization methods for transaction
+// states. The byte format is canonical, meaning that two equal states
+// always have the same binary representation (relationships are sorted
+// by their id), so the encoding can be hashed into commitments. The
+// current (version 0x0) byte format of `TxState` is:
+
+//    field            length
+//  .------------------------.
+//  | version         | 2    |
+//  |------------------------|
+//  | total_rel_state | 1/35 |
+//  |------------------------|
+//  | rel_count       | 2    |
+//  |------------------------|
+//  | rel_id          | 1    |  <-. repeated rel_count times,
+//  |------------------------|    | in ascending order of
+//  | rel             | n    |  <-' rel_id
+//  '------------------------'
+
+// with the following formats for its fields:
+
+//  TxId:             block_id (32) | tx_index (2)
+//  TxTotalRelState:  0x00 (Claimable) | 0x01 (Unclaimable) |
+//                    0x02 (Finalized) followed by a TxId
+//  TxRelId:          a single tag byte (0x00: Dummy,
+//                    0x01: CollectiveParent,
+//                    0x02: TrustCancellation, ...
+//                    see read_tx_rel_id)
+//  TxRel:            0x00 (OneToOne) | claimed flag (1) | [TxId]
+//                    0x01 (OneToMany) | claimer_count (2) | TxIds
+
+impl BinFormat<TxId> for TxId{
+
+    fn as_bytes(&self) -> Vec<u8>{
+        let BlockId(block_hash) = self.block_id;
+        let TxIndex(tx_index) = self.tx_index;
+        [&block_hash[..], &u16_to_u8le(tx_index)[..]].concat()
+    }
+
+    fn from_bytes(bytes: Vec<u8>) -> Result<TxId, BinFormatError>{
+        let mut reader = BinReader::new(&bytes);
+        let tx_id = read_tx_id(&mut reader)?;
+        reader.finish()?;
+        Ok(tx_id)
+    }
+
+}
+
+impl BinFormat<TxRelId> for TxRelId{
+
+    fn as_bytes(&self) -> Vec<u8>{
+        match *self{
+            TxRelId::Dummy => vec![0x00],
+            TxRelId::CollectiveParent => vec![0x01],
+            TxRelId::TrustCancellation => vec![0x02],
+            TxRelId::OrderEstimation => vec![0x03],
+            TxRelId::OrderRejection => vec![0x04],
+            TxRelId::OrderSubOrders => vec![0x05],
+            TxRelId::EstimationProductionStart => vec![0x06],
+            TxRelId::ProductionWorkloads => vec![0x07],
+            TxRelId::ProductionResources => vec![0x08],
+            TxRelId::ProductionMopAllocations => vec![0x09],
+            TxRelId::ProductionMopDeallocations => vec![0x0A],
+            TxRelId::MopDeallocation => vec![0x0B],
+            TxRelId::ResourceConsumption => vec![0x0C],
+            TxRelId::WorkloadCoupon => vec![0x0D],
+            TxRelId::ResourceTransfer => vec![0x0E],
+            TxRelId::EstimationVote => vec![0x0F],
+            TxRelId::VoteBallots => vec![0x10]
+        }
+    }
+
+    fn from_bytes(bytes: Vec<u8>) -> Result<TxRelId, BinFormatError>{
+        let mut reader = BinReader::new(&bytes);
+        let tx_rel_id = read_tx_rel_id(&mut reader)?;
+        reader.finish()?;
+        Ok(tx_rel_id)
+    }
+
+}
+
+impl BinFormat<TxRel> for TxRel{
+
+    fn as_bytes(&self) -> Vec<u8>{
+
+        match *self{
+            TxRel::OneToOne(None) => vec![0x00, 0x00],
+            TxRel::OneToOne(Some(claimer_tx_id)) => {
+                [&[0x00, 0x01][..], &claimer_tx_id.as_bytes()[..]].concat()
+            },
+            TxRel::OneToMany(ref claimer_tx_ids) => {
+                let count_u8le = u16_to_u8le(claimer_tx_ids.len() as u16);
+                let mut bytes = [&[0x01][..], &count_u8le[..]].concat();
+                for claimer_tx_id in claimer_tx_ids{
+                    bytes.extend(claimer_tx_id.as_bytes());
+                }
+                bytes
+            }
+        }
+
+    }
+
+    fn from_bytes(bytes: Vec<u8>) -> Result<TxRel, BinFormatError>{
+        let mut reader = BinReader::new(&bytes);
+        let tx_rel = read_tx_rel(&mut reader)?;
+        reader.finish()?;
+        Ok(tx_rel)
+    }
+
+}
+
+impl BinFormat<TxTotalRelState> for TxTotalRelState{
+
+    fn as_bytes(&self) -> Vec<u8>{
+        match *self{
+            TxTotalRelState::Claimable => vec![0x00],
+            TxTotalRelState::Unclaimable => vec![0x01],
+            TxTotalRelState::Finalized(fin_tx_id) => {
+                [&[0x02][..], &fin_tx_id.as_bytes()[..]].concat()
+            }
+        }
+    }
+
+    fn from_bytes(bytes: Vec<u8>) -> Result<TxTotalRelState, BinFormatError>{
+        let mut reader = BinReader::new(&bytes);
+        let total_rel_state = read_total_rel_state(&mut reader)?;
+        reader.finish()?;
+        Ok(total_rel_state)
+    }
+
+}
+
+impl BinFormat<TxState> for TxState{
+
+    fn as_bytes(&self) -> Vec<u8>{
+
+        let mut relationships: Vec<(Vec<u8>, &TxRel)> =
+            self.relationships.iter()
+                              .map(|(tx_rel_id, tx_rel)| (tx_rel_id.as_bytes(), tx_rel))
+                              .collect();
+        relationships.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let version_u8le = u16_to_u8le(0x0);
+        let rel_count_u8le = u16_to_u8le(relationships.len() as u16);
+
+        let mut bytes = [&version_u8le[..],
+                         &self.total_rel_state.as_bytes()[..],
+                         &rel_count_u8le[..]].concat();
+
+        for (tx_rel_id_bytes, tx_rel) in relationships{
+            bytes.extend(tx_rel_id_bytes);
+            bytes.extend(tx_rel.as_bytes());
+        }
+
+        bytes
+
+    }
+
+    fn from_bytes(bytes: Vec<u8>) -> Result<TxState, BinFormatError>{
+
+        let mut reader = BinReader::new(&bytes);
+
+        if reader.read_u16()? != 0x0{
+            let reason = BinFormatErrorReason::UnsupportedVersion;
+            return Err(BinFormatError::new(reason));
+        }
+
+        let total_rel_state = read_total_rel_state(&mut reader)?;
+        let rel_count = reader.read_u16()?;
+
+        let mut relationships = HashMap::new();
+        let mut prev_tx_rel_id_bytes: Option<Vec<u8>> = None;
+
+        for _i in 0..rel_count{
+
+            let tx_rel_id = read_tx_rel_id(&mut reader)?;
+            let tx_rel_id_bytes = tx_rel_id.as_bytes();
+
+            // relationship ids must be unique and sorted,
+            // otherwise the encoding wouldn't be canonical
+
+            if let Some(prev_tx_rel_id_bytes) = prev_tx_rel_id_bytes{
+                if tx_rel_id_bytes <= prev_tx_rel_id_bytes{
+                    let field_name = String::from("rel_id");
+                    let reason = BinFormatErrorReason::InvalidFieldData(field_name);
+                    return Err(BinFormatError::new(reason));
+                }
+            }
+
+            let tx_rel = read_tx_rel(&mut reader)?;
+            relationships.insert(tx_rel_id, tx_rel);
+            prev_tx_rel_id_bytes = Some(tx_rel_id_bytes);
+
+        }
+
+        reader.finish()?;
+
+        Ok(TxState{
+            total_rel_state: total_rel_state,
+            relationships: relationships
+        })
+
+    }
+
+}
+
+impl Hashable for TxState{
+
+    fn to_sha3_hash(&self) -> [u8; 32]{
+        sha3_256(&self.as_bytes())
+    }
+
+}
+
+/// Reads a `TxId` from a `BinReader`. Since a block id of all
+/// zeroes denotes a missing predecessor in block headers, such
+/// a transaction id is rejected as malformed.
+
+pub fn read_tx_id(reader: &mut BinReader) -> Result<TxId, BinFormatError>{
+
+    let block_hash = reader.read_hash()?;
+
+    if block_hash == [0; 32]{
+        let field_name = String::from("block_id");
+        let reason = BinFormatErrorReason::InvalidFieldData(field_name);
+        return Err(BinFormatError::new(reason));
+    }
+
+    let tx_index = reader.read_u16()?;
+    Ok(TxId::new(BlockId(block_hash), TxIndex(tx_index)))
+
+}
+
+/// Reads a `TxRelId` from a `BinReader`
+
+pub fn read_tx_rel_id(reader: &mut BinReader) -> Result<TxRelId, BinFormatError>{
+
+    match reader.read_u8()?{
+        0x00 => Ok(TxRelId::Dummy),
+        0x01 => Ok(TxRelId::CollectiveParent),
+        0x02 => Ok(TxRelId::TrustCancellation),
+        0x03 => Ok(TxRelId::OrderEstimation),
+        0x04 => Ok(TxRelId::OrderRejection),
+        0x05 => Ok(TxRelId::OrderSubOrders),
+        0x06 => Ok(TxRelId::EstimationProductionStart),
+        0x07 => Ok(TxRelId::ProductionWorkloads),
+        0x08 => Ok(TxRelId::ProductionResources),
+        0x09 => Ok(TxRelId::ProductionMopAllocations),
+        0x0A => Ok(TxRelId::ProductionMopDeallocations),
+        0x0B => Ok(TxRelId::MopDeallocation),
+        0x0C => Ok(TxRelId::ResourceConsumption),
+        0x0D => Ok(TxRelId::WorkloadCoupon),
+        0x0E => Ok(TxRelId::ResourceTransfer),
+        0x0F => Ok(TxRelId::EstimationVote),
+        0x10 => Ok(TxRelId::VoteBallots),
+        _ => {
+            let field_name = String::from("rel_id");
+            let reason = BinFormatErrorReason::InvalidFieldData(field_name);
+            Err(BinFormatError::new(reason))
+        }
+    }
+
+}
+
+/// Reads a `TxRel` from a `BinReader`
+
+pub fn read_tx_rel(reader: &mut BinReader) -> Result<TxRel, BinFormatError>{
+
+    match reader.read_u8()?{
+
+        0x00 => match reader.read_u8()?{
+            0x00 => Ok(TxRel::OneToOne(None)),
+            0x01 => Ok(TxRel::OneToOne(Some(read_tx_id(reader)?))),
+            _ => {
+                let field_name = String::from("claimed");
+                let reason = BinFormatErrorReason::InvalidFieldData(field_name);
+                Err(BinFormatError::new(reason))
+            }
+        },
+
+        0x01 => {
+            let claimer_count = reader.read_u16()?;
+            let mut claimer_tx_ids = vec![];
+            for _i in 0..claimer_count{
+                claimer_tx_ids.push(read_tx_id(reader)?);
+            }
+            Ok(TxRel::OneToMany(claimer_tx_ids))
+        },
+
+        _ => {
+            let field_name = String::from("rel_kind");
+            let reason = BinFormatErrorReason::InvalidFieldData(field_name);
+            Err(BinFormatError::new(reason))
+        }
+
+    }
+
+}
+
+/// Reads a `TxTotalRelState` from a `BinReader`
+
+pub fn read_total_rel_state(reader: &mut BinReader)
+                            -> Result<TxTotalRelState, BinFormatError>{
+
+    match reader.read_u8()?{
+        0x00 => Ok(TxTotalRelState::Claimable),
+        0x01 => Ok(TxTotalRelState::Unclaimable),
+        0x02 => Ok(TxTotalRelState::Finalized(read_tx_id(reader)?)),
+        _ => {
+            let field_name = String::from("total_rel_state");
+            let reason = BinFormatErrorReason::InvalidFieldData(field_name);
+            Err(BinFormatError::new(reason))
+        }
+    }
+
+}
+
 #[test]
 fn test_tx_claim_total_rel_state_unclaimable(){
 
@@ -667,6 +1596,37 @@ fn test_tx_claim_one_to_many_rel(){
 
 }
 
+#[test]
+fn test_tx_claim_one_to_many_rel_limit(){
+
+    // the number of claimers is limited
+    // to what can be serialized
+
+    let mut tx_state = TxState::new(TxTotalRelState::Claimable);
+    tx_state.add_one_to_many_rel(TxRelId::Dummy).unwrap();
+
+    let block_id = BlockId([1; 32]);
+    for index in 0..u16::MAX{
+        let tx_id = TxId::new(block_id, TxIndex(index));
+        tx_state.claim_rel(TxRelId::Dummy, tx_id).unwrap();
+    }
+
+    let tx_id = TxId::new(block_id, TxIndex(u16::MAX));
+    match tx_state.claim_rel(TxRelId::Dummy, tx_id){
+        Err(BadClaim{reason: BadClaimReason::RelFull(TxRelId::Dummy)}) => {},
+        _ => panic!("1:n relationship was claimed beyond its limit")
+    }
+
+    // a full relationship survives serialization
+
+    let rebuild = TxState::from_bytes(tx_state.as_bytes()).unwrap();
+    match rebuild.get_rel(TxRelId::Dummy){
+        Ok(TxRel::OneToMany(claimers)) => assert_eq!(claimers.len(), u16::MAX as usize),
+        _ => panic!("Full 1:n relationship was not restored")
+    }
+
+}
+
 #[test]
 fn test_tx_create_rel(){
 
@@ -689,3 +1649,240 @@ fn test_tx_create_rel(){
     assert!(second_def.is_err(), "add_one_to_many_rel did permit rel id overwrite");
 
 }
+
+#[test]
+fn test_transaction_to_bytes_from_bytes(){
+
+    let transaction = Transaction::new(TxPayload::Dummy, vec![[0x01; 32], [0x02; 32]]);
+
+    let as_bytes = transaction.as_bytes();
+
+    // version (2) + type (1) + payload length (4) + signer
+    // count (1) + two public keys and two signatures
+
+    assert_eq!(as_bytes.len(), 2 + 1 + 4 + 1 + 2 * 32 + 2 * 64);
+    assert_eq!(&as_bytes[0..8], &[0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02]);
+
+    let rebuild = Transaction::from_bytes(as_bytes.clone());
+    match rebuild{
+        Ok(rebuild) => {
+            assert_eq!(rebuild.as_bytes(), as_bytes);
+            assert_eq!(rebuild.to_sha3_hash(), transaction.to_sha3_hash());
+            assert_eq!(rebuild.get_signer_pubkeys(), &vec![[0x01; 32], [0x02; 32]]);
+        },
+        _ => assert!(false, "Valid binary representation of Transaction \
+                             could not be deserialized into an object")
+    }
+
+    // unknown versions, unknown types, missing
+    // signatures and trailing data must be rejected
+
+    let mut faulty = as_bytes.clone();
+    faulty[0] = 0x01;
+    match Transaction::from_bytes(faulty){
+        Err(err) => match err.reason{
+            BinFormatErrorReason::UnsupportedVersion => {},
+            _ => assert!(false, "Unknown version was rejected for the wrong reason")
+        },
+        Ok(_) => assert!(false, "Transaction with unknown version was accepted")
+    }
+
+    let mut faulty = as_bytes.clone();
+    faulty[2] = 0xFF;
+    match Transaction::from_bytes(faulty){
+        Err(err) => match err.reason{
+            BinFormatErrorReason::InvalidFieldData(_) => {},
+            _ => assert!(false, "Unknown type was rejected for the wrong reason")
+        },
+        Ok(_) => assert!(false, "Transaction with unknown type was accepted")
+    }
+
+    let mut faulty = as_bytes.clone();
+    faulty.truncate(as_bytes.len() - 1);
+    match Transaction::from_bytes(faulty){
+        Err(err) => match err.reason{
+            BinFormatErrorReason::InvalidDataSize => {},
+            _ => assert!(false, "Truncated signature was rejected for the wrong reason")
+        },
+        Ok(_) => assert!(false, "Transaction with truncated signature was accepted")
+    }
+
+    let mut faulty = as_bytes.clone();
+    faulty.push(0x00);
+    match Transaction::from_bytes(faulty){
+        Err(err) => match err.reason{
+            BinFormatErrorReason::InvalidDataSize => {},
+            _ => assert!(false, "Trailing data was rejected for the wrong reason")
+        },
+        Ok(_) => assert!(false, "Transaction with trailing data was accepted")
+    }
+
+}
+
+#[test]
+fn test_transaction_signatures(){
+
+    let (secret_key_a, public_key_a) = ed25519::keypair(&[0x0A; 32]);
+    let (secret_key_b, public_key_b) = ed25519::keypair(&[0x0B; 32]);
+
+    let mut transaction = Transaction::new(TxPayload::Dummy,
+                                           vec![public_key_a, public_key_b]);
+
+    // signatures are only valid once all signers signed
+
+    assert!(transaction.verify_signatures().is_err(),
+            "Unsigned transaction passed the signature check");
+
+    transaction.sign(&secret_key_a);
+    assert!(transaction.verify_signatures().is_err(),
+            "Partially signed transaction passed the signature check");
+
+    transaction.sign(&secret_key_b);
+    assert!(transaction.verify_signatures().is_ok(),
+            "Transaction was correctly signed, but sig check failed");
+
+    // signatures survive serialization
+
+    let rebuild = Transaction::from_bytes(transaction.as_bytes()).unwrap();
+    assert!(rebuild.verify_signatures().is_ok(),
+            "Deserialized transaction failed the signature check");
+
+    // the hash covers the signers but not the signatures, and
+    // transactions with different signers have different hashes
+
+    let unsigned = Transaction::new(TxPayload::Dummy, vec![public_key_a, public_key_b]);
+    let other = Transaction::new(TxPayload::Dummy, vec![public_key_b, public_key_a]);
+
+    assert_eq!(unsigned.to_sha3_hash(), transaction.to_sha3_hash());
+    assert!(other.to_sha3_hash() != transaction.to_sha3_hash());
+
+    // a signature over a different message is rejected
+
+    let mut tampered = transaction.as_bytes();
+    let last = tampered.len() - 1;
+    tampered[last] ^= 0x01;
+    let tampered = Transaction::from_bytes(tampered).unwrap();
+    assert!(tampered.verify_signatures().is_err(),
+            "Tampered signature passed the signature check");
+
+}
+
+#[test]
+fn test_tx_state_to_bytes_from_bytes(){
+
+    let block_id = BlockId([1; 32]);
+    let claimer_a = TxId::new(BlockId([2; 32]), TxIndex(0));
+    let claimer_b = TxId::new(BlockId([3; 32]), TxIndex(7));
+
+    let mut tx_state = TxState::new(TxTotalRelState::Claimable);
+    tx_state.add_one_to_many_rel(TxRelId::Dummy).unwrap();
+    tx_state.claim_rel(TxRelId::Dummy, claimer_a).unwrap();
+    tx_state.claim_rel(TxRelId::Dummy, claimer_b).unwrap();
+    tx_state.set_total_rel_state(TxTotalRelState::Finalized(claimer_b));
+
+    let as_bytes = tx_state.as_bytes();
+
+    // version (2) + finalized (1 + 34) + rel count (2) +
+    // rel id (1) + kind (1) + claimer count (2) + 2 * 34
+
+    assert_eq!(as_bytes.len(), 2 + 35 + 2 + 1 + 1 + 2 + 68);
+
+    let rebuild = TxState::from_bytes(as_bytes.clone());
+    assert!(rebuild.is_ok(), "Valid binary representation of TxState \
+                              could not be deserialized into an object");
+
+    // the encoding is canonical, so re-encoding the deserialized
+    // object must result in exactly the same bytes and hash
+
+    let rebuild = rebuild.unwrap();
+    assert_eq!(rebuild.as_bytes(), as_bytes);
+    assert_eq!(rebuild.to_sha3_hash(), tx_state.to_sha3_hash());
+
+    match *rebuild.get_total_rel_state(){
+        TxTotalRelState::Finalized(fin_tx_id) => assert_eq!(fin_tx_id, claimer_b),
+        _ => assert!(false, "Total relationship state was not deserialized correctly")
+    }
+
+    match rebuild.get_rel(TxRelId::Dummy){
+        Ok(&TxRel::OneToMany(ref claimers)) => {
+            assert_eq!(claimers, &vec![claimer_a, claimer_b]);
+        },
+        _ => assert!(false, "Relationship was not deserialized correctly")
+    }
+
+    // single relationship types and ids roundtrip on their own
+
+    let tx_id = TxId::new(block_id, TxIndex(0xBEEF));
+    assert_eq!(TxId::from_bytes(tx_id.as_bytes()).unwrap(), tx_id);
+
+    let tx_rel = TxRel::OneToOne(Some(tx_id));
+    let rebuild = TxRel::from_bytes(tx_rel.as_bytes()).unwrap();
+    assert_eq!(rebuild.as_bytes(), tx_rel.as_bytes());
+
+    let tx_rel = TxRel::OneToOne(None);
+    assert_eq!(tx_rel.as_bytes(), vec![0x00, 0x00]);
+    assert!(TxRel::from_bytes(tx_rel.as_bytes()).is_ok());
+
+}
+
+#[test]
+fn test_tx_state_from_bytes_errors(){
+
+    let claimer = TxId::new(BlockId([2; 32]), TxIndex(0));
+
+    // a state with the same relationship id twice
+
+    let mut bytes = vec![0x00, 0x00, 0x00, 0x02, 0x00];
+    bytes.extend(vec![0x00, 0x00, 0x00]);
+    bytes.extend(vec![0x00, 0x00, 0x00]);
+
+    match TxState::from_bytes(bytes){
+        Err(err) => match err.reason{
+            BinFormatErrorReason::InvalidFieldData(ref field) if field == "rel_id" => {},
+            _ => assert!(false, "Duplicate relationship id was rejected for the wrong reason")
+        },
+        Ok(_) => assert!(false, "TxState with duplicate relationship ids was accepted")
+    }
+
+    // a malformed (zeroed) tx id inside a relationship
+
+    let mut bytes = vec![0x00, 0x00, 0x00, 0x01, 0x00];
+    bytes.extend(vec![0x00, 0x00, 0x01]);
+    bytes.extend(vec![0; 34]);
+
+    match TxState::from_bytes(bytes){
+        Err(err) => match err.reason{
+            BinFormatErrorReason::InvalidFieldData(ref field) if field == "block_id" => {},
+            _ => assert!(false, "Malformed TxId was rejected for the wrong reason")
+        },
+        Ok(_) => assert!(false, "TxState with a malformed TxId was accepted")
+    }
+
+    // truncated tx ids, unknown versions and trailing data
+
+    let truncated = claimer.as_bytes()[..33].to_vec();
+    match TxId::from_bytes(truncated){
+        Err(err) => match err.reason{
+            BinFormatErrorReason::InvalidDataSize => {},
+            _ => assert!(false, "Truncated TxId was rejected for the wrong reason")
+        },
+        Ok(_) => assert!(false, "Truncated TxId was accepted")
+    }
+
+    match TxState::from_bytes(vec![0x01, 0x00, 0x00, 0x00, 0x00]){
+        Err(err) => match err.reason{
+            BinFormatErrorReason::UnsupportedVersion => {},
+            _ => assert!(false, "Unknown version was rejected for the wrong reason")
+        },
+        Ok(_) => assert!(false, "TxState with unknown version was accepted")
+    }
+
+    match TxState::from_bytes(vec![0x00, 0x00, 0x00, 0x00, 0x00, 0x00]){
+        Err(err) => match err.reason{
+            BinFormatErrorReason::InvalidDataSize => {},
+            _ => assert!(false, "Trailing data was rejected for the wrong reason")
+        },
+        Ok(_) => assert!(false, "TxState with trailing data was accepted")
+    }
+
+}
@@ -0,0 +1,1212 @@
+//
+//    Stachanov
+//    Copyright (C) 2017 Stachanov Developer Collective
+//
+//    This file is part of Stachanov.
+//
+//    This program is free software: you can redistribute it and/or
+//    modify it under the terms of the GNU Affero General Public
+//    License, version 3, as published by the Free Software Foundation.
+//
+//    This program is distributed in the hope that it will be useful,
+//    but WITHOUT ANY WARRANTY; without even the implied warranty of
+//    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//    GNU Affero General Public License for more details.
+//
+//    You should have received a copy of the
+//               GNU Affero General Public License
+//    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+// This module implements the transactions allocating labor
+// and resources to a running production. Every allocation
+// claims a 1:n relationship of the production start, so
+// allocations are rejected once the production was finalized
+// by its output.
+//
+// Commons productions can't use exclusive resources, except for
+// means of production that are explicitly flagged as repurposed.
+// The value repurposed means of production transfer is taxed as
+// part of the commons sum c of the final revision.
+
+use blockchain::traits::BinFormat;
+use blockchain::traits::ChainStorage;
+use blockchain::transactions::Transaction;
+use blockchain::transactions::TxPayload;
+use blockchain::transactions::TxId;
+use blockchain::transactions::TxRelId;
+use blockchain::transactions::TxState;
+use blockchain::transactions::TxTotalRelState;
+use blockchain::transactions::read_tx_id;
+use blockchain::errors::BinFormatError;
+use blockchain::errors::VerificationError;
+use blockchain::errors::VerificationErrorReason;
+use blockchain::txtypes::TxContext;
+use blockchain::txtypes::verify_signers;
+use blockchain::txtypes::read_bool;
+use blockchain::txtypes::production::get_production_collective;
+use blockchain::txtypes::production::verify_label;
+use blockchain::txtypes::product::ProductRegistry;
+use blockchain::txtypes::product::ProductTypeIndex;
+use blockchain::txtypes::resource::verify_available;
+use blockchain::depreciation::theta;
+use blockchain::utils::u64_to_u8le;
+use blockchain::utils::BinReader;
+
+/// `WorkloadAllocation` registers a timespan a worker spent
+/// on a production. The time (in minutes) is fully transferred
+/// to the value of the output and can later be claimed as
+/// labor coupons by the holder of the wallet. The workload
+/// carries the label of the production.
+///
+/// Workloads hold the following relationships:
+///
+/// * `WorkloadCoupon` (1:1): Claimed by the coupon claim
+
+#[derive(Clone)]
+#[derive(Debug)]
+pub struct WorkloadAllocation{
+    production_start: TxId,
+    wallet_pubkey: [u8; 32],
+    minutes: u64,
+    common: bool
+}
+
+impl WorkloadAllocation{
+
+    /// Creates a new `WorkloadAllocation`
+    ///
+    /// # Arguments
+    /// * `production_start`: The id of the production
+    /// * `wallet_pubkey`: The public key of the wallet of the worker
+    /// * `minutes`: The working time in minutes
+    /// * `common`: Whether the workload is part of commons production
+
+    pub fn new(production_start: TxId,
+               wallet_pubkey: [u8; 32],
+               minutes: u64,
+               common: bool) -> WorkloadAllocation{
+
+        WorkloadAllocation{
+            production_start: production_start,
+            wallet_pubkey: wallet_pubkey,
+            minutes: minutes,
+            common: common
+        }
+
+    }
+
+    /// Gets the id of the production
+
+    pub fn get_production_start(&self) -> TxId{
+        self.production_start
+    }
+
+    /// Gets the public key of the wallet of the worker
+
+    pub fn get_wallet_pubkey(&self) -> [u8; 32]{
+        self.wallet_pubkey
+    }
+
+    /// Gets the working time in minutes
+
+    pub fn get_minutes(&self) -> u64{
+        self.minutes
+    }
+
+    /// Checks if the workload is part of commons production
+
+    pub fn is_common(&self) -> bool{
+        self.common
+    }
+
+    /// Verifies the workload allocation against the chain
+    ///
+    /// # Arguments
+    /// * `transaction`: The transaction holding the allocation
+    /// * `context`: The verification context
+
+    pub fn verify<C>(&self,
+                     transaction: &Transaction,
+                     context: &mut TxContext<C>)
+                     -> Result<(), VerificationError> where C: ChainStorage{
+
+        let collective_pubkey = get_production_collective(context, self.production_start)?;
+        verify_signers(transaction, &[collective_pubkey])?;
+        verify_label(context, self.production_start, self.common)?;
+
+        if self.minutes == 0{
+            let reason = VerificationErrorReason::InvalidAmount;
+            return Err(VerificationError::new(reason))
+        }
+
+        context.claim_rel(self.production_start, TxRelId::ProductionWorkloads)
+
+    }
+
+    /// Returns the initial state of the workload allocation
+
+    pub fn initial_state(&self) -> TxState{
+
+        let mut tx_state = TxState::new(TxTotalRelState::Claimable);
+        tx_state.add_one_to_one_rel(TxRelId::WorkloadCoupon)
+                .expect("Fresh TxState already had a relationship");
+        tx_state
+
+    }
+
+}
+
+impl BinFormat<WorkloadAllocation> for WorkloadAllocation{
+
+    // The byte format of WorkloadAllocation is:
+
+    //    field              length
+    //  .--------------------------.
+    //  | production_start  | 34   |
+    //  |--------------------------|
+    //  | wallet_pubkey     | 32   |
+    //  |--------------------------|
+    //  | minutes           | 8    |
+    //  |--------------------------|
+    //  | common            | 1    |  (bool)
+    //  '--------------------------'
+
+    /// Returns the workload allocation as an u8 vector
+
+    fn as_bytes(&self) -> Vec<u8>{
+
+        [&self.production_start.as_bytes()[..],
+         &self.wallet_pubkey[..],
+         &u64_to_u8le(self.minutes)[..],
+         &[self.common as u8][..]].concat()
+
+    }
+
+    /// Creates a new workload allocation from a byte vector.
+    ///
+    /// * `bytes`: A byte vector
+
+    fn from_bytes(bytes: Vec<u8>) -> Result<WorkloadAllocation, BinFormatError>{
+
+        let mut reader = BinReader::new(&bytes);
+
+        let production_start = read_tx_id(&mut reader)?;
+        let wallet_pubkey = reader.read_hash()?;
+        let minutes = reader.read_u64()?;
+        let common = read_bool(&mut reader, "common")?;
+
+        reader.finish()?;
+
+        Ok(WorkloadAllocation::new(production_start, wallet_pubkey, minutes, common))
+
+    }
+
+}
+
+/// `ConsumptiveResourceAllocation` consumes a resource owned
+/// by the producing collective. The complete value of the
+/// resource is transferred to the output. It claims the
+/// `ResourceConsumption` relationship of the resource, so
+/// every resource is consumed at most once. Commons
+/// productions can't consume exclusive resources.
+
+#[derive(Clone)]
+#[derive(Debug)]
+pub struct ConsumptiveResourceAllocation{
+    production_start: TxId,
+    resource: TxId
+}
+
+impl ConsumptiveResourceAllocation{
+
+    /// Creates a new `ConsumptiveResourceAllocation`
+    ///
+    /// # Arguments
+    /// * `production_start`: The id of the production
+    /// * `resource`: The id of the consumed resource
+
+    pub fn new(production_start: TxId, resource: TxId) -> ConsumptiveResourceAllocation{
+        ConsumptiveResourceAllocation{production_start: production_start, resource: resource}
+    }
+
+    /// Gets the id of the production
+
+    pub fn get_production_start(&self) -> TxId{
+        self.production_start
+    }
+
+    /// Gets the id of the consumed resource
+
+    pub fn get_resource(&self) -> TxId{
+        self.resource
+    }
+
+    /// Verifies the resource allocation against the chain
+    ///
+    /// # Arguments
+    /// * `transaction`: The transaction holding the allocation
+    /// * `context`: The verification context
+
+    pub fn verify<C>(&self,
+                     transaction: &Transaction,
+                     context: &mut TxContext<C>)
+                     -> Result<(), VerificationError> where C: ChainStorage{
+
+        let collective_pubkey = get_production_collective(context, self.production_start)?;
+        verify_signers(transaction, &[collective_pubkey])?;
+
+        let resource = get_resource(context, self.resource)?;
+        if resource.owner != collective_pubkey{
+            let reason = VerificationErrorReason::InvalidOwner;
+            return Err(VerificationError::new(reason))
+        }
+
+        if context.get_indexes().production.is_common(self.production_start) && !resource.common{
+            let reason = VerificationErrorReason::SeparationViolation;
+            return Err(VerificationError::new(reason))
+        }
+
+        // allocated means of production can't be consumed
+
+        if context.get_indexes().production.get_open_allocation(self.resource).is_some(){
+            let reason = VerificationErrorReason::ResourceInUse;
+            return Err(VerificationError::new(reason))
+        }
+
+        context.claim_rel(self.production_start, TxRelId::ProductionResources)?;
+        context.claim_rel(self.resource, TxRelId::ResourceConsumption)
+
+    }
+
+}
+
+impl BinFormat<ConsumptiveResourceAllocation> for ConsumptiveResourceAllocation{
+
+    // The byte format of ConsumptiveResourceAllocation is:
+
+    //    field              length
+    //  .--------------------------.
+    //  | production_start  | 34   |
+    //  |--------------------------|
+    //  | resource          | 34   |
+    //  '--------------------------'
+
+    /// Returns the resource allocation as an u8 vector
+
+    fn as_bytes(&self) -> Vec<u8>{
+        [self.production_start.as_bytes(), self.resource.as_bytes()].concat()
+    }
+
+    /// Creates a new resource allocation from a byte vector.
+    ///
+    /// * `bytes`: A byte vector
+
+    fn from_bytes(bytes: Vec<u8>) -> Result<ConsumptiveResourceAllocation, BinFormatError>{
+
+        let mut reader = BinReader::new(&bytes);
+        let production_start = read_tx_id(&mut reader)?;
+        let resource = read_tx_id(&mut reader)?;
+        reader.finish()?;
+
+        Ok(ConsumptiveResourceAllocation::new(production_start, resource))
+
+    }
+
+}
+
+/// `MopAllocation` allocates a resource owned by the producing
+/// collective as a mean of production. Only a part of its value
+/// is transferred to the output, which depends on the time
+/// between allocation and deallocation. A mean of production
+/// can only be allocated to one production at a time.
+///
+/// The allocation carries the label of the production.
+/// Commons productions can only allocate exclusive means of
+/// production, if the allocation flags them as repurposed.
+///
+/// Allocations hold the following relationships:
+///
+/// * `MopDeallocation` (1:1): Claimed by the deallocation
+
+#[derive(Clone)]
+#[derive(Debug)]
+pub struct MopAllocation{
+    production_start: TxId,
+    mop: TxId,
+    common: bool,
+    repurposed: bool
+}
+
+impl MopAllocation{
+
+    /// Creates a new `MopAllocation`
+    ///
+    /// # Arguments
+    /// * `production_start`: The id of the production
+    /// * `mop`: The id of the allocated resource
+    /// * `common`: Whether the allocation is part of commons production
+    /// * `repurposed`: Whether an exclusive mean of production
+    ///                 is repurposed for commons production
+
+    pub fn new(production_start: TxId,
+               mop: TxId,
+               common: bool,
+               repurposed: bool) -> MopAllocation{
+
+        MopAllocation{
+            production_start: production_start,
+            mop: mop,
+            common: common,
+            repurposed: repurposed
+        }
+
+    }
+
+    /// Gets the id of the production
+
+    pub fn get_production_start(&self) -> TxId{
+        self.production_start
+    }
+
+    /// Gets the id of the allocated resource
+
+    pub fn get_mop(&self) -> TxId{
+        self.mop
+    }
+
+    /// Checks if the allocation is part of commons production
+
+    pub fn is_common(&self) -> bool{
+        self.common
+    }
+
+    /// Checks if an exclusive mean of production is
+    /// repurposed for commons production
+
+    pub fn is_repurposed(&self) -> bool{
+        self.repurposed
+    }
+
+    /// Verifies the allocation against the chain
+    ///
+    /// # Arguments
+    /// * `transaction`: The transaction holding the allocation
+    /// * `context`: The verification context
+
+    pub fn verify<C>(&self,
+                     transaction: &Transaction,
+                     context: &mut TxContext<C>)
+                     -> Result<(), VerificationError> where C: ChainStorage{
+
+        let collective_pubkey = get_production_collective(context, self.production_start)?;
+        verify_signers(transaction, &[collective_pubkey])?;
+
+        verify_label(context, self.production_start, self.common)?;
+
+        let resource = get_resource(context, self.mop)?;
+        if resource.owner != collective_pubkey{
+            let reason = VerificationErrorReason::InvalidOwner;
+            return Err(VerificationError::new(reason))
+        }
+
+        // only exclusive means of production allocated
+        // to commons productions are repurposed
+
+        if self.repurposed && (!self.common || resource.common){
+            let reason = VerificationErrorReason::InvalidLabel;
+            return Err(VerificationError::new(reason))
+        }
+
+        if self.common && !resource.common && !self.repurposed{
+            let reason = VerificationErrorReason::SeparationViolation;
+            return Err(VerificationError::new(reason))
+        }
+
+        // consumed or written off resources and means of
+        // production that are already allocated can't be allocated
+
+        verify_available(context, self.mop)?;
+        context.claim_rel(self.production_start, TxRelId::ProductionMopAllocations)
+
+    }
+
+    /// Returns the initial state of the allocation
+
+    pub fn initial_state(&self) -> TxState{
+
+        let mut tx_state = TxState::new(TxTotalRelState::Claimable);
+        tx_state.add_one_to_one_rel(TxRelId::MopDeallocation)
+                .expect("Fresh TxState already had a relationship");
+        tx_state
+
+    }
+
+}
+
+impl BinFormat<MopAllocation> for MopAllocation{
+
+    // The byte format of MopAllocation is:
+
+    //    field              length
+    //  .--------------------------.
+    //  | production_start  | 34   |
+    //  |--------------------------|
+    //  | mop               | 34   |
+    //  |--------------------------|
+    //  | common            | 1    |  (bool)
+    //  |--------------------------|
+    //  | repurposed        | 1    |  (bool)
+    //  '--------------------------'
+
+    /// Returns the allocation as an u8 vector
+
+    fn as_bytes(&self) -> Vec<u8>{
+
+        [&self.production_start.as_bytes()[..],
+         &self.mop.as_bytes()[..],
+         &[self.common as u8, self.repurposed as u8][..]].concat()
+
+    }
+
+    /// Creates a new allocation from a byte vector.
+    ///
+    /// * `bytes`: A byte vector
+
+    fn from_bytes(bytes: Vec<u8>) -> Result<MopAllocation, BinFormatError>{
+
+        let mut reader = BinReader::new(&bytes);
+        let production_start = read_tx_id(&mut reader)?;
+        let mop = read_tx_id(&mut reader)?;
+        let common = read_bool(&mut reader, "common")?;
+        let repurposed = read_bool(&mut reader, "repurposed")?;
+        reader.finish()?;
+
+        Ok(MopAllocation::new(production_start, mop, common, repurposed))
+
+    }
+
+}
+
+/// `MopDeallocation` ends the allocation of a mean of
+/// production. It claims the `MopDeallocation` relationship
+/// of the allocation and the `ProductionMopDeallocations`
+/// relationship of the production, so it must be issued
+/// before the production is finalized. Once deallocated, the
+/// mean of production can be allocated again.
+
+#[derive(Clone)]
+#[derive(Debug)]
+pub struct MopDeallocation{
+    allocation: TxId
+}
+
+impl MopDeallocation{
+
+    /// Creates a new `MopDeallocation`
+    ///
+    /// # Arguments
+    /// * `allocation`: The id of the allocation
+
+    pub fn new(allocation: TxId) -> MopDeallocation{
+        MopDeallocation{allocation: allocation}
+    }
+
+    /// Gets the id of the allocation
+
+    pub fn get_allocation(&self) -> TxId{
+        self.allocation
+    }
+
+    /// Verifies the deallocation against the chain
+    ///
+    /// # Arguments
+    /// * `transaction`: The transaction holding the deallocation
+    /// * `context`: The verification context
+
+    pub fn verify<C>(&self,
+                     transaction: &Transaction,
+                     context: &mut TxContext<C>)
+                     -> Result<(), VerificationError> where C: ChainStorage{
+
+        let allocation = context.get_indexes().production.get_mop_allocation(self.allocation);
+        let production_start = match allocation{
+            Some((production_start, _)) => production_start,
+            None => {
+                let reason = VerificationErrorReason::InvalidTxRef(self.allocation);
+                return Err(VerificationError::new(reason))
+            }
+        };
+
+        let collective_pubkey = get_production_collective(context, production_start)?;
+        verify_signers(transaction, &[collective_pubkey])?;
+
+        context.claim_rel(self.allocation, TxRelId::MopDeallocation)?;
+        context.claim_rel(production_start, TxRelId::ProductionMopDeallocations)
+
+    }
+
+}
+
+impl BinFormat<MopDeallocation> for MopDeallocation{
+
+    // The byte format of MopDeallocation is:
+
+    //    field            length
+    //  .------------------------.
+    //  | allocation      | 34   |
+    //  '------------------------'
+
+    /// Returns the deallocation as an u8 vector
+
+    fn as_bytes(&self) -> Vec<u8>{
+        self.allocation.as_bytes()
+    }
+
+    /// Creates a new deallocation from a byte vector.
+    ///
+    /// * `bytes`: A byte vector
+
+    fn from_bytes(bytes: Vec<u8>) -> Result<MopDeallocation, BinFormatError>{
+
+        let mut reader = BinReader::new(&bytes);
+        let allocation = read_tx_id(&mut reader)?;
+        reader.finish()?;
+
+        Ok(MopDeallocation::new(allocation))
+
+    }
+
+}
+
+// ------------------------------------------------------------------------
+
+/// Computes the value (in minutes) transferred to the output
+/// of a production by its allocations, that is the sum of
+/// all workloads, the values of all consumed resources and
+/// the value transfer of all deallocated means of production.
+///
+/// Returns a VerificationError with reason InvalidAmount
+/// if the sum overflows
+///
+/// # Arguments
+/// * `context`: The verification context
+/// * `production_start`: The id of the production start
+
+pub fn production_value<C>(context: &TxContext<C>, production_start: TxId)
+                           -> Result<u64, VerificationError> where C: ChainStorage{
+
+    let mut values = vec![];
+
+    for tx_id in context.get_claimers(production_start, TxRelId::ProductionWorkloads){
+        let transaction = context.get_transaction(tx_id)?;
+        if let TxPayload::WorkloadAllocation(ref workload) = *transaction.get_payload(){
+            values.push(workload.get_minutes());
+        }
+    }
+
+    for tx_id in context.get_claimers(production_start, TxRelId::ProductionResources){
+        let transaction = context.get_transaction(tx_id)?;
+        if let TxPayload::ConsumptiveResourceAllocation(ref allocation) = *transaction.get_payload(){
+            values.push(get_resource(context, allocation.get_resource())?.value);
+        }
+    }
+
+    for tx_id in context.get_claimers(production_start, TxRelId::ProductionMopDeallocations){
+        values.push(mop_value_transfer(context, tx_id)?);
+    }
+
+    values.into_iter().try_fold(0u64, |sum, value| sum.checked_add(value)).ok_or_else(|| {
+        VerificationError::new(VerificationErrorReason::InvalidAmount)
+    })
+
+}
+
+/// Computes the value (in minutes) a mean of production
+/// transferred to a production between its allocation and
+/// the supplied deallocation (see `deallocation_value`).
+///
+/// Returns a VerificationError with reason InvalidAmount
+/// if the transfer overflows
+///
+/// # Arguments
+/// * `context`: The verification context
+/// * `deallocation`: The id of the deallocation
+
+pub fn mop_value_transfer<C>(context: &TxContext<C>, deallocation: TxId)
+                             -> Result<u64, VerificationError> where C: ChainStorage{
+
+    let products = &context.get_indexes().products;
+    deallocation_value(context.get_storage(), products, deallocation).ok_or_else(|| {
+        VerificationError::new(VerificationErrorReason::InvalidAmount)
+    })
+
+}
+
+/// Computes the value (in minutes) a mean of production
+/// transferred to a production between its allocation and
+/// the supplied deallocation. The transfer θ(P, k1, k2) of its
+/// product type (see the depreciation module) is scaled by the
+/// declared value of the resource relative to the aggregated
+/// value v*(P), so a resource never transfers more (or less)
+/// than its declared value over its whole durability. The ages
+/// k1 and k2 are the minutes between the creation of the
+/// resource and the blocks holding the allocation and
+/// deallocation.
+///
+/// Collectivized means of production carry no value and
+/// transfer nothing.
+///
+/// Returns None if the transactions can't be found, the
+/// product type isn't registered or the transfer overflows
+///
+/// # Arguments
+/// * `storage`: The storage holding the chain
+/// * `products`: The product type index of the chain
+/// * `deallocation`: The id of the deallocation
+
+pub fn deallocation_value<C>(storage: &C,
+                             products: &ProductTypeIndex,
+                             deallocation: TxId) -> Option<u64> where C: ChainStorage{
+
+    let allocation = match *storage.get_transaction(deallocation)?.get_payload(){
+        TxPayload::MopDeallocation(ref payload) => payload.get_allocation(),
+        _ => return None
+    };
+
+    let mop = match *storage.get_transaction(allocation)?.get_payload(){
+        TxPayload::MopAllocation(ref payload) => payload.get_mop(),
+        _ => return None
+    };
+
+    let resource = created_resource(&storage.get_transaction(mop)?)?;
+    let registry = ProductRegistry::new(storage, products);
+
+    if resource.value == 0{
+        return Some(0)
+    }
+
+    let aggregated_value = registry.get_aggregated_value(resource.product_type)?;
+    if aggregated_value == 0{
+        return Some(0)
+    }
+
+    let timestamp = |tx_id: TxId| storage.get_header(tx_id.block_id).map(|header| header.get_timestamp());
+
+    let created = timestamp(mop)?;
+    let k1 = timestamp(allocation)?.saturating_sub(created) / 60;
+    let k2 = timestamp(deallocation)?.saturating_sub(created) / 60;
+
+    theta(&registry, resource.product_type, k1, k2)
+        .and_then(|transfer| transfer.checked_mul_integer(resource.value))
+        .and_then(|transfer| transfer.checked_div_integer(aggregated_value))
+        .and_then(|transfer| transfer.floor())
+
+}
+
+// ------------------------------------------------------------------------
+
+/// `Resource` describes a product that can be allocated
+/// to a production, either consumptively or as a mean
+/// of production.
+
+#[derive(Clone)]
+#[derive(Debug)]
+pub struct Resource{
+    pub owner: [u8; 32],
+    pub product_type: [u8; 32],
+    pub amount: u64,
+    pub value: u64,
+    pub common: bool
+}
+
+/// Returns the resource created by a transaction as it was
+/// created, that is owned by the collective that signed the
+/// transaction, or None if the transaction doesn't create a
+/// resource. Collectivized resources carry no value and
+/// count as commons.
+///
+/// # Arguments
+/// * `transaction`: The transaction creating the resource
+
+pub fn created_resource(transaction: &Transaction) -> Option<Resource>{
+
+    let owner = *transaction.get_signer_pubkeys().first()?;
+
+    match *transaction.get_payload(){
+        TxPayload::ProductionOutput(ref output) => {
+            Some(Resource{
+                owner: owner,
+                product_type: output.get_product_type(),
+                amount: output.get_amount(),
+                value: output.get_value(),
+                common: output.is_common()
+            })
+        },
+        TxPayload::Collectivization(ref payload) => {
+            Some(Resource{
+                owner: owner,
+                product_type: payload.get_product_type(),
+                amount: payload.get_amount(),
+                value: 0,
+                common: true
+            })
+        },
+        _ => None
+    }
+
+}
+
+/// Fetches the resource created by a transaction. The owner
+/// is the collective the resource was last transferred to
+/// (see `OwnershipIndex`).
+///
+/// Returns a VerificationError with reason InvalidTxRef
+/// if the transaction doesn't create a resource
+///
+/// # Arguments
+/// * `context`: The verification context
+/// * `tx_id`: The id of the transaction creating the resource
+
+pub fn get_resource<C>(context: &TxContext<C>, tx_id: TxId)
+                       -> Result<Resource, VerificationError> where C: ChainStorage{
+
+    let transaction = context.get_transaction(tx_id)?;
+    let resource = created_resource(&transaction);
+    let owner = context.get_indexes().ownership.get_owner(tx_id);
+
+    match (resource, owner){
+        (Some(resource), Some(owner)) => Ok(Resource{owner: owner, ..resource}),
+        _ => {
+            let reason = VerificationErrorReason::InvalidTxRef(tx_id);
+            Err(VerificationError::new(reason))
+        }
+    }
+
+}
+
+// ------------------------------------------------------------------------
+
+#[test]
+fn test_allocation_bytes(){
+
+    use blockchain::block::BlockId;
+    use blockchain::transactions::TxIndex;
+
+    let production_start = TxId::new(BlockId([0x01; 32]), TxIndex(1));
+    let resource = TxId::new(BlockId([0x02; 32]), TxIndex(2));
+
+    let workload = WorkloadAllocation::new(production_start, [0x03; 32], 480, true);
+    let bytes = workload.as_bytes();
+    assert_eq!(bytes.len(), 34 + 32 + 8 + 1);
+    let decoded = WorkloadAllocation::from_bytes(bytes).unwrap();
+    assert_eq!(decoded.get_production_start(), production_start);
+    assert_eq!(decoded.get_wallet_pubkey(), [0x03; 32]);
+    assert_eq!(decoded.get_minutes(), 480);
+    assert!(decoded.is_common());
+
+    let allocation = ConsumptiveResourceAllocation::new(production_start, resource);
+    let decoded = ConsumptiveResourceAllocation::from_bytes(allocation.as_bytes()).unwrap();
+    assert_eq!(decoded.get_production_start(), production_start);
+    assert_eq!(decoded.get_resource(), resource);
+
+    let allocation = MopAllocation::new(production_start, resource, true, true);
+    let bytes = allocation.as_bytes();
+    assert_eq!(bytes.len(), 34 + 34 + 2);
+    let decoded = MopAllocation::from_bytes(bytes.clone()).unwrap();
+    assert_eq!(decoded.get_production_start(), production_start);
+    assert_eq!(decoded.get_mop(), resource);
+    assert!(decoded.is_common() && decoded.is_repurposed());
+    assert!(MopAllocation::from_bytes(bytes[..69].to_vec()).is_err());
+
+    let deallocation = MopDeallocation::new(resource);
+    let decoded = MopDeallocation::from_bytes(deallocation.as_bytes()).unwrap();
+    assert_eq!(decoded.get_allocation(), resource);
+
+}
+
+#[test]
+fn test_production_allocations(){
+
+    use blockchain::transactions::TxTotalRelState;
+    use blockchain::transactions::BadClaimReason;
+    use blockchain::traits::ChainStorage;
+    use blockchain::txtypes::production::ProductionOutput;
+    use blockchain::txtypes::TestChain;
+    use blockchain::txtypes::signed_transaction;
+    use blockchain::txtypes::register_collective;
+    use blockchain::txtypes::define_product_type;
+    use blockchain::txtypes::start_production;
+    use blockchain::txtypes::keypair;
+
+    let (secret_a, _) = keypair(0x0A);
+    let (secret_b, _) = keypair(0x0B);
+
+    let mut chain = TestChain::new();
+    chain.append(1, vec![register_collective(&secret_a), register_collective(&secret_b)]).unwrap();
+    let (bread, definition) = define_product_type(&secret_a, "bread");
+    chain.append(2, vec![definition]).unwrap();
+
+    let workload = |production_start, minutes| {
+        let payload = WorkloadAllocation::new(production_start, [0x01; 32], minutes, false);
+        signed_transaction(TxPayload::WorkloadAllocation(payload), &[&secret_a])
+    };
+    let consume = |production_start, resource| {
+        let payload = ConsumptiveResourceAllocation::new(production_start, resource);
+        signed_transaction(TxPayload::ConsumptiveResourceAllocation(payload), &[&secret_a])
+    };
+    let allocate = |production_start, mop| {
+        let payload = MopAllocation::new(production_start, mop, false, false);
+        signed_transaction(TxPayload::MopAllocation(payload), &[&secret_a])
+    };
+    let deallocate = |allocation| {
+        let payload = MopDeallocation::new(allocation);
+        signed_transaction(TxPayload::MopDeallocation(payload), &[&secret_a])
+    };
+    let output = |secret_key: &[u8; 64], production_start| {
+        let payload = ProductionOutput::new(production_start, bread, 1, 120, false);
+        signed_transaction(TxPayload::ProductionOutput(payload), &[secret_key])
+    };
+
+    // produce two resources in two productions
+
+    let production_1 = start_production(&mut chain, &secret_a, 10);
+    let production_2 = start_production(&mut chain, &secret_a, 20);
+
+    let ids = chain.append(30, vec![workload(production_1, 60),
+                                    workload(production_1, 60),
+                                    workload(production_2, 120)]).unwrap();
+
+    match chain.append(31, vec![workload(production_1, 0)]){
+        Err(VerificationErrorReason::InvalidAmount) => {},
+        result => panic!("Unexpected result {:?}", result)
+    }
+
+    // only the producing collective can finish a production
+
+    match chain.append(31, vec![output(&secret_b, production_1)]){
+        Err(VerificationErrorReason::InvalidTxSigners) => {},
+        result => panic!("Unexpected result {:?}", result)
+    }
+
+    let ids = [ids, chain.append(31, vec![output(&secret_a, production_1),
+                                          output(&secret_a, production_2)]).unwrap()].concat();
+    let (resource_1, resource_2) = (ids[3], ids[4]);
+
+    let tx_state = chain.storage.get_transaction_state(production_1).unwrap();
+    match *tx_state.get_total_rel_state(){
+        TxTotalRelState::Finalized(tx_id) => assert_eq!(tx_id, resource_1),
+        _ => panic!("Production output didn't finalize the production")
+    }
+
+    // finalized productions accept no further allocations
+
+    match chain.append(32, vec![workload(production_1, 60)]){
+        Err(VerificationErrorReason::BadClaim(BadClaimReason::TxFinalized(tx_id))) => {
+            assert_eq!(tx_id, resource_1)
+        },
+        result => panic!("Unexpected result {:?}", result)
+    }
+
+    // a third production consumes the first resource and
+    // uses the second one as mean of production
+
+    let production_3 = start_production(&mut chain, &secret_a, 40);
+    let ids = chain.append(50, vec![consume(production_3, resource_1),
+                                    allocate(production_3, resource_2)]).unwrap();
+    let allocation = ids[1];
+    assert!(chain.indexes.production.get_open_allocations(production_3).contains(&allocation));
+
+    match chain.append(51, vec![consume(production_3, resource_1)]){
+        Err(VerificationErrorReason::BadClaim(BadClaimReason::RelClaimed(..))) => {},
+        result => panic!("Unexpected result {:?}", result)
+    }
+
+    match chain.append(51, vec![consume(production_3, resource_2)]){
+        Err(VerificationErrorReason::ResourceInUse) => {},
+        result => panic!("Unexpected result {:?}", result)
+    }
+
+    match chain.append(51, vec![allocate(production_3, resource_2)]){
+        Err(VerificationErrorReason::ResourceInUse) => {},
+        result => panic!("Unexpected result {:?}", result)
+    }
+
+    match chain.append(51, vec![allocate(production_3, resource_1)]){
+        Err(VerificationErrorReason::ResourceInUse) => {},
+        result => panic!("Unexpected result {:?}", result)
+    }
+
+    // resources of other collectives can't be allocated
+
+    let production_b = start_production(&mut chain, &secret_b, 60);
+    let payload = MopAllocation::new(production_b, resource_2, false, false);
+    let foreign = signed_transaction(TxPayload::MopAllocation(payload), &[&secret_b]);
+    match chain.append(70, vec![foreign]){
+        Err(VerificationErrorReason::InvalidOwner) => {},
+        result => panic!("Unexpected result {:?}", result)
+    }
+
+    // after the deallocation the mean of
+    // production can be allocated again
+
+    chain.append(71, vec![deallocate(allocation)]).unwrap();
+    assert!(chain.indexes.production.get_open_allocations(production_3).is_empty());
+
+    match chain.append(72, vec![deallocate(allocation)]){
+        Err(VerificationErrorReason::BadClaim(BadClaimReason::RelClaimed(..))) => {},
+        result => panic!("Unexpected result {:?}", result)
+    }
+
+    chain.append(72, vec![allocate(production_3, resource_2)]).unwrap();
+    assert!(chain.indexes.production.get_open_allocation(resource_2).is_some());
+
+}
+
+#[test]
+fn test_mop_value_transfer(){
+
+    use blockchain::txtypes::production::ProductionOutput;
+    use blockchain::txtypes::product::ProductTypeDefinition;
+    use blockchain::txtypes::TestChain;
+    use blockchain::txtypes::signed_transaction;
+    use blockchain::txtypes::register_collective;
+    use blockchain::txtypes::start_production;
+    use blockchain::txtypes::keypair;
+
+    let (secret_a, _) = keypair(0x0A);
+    let sign = |payload| signed_transaction(payload, &[&secret_a]);
+
+    let mut chain = TestChain::new();
+    chain.append(1, vec![register_collective(&secret_a)]).unwrap();
+
+    // an oven lasting 1000 minutes with a value of 300 minutes
+
+    let definition = ProductTypeDefinition::new(String::from("oven"), 1000, 300, vec![]);
+    let oven = definition.get_product_type();
+    chain.append(2, vec![sign(TxPayload::ProductTypeDefinition(definition))]).unwrap();
+
+    let production_1 = start_production(&mut chain, &secret_a, 10);
+    let payload = WorkloadAllocation::new(production_1, [0x01; 32], 600, false);
+    chain.append(20, vec![sign(TxPayload::WorkloadAllocation(payload))]).unwrap();
+    let payload = ProductionOutput::new(production_1, oven, 2, 600, false);
+    let mop = chain.append(60, vec![sign(TxPayload::ProductionOutput(payload))]).unwrap()[0];
+
+    // the ovens are allocated at the age of 50 minutes
+    // and deallocated at the age of 150 minutes
+
+    let production_2 = start_production(&mut chain, &secret_a, 100);
+    let payload = MopAllocation::new(production_2, mop, false, false);
+    let allocation = chain.append(60 + 50 * 60,
+                                  vec![sign(TxPayload::MopAllocation(payload))]).unwrap()[0];
+    let payload = MopDeallocation::new(allocation);
+    chain.append(60 + 150 * 60, vec![sign(TxPayload::MopDeallocation(payload))]).unwrap();
+
+    // both ovens transfer a tenth of their value
+
+    let payload = ProductionOutput::new(production_2, oven, 1, 0, false);
+    match chain.append(60 + 151 * 60, vec![sign(TxPayload::ProductionOutput(payload))]){
+        Err(VerificationErrorReason::ValueImbalance(_, transferred)) => assert_eq!(transferred, 60),
+        result => panic!("Unexpected result {:?}", result)
+    }
+
+    let payload = ProductionOutput::new(production_2, oven, 1, 60, false);
+    chain.append(60 + 151 * 60, vec![sign(TxPayload::ProductionOutput(payload))]).unwrap();
+
+}
+
+#[test]
+fn test_mop_declared_value(){
+
+    use blockchain::txtypes::production::ProductionOutput;
+    use blockchain::txtypes::product::ProductTypeDefinition;
+    use blockchain::txtypes::TestChain;
+    use blockchain::txtypes::signed_transaction;
+    use blockchain::txtypes::register_collective;
+    use blockchain::txtypes::start_production;
+    use blockchain::txtypes::keypair;
+
+    let (secret_a, _) = keypair(0x0A);
+    let sign = |payload| signed_transaction(payload, &[&secret_a]);
+
+    let mut chain = TestChain::new();
+    chain.append(1, vec![register_collective(&secret_a)]).unwrap();
+
+    // two ovens worth 300 minutes each are produced
+    // with a workload of only 100 minutes
+
+    let definition = ProductTypeDefinition::new(String::from("oven"), 1000, 300, vec![]);
+    let oven = definition.get_product_type();
+    chain.append(2, vec![sign(TxPayload::ProductTypeDefinition(definition))]).unwrap();
+
+    let production_1 = start_production(&mut chain, &secret_a, 10);
+    let production_2 = start_production(&mut chain, &secret_a, 20);
+    let payload = WorkloadAllocation::new(production_1, [0x01; 32], 100, false);
+    chain.append(30, vec![sign(TxPayload::WorkloadAllocation(payload))]).unwrap();
+    let payload = ProductionOutput::new(production_1, oven, 2, 100, false);
+    let mop = chain.append(60, vec![sign(TxPayload::ProductionOutput(payload))]).unwrap()[0];
+
+    // used over their whole durability, the ovens transfer
+    // their declared value instead of 2 * 300 minutes
+
+    let payload = MopAllocation::new(production_2, mop, false, false);
+    let allocation = chain.append(61, vec![sign(TxPayload::MopAllocation(payload))]).unwrap()[0];
+    let payload = MopDeallocation::new(allocation);
+    chain.append(60 + 1000 * 60, vec![sign(TxPayload::MopDeallocation(payload))]).unwrap();
+
+    let payload = ProductionOutput::new(production_2, oven, 1, 0, false);
+    match chain.append(60 + 1001 * 60, vec![sign(TxPayload::ProductionOutput(payload))]){
+        Err(VerificationErrorReason::ValueImbalance(_, transferred)) => assert_eq!(transferred, 100),
+        result => panic!("Unexpected result {:?}", result)
+    }
+
+    // outputs of unregistered product types have no
+    // known value transfer and are rejected
+
+    let payload = ProductionOutput::new(production_2, [0x02; 32], 1, 100, false);
+    match chain.append(60 + 1001 * 60, vec![sign(TxPayload::ProductionOutput(payload))]){
+        Err(VerificationErrorReason::UnknownProductType) => {},
+        result => panic!("Unexpected result {:?}", result)
+    }
+
+    let payload = ProductionOutput::new(production_2, oven, 1, 100, false);
+    chain.append(60 + 1001 * 60, vec![sign(TxPayload::ProductionOutput(payload))]).unwrap();
+
+}
+
+#[test]
+fn test_commons_separation(){
+
+    use blockchain::transactions::Transaction;
+    use blockchain::txtypes::production::Order;
+    use blockchain::txtypes::production::ValueEstimation;
+    use blockchain::txtypes::production::ProductionStart;
+    use blockchain::txtypes::production::ProductionOutput;
+    use blockchain::txtypes::product::ProductTypeDefinition;
+    use blockchain::txtypes::resource::Collectivization;
+    use blockchain::txtypes::revision::FinalRevision;
+    use blockchain::txtypes::revision::REVISION_PERIOD;
+    use blockchain::txtypes::TestChain;
+    use blockchain::txtypes::signed_transaction;
+    use blockchain::txtypes::register_collective;
+    use blockchain::txtypes::start_production;
+    use blockchain::txtypes::define_product_type;
+    use blockchain::txtypes::start_labeled_production;
+    use blockchain::txtypes::keypair;
+
+    let (secret_a, pubkey_a) = keypair(0x0A);
+    let sign = |payload| signed_transaction(payload, &[&secret_a]);
+
+    let mut chain = TestChain::new();
+    chain.append(1, vec![register_collective(&secret_a)]).unwrap();
+
+    // exclusive ovens (a registered mean of production),
+    // exclusive bread and collectivized flour
+
+    let definition = ProductTypeDefinition::new(String::from("oven"), 1000, 300, vec![]);
+    let oven = definition.get_product_type();
+    let (bread_type, bread_definition) = define_product_type(&secret_a, "bread");
+    let (soup_type, soup_definition) = define_product_type(&secret_a, "soup");
+    chain.append(2, vec![sign(TxPayload::ProductTypeDefinition(definition)),
+                         bread_definition,
+                         soup_definition]).unwrap();
+
+    let production_1 = start_production(&mut chain, &secret_a, 10);
+    let production_2 = start_production(&mut chain, &secret_a, 20);
+    let payload_1 = WorkloadAllocation::new(production_1, [0x01; 32], 600, false);
+    let payload_2 = WorkloadAllocation::new(production_2, [0x01; 32], 30, false);
+    chain.append(30, vec![sign(TxPayload::WorkloadAllocation(payload_1)),
+                          sign(TxPayload::WorkloadAllocation(payload_2))]).unwrap();
+    let payload_1 = ProductionOutput::new(production_1, oven, 2, 600, false);
+    let payload_2 = ProductionOutput::new(production_2, bread_type, 10, 30, false);
+    let payload_3 = Collectivization::new([0x03; 32], 5);
+    let ids = chain.append(60, vec![sign(TxPayload::ProductionOutput(payload_1)),
+                                    sign(TxPayload::ProductionOutput(payload_2)),
+                                    sign(TxPayload::Collectivization(payload_3))]).unwrap();
+    let (mop, bread, flour) = (ids[0], ids[1], ids[2]);
+
+    // all transactions of a production cycle carry its label
+
+    let order = Order::new([0xFF; 32], 1, pubkey_a, pubkey_a, 70, None, true);
+    let commons_order = chain.append(70, vec![sign(TxPayload::Order(order))]).unwrap()[0];
+
+    let sub_order = Order::new([0xFF; 32], 1, pubkey_a, pubkey_a, 70, Some(commons_order), false);
+    match chain.append(71, vec![sign(TxPayload::Order(sub_order))]){
+        Err(VerificationErrorReason::InvalidLabel) => {},
+        result => panic!("Unexpected result {:?}", result)
+    }
+
+    let estimation = ValueEstimation::new(commons_order, 60);
+    let estimation = chain.append(71, vec![sign(TxPayload::ValueEstimation(estimation))]).unwrap()[0];
+    let start = ProductionStart::new(estimation, false);
+    match chain.append(72, vec![sign(TxPayload::ProductionStart(start))]){
+        Err(VerificationErrorReason::InvalidLabel) => {},
+        result => panic!("Unexpected result {:?}", result)
+    }
+
+    let production_c = start_labeled_production(&mut chain, &secret_a, 80, true);
+    let production_e = start_production(&mut chain, &secret_a, 90);
+
+    let payload = WorkloadAllocation::new(production_c, [0x01; 32], 120, false);
+    match chain.append(100, vec![sign(TxPayload::WorkloadAllocation(payload))]){
+        Err(VerificationErrorReason::InvalidLabel) => {},
+        result => panic!("Unexpected result {:?}", result)
+    }
+
+    // commons productions don't consume exclusive resources
+
+    let payload = ConsumptiveResourceAllocation::new(production_c, bread);
+    match chain.append(100, vec![sign(TxPayload::ConsumptiveResourceAllocation(payload))]){
+        Err(VerificationErrorReason::SeparationViolation) => {},
+        result => panic!("Unexpected result {:?}", result)
+    }
+
+    // exclusive means of production must be repurposed, which
+    // only applies to commons productions
+
+    let allocation = |production_start, common, repurposed| {
+        sign(TxPayload::MopAllocation(MopAllocation::new(production_start, mop, common, repurposed)))
+    };
+    match chain.append(100, vec![allocation(production_c, true, false)]){
+        Err(VerificationErrorReason::SeparationViolation) => {},
+        result => panic!("Unexpected result {:?}", result)
+    }
+    match chain.append(100, vec![allocation(production_c, false, true)]){
+        Err(VerificationErrorReason::InvalidLabel) => {},
+        result => panic!("Unexpected result {:?}", result)
+    }
+    match chain.append(100, vec![allocation(production_e, false, true)]){
+        Err(VerificationErrorReason::InvalidLabel) => {},
+        result => panic!("Unexpected result {:?}", result)
+    }
+
+    let workload = WorkloadAllocation::new(production_c, [0x01; 32], 120, true);
+    let consumption = ConsumptiveResourceAllocation::new(production_c, flour);
+    let ids = chain.append(60 + 50 * 60, vec![sign(TxPayload::WorkloadAllocation(workload)),
+                                              sign(TxPayload::ConsumptiveResourceAllocation(consumption)),
+                                              allocation(production_c, true, true)]).unwrap();
+    let deallocation = MopDeallocation::new(ids[2]);
+    chain.append(60 + 150 * 60, vec![sign(TxPayload::MopDeallocation(deallocation))]).unwrap();
+
+    let output = |common| {
+        sign(TxPayload::ProductionOutput(ProductionOutput::new(production_c, soup_type, 1, 180, common)))
+    };
+    match chain.append(60 + 151 * 60, vec![output(false)]){
+        Err(VerificationErrorReason::InvalidLabel) => {},
+        result => panic!("Unexpected result {:?}", result)
+    }
+    chain.append(60 + 151 * 60, vec![output(true)]).unwrap();
+
+    // the commons sum holds the commons workloads and the
+    // value transferred by the repurposed ovens
+
+    let revision = FinalRevision::from_chain(&chain.storage, &chain.indexes.products, 0, REVISION_PERIOD)
+                                 .unwrap();
+    assert_eq!(revision.get_workloads(), 750);
+    assert_eq!(revision.get_commons(), 120 + 60);
+
+    let payload = FinalRevision::new(0, REVISION_PERIOD, 750, 180, 0);
+    chain.append(REVISION_PERIOD + 1, vec![Transaction::new(TxPayload::FinalRevision(payload),
+                                                            vec![])]).unwrap();
+
+}
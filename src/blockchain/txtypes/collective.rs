@@ -0,0 +1,598 @@
+//
+//    Stachanov
+//    Copyright (C) 2017 Stachanov Developer Collective
+//
+//    This file is part of Stachanov.
+//
+//    This program is free software: you can redistribute it and/or
+//    modify it under the terms of the GNU Affero General Public
+//    License, version 3, as published by the Free Software Foundation.
+//
+//    This program is distributed in the hope that it will be useful,
+//    but WITHOUT ANY WARRANTY; without even the implied warranty of
+//    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//    GNU Affero General Public License for more details.
+//
+//    You should have received a copy of the
+//               GNU Affero General Public License
+//    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+use blockchain::traits::BinFormat;
+use blockchain::traits::ChainStorage;
+use blockchain::transactions::Transaction;
+use blockchain::transactions::TxPayload;
+use blockchain::transactions::TxId;
+use blockchain::transactions::TxRelId;
+use blockchain::transactions::TxState;
+use blockchain::transactions::TxTotalRelState;
+use blockchain::errors::BinFormatError;
+use blockchain::errors::VerificationError;
+use blockchain::errors::VerificationErrorReason;
+use blockchain::journal::Journal;
+use blockchain::journal::Journaled;
+use blockchain::txtypes::TxContext;
+use blockchain::txtypes::verify_signers;
+use blockchain::txtypes::string_as_bytes;
+use blockchain::txtypes::read_string;
+use blockchain::txtypes::optional_tx_id_as_bytes;
+use blockchain::txtypes::read_optional_tx_id;
+use blockchain::txtypes::read_bool;
+use blockchain::utils::u16_to_u8le;
+use blockchain::utils::BinReader;
+
+/// `CollectiveRegistration` registers a collective on the chain
+/// or attaches an already registered collective to its parent.
+///
+/// A registration without a parent introduces the collective
+/// public key to the chain and must be signed by the collective
+/// only. Every collective has exactly one of these registrations,
+/// which holds the 1:1 `CollectiveParent` relationship.
+///
+/// A registration with a parent refers to the registration of
+/// the parent collective and claims the `CollectiveParent`
+/// relationship of the collective's own registration, so every
+/// collective gets at most one parent. It must be signed by the
+/// collective and by the parent collective (in this order).
+/// Parent relationships that would create a cycle are rejected,
+/// so collectives always form well-defined trees.
+///
+/// The registration without a parent also declares whether the
+/// collective is a C2I collective (collective to individual),
+/// which hands products over to consumers. The flag of
+/// registrations with a parent has no effect.
+
+#[derive(Clone)]
+#[derive(Debug)]
+pub struct CollectiveRegistration{
+    collective_pubkey: [u8; 32],
+    name: String,
+    metadata: Vec<u8>,
+    parent: Option<TxId>,
+    c2i: bool
+}
+
+impl CollectiveRegistration{
+
+    /// Creates a new `CollectiveRegistration`
+    ///
+    /// # Arguments
+    /// * `collective_pubkey`: The public key of the collective
+    /// * `name`: The name of the collective
+    /// * `metadata`: Arbitrary data describing the collective
+    /// * `parent`: The id of the registration of the parent
+    ///             collective or None
+    /// * `c2i`: Whether the collective is a C2I collective
+
+    pub fn new(collective_pubkey: [u8; 32],
+               name: String,
+               metadata: Vec<u8>,
+               parent: Option<TxId>,
+               c2i: bool) -> CollectiveRegistration{
+
+        assert!(name.len() <= 0xFFFF, "Collective name exceeds the maximum length");
+        assert!(metadata.len() <= 0xFFFF, "Collective metadata exceeds the maximum length");
+
+        CollectiveRegistration{
+            collective_pubkey: collective_pubkey,
+            name: name,
+            metadata: metadata,
+            parent: parent,
+            c2i: c2i
+        }
+
+    }
+
+    /// Gets the public key of the collective
+
+    pub fn get_collective_pubkey(&self) -> [u8; 32]{
+        self.collective_pubkey
+    }
+
+    /// Gets the name of the collective
+
+    pub fn get_name(&self) -> &str{
+        &self.name
+    }
+
+    /// Gets the metadata of the collective
+
+    pub fn get_metadata(&self) -> &[u8]{
+        &self.metadata
+    }
+
+    /// Gets the id of the registration of the parent collective
+
+    pub fn get_parent(&self) -> Option<TxId>{
+        self.parent
+    }
+
+    /// Checks if the collective is a C2I collective
+
+    pub fn is_c2i(&self) -> bool{
+        self.c2i
+    }
+
+    /// Verifies the registration against the chain
+    ///
+    /// # Arguments
+    /// * `transaction`: The transaction holding the registration
+    /// * `context`: The verification context
+
+    pub fn verify<C>(&self,
+                     transaction: &Transaction,
+                     context: &mut TxContext<C>)
+                     -> Result<(), VerificationError> where C: ChainStorage{
+
+        let parent_tx_id = match self.parent{
+            Some(parent_tx_id) => parent_tx_id,
+            None => {
+
+                verify_signers(transaction, &[self.collective_pubkey])?;
+
+                let collectives = &context.get_indexes().collectives;
+                if collectives.get_registration(self.collective_pubkey).is_some(){
+                    let reason = VerificationErrorReason::DuplicateCollective;
+                    return Err(VerificationError::new(reason))
+                }
+                return Ok(())
+
+            }
+        };
+
+        let (registration, parent_pubkey) = {
+
+            let collectives = &context.get_indexes().collectives;
+
+            let registration = match collectives.get_registration(self.collective_pubkey){
+                Some(registration) => registration,
+                None => {
+                    let reason = VerificationErrorReason::UnknownCollective;
+                    return Err(VerificationError::new(reason))
+                }
+            };
+
+            // the parent must be referenced by its
+            // registration without a parent
+
+            let parent_pubkey = match collectives.get_collective_pubkey(parent_tx_id){
+                Some(parent_pubkey) => parent_pubkey,
+                None => {
+                    let reason = VerificationErrorReason::InvalidTxRef(parent_tx_id);
+                    return Err(VerificationError::new(reason))
+                }
+            };
+
+            if collectives.is_ancestor_or_self(self.collective_pubkey, parent_pubkey){
+                let reason = VerificationErrorReason::CollectiveCycle;
+                return Err(VerificationError::new(reason))
+            }
+
+            (registration, parent_pubkey)
+
+        };
+
+        verify_signers(transaction, &[self.collective_pubkey, parent_pubkey])?;
+        context.claim_rel(registration, TxRelId::CollectiveParent)
+
+    }
+
+    /// Returns the initial state of the registration
+
+    pub fn initial_state(&self) -> TxState{
+
+        if self.parent.is_some(){
+            return TxState::new(TxTotalRelState::Unclaimable)
+        }
+
+        let mut tx_state = TxState::new(TxTotalRelState::Claimable);
+        tx_state.add_one_to_one_rel(TxRelId::CollectiveParent)
+                .expect("Fresh TxState already had a relationship");
+        tx_state
+
+    }
+
+}
+
+impl BinFormat<CollectiveRegistration> for CollectiveRegistration{
+
+    // This section implements (de)serialization methods for
+    // CollectiveRegistration. The byte format is (see
+    // the txtypes module for the shared field formats):
+
+    //    field              length
+    //  .--------------------------.
+    //  | collective_pubkey | 32   |
+    //  |--------------------------|
+    //  | name              | 2+n  |  (string)
+    //  |--------------------------|
+    //  | metadata_len      | 2    |
+    //  |--------------------------|
+    //  | metadata          | n    |
+    //  |--------------------------|
+    //  | parent            | 1/35 |  (optional TxId)
+    //  |--------------------------|
+    //  | c2i               | 1    |  (bool)
+    //  '--------------------------'
+
+    /// Returns the registration as an u8 vector
+
+    fn as_bytes(&self) -> Vec<u8>{
+
+        let metadata_len_u8le = u16_to_u8le(self.metadata.len() as u16);
+
+        [&self.collective_pubkey[..],
+         &string_as_bytes(&self.name)[..],
+         &metadata_len_u8le[..],
+         &self.metadata[..],
+         &optional_tx_id_as_bytes(self.parent)[..],
+         &[self.c2i as u8][..]].concat()
+
+    }
+
+    /// Creates a new registration from a byte vector.
+    ///
+    /// * `bytes`: A byte vector
+
+    fn from_bytes(bytes: Vec<u8>) -> Result<CollectiveRegistration, BinFormatError>{
+
+        let mut reader = BinReader::new(&bytes);
+
+        let collective_pubkey = reader.read_hash()?;
+
+        let name = read_string(&mut reader, "name")?;
+        let metadata_len = reader.read_u16()? as usize;
+        let metadata = reader.read_bytes(metadata_len)?.to_vec();
+        let parent = read_optional_tx_id(&mut reader, "has_parent")?;
+        let c2i = read_bool(&mut reader, "c2i")?;
+
+        reader.finish()?;
+
+        Ok(CollectiveRegistration{
+            collective_pubkey: collective_pubkey,
+            name: name,
+            metadata: metadata,
+            parent: parent,
+            c2i: c2i
+        })
+
+    }
+
+}
+
+// ------------------------------------------------------------------------
+
+/// `CollectiveIndex` keeps track of all registered collectives,
+/// their parents and which of them are C2I collectives. It is
+/// updated with every applied block.
+
+pub struct CollectiveIndex{
+    registrations: HashMap<[u8; 32], TxId>,
+    pubkeys: HashMap<TxId, [u8; 32]>,
+    parents: HashMap<[u8; 32], [u8; 32]>,
+    c2i: HashSet<[u8; 32]>,
+    journal: Journal<CollectiveIndex>
+}
+
+impl CollectiveIndex{
+
+    /// Creates a new empty `CollectiveIndex`
+
+    pub fn new() -> CollectiveIndex{
+        CollectiveIndex{
+            registrations: HashMap::new(),
+            pubkeys: HashMap::new(),
+            parents: HashMap::new(),
+            c2i: HashSet::new(),
+            journal: Journal::new()
+        }
+    }
+
+    /// Fetches the id of the registration (without
+    /// parent) of a collective
+    ///
+    /// # Arguments
+    /// * `collective_pubkey`: The public key of the collective
+
+    pub fn get_registration(&self, collective_pubkey: [u8; 32]) -> Option<TxId>{
+        self.registrations.get(&collective_pubkey).cloned()
+    }
+
+    /// Fetches the public key of the collective registered
+    /// by the supplied registration (without parent)
+    ///
+    /// # Arguments
+    /// * `registration`: The id of the registration
+
+    pub fn get_collective_pubkey(&self, registration: TxId) -> Option<[u8; 32]>{
+        self.pubkeys.get(&registration).cloned()
+    }
+
+    /// Fetches the public key of the parent of a collective
+    ///
+    /// # Arguments
+    /// * `collective_pubkey`: The public key of the collective
+
+    pub fn get_parent(&self, collective_pubkey: [u8; 32]) -> Option<[u8; 32]>{
+        self.parents.get(&collective_pubkey).cloned()
+    }
+
+    /// Checks if a registered collective is a C2I collective
+    ///
+    /// # Arguments
+    /// * `collective_pubkey`: The public key of the collective
+
+    pub fn is_c2i(&self, collective_pubkey: [u8; 32]) -> bool{
+        self.c2i.contains(&collective_pubkey)
+    }
+
+    /// Checks if a collective is the supplied
+    /// collective or one of its ancestors
+    ///
+    /// # Arguments
+    /// * `ancestor_pubkey`: The public key of the possible ancestor
+    /// * `collective_pubkey`: The public key of the collective
+
+    pub fn is_ancestor_or_self(&self,
+                               ancestor_pubkey: [u8; 32],
+                               collective_pubkey: [u8; 32]) -> bool{
+
+        // parent relationships can't form cycles,
+        // so the walk always terminates
+
+        let mut current = Some(collective_pubkey);
+        while let Some(pubkey) = current{
+            if pubkey == ancestor_pubkey{
+                return true
+            }
+            current = self.get_parent(pubkey);
+        }
+        false
+
+    }
+
+    /// Updates the index with a verified transaction
+    ///
+    /// # Arguments
+    /// * `tx_id`: The id of the transaction
+    /// * `transaction`: The verified transaction
+
+    pub fn apply_transaction(&mut self, tx_id: TxId, transaction: &Transaction){
+
+        if let TxPayload::CollectiveRegistration(ref payload) = *transaction.get_payload(){
+
+            let collective_pubkey = payload.get_collective_pubkey();
+
+            match payload.get_parent(){
+                None => {
+                    let previous = self.registrations.insert(collective_pubkey, tx_id);
+                    self.journal.record_entry(|index| &mut index.registrations, collective_pubkey, previous);
+                    let previous = self.pubkeys.insert(tx_id, collective_pubkey);
+                    self.journal.record_entry(|index| &mut index.pubkeys, tx_id, previous);
+                    if payload.is_c2i(){
+                        let was_member = !self.c2i.insert(collective_pubkey);
+                        self.journal.record_member(|index| &mut index.c2i, collective_pubkey, was_member);
+                    }
+                },
+                Some(parent_tx_id) => {
+                    if let Some(parent_pubkey) = self.get_collective_pubkey(parent_tx_id){
+                        let previous = self.parents.insert(collective_pubkey, parent_pubkey);
+                        self.journal.record_entry(|index| &mut index.parents, collective_pubkey, previous);
+                    }
+                }
+            }
+
+        }
+
+    }
+
+}
+
+impl Journaled for CollectiveIndex{
+    fn get_journal(&mut self) -> &mut Journal<CollectiveIndex>{
+        &mut self.journal
+    }
+}
+
+#[test]
+fn test_collective_registration_to_bytes_from_bytes(){
+
+    use blockchain::block::BlockId;
+    use blockchain::transactions::TxIndex;
+    use blockchain::errors::BinFormatErrorReason;
+
+    let parent = TxId::new(BlockId([0x0A; 32]), TxIndex(3));
+    let registration = CollectiveRegistration::new([0x01; 32],
+                                                   String::from("Kolchose"),
+                                                   vec![0xBE, 0xEF],
+                                                   Some(parent),
+                                                   true);
+
+    let as_bytes = registration.as_bytes();
+    assert_eq!(as_bytes.len(), 32 + 2 + 8 + 2 + 2 + 1 + 34 + 1);
+
+    let rebuild = CollectiveRegistration::from_bytes(as_bytes.clone());
+    match rebuild{
+        Ok(rebuild) => {
+            assert_eq!(rebuild.get_collective_pubkey(), [0x01; 32]);
+            assert_eq!(rebuild.get_name(), "Kolchose");
+            assert_eq!(rebuild.get_metadata(), &[0xBE, 0xEF]);
+            assert_eq!(rebuild.get_parent(), Some(parent));
+            assert!(rebuild.is_c2i());
+        },
+        Err(_) => assert!(false, "Valid binary representation of CollectiveRegistration \
+                                  could not be deserialized into an object")
+    }
+
+    // invalid UTF-8 in the name
+
+    let mut faulty = as_bytes.clone();
+    faulty[34] = 0xFF;
+    match CollectiveRegistration::from_bytes(faulty){
+        Err(err) => match err.reason{
+            BinFormatErrorReason::InvalidFieldData(ref field) if field == "name" => {},
+            _ => assert!(false, "Invalid name was rejected for the wrong reason")
+        },
+        Ok(_) => assert!(false, "Registration with invalid name was accepted")
+    }
+
+    // the registration roundtrips as part of a transaction
+
+    let transaction = Transaction::new(TxPayload::CollectiveRegistration(registration),
+                                       vec![[0x01; 32]]);
+    let rebuild = Transaction::from_bytes(transaction.as_bytes()).unwrap();
+    assert_eq!(rebuild.get_type(), 0x01);
+    assert_eq!(rebuild.as_bytes(), transaction.as_bytes());
+
+}
+
+#[test]
+fn test_collective_registration_hierarchy(){
+
+    use blockchain::block::Block;
+    use blockchain::block::BlockErrorReason;
+    use blockchain::transactions::TxIndex;
+    use blockchain::transactions::TxRel;
+    use blockchain::transactions::BadClaimReason;
+    use blockchain::storages::memory::MemoryChainStorage;
+    use blockchain::processor::ChainIndexes;
+    use blockchain::processor::apply_block;
+    use blockchain::txtypes::signed_transaction;
+    use blockchain::txtypes::keypair;
+
+    let (secret_a, pubkey_a) = keypair(0x0A);
+    let (secret_b, pubkey_b) = keypair(0x0B);
+    let (secret_c, pubkey_c) = keypair(0x0C);
+
+    let register = |pubkey, parent, secret_keys: &[&[u8]]| {
+        let payload = CollectiveRegistration::new(pubkey, String::from("collective"),
+                                                  vec![], parent, false);
+        signed_transaction(TxPayload::CollectiveRegistration(payload), secret_keys)
+    };
+
+    let rejection = |result: Result<(), _>| {
+        match result{
+            Err(::blockchain::block::BlockError{
+                reason: BlockErrorReason::InvalidTransaction(_, err)}) => err.reason,
+            result => panic!("Unexpected result {:?}", result)
+        }
+    };
+
+    let mut storage = MemoryChainStorage::new();
+    let mut indexes = ChainIndexes::new();
+
+    // register a, b and c without parents
+
+    let genesis = Block::new([0; 32], None, 1,
+                             vec![register(pubkey_a, None, &[&secret_a]),
+                                  register(pubkey_b, None, &[&secret_b]),
+                                  register(pubkey_c, None, &[&secret_c])]);
+    let genesis_id = genesis.get_id();
+    let reg_a = TxId::new(genesis_id, TxIndex(0));
+    let reg_b = TxId::new(genesis_id, TxIndex(1));
+    let reg_c = TxId::new(genesis_id, TxIndex(2));
+
+    apply_block(&mut storage, &mut indexes, genesis.clone()).unwrap();
+    assert_eq!(indexes.collectives.get_registration(pubkey_b), Some(reg_b));
+
+    // registering a collective twice is rejected
+
+    let block = Block::new([0; 32], Some(&genesis), 2,
+                           vec![register(pubkey_a, None, &[&secret_a])]);
+    assert!(matches!(rejection(apply_block(&mut storage, &mut indexes, block)),
+                     VerificationErrorReason::DuplicateCollective));
+
+    // attaching needs the signature of the parent
+
+    let block = Block::new([0; 32], Some(&genesis), 2,
+                           vec![register(pubkey_b, Some(reg_a), &[&secret_b])]);
+    assert!(matches!(rejection(apply_block(&mut storage, &mut indexes, block)),
+                     VerificationErrorReason::InvalidTxSigners));
+
+    // attach b to a
+
+    let attach_b = register(pubkey_b, Some(reg_a), &[&secret_b, &secret_a]);
+    let block_2 = Block::new([0; 32], Some(&genesis), 2, vec![attach_b]);
+    let attach_b_id = TxId::new(block_2.get_id(), TxIndex(0));
+
+    apply_block(&mut storage, &mut indexes, block_2.clone()).unwrap();
+    assert_eq!(indexes.collectives.get_parent(pubkey_b), Some(pubkey_a));
+
+    match storage.get_transaction_state(reg_b).unwrap().get_rel(TxRelId::CollectiveParent){
+        Ok(&TxRel::OneToOne(Some(claimer))) => assert_eq!(claimer, attach_b_id),
+        _ => assert!(false, "Parent relationship was not claimed")
+    }
+
+    // a second parent for b is rejected by the claim
+
+    let block = Block::new([0; 32], Some(&block_2), 3,
+                           vec![register(pubkey_b, Some(reg_c), &[&secret_b, &secret_c])]);
+    match apply_block(&mut storage, &mut indexes, block){
+        Err(::blockchain::block::BlockError{
+            reason: BlockErrorReason::InvalidTransaction(_, err)}) => match err.reason{
+            VerificationErrorReason::BadClaim(BadClaimReason::RelClaimed(..)) => {},
+            _ => assert!(false, "Double parent was rejected for the wrong reason")
+        },
+        _ => assert!(false, "Double parent was accepted")
+    }
+
+    // a can't become a child of b (a -> b -> a)
+    // and b can't become its own parent
+
+    let block = Block::new([0; 32], Some(&block_2), 3,
+                           vec![register(pubkey_a, Some(reg_b), &[&secret_a, &secret_b])]);
+    assert!(matches!(rejection(apply_block(&mut storage, &mut indexes, block)),
+                     VerificationErrorReason::CollectiveCycle));
+
+    let block = Block::new([0; 32], Some(&block_2), 3,
+                           vec![register(pubkey_c, Some(reg_c), &[&secret_c])]);
+    assert!(matches!(rejection(apply_block(&mut storage, &mut indexes, block)),
+                     VerificationErrorReason::CollectiveCycle));
+
+    // two parents in the same block are rejected as well
+
+    let block = Block::new([0; 32], Some(&block_2), 3,
+                           vec![register(pubkey_c, Some(reg_a), &[&secret_c, &secret_a]),
+                                register(pubkey_c, Some(reg_b), &[&secret_c, &secret_b])]);
+    match apply_block(&mut storage, &mut indexes, block){
+        Err(::blockchain::block::BlockError{
+            reason: BlockErrorReason::InvalidTransaction(tx_id, _)}) => {
+            assert_eq!(tx_id.tx_index, TxIndex(1));
+        },
+        _ => assert!(false, "Double parent in the same block was accepted")
+    }
+
+    // failed blocks leave the chain and the indexes untouched
+
+    assert_eq!(storage.get_tail_block().unwrap().get_id(), block_2.get_id());
+    assert_eq!(indexes.collectives.get_parent(pubkey_c), None);
+
+    // the indexes can be rebuilt from the chain
+
+    let rebuilt = ChainIndexes::rebuild(&storage, indexes.params);
+    assert_eq!(rebuilt.collectives.get_parent(pubkey_b), Some(pubkey_a));
+    assert_eq!(rebuilt.collectives.get_registration(pubkey_c), Some(reg_c));
+
+}
@@ -0,0 +1,670 @@
+//
+//    Stachanov
+//    Copyright (C) 2017 Stachanov Developer Collective
+//
+//    This file is part of Stachanov.
+//
+//    This program is free software: you can redistribute it and/or
+//    modify it under the terms of the GNU Affero General Public
+//    License, version 3, as published by the Free Software Foundation.
+//
+//    This program is distributed in the hope that it will be useful,
+//    but WITHOUT ANY WARRANTY; without even the implied warranty of
+//    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//    GNU Affero General Public License for more details.
+//
+//    You should have received a copy of the
+//               GNU Affero General Public License
+//    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+// This module implements labor coupons. Workloads are transformed
+// into coupons by the holder of the wallet they were registered
+// for, once the period they were rendered in was revised.
+// Coupons are no currency: they can't be transferred and are
+// deleted when they are used to appropriate goods.
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+use blockchain::traits::BinFormat;
+use blockchain::traits::ChainStorage;
+use blockchain::transactions::Transaction;
+use blockchain::transactions::TxPayload;
+use blockchain::transactions::TxId;
+use blockchain::transactions::TxRelId;
+use blockchain::transactions::read_tx_id;
+use blockchain::errors::BinFormatError;
+use blockchain::errors::VerificationError;
+use blockchain::errors::VerificationErrorReason;
+use blockchain::txtypes::TxContext;
+use blockchain::txtypes::verify_signers;
+use blockchain::journal::Journal;
+use blockchain::journal::Journaled;
+use blockchain::utils::u64_to_u8le;
+use blockchain::utils::BinReader;
+
+/// `CouponClaim` transforms a workload l into labor coupons
+/// worth (1 - (c+w)/a) * l minutes, using the tax rate of the
+/// final revision of the period the workload was registered
+/// in. It claims the `WorkloadCoupon` relationship of the
+/// workload, so every workload is claimed at most once.
+///
+/// The claim is signed by the wallet of the workload and
+/// declares the credited amount, which is recomputed by
+/// every node.
+
+#[derive(Clone)]
+#[derive(Debug)]
+pub struct CouponClaim{
+    workload: TxId,
+    revision: TxId,
+    amount: u64
+}
+
+impl CouponClaim{
+
+    /// Creates a new `CouponClaim`
+    ///
+    /// # Arguments
+    /// * `workload`: The id of the claimed workload
+    /// * `revision`: The id of the final revision of its period
+    /// * `amount`: The credited amount in minutes
+
+    pub fn new(workload: TxId, revision: TxId, amount: u64) -> CouponClaim{
+        CouponClaim{workload: workload, revision: revision, amount: amount}
+    }
+
+    /// Gets the id of the claimed workload
+
+    pub fn get_workload(&self) -> TxId{
+        self.workload
+    }
+
+    /// Gets the id of the final revision
+
+    pub fn get_revision(&self) -> TxId{
+        self.revision
+    }
+
+    /// Gets the credited amount in minutes
+
+    pub fn get_amount(&self) -> u64{
+        self.amount
+    }
+
+    /// Verifies the claim against the chain
+    ///
+    /// # Arguments
+    /// * `transaction`: The transaction holding the claim
+    /// * `context`: The verification context
+
+    pub fn verify<C>(&self,
+                     transaction: &Transaction,
+                     context: &mut TxContext<C>)
+                     -> Result<(), VerificationError> where C: ChainStorage{
+
+        let workload = match *context.get_transaction(self.workload)?.get_payload(){
+            TxPayload::WorkloadAllocation(ref workload) => workload.clone(),
+            _ => {
+                let reason = VerificationErrorReason::InvalidTxRef(self.workload);
+                return Err(VerificationError::new(reason))
+            }
+        };
+
+        let revision = match *context.get_transaction(self.revision)?.get_payload(){
+            TxPayload::FinalRevision(ref revision) => revision.clone(),
+            _ => {
+                let reason = VerificationErrorReason::InvalidTxRef(self.revision);
+                return Err(VerificationError::new(reason))
+            }
+        };
+
+        verify_signers(transaction, &[workload.get_wallet_pubkey()])?;
+
+        // the workload must have been registered
+        // in the period ]k1, k2] of the revision
+
+        let timestamp = context.get_timestamp(self.workload)?;
+        if timestamp <= revision.get_period_start() || timestamp > revision.get_period_end(){
+            let reason = VerificationErrorReason::WorkloadNotRevised;
+            return Err(VerificationError::new(reason))
+        }
+
+        if self.amount != revision.get_coupon_value(workload.get_minutes()){
+            let reason = VerificationErrorReason::InvalidAmount;
+            return Err(VerificationError::new(reason))
+        }
+
+        context.claim_rel(self.workload, TxRelId::WorkloadCoupon)
+
+    }
+
+}
+
+impl BinFormat<CouponClaim> for CouponClaim{
+
+    // The byte format of CouponClaim is:
+
+    //    field            length
+    //  .------------------------.
+    //  | workload        | 34   |
+    //  |------------------------|
+    //  | revision        | 34   |
+    //  |------------------------|
+    //  | amount          | 8    |
+    //  '------------------------'
+
+    /// Returns the claim as an u8 vector
+
+    fn as_bytes(&self) -> Vec<u8>{
+
+        [&self.workload.as_bytes()[..],
+         &self.revision.as_bytes()[..],
+         &u64_to_u8le(self.amount)[..]].concat()
+
+    }
+
+    /// Creates a new claim from a byte vector.
+    ///
+    /// * `bytes`: A byte vector
+
+    fn from_bytes(bytes: Vec<u8>) -> Result<CouponClaim, BinFormatError>{
+
+        let mut reader = BinReader::new(&bytes);
+
+        let workload = read_tx_id(&mut reader)?;
+        let revision = read_tx_id(&mut reader)?;
+        let amount = reader.read_u64()?;
+
+        reader.finish()?;
+
+        Ok(CouponClaim::new(workload, revision, amount))
+
+    }
+
+}
+
+/// `Appropriation` deletes coupons of a wallet, which are used
+/// to appropriate exclusive goods at a dispatching C2I collective.
+/// The coupons must stem from workloads registered at a
+/// collective (the issuer), that established weak trust with
+/// the dispatcher. Appropriations are also rejected while a
+/// settlement between the subsystems of the issuer and the
+/// dispatcher is pending. The appropriation is signed by the wallet.
+
+#[derive(Clone)]
+#[derive(Debug)]
+pub struct Appropriation{
+    dispatcher: [u8; 32],
+    issuer: [u8; 32],
+    amount: u64
+}
+
+impl Appropriation{
+
+    /// Creates a new `Appropriation`
+    ///
+    /// # Arguments
+    /// * `dispatcher`: The public key of the dispatching collective
+    /// * `issuer`: The public key of the collective the coupons stem from
+    /// * `amount`: The deleted amount of coupons in minutes
+
+    pub fn new(dispatcher: [u8; 32], issuer: [u8; 32], amount: u64) -> Appropriation{
+        Appropriation{dispatcher: dispatcher, issuer: issuer, amount: amount}
+    }
+
+    /// Gets the public key of the dispatching collective
+
+    pub fn get_dispatcher(&self) -> [u8; 32]{
+        self.dispatcher
+    }
+
+    /// Gets the public key of the collective the coupons stem from
+
+    pub fn get_issuer(&self) -> [u8; 32]{
+        self.issuer
+    }
+
+    /// Gets the deleted amount of coupons in minutes
+
+    pub fn get_amount(&self) -> u64{
+        self.amount
+    }
+
+    /// Verifies the appropriation against the chain
+    ///
+    /// # Arguments
+    /// * `transaction`: The transaction holding the appropriation
+    /// * `context`: The verification context
+
+    pub fn verify<C>(&self,
+                     transaction: &Transaction,
+                     context: &mut TxContext<C>)
+                     -> Result<(), VerificationError> where C: ChainStorage{
+
+        let signer_pubkeys = transaction.get_signer_pubkeys();
+        if signer_pubkeys.len() != 1{
+            let reason = VerificationErrorReason::InvalidTxSigners;
+            return Err(VerificationError::new(reason))
+        }
+
+        if self.amount == 0{
+            let reason = VerificationErrorReason::InvalidAmount;
+            return Err(VerificationError::new(reason))
+        }
+
+        let indexes = context.get_indexes();
+
+        if indexes.collectives.get_registration(self.dispatcher).is_none(){
+            let reason = VerificationErrorReason::UnknownCollective;
+            return Err(VerificationError::new(reason))
+        }
+
+        if !indexes.collectives.is_c2i(self.dispatcher){
+            let reason = VerificationErrorReason::NotC2I;
+            return Err(VerificationError::new(reason))
+        }
+
+        if !indexes.trust.weak_trust(self.issuer, self.dispatcher){
+            let reason = VerificationErrorReason::MissingTrust;
+            return Err(VerificationError::new(reason))
+        }
+
+        if indexes.settlements.is_pending(self.issuer, self.dispatcher){
+            let reason = VerificationErrorReason::PendingSettlement;
+            return Err(VerificationError::new(reason))
+        }
+
+        if indexes.wallets.balance_from(signer_pubkeys[0], self.issuer) < self.amount{
+            let reason = VerificationErrorReason::InsufficientBalance;
+            return Err(VerificationError::new(reason))
+        }
+
+        Ok(())
+
+    }
+
+}
+
+impl BinFormat<Appropriation> for Appropriation{
+
+    // The byte format of Appropriation is:
+
+    //    field            length
+    //  .------------------------.
+    //  | dispatcher      | 32   |
+    //  |------------------------|
+    //  | issuer          | 32   |
+    //  |------------------------|
+    //  | amount          | 8    |
+    //  '------------------------'
+
+    /// Returns the appropriation as an u8 vector
+
+    fn as_bytes(&self) -> Vec<u8>{
+        [&self.dispatcher[..], &self.issuer[..], &u64_to_u8le(self.amount)[..]].concat()
+    }
+
+    /// Creates a new appropriation from a byte vector.
+    ///
+    /// * `bytes`: A byte vector
+
+    fn from_bytes(bytes: Vec<u8>) -> Result<Appropriation, BinFormatError>{
+
+        let mut reader = BinReader::new(&bytes);
+
+        let dispatcher = reader.read_hash()?;
+        let issuer = reader.read_hash()?;
+        let amount = reader.read_u64()?;
+
+        reader.finish()?;
+
+        Ok(Appropriation::new(dispatcher, issuer, amount))
+
+    }
+
+}
+
+// ------------------------------------------------------------------------
+
+/// `WalletIndex` keeps track of the coupon balances (in
+/// minutes) of all wallets. Balances are kept per collective
+/// the workloads were registered at, since coupons can only be
+/// used at dispatchers that established weak trust with it.
+/// The index is updated with every applied block.
+
+pub struct WalletIndex{
+    balances: HashMap<[u8; 32], HashMap<[u8; 32], u64>>,
+    workloads: HashMap<TxId, [u8; 32]>,
+    journal: Journal<WalletIndex>
+}
+
+impl WalletIndex{
+
+    /// Creates a new empty `WalletIndex`
+
+    pub fn new() -> WalletIndex{
+        WalletIndex{
+            balances: HashMap::new(),
+            workloads: HashMap::new(),
+            journal: Journal::new()
+        }
+    }
+
+    /// Returns the coupon balance of a wallet in minutes
+    ///
+    /// # Arguments
+    /// * `wallet_pubkey`: The public key of the wallet
+
+    pub fn balance(&self, wallet_pubkey: [u8; 32]) -> u64{
+        match self.balances.get(&wallet_pubkey){
+            Some(balances) => balances.values().fold(0, |sum, balance| sum.saturating_add(*balance)),
+            None => 0
+        }
+    }
+
+    /// Returns the coupon balance of a wallet in minutes, that
+    /// stems from workloads registered at a collective
+    ///
+    /// # Arguments
+    /// * `wallet_pubkey`: The public key of the wallet
+    /// * `issuer`: The public key of the collective
+
+    pub fn balance_from(&self, wallet_pubkey: [u8; 32], issuer: [u8; 32]) -> u64{
+        self.balances.get(&wallet_pubkey)
+                     .and_then(|balances| balances.get(&issuer))
+                     .cloned()
+                     .unwrap_or(0)
+    }
+
+    /// Returns the coupons in minutes held by all wallets,
+    /// that stem from workloads registered at a set of collectives
+    ///
+    /// # Arguments
+    /// * `issuers`: The public keys of the collectives
+
+    pub fn outstanding(&self, issuers: &HashSet<[u8; 32]>) -> u64{
+
+        let mut outstanding: u64 = 0;
+        for balances in self.balances.values(){
+            for (issuer, balance) in balances{
+                if issuers.contains(issuer){
+                    outstanding = outstanding.saturating_add(*balance);
+                }
+            }
+        }
+        outstanding
+
+    }
+
+    /// Updates the index with a verified transaction
+    ///
+    /// # Arguments
+    /// * `tx_id`: The id of the transaction
+    /// * `transaction`: The verified transaction
+
+    pub fn apply_transaction(&mut self, tx_id: TxId, transaction: &Transaction){
+
+        // workloads are signed by the collective they
+        // were registered at, claims and appropriations
+        // by the wallet
+
+        match *transaction.get_payload(){
+
+            TxPayload::WorkloadAllocation(_) => {
+                let previous = self.workloads.insert(tx_id, transaction.get_signer_pubkeys()[0]);
+                self.journal.record_entry(|index| &mut index.workloads, tx_id, previous);
+            },
+
+            TxPayload::CouponClaim(ref payload) => {
+                let workload = payload.get_workload();
+                let previous = self.workloads.remove(&workload);
+                self.journal.record_entry(|index| &mut index.workloads, workload, previous);
+                if let Some(issuer) = previous{
+                    let wallet_pubkey = transaction.get_signer_pubkeys()[0];
+                    self.record_balances(wallet_pubkey);
+                    let balance = self.balances.entry(wallet_pubkey)
+                                               .or_default()
+                                               .entry(issuer)
+                                               .or_insert(0);
+                    *balance = balance.saturating_add(payload.get_amount());
+                }
+            },
+
+            TxPayload::Appropriation(ref payload) => {
+                let wallet_pubkey = transaction.get_signer_pubkeys()[0];
+                self.record_balances(wallet_pubkey);
+                if let Some(balances) = self.balances.get_mut(&wallet_pubkey){
+                    if let Some(balance) = balances.get_mut(&payload.get_issuer()){
+                        *balance = balance.saturating_sub(payload.get_amount());
+                    }
+                }
+            },
+
+            _ => {}
+
+        }
+
+    }
+
+    /// Journals the balances of a wallet before they change
+
+    fn record_balances(&mut self, wallet_pubkey: [u8; 32]){
+        let previous = self.balances.get(&wallet_pubkey).cloned();
+        self.journal.record_entry(|index| &mut index.balances, wallet_pubkey, previous);
+    }
+
+}
+
+impl Journaled for WalletIndex{
+    fn get_journal(&mut self) -> &mut Journal<WalletIndex>{
+        &mut self.journal
+    }
+}
+
+// ------------------------------------------------------------------------
+
+#[test]
+fn test_coupon_claim_bytes(){
+
+    use blockchain::block::BlockId;
+    use blockchain::transactions::TxIndex;
+
+    let workload = TxId::new(BlockId([0x01; 32]), TxIndex(1));
+    let revision = TxId::new(BlockId([0x02; 32]), TxIndex(0));
+
+    let claim = CouponClaim::new(workload, revision, 384);
+    let bytes = claim.as_bytes();
+    assert_eq!(bytes.len(), 34 + 34 + 8);
+
+    let decoded = CouponClaim::from_bytes(bytes.clone()).unwrap();
+    assert_eq!(decoded.get_workload(), workload);
+    assert_eq!(decoded.get_revision(), revision);
+    assert_eq!(decoded.get_amount(), 384);
+    assert!(CouponClaim::from_bytes([&bytes[..], &[0x00][..]].concat()).is_err());
+
+    let appropriation = Appropriation::new([0x03; 32], [0x04; 32], 90);
+    let bytes = appropriation.as_bytes();
+    assert_eq!(bytes.len(), 32 + 32 + 8);
+
+    let decoded = Appropriation::from_bytes(bytes.clone()).unwrap();
+    assert_eq!(decoded.get_dispatcher(), [0x03; 32]);
+    assert_eq!(decoded.get_issuer(), [0x04; 32]);
+    assert_eq!(decoded.get_amount(), 90);
+    assert!(Appropriation::from_bytes(bytes[1..].to_vec()).is_err());
+
+}
+
+#[test]
+fn test_coupon_claim(){
+
+    use blockchain::transactions::BadClaimReason;
+    use blockchain::txtypes::allocation::WorkloadAllocation;
+    use blockchain::txtypes::revision::FinalRevision;
+    use blockchain::txtypes::revision::REVISION_PERIOD;
+    use blockchain::txtypes::TestChain;
+    use blockchain::txtypes::signed_transaction;
+    use blockchain::txtypes::register_collective;
+    use blockchain::txtypes::start_production;
+    use blockchain::txtypes::keypair;
+
+    const P: u64 = REVISION_PERIOD;
+
+    let (secret_a, _) = keypair(0x0A);
+    let (secret_w, wallet) = keypair(0x01);
+
+    let mut chain = TestChain::new();
+    chain.append(1, vec![register_collective(&secret_a)]).unwrap();
+
+    let production_start = start_production(&mut chain, &secret_a, 10);
+    let workload = |minutes| {
+        let payload = WorkloadAllocation::new(production_start, wallet, minutes, false);
+        signed_transaction(TxPayload::WorkloadAllocation(payload), &[&secret_a])
+    };
+    let claim = |secret_key: &[u8; 64], workload, revision, amount| {
+        let payload = CouponClaim::new(workload, revision, amount);
+        signed_transaction(TxPayload::CouponClaim(payload), &[secret_key])
+    };
+
+    let workloads = chain.append(P + 1, vec![workload(480), workload(120)]).unwrap();
+    let late_workload = chain.append(2 * P + 1, vec![workload(60)]).unwrap()[0];
+
+    let payload = FinalRevision::new(P, 2 * P, 600, 0, 0);
+    let revision = chain.append(2 * P + 2,
+                                vec![Transaction::new(TxPayload::FinalRevision(payload),
+                                                      vec![])]).unwrap()[0];
+
+    // only the wallet claims, workloads must be part of the
+    // period and the amount must match the revision
+
+    match chain.append(2 * P + 3, vec![claim(&secret_a, workloads[0], revision, 480)]){
+        Err(VerificationErrorReason::InvalidTxSigners) => {},
+        result => panic!("Unexpected result {:?}", result)
+    }
+    match chain.append(2 * P + 3, vec![claim(&secret_w, late_workload, revision, 60)]){
+        Err(VerificationErrorReason::WorkloadNotRevised) => {},
+        result => panic!("Unexpected result {:?}", result)
+    }
+    match chain.append(2 * P + 3, vec![claim(&secret_w, workloads[0], revision, 481)]){
+        Err(VerificationErrorReason::InvalidAmount) => {},
+        result => panic!("Unexpected result {:?}", result)
+    }
+    match chain.append(2 * P + 3, vec![claim(&secret_w, revision, revision, 480)]){
+        Err(VerificationErrorReason::InvalidTxRef(tx_id)) => assert_eq!(tx_id, revision),
+        result => panic!("Unexpected result {:?}", result)
+    }
+
+    assert_eq!(chain.indexes.wallets.balance(wallet), 0);
+
+    chain.append(2 * P + 3, vec![claim(&secret_w, workloads[0], revision, 480),
+                                 claim(&secret_w, workloads[1], revision, 120)]).unwrap();
+    assert_eq!(chain.indexes.wallets.balance(wallet), 600);
+
+    // every workload is claimed once
+
+    match chain.append(2 * P + 4, vec![claim(&secret_w, workloads[0], revision, 480)]){
+        Err(VerificationErrorReason::BadClaim(BadClaimReason::RelClaimed(TxRelId::WorkloadCoupon, _))) => {},
+        result => panic!("Unexpected result {:?}", result)
+    }
+
+    assert_eq!(chain.indexes.wallets.balance(wallet), 600);
+
+}
+
+#[test]
+fn test_appropriation(){
+
+    use blockchain::txtypes::allocation::WorkloadAllocation;
+    use blockchain::txtypes::revision::FinalRevision;
+    use blockchain::txtypes::revision::REVISION_PERIOD;
+    use blockchain::txtypes::trust::TrustEstablish;
+    use blockchain::txtypes::TestChain;
+    use blockchain::txtypes::signed_transaction;
+    use blockchain::txtypes::register_collective;
+    use blockchain::txtypes::register_c2i_collective;
+    use blockchain::txtypes::start_production;
+    use blockchain::txtypes::keypair;
+
+    const P: u64 = REVISION_PERIOD;
+
+    let (secret_a, pubkey_a) = keypair(0x0A);
+    let (secret_b, pubkey_b) = keypair(0x0B);
+    let (secret_c, pubkey_c) = keypair(0x0C);
+    let (secret_d, pubkey_d) = keypair(0x0D);
+    let (secret_e, pubkey_e) = keypair(0x0E);
+    let (secret_w, wallet) = keypair(0x01);
+
+    // the C2I collectives a and b are in the same subsystem,
+    // c isn't, d isn't registered and e isn't a C2I collective
+
+    let trust = TrustEstablish::new(pubkey_a, pubkey_b);
+    let mut chain = TestChain::new();
+    chain.append(1, vec![register_c2i_collective(&secret_a),
+                         register_c2i_collective(&secret_b),
+                         register_c2i_collective(&secret_c),
+                         register_collective(&secret_e),
+                         signed_transaction(TxPayload::TrustEstablish(trust),
+                                            &[&secret_a, &secret_b])]).unwrap();
+
+    let production_start = start_production(&mut chain, &secret_a, 10);
+    let payload = WorkloadAllocation::new(production_start, wallet, 480, false);
+    let workload = chain.append(P + 1, vec![signed_transaction(TxPayload::WorkloadAllocation(payload),
+                                                               &[&secret_a])]).unwrap()[0];
+
+    let payload = FinalRevision::new(P, 2 * P, 480, 0, 0);
+    let revision = chain.append(2 * P + 1,
+                                vec![Transaction::new(TxPayload::FinalRevision(payload),
+                                                      vec![])]).unwrap()[0];
+
+    let payload = CouponClaim::new(workload, revision, 480);
+    chain.append(2 * P + 2, vec![signed_transaction(TxPayload::CouponClaim(payload),
+                                                    &[&secret_w])]).unwrap();
+
+    assert_eq!(chain.indexes.wallets.balance_from(wallet, pubkey_a), 480);
+    assert_eq!(chain.indexes.wallets.balance_from(wallet, pubkey_b), 0);
+
+    let appropriation = |secret_key: &[u8; 64], dispatcher, issuer, amount| {
+        let payload = Appropriation::new(dispatcher, issuer, amount);
+        signed_transaction(TxPayload::Appropriation(payload), &[secret_key])
+    };
+
+    match chain.append(2 * P + 3, vec![appropriation(&secret_w, pubkey_d, pubkey_a, 60)]){
+        Err(VerificationErrorReason::UnknownCollective) => {},
+        result => panic!("Unexpected result {:?}", result)
+    }
+    match chain.append(2 * P + 3, vec![appropriation(&secret_w, pubkey_e, pubkey_a, 60)]){
+        Err(VerificationErrorReason::NotC2I) => {},
+        result => panic!("Unexpected result {:?}", result)
+    }
+    match chain.append(2 * P + 3, vec![appropriation(&secret_w, pubkey_c, pubkey_a, 60)]){
+        Err(VerificationErrorReason::MissingTrust) => {},
+        result => panic!("Unexpected result {:?}", result)
+    }
+    match chain.append(2 * P + 3, vec![appropriation(&secret_w, pubkey_b, pubkey_a, 0)]){
+        Err(VerificationErrorReason::InvalidAmount) => {},
+        result => panic!("Unexpected result {:?}", result)
+    }
+    match chain.append(2 * P + 3, vec![appropriation(&secret_w, pubkey_b, pubkey_b, 60)]){
+        Err(VerificationErrorReason::InsufficientBalance) => {},
+        result => panic!("Unexpected result {:?}", result)
+    }
+    match chain.append(2 * P + 3, vec![appropriation(&secret_d, pubkey_b, pubkey_a, 60)]){
+        Err(VerificationErrorReason::InsufficientBalance) => {},
+        result => panic!("Unexpected result {:?}", result)
+    }
+
+    // coupons can be used at every collective of the subsystem,
+    // but the balance can't be spent twice within a block
+
+    chain.append(2 * P + 3, vec![appropriation(&secret_w, pubkey_b, pubkey_a, 300),
+                                 appropriation(&secret_w, pubkey_a, pubkey_a, 120)]).unwrap();
+    assert_eq!(chain.indexes.wallets.balance(wallet), 60);
+
+    match chain.append(2 * P + 4, vec![appropriation(&secret_w, pubkey_a, pubkey_a, 40),
+                                       appropriation(&secret_w, pubkey_b, pubkey_a, 40)]){
+        Err(VerificationErrorReason::InsufficientBalance) => {},
+        result => panic!("Unexpected result {:?}", result)
+    }
+    assert_eq!(chain.indexes.wallets.balance(wallet), 60);
+
+}
@@ -0,0 +1,472 @@
+//
+//    Stachanov
+//    Copyright (C) 2017 Stachanov Developer Collective
+//
+//    This file is part of Stachanov.
+//
+//    This program is free software: you can redistribute it and/or
+//    modify it under the terms of the GNU Affero General Public
+//    License, version 3, as published by the Free Software Foundation.
+//
+//    This program is distributed in the hope that it will be useful,
+//    but WITHOUT ANY WARRANTY; without even the implied warranty of
+//    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//    GNU Affero General Public License for more details.
+//
+//    You should have received a copy of the
+//               GNU Affero General Public License
+//    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+// This module implements the governance of the commons
+// classification. Whether a product type is produced as
+// commons or exclusively is a political decision of an
+// economic subsystem: a collective proposes a classification
+// of a product type, which takes effect from an activation
+// height (block index) on. Until then the collectives of the
+// proposer's subsystem cast their ballots (see `vote`), and
+// the proposal takes effect only if the vote passed.
+//
+// Classifications are scoped to the subsystem that voted on
+// them. Labeled orders and outputs of its collectives must
+// follow the classification in force at the height of the
+// block holding them. Product types without a classification
+// in force can be labeled freely.
+
+use std::collections::HashMap;
+use blockchain::traits::BinFormat;
+use blockchain::traits::ChainStorage;
+use blockchain::transactions::Transaction;
+use blockchain::transactions::TxPayload;
+use blockchain::transactions::TxId;
+use blockchain::transactions::TxRelId;
+use blockchain::transactions::TxState;
+use blockchain::transactions::TxTotalRelState;
+use blockchain::errors::BinFormatError;
+use blockchain::errors::VerificationError;
+use blockchain::errors::VerificationErrorReason;
+use blockchain::txtypes::TxContext;
+use blockchain::txtypes::read_bool;
+use blockchain::txtypes::vote::VoteIndex;
+use blockchain::params::ConsensusParams;
+use blockchain::journal::Journal;
+use blockchain::journal::Journaled;
+use blockchain::utils::u64_to_u8le;
+use blockchain::utils::BinReader;
+
+/// `ClassificationProposal` proposes to classify a registered
+/// product type as commons or exclusive from an activation
+/// height on. It is signed by the proposing collective. The
+/// electorate is the subsystem of the proposing collective
+/// when the proposal is made and ballots are accepted until
+/// the activation height.
+///
+/// Classification proposals hold the following relationships:
+///
+/// * `VoteBallots` (1:n): Claimed by the ballots
+
+#[derive(Clone)]
+#[derive(Debug)]
+pub struct ClassificationProposal{
+    product_type: [u8; 32],
+    common: bool,
+    activation: u64
+}
+
+impl ClassificationProposal{
+
+    /// Creates a new `ClassificationProposal`
+    ///
+    /// # Arguments
+    /// * `product_type`: The id of the product type
+    /// * `common`: Whether the product type is commons
+    /// * `activation`: The block index the classification takes effect at
+
+    pub fn new(product_type: [u8; 32], common: bool, activation: u64) -> ClassificationProposal{
+        ClassificationProposal{
+            product_type: product_type,
+            common: common,
+            activation: activation
+        }
+    }
+
+    /// Gets the id of the product type
+
+    pub fn get_product_type(&self) -> [u8; 32]{
+        self.product_type
+    }
+
+    /// Checks if the product type is classified as commons
+
+    pub fn is_common(&self) -> bool{
+        self.common
+    }
+
+    /// Gets the block index the classification takes effect at
+
+    pub fn get_activation(&self) -> u64{
+        self.activation
+    }
+
+    /// Verifies the proposal against the chain
+    ///
+    /// # Arguments
+    /// * `transaction`: The transaction holding the proposal
+    /// * `context`: The verification context
+
+    pub fn verify<C>(&self,
+                     transaction: &Transaction,
+                     context: &mut TxContext<C>)
+                     -> Result<(), VerificationError> where C: ChainStorage{
+
+        let signer_pubkeys = transaction.get_signer_pubkeys();
+        if signer_pubkeys.len() != 1{
+            let reason = VerificationErrorReason::InvalidTxSigners;
+            return Err(VerificationError::new(reason))
+        }
+
+        let indexes = context.get_indexes();
+
+        if indexes.collectives.get_registration(signer_pubkeys[0]).is_none(){
+            let reason = VerificationErrorReason::UnknownCollective;
+            return Err(VerificationError::new(reason))
+        }
+
+        if indexes.products.get_definition_id(self.product_type).is_none(){
+            let reason = VerificationErrorReason::UnknownProductType;
+            return Err(VerificationError::new(reason))
+        }
+
+        if self.activation <= context.get_header().get_index(){
+            let reason = VerificationErrorReason::InvalidActivation;
+            return Err(VerificationError::new(reason))
+        }
+
+        Ok(())
+
+    }
+
+    /// Returns the initial state of the proposal
+
+    pub fn initial_state(&self) -> TxState{
+
+        let mut tx_state = TxState::new(TxTotalRelState::Claimable);
+        tx_state.add_one_to_many_rel(TxRelId::VoteBallots)
+                .expect("Fresh TxState already had a relationship");
+        tx_state
+
+    }
+
+}
+
+impl BinFormat<ClassificationProposal> for ClassificationProposal{
+
+    // The byte format of ClassificationProposal is:
+
+    //    field            length
+    //  .------------------------.
+    //  | product_type    | 32   |
+    //  |------------------------|
+    //  | common          | 1    |  (bool)
+    //  |------------------------|
+    //  | activation      | 8    |
+    //  '------------------------'
+
+    /// Returns the proposal as an u8 vector
+
+    fn as_bytes(&self) -> Vec<u8>{
+        [&self.product_type[..],
+         &[self.common as u8][..],
+         &u64_to_u8le(self.activation)[..]].concat()
+    }
+
+    /// Creates a new proposal from a byte vector.
+    ///
+    /// * `bytes`: A byte vector
+
+    fn from_bytes(bytes: Vec<u8>) -> Result<ClassificationProposal, BinFormatError>{
+
+        let mut reader = BinReader::new(&bytes);
+
+        let product_type = reader.read_hash()?;
+        let common = read_bool(&mut reader, "common")?;
+        let activation = reader.read_u64()?;
+
+        reader.finish()?;
+
+        Ok(ClassificationProposal::new(product_type, common, activation))
+
+    }
+
+}
+
+// ------------------------------------------------------------------------
+
+/// `ClassificationIndex` keeps track of the classification
+/// proposals of every product type. The ballots are counted
+/// by the `VoteIndex`, so both indexes are needed to find
+/// the classification in force.
+
+pub struct ClassificationIndex{
+    proposals: HashMap<[u8; 32], Vec<(TxId, ClassificationProposal)>>,
+    journal: Journal<ClassificationIndex>
+}
+
+impl ClassificationIndex{
+
+    /// Creates a new empty `ClassificationIndex`
+
+    pub fn new() -> ClassificationIndex{
+        ClassificationIndex{
+            proposals: HashMap::new(),
+            journal: Journal::new()
+        }
+    }
+
+    /// Returns whether a product type is classified as commons
+    /// for a collective at a block index or None, if no passed
+    /// proposal of the collective's subsystem is in force. Of
+    /// several proposals in force the latest activated one wins.
+    ///
+    /// # Arguments
+    /// * `votes`: The vote index of the chain
+    /// * `params`: The consensus parameters of the chain
+    /// * `product_type`: The id of the product type
+    /// * `collective_pubkey`: The public key of the collective
+    /// * `height`: The block index
+
+    pub fn classification(&self,
+                          votes: &VoteIndex,
+                          params: &ConsensusParams,
+                          product_type: [u8; 32],
+                          collective_pubkey: [u8; 32],
+                          height: u64) -> Option<bool>{
+
+        let proposals = self.proposals.get(&product_type)?;
+        let mut in_force: Option<&ClassificationProposal> = None;
+
+        for (tx_id, proposal) in proposals{
+
+            if proposal.activation > height ||
+               in_force.is_some_and(|current| current.activation > proposal.activation){
+                continue
+            }
+
+            let passed = votes.get_vote(*tx_id).is_some_and(|vote| {
+                vote.electorate.contains(&collective_pubkey) && vote.is_passed(params)
+            });
+
+            if passed{
+                in_force = Some(proposal);
+            }
+
+        }
+
+        in_force.map(|proposal| proposal.common)
+
+    }
+
+    /// Updates the index with a verified transaction
+    ///
+    /// # Arguments
+    /// * `tx_id`: The id of the transaction
+    /// * `transaction`: The verified transaction
+
+    pub fn apply_transaction(&mut self, tx_id: TxId, transaction: &Transaction){
+
+        if let TxPayload::ClassificationProposal(ref payload) = *transaction.get_payload(){
+            let previous = self.proposals.get(&payload.product_type).cloned();
+            self.journal.record_entry(|index| &mut index.proposals, payload.product_type, previous);
+            self.proposals.entry(payload.product_type)
+                          .or_default()
+                          .push((tx_id, payload.clone()));
+        }
+
+    }
+
+}
+
+impl Journaled for ClassificationIndex{
+    fn get_journal(&mut self) -> &mut Journal<ClassificationIndex>{
+        &mut self.journal
+    }
+}
+
+/// Checks that the label of an order or output follows the
+/// classification of its product type in force at the height
+/// of the block.
+///
+/// Returns a VerificationError with reason InvalidLabel if
+/// the label contradicts the classification
+///
+/// # Arguments
+/// * `context`: The verification context
+/// * `product_type`: The id of the product type
+/// * `collective_pubkey`: The public key of the producing collective
+/// * `common`: The label
+
+pub fn verify_classification<C>(context: &TxContext<C>,
+                                product_type: [u8; 32],
+                                collective_pubkey: [u8; 32],
+                                common: bool)
+                                -> Result<(), VerificationError> where C: ChainStorage{
+
+    let indexes = context.get_indexes();
+    let height = context.get_header().get_index();
+
+    match indexes.classifications.classification(&indexes.votes,
+                                                 context.get_params(),
+                                                 product_type,
+                                                 collective_pubkey,
+                                                 height){
+        Some(classified_common) if classified_common != common => {
+            let reason = VerificationErrorReason::InvalidLabel;
+            Err(VerificationError::new(reason))
+        },
+        _ => Ok(())
+    }
+
+}
+
+#[test]
+fn test_classification_proposal_bytes(){
+
+    let proposal = ClassificationProposal::new([0x02; 32], true, 300);
+    let mut bytes = proposal.as_bytes();
+    assert_eq!(bytes.len(), 32 + 1 + 8);
+    let decoded = ClassificationProposal::from_bytes(bytes.clone()).unwrap();
+    assert_eq!(decoded.get_product_type(), [0x02; 32]);
+    assert!(decoded.is_common());
+    assert_eq!(decoded.get_activation(), 300);
+    assert!(ClassificationProposal::from_bytes(bytes[1..].to_vec()).is_err());
+
+    bytes[32] = 0x02;
+    assert!(ClassificationProposal::from_bytes(bytes).is_err());
+
+}
+
+#[test]
+fn test_classification(){
+
+    use blockchain::txtypes::product::ProductTypeDefinition;
+    use blockchain::txtypes::production::Order;
+    use blockchain::txtypes::production::ValueEstimation;
+    use blockchain::txtypes::production::ProductionStart;
+    use blockchain::txtypes::production::ProductionOutput;
+    use blockchain::txtypes::trust::TrustEstablish;
+    use blockchain::txtypes::vote::Ballot;
+    use blockchain::txtypes::TestChain;
+    use blockchain::txtypes::signed_transaction;
+    use blockchain::txtypes::register_collective;
+    use blockchain::txtypes::keypair;
+
+    let (secret_a, pubkey_a) = keypair(0x0A);
+    let (secret_b, pubkey_b) = keypair(0x0B);
+    let (secret_c, pubkey_c) = keypair(0x0C);
+    let (secret_d, pubkey_d) = keypair(0x0D);
+    let (secret_e, _) = keypair(0x0E);
+    let sign = |payload, secret_key: &[u8; 64]| signed_transaction(payload, &[secret_key]);
+    let definition = ProductTypeDefinition::new(String::from("bread"), 2, 30, vec![]);
+    let bread = definition.get_product_type();
+    let order = |pubkey, common| Order::new(bread, 1, pubkey, pubkey, 100, None, common);
+
+    // a, b and c form a subsystem, d is on its own (block 0)
+
+    let trust_ab = TrustEstablish::new(pubkey_a, pubkey_b);
+    let trust_bc = TrustEstablish::new(pubkey_b, pubkey_c);
+    let mut chain = TestChain::new();
+    chain.append(1, vec![register_collective(&secret_a),
+                         register_collective(&secret_b),
+                         register_collective(&secret_c),
+                         register_collective(&secret_d),
+                         signed_transaction(TxPayload::TrustEstablish(trust_ab),
+                                            &[&secret_a, &secret_b]),
+                         signed_transaction(TxPayload::TrustEstablish(trust_bc),
+                                            &[&secret_b, &secret_c])]).unwrap();
+
+    // block 1
+
+    chain.append(2, vec![sign(TxPayload::ProductTypeDefinition(definition), &secret_a)]).unwrap();
+
+    let proposal = |secret_key: &[u8; 64], product_type, activation| {
+        let payload = ClassificationProposal::new(product_type, true, activation);
+        sign(TxPayload::ClassificationProposal(payload), secret_key)
+    };
+
+    match chain.append(3, vec![proposal(&secret_e, bread, 8)]){
+        Err(VerificationErrorReason::UnknownCollective) => {},
+        result => panic!("Unexpected result {:?}", result)
+    }
+    match chain.append(3, vec![proposal(&secret_a, [0x02; 32], 8)]){
+        Err(VerificationErrorReason::UnknownProductType) => {},
+        result => panic!("Unexpected result {:?}", result)
+    }
+    match chain.append(3, vec![proposal(&secret_a, bread, 2)]){
+        Err(VerificationErrorReason::InvalidActivation) => {},
+        result => panic!("Unexpected result {:?}", result)
+    }
+
+    // block 2: bread becomes commons for the subsystem
+    // of a from block 8 on, if the vote passes (block 3)
+
+    let proposal = chain.append(3, vec![proposal(&secret_a, bread, 8)]).unwrap()[0];
+    let ballot = |secret_key: &[u8; 64]| sign(TxPayload::Ballot(Ballot::new(proposal, true)), secret_key);
+
+    match chain.append(4, vec![ballot(&secret_d)]){
+        Err(VerificationErrorReason::MissingTrust) => {},
+        result => panic!("Unexpected result {:?}", result)
+    }
+    chain.append(4, vec![ballot(&secret_a), ballot(&secret_b)]).unwrap();
+
+    // blocks 4 to 7: exclusive production
+    // before the activation is fine
+
+    let orders = chain.append(5, vec![sign(TxPayload::Order(order(pubkey_a, false)), &secret_a),
+                                      sign(TxPayload::Order(order(pubkey_a, false)), &secret_a)])
+                      .unwrap();
+    let estimations = chain.append(6, orders.iter().map(|order| {
+        sign(TxPayload::ValueEstimation(ValueEstimation::new(*order, 60)), &secret_a)
+    }).collect()).unwrap();
+    let starts = chain.append(7, estimations.iter().map(|estimation| {
+        sign(TxPayload::ProductionStart(ProductionStart::new(*estimation, false)), &secret_a)
+    }).collect()).unwrap();
+    let output = |start| {
+        let payload = ProductionOutput::new(start, bread, 1, 0, false);
+        sign(TxPayload::ProductionOutput(payload), &secret_a)
+    };
+    chain.append(8, vec![output(starts[0])]).unwrap();
+
+    assert_eq!(chain.indexes.classifications.classification(&chain.indexes.votes,
+                                                           &chain.indexes.params,
+                                                           bread,
+                                                           pubkey_a,
+                                                           7), None);
+
+    // block 8: the classification is in force
+    // for the subsystem and the vote is closed
+
+    match chain.append(9, vec![output(starts[1])]){
+        Err(VerificationErrorReason::InvalidLabel) => {},
+        result => panic!("Unexpected result {:?}", result)
+    }
+    match chain.append(9, vec![sign(TxPayload::Order(order(pubkey_c, false)), &secret_c)]){
+        Err(VerificationErrorReason::InvalidLabel) => {},
+        result => panic!("Unexpected result {:?}", result)
+    }
+    match chain.append(9, vec![ballot(&secret_c)]){
+        Err(VerificationErrorReason::VotingClosed) => {},
+        result => panic!("Unexpected result {:?}", result)
+    }
+
+    chain.append(9, vec![sign(TxPayload::Order(order(pubkey_c, true)), &secret_c),
+                         sign(TxPayload::Order(order(pubkey_d, false)), &secret_d)]).unwrap();
+
+    for &(pubkey, classification) in &[(pubkey_a, Some(true)), (pubkey_d, None)]{
+        assert_eq!(chain.indexes.classifications.classification(&chain.indexes.votes,
+                                                               &chain.indexes.params,
+                                                               bread,
+                                                               pubkey,
+                                                               8), classification);
+    }
+
+}
@@ -0,0 +1,1117 @@
+//
+//    Stachanov
+//    Copyright (C) 2017 Stachanov Developer Collective
+//
+//    This file is part of Stachanov.
+//
+//    This program is free software: you can redistribute it and/or
+//    modify it under the terms of the GNU Affero General Public
+//    License, version 3, as published by the Free Software Foundation.
+//
+//    This program is distributed in the hope that it will be useful,
+//    but WITHOUT ANY WARRANTY; without even the implied warranty of
+//    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//    GNU Affero General Public License for more details.
+//
+//    You should have received a copy of the
+//               GNU Affero General Public License
+//    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+// This module implements the inventory of C2I collectives.
+// C2I collectives hand products over to consumers, who pay
+// with labor coupons. The appropriation price of a product
+// type is its socially necessary labor time, that is the
+// average value of all units of the product type held by C2I
+// collectives (total value / total amount).
+//
+// C2I collectives record the hand-over of units of their
+// resources with dispatch transactions. To protect the privacy
+// of consumers, dispatches don't reference a wallet, so products
+// can't be linked to the coupons that were deleted for them.
+// From time to time the collectives take a physical inventory
+// and reconcile their stocks on the chain.
+//
+// Dispatching software listens for price changes by
+// subscribing to the `InventoryIndex`. Subscribers are only
+// notified once a block was applied completely.
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::rc::Rc;
+use blockchain::traits::BinFormat;
+use blockchain::traits::ChainStorage;
+use blockchain::transactions::Transaction;
+use blockchain::transactions::TxPayload;
+use blockchain::transactions::TxId;
+use blockchain::transactions::read_tx_id;
+use blockchain::errors::BinFormatError;
+use blockchain::errors::VerificationError;
+use blockchain::errors::VerificationErrorReason;
+use blockchain::depreciation::Rational;
+use blockchain::txtypes::TxContext;
+use blockchain::txtypes::collective::CollectiveIndex;
+use blockchain::txtypes::resource::OwnershipIndex;
+use blockchain::txtypes::allocation::created_resource;
+use blockchain::txtypes::allocation::get_resource;
+use blockchain::txtypes::resource::verify_available;
+use blockchain::txtypes::verify_signers;
+use blockchain::journal::Journal;
+use blockchain::journal::Journaled;
+use blockchain::journal::restore;
+use blockchain::utils::u64_to_u8le;
+use blockchain::utils::BinReader;
+
+/// `Dispatch` records that a C2I collective handed an amount
+/// of one of its resources to some consumer. It is signed by
+/// the collective and deliberately carries no wallet reference.
+/// The units are taken from the resource at its value, so the
+/// stock of the collective holds exactly the value of the units
+/// that are left.
+///
+/// Once a resource is dispatched or units of it are missing
+/// in a reconciliation, it can only be dispatched further. It can't be transferred, consumed, allocated or
+/// written off anymore, missing units are corrected by
+/// reconciliations. The dispatch of the last unit finalizes
+/// the resource.
+
+#[derive(Clone)]
+#[derive(Debug)]
+pub struct Dispatch{
+    resource: TxId,
+    amount: u64
+}
+
+impl Dispatch{
+
+    /// Creates a new `Dispatch`
+    ///
+    /// # Arguments
+    /// * `resource`: The id of the dispatched resource
+    /// * `amount`: The dispatched amount
+
+    pub fn new(resource: TxId, amount: u64) -> Dispatch{
+        Dispatch{resource: resource, amount: amount}
+    }
+
+    /// Gets the id of the dispatched resource
+
+    pub fn get_resource(&self) -> TxId{
+        self.resource
+    }
+
+    /// Gets the dispatched amount
+
+    pub fn get_amount(&self) -> u64{
+        self.amount
+    }
+
+    /// Verifies the dispatch against the chain
+    ///
+    /// # Arguments
+    /// * `transaction`: The transaction holding the dispatch
+    /// * `context`: The verification context
+
+    pub fn verify<C>(&self,
+                     transaction: &Transaction,
+                     context: &mut TxContext<C>)
+                     -> Result<(), VerificationError> where C: ChainStorage{
+
+        verify_c2i_signer(transaction, context)?;
+        let resource = get_resource(context, self.resource)?;
+        verify_signers(transaction, &[resource.owner])?;
+
+        if self.amount == 0{
+            let reason = VerificationErrorReason::InvalidAmount;
+            return Err(VerificationError::new(reason))
+        }
+
+        // resources being dispatched are only
+        // available for further dispatches
+
+        let inventory = &context.get_indexes().inventory;
+        if !inventory.is_dispatched(self.resource){
+            verify_available(context, self.resource)?;
+        }
+
+        let remaining = inventory.get_remaining(self.resource);
+        if remaining.amount < self.amount{
+            let reason = VerificationErrorReason::InsufficientStock;
+            return Err(VerificationError::new(reason))
+        }
+
+        if remaining.amount == self.amount{
+            context.finalize(self.resource)?;
+        }
+
+        Ok(())
+
+    }
+
+}
+
+impl BinFormat<Dispatch> for Dispatch{
+
+    // The byte format of Dispatch is:
+
+    //    field            length
+    //  .------------------------.
+    //  | resource        | 34   |
+    //  |------------------------|
+    //  | amount          | 8    |
+    //  '------------------------'
+
+    /// Returns the dispatch as an u8 vector
+
+    fn as_bytes(&self) -> Vec<u8>{
+        [self.resource.as_bytes(), u64_to_u8le(self.amount).to_vec()].concat()
+    }
+
+    /// Creates a new dispatch from a byte vector.
+    ///
+    /// * `bytes`: A byte vector
+
+    fn from_bytes(bytes: Vec<u8>) -> Result<Dispatch, BinFormatError>{
+
+        let mut reader = BinReader::new(&bytes);
+
+        let resource = read_tx_id(&mut reader)?;
+        let amount = reader.read_u64()?;
+
+        reader.finish()?;
+
+        Ok(Dispatch::new(resource, amount))
+
+    }
+
+}
+
+/// `InventoryReconciliation` records the result of a physical
+/// inventory of a product type taken by a C2I collective. It
+/// is signed by the collective and sets the collective's stock
+/// to the counted amount:
+///
+/// * Missing units are taken from the resources the collective
+///         holds, like dispatched units. Units that were added
+///         by earlier reconciliations are taken first, then the
+///         units of the resources in the order they were
+///         received.
+/// * Surplus units are added without value, like collectivized
+///         resources, as no labor was registered for them. They
+///         aren't tied to a resource.
+
+#[derive(Clone)]
+#[derive(Debug)]
+pub struct InventoryReconciliation{
+    product_type: [u8; 32],
+    amount: u64
+}
+
+impl InventoryReconciliation{
+
+    /// Creates a new `InventoryReconciliation`
+    ///
+    /// # Arguments
+    /// * `product_type`: The id of the product type
+    /// * `amount`: The counted amount
+
+    pub fn new(product_type: [u8; 32], amount: u64) -> InventoryReconciliation{
+        InventoryReconciliation{product_type: product_type, amount: amount}
+    }
+
+    /// Gets the id of the product type
+
+    pub fn get_product_type(&self) -> [u8; 32]{
+        self.product_type
+    }
+
+    /// Gets the counted amount
+
+    pub fn get_amount(&self) -> u64{
+        self.amount
+    }
+
+    /// Verifies the reconciliation against the chain
+    ///
+    /// # Arguments
+    /// * `transaction`: The transaction holding the reconciliation
+    /// * `context`: The verification context
+
+    pub fn verify<C>(&self,
+                     transaction: &Transaction,
+                     context: &mut TxContext<C>)
+                     -> Result<(), VerificationError> where C: ChainStorage{
+
+        verify_c2i_signer(transaction, context)?;
+        Ok(())
+
+    }
+
+}
+
+impl BinFormat<InventoryReconciliation> for InventoryReconciliation{
+
+    // The byte format of InventoryReconciliation is:
+
+    //    field            length
+    //  .------------------------.
+    //  | product_type    | 32   |
+    //  |------------------------|
+    //  | amount          | 8    |
+    //  '------------------------'
+
+    /// Returns the reconciliation as an u8 vector
+
+    fn as_bytes(&self) -> Vec<u8>{
+        [&self.product_type[..], &u64_to_u8le(self.amount)[..]].concat()
+    }
+
+    /// Creates a new reconciliation from a byte vector.
+    ///
+    /// * `bytes`: A byte vector
+
+    fn from_bytes(bytes: Vec<u8>) -> Result<InventoryReconciliation, BinFormatError>{
+
+        let mut reader = BinReader::new(&bytes);
+
+        let product_type = reader.read_hash()?;
+        let amount = reader.read_u64()?;
+
+        reader.finish()?;
+
+        Ok(InventoryReconciliation::new(product_type, amount))
+
+    }
+
+}
+
+/// Verifies that a transaction is signed by exactly one
+/// registered C2I collective and returns its public key
+///
+/// # Arguments
+/// * `transaction`: The transaction
+/// * `context`: The verification context
+
+fn verify_c2i_signer<C>(transaction: &Transaction,
+                        context: &TxContext<C>)
+                        -> Result<[u8; 32], VerificationError> where C: ChainStorage{
+
+    let signer_pubkeys = transaction.get_signer_pubkeys();
+    if signer_pubkeys.len() != 1{
+        let reason = VerificationErrorReason::InvalidTxSigners;
+        return Err(VerificationError::new(reason))
+    }
+
+    let collectives = &context.get_indexes().collectives;
+
+    if collectives.get_registration(signer_pubkeys[0]).is_none(){
+        let reason = VerificationErrorReason::UnknownCollective;
+        return Err(VerificationError::new(reason))
+    }
+
+    if !collectives.is_c2i(signer_pubkeys[0]){
+        let reason = VerificationErrorReason::NotC2I;
+        return Err(VerificationError::new(reason))
+    }
+
+    Ok(signer_pubkeys[0])
+
+}
+
+/// `Stock` is an amount of a product type and its value
+/// (in minutes)
+
+#[derive(Clone)]
+#[derive(Copy)]
+#[derive(Debug)]
+#[derive(Default)]
+#[derive(PartialEq)]
+#[derive(Eq)]
+pub struct Stock{
+    pub amount: u64,
+    pub value: u64
+}
+
+impl Stock{
+
+    /// Creates a new `Stock`
+    ///
+    /// # Arguments
+    /// * `amount`: The amount of the product type
+    /// * `value`: The value of the amount in minutes
+
+    pub fn new(amount: u64, value: u64) -> Stock{
+        Stock{amount: amount, value: value}
+    }
+
+}
+
+/// A callback notified with the product type and the new
+/// appropriation price whenever the inventory of a product
+/// type changed
+
+pub type PriceSubscriber = Rc<dyn Fn([u8; 32], Option<Rational>)>;
+
+/// `InventoryIndex` keeps track of the stocks of all product
+/// types held by C2I collectives. It is updated incrementally
+/// with every applied block: outputs and collectivizations of
+/// C2I collectives add to their stocks, transfers move stocks
+/// between collectives, write-offs, consumptions and dispatches
+/// remove them and reconciliations correct them.
+///
+/// The index keeps what is left of every resource and which
+/// resources every C2I collective holds, as dispatches and
+/// reconciliations take units from concrete resources. Only
+/// the surplus units of reconciliations aren't tied to a
+/// resource.
+
+pub struct InventoryIndex{
+    resources: HashMap<TxId, ([u8; 32], Stock)>,
+    dispatched: HashSet<TxId>,
+    holdings: HashMap<([u8; 32], [u8; 32]), Vec<TxId>>,
+    stocks: HashMap<[u8; 32], HashMap<[u8; 32], Stock>>,
+    totals: HashMap<[u8; 32], Stock>,
+    changes: Vec<[u8; 32]>,
+    subscribers: Vec<PriceSubscriber>,
+    journal: Journal<InventoryIndex>
+}
+
+impl InventoryIndex{
+
+    /// Creates a new empty `InventoryIndex`
+
+    pub fn new() -> InventoryIndex{
+        InventoryIndex{
+            resources: HashMap::new(),
+            dispatched: HashSet::new(),
+            holdings: HashMap::new(),
+            stocks: HashMap::new(),
+            totals: HashMap::new(),
+            changes: vec![],
+            subscribers: vec![],
+            journal: Journal::new()
+        }
+    }
+
+    /// Returns the stock of a product type held by a C2I collective
+    ///
+    /// # Arguments
+    /// * `collective_pubkey`: The public key of the collective
+    /// * `product_type`: The id of the product type
+
+    pub fn get_stock(&self, collective_pubkey: [u8; 32], product_type: [u8; 32]) -> Stock{
+        self.stocks.get(&collective_pubkey)
+                   .and_then(|stocks| stocks.get(&product_type))
+                   .cloned()
+                   .unwrap_or_default()
+    }
+
+    /// Returns the units of a resource that weren't dispatched
+    /// or reconciled away yet together with their value
+    ///
+    /// # Arguments
+    /// * `resource`: The id of the resource
+
+    pub fn get_remaining(&self, resource: TxId) -> Stock{
+        self.resources.get(&resource).map(|&(_, stock)| stock).unwrap_or_default()
+    }
+
+    /// Checks if units of a resource were dispatched
+    /// or reconciled away
+    ///
+    /// # Arguments
+    /// * `resource`: The id of the resource
+
+    pub fn is_dispatched(&self, resource: TxId) -> bool{
+        self.dispatched.contains(&resource)
+    }
+
+    /// Returns the stock of a product type held by all C2I collectives
+    ///
+    /// # Arguments
+    /// * `product_type`: The id of the product type
+
+    pub fn get_total(&self, product_type: [u8; 32]) -> Stock{
+        self.totals.get(&product_type).cloned().unwrap_or_default()
+    }
+
+    /// Returns the value (in minutes) of all stocks held
+    /// by a set of collectives
+    ///
+    /// # Arguments
+    /// * `collectives`: The public keys of the collectives
+
+    pub fn value_held_by(&self, collectives: &HashSet<[u8; 32]>) -> u64{
+
+        let mut value: u64 = 0;
+        for (collective_pubkey, stocks) in &self.stocks{
+            if collectives.contains(collective_pubkey){
+                for stock in stocks.values(){
+                    value = value.saturating_add(stock.value);
+                }
+            }
+        }
+        value
+
+    }
+
+    /// Returns the appropriation price (in minutes) of one unit
+    /// of a product type or None, if C2I collectives don't
+    /// hold any units of it
+    ///
+    /// # Arguments
+    /// * `product_type`: The id of the product type
+
+    pub fn appropriation_price(&self, product_type: [u8; 32]) -> Option<Rational>{
+
+        let total = self.get_total(product_type);
+        if total.amount == 0{
+            return None
+        }
+        Some(Rational::new(total.value as u128, total.amount as u128))
+
+    }
+
+    /// Registers a callback, which is notified whenever the
+    /// appropriation price of a product type might have changed
+    ///
+    /// # Arguments
+    /// * `subscriber`: The callback
+
+    pub fn subscribe(&mut self, subscriber: PriceSubscriber){
+        self.subscribers.push(subscriber);
+    }
+
+    /// Notifies all subscribers of the product types that
+    /// changed since the last notification
+    ///
+    /// Called once a block was applied, so subscribers don't
+    /// see changes of blocks that are rejected.
+
+    pub fn notify_subscribers(&mut self){
+
+        let changes: Vec<[u8; 32]> = self.changes.drain(..).collect();
+        for product_type in changes{
+            let price = self.appropriation_price(product_type);
+            for subscriber in &self.subscribers{
+                subscriber(product_type, price);
+            }
+        }
+
+    }
+
+    /// Forgets the changes since the last notification. Called
+    /// when a rejected block was undone, as the prices are the
+    /// same as before the block.
+
+    pub fn discard_changes(&mut self){
+        self.changes.clear();
+    }
+
+    /// Updates the index with a verified transaction. Must be
+    /// called before the ownership index is updated with the
+    /// same transaction, so the previous owners are known.
+    ///
+    /// # Arguments
+    /// * `tx_id`: The id of the transaction
+    /// * `transaction`: The verified transaction
+    /// * `collectives`: The collective index of the chain
+    /// * `ownership`: The ownership index of the chain
+
+    pub fn apply_transaction(&mut self,
+                             tx_id: TxId,
+                             transaction: &Transaction,
+                             collectives: &CollectiveIndex,
+                             ownership: &OwnershipIndex){
+
+        match *transaction.get_payload(){
+
+            TxPayload::ProductionOutput(_) | TxPayload::Collectivization(_) => {
+                if let Some(resource) = created_resource(transaction){
+                    let stock = Stock::new(resource.amount, resource.value);
+                    let previous = self.resources.insert(tx_id, (resource.product_type, stock));
+                    self.journal.record_entry(|index| &mut index.resources, tx_id, previous);
+                    if collectives.is_c2i(resource.owner){
+                        self.add_resource(resource.owner, tx_id);
+                    }
+                }
+            },
+
+            TxPayload::OwnershipTransfer(ref payload) => {
+                let resource = ownership.get_resource_id(payload.get_previous());
+                if let Some(owner) = ownership.get_owner(resource){
+                    if collectives.is_c2i(owner){
+                        self.remove_resource(owner, resource);
+                    }
+                }
+                if collectives.is_c2i(payload.get_receiver()){
+                    self.add_resource(payload.get_receiver(), resource);
+                }
+            },
+
+            TxPayload::WriteOff(ref payload) => {
+                self.remove_owned_resource(payload.get_resource(), collectives, ownership);
+            },
+
+            TxPayload::ConsumptiveResourceAllocation(ref payload) => {
+                self.remove_owned_resource(payload.get_resource(), collectives, ownership);
+            },
+
+            TxPayload::Dispatch(ref payload) => {
+                let collective_pubkey = transaction.get_signer_pubkeys()[0];
+                self.dispatch(collective_pubkey, payload.get_resource(), payload.get_amount());
+            },
+
+            TxPayload::InventoryReconciliation(ref payload) => {
+                let collective_pubkey = transaction.get_signer_pubkeys()[0];
+                let product_type = payload.get_product_type();
+                let stock = self.get_stock(collective_pubkey, product_type);
+                if payload.get_amount() < stock.amount{
+                    let missing = stock.amount - payload.get_amount();
+                    self.remove_missing(collective_pubkey, product_type, missing);
+                }else{
+                    let surplus = payload.get_amount() - stock.amount;
+                    self.add(collective_pubkey, product_type, Stock::new(surplus, 0));
+                }
+            },
+
+            _ => {}
+
+        }
+
+    }
+
+    /// Removes a resource from the stocks of its owner,
+    /// if the owner is a C2I collective
+
+    fn remove_owned_resource(&mut self,
+                             resource: TxId,
+                             collectives: &CollectiveIndex,
+                             ownership: &OwnershipIndex){
+
+        if let Some(owner) = ownership.get_owner(resource){
+            if collectives.is_c2i(owner){
+                self.remove_resource(owner, resource);
+            }
+        }
+
+    }
+
+    /// Removes dispatched units of a resource together with
+    /// their share of the resource's value from the stocks of
+    /// a collective
+
+    fn dispatch(&mut self, collective_pubkey: [u8; 32], resource: TxId, amount: u64){
+
+        let previous = self.resources.get(&resource).cloned();
+        self.journal.record_entry(|index| &mut index.resources, resource, previous);
+
+        let (product_type, dispatched) = match self.resources.get_mut(&resource){
+            Some(&mut (product_type, ref mut remaining)) if remaining.amount > 0 => {
+                let amount = amount.min(remaining.amount);
+                let value = remaining.value as u128 * amount as u128 / remaining.amount as u128;
+                let dispatched = Stock::new(amount, value as u64);
+                remaining.amount -= dispatched.amount;
+                remaining.value -= dispatched.value;
+                (product_type, dispatched)
+            },
+            _ => return
+        };
+
+        let was_member = !self.dispatched.insert(resource);
+        self.journal.record_member(|index| &mut index.dispatched, resource, was_member);
+        self.remove(collective_pubkey, product_type, dispatched);
+
+        // a resource without units is
+        // of no use to the collective
+
+        if self.get_remaining(resource).amount == 0{
+            self.release(collective_pubkey, product_type, resource);
+        }
+
+    }
+
+    /// Removes missing units of a product type from the stocks
+    /// of a collective. The units that aren't tied to a resource
+    /// are removed first, then the units of the held resources
+    /// are dispatched in the order they were received.
+
+    fn remove_missing(&mut self, collective_pubkey: [u8; 32], product_type: [u8; 32], missing: u64){
+
+        let held = self.holdings.get(&(collective_pubkey, product_type))
+                                .cloned()
+                                .unwrap_or_default();
+        let tied = held.iter().fold(0u64, |tied, resource| {
+            tied.saturating_add(self.get_remaining(*resource).amount)
+        });
+
+        let stock = self.get_stock(collective_pubkey, product_type);
+        let untied = stock.amount.saturating_sub(tied).min(missing);
+        self.remove(collective_pubkey, product_type, Stock::new(untied, 0));
+
+        let mut missing = missing - untied;
+        for resource in held{
+            if missing == 0{
+                break
+            }
+            let amount = missing.min(self.get_remaining(resource).amount);
+            if amount > 0{
+                self.dispatch(collective_pubkey, resource, amount);
+                missing -= amount;
+            }
+        }
+
+    }
+
+    /// Adds a resource to the stocks of a collective
+
+    fn add_resource(&mut self, collective_pubkey: [u8; 32], resource: TxId){
+        if let Some((product_type, stock)) = self.resources.get(&resource).cloned(){
+            self.hold(collective_pubkey, product_type, resource);
+            self.add(collective_pubkey, product_type, stock);
+        }
+    }
+
+    /// Removes a resource from the stocks of a collective
+
+    fn remove_resource(&mut self, collective_pubkey: [u8; 32], resource: TxId){
+        if let Some((product_type, stock)) = self.resources.get(&resource).cloned(){
+            self.release(collective_pubkey, product_type, resource);
+            self.remove(collective_pubkey, product_type, stock);
+        }
+    }
+
+    /// Records that a collective holds a resource
+
+    fn hold(&mut self, collective_pubkey: [u8; 32], product_type: [u8; 32], resource: TxId){
+        let key = (collective_pubkey, product_type);
+        let previous = self.holdings.get(&key).cloned();
+        self.journal.record_entry(|index| &mut index.holdings, key, previous);
+        self.holdings.entry(key).or_default().push(resource);
+    }
+
+    /// Records that a collective doesn't hold a resource anymore
+
+    fn release(&mut self, collective_pubkey: [u8; 32], product_type: [u8; 32], resource: TxId){
+        let key = (collective_pubkey, product_type);
+        let previous = self.holdings.get(&key).cloned();
+        self.journal.record_entry(|index| &mut index.holdings, key, previous);
+        if let Some(held) = self.holdings.get_mut(&key){
+            held.retain(|held_resource| *held_resource != resource);
+        }
+    }
+
+    /// Adds a stock to the stocks of a collective
+
+    fn add(&mut self, collective_pubkey: [u8; 32], product_type: [u8; 32], stock: Stock){
+
+        self.record_stock(collective_pubkey, product_type);
+        let collective_stock = self.stocks.entry(collective_pubkey)
+                                          .or_default()
+                                          .entry(product_type)
+                                          .or_default();
+        collective_stock.amount = collective_stock.amount.saturating_add(stock.amount);
+        collective_stock.value = collective_stock.value.saturating_add(stock.value);
+
+        let total = self.totals.entry(product_type).or_default();
+        total.amount = total.amount.saturating_add(stock.amount);
+        total.value = total.value.saturating_add(stock.value);
+
+        self.changed(product_type);
+
+    }
+
+    /// Removes a stock from the stocks of a collective. The
+    /// removed stock is limited to the stock of the collective.
+
+    fn remove(&mut self, collective_pubkey: [u8; 32], product_type: [u8; 32], stock: Stock){
+
+        self.record_stock(collective_pubkey, product_type);
+        let removed = {
+            let collective_stock = self.stocks.entry(collective_pubkey)
+                                              .or_default()
+                                              .entry(product_type)
+                                              .or_default();
+            let removed = Stock::new(stock.amount.min(collective_stock.amount),
+                                     stock.value.min(collective_stock.value));
+            collective_stock.amount -= removed.amount;
+            collective_stock.value -= removed.value;
+            removed
+        };
+
+        let total = self.totals.entry(product_type).or_default();
+        total.amount = total.amount.saturating_sub(removed.amount);
+        total.value = total.value.saturating_sub(removed.value);
+
+        self.changed(product_type);
+
+    }
+
+    /// Journals the stock of a collective and the total of a
+    /// product type before they change. Undoing the change
+    /// records that the stocks of the product type changed.
+
+    fn record_stock(&mut self, collective_pubkey: [u8; 32], product_type: [u8; 32]){
+
+        let previous_stock = self.stocks.get(&collective_pubkey)
+                                        .and_then(|stocks| stocks.get(&product_type))
+                                        .cloned();
+        let previous_total = self.totals.get(&product_type).cloned();
+
+        self.journal.record(move |index: &mut InventoryIndex| {
+            restore(index.stocks.entry(collective_pubkey).or_default(), product_type, previous_stock);
+            restore(&mut index.totals, product_type, previous_total);
+            index.changed(product_type);
+        });
+
+    }
+
+    /// Records that the stocks of a product type changed
+
+    fn changed(&mut self, product_type: [u8; 32]){
+        if !self.changes.contains(&product_type){
+            self.changes.push(product_type);
+        }
+    }
+
+}
+
+impl Journaled for InventoryIndex{
+    fn get_journal(&mut self) -> &mut Journal<InventoryIndex>{
+        &mut self.journal
+    }
+}
+
+#[test]
+fn test_inventory_bytes(){
+
+    use blockchain::block::BlockId;
+    use blockchain::transactions::TxIndex;
+
+    let resource = TxId::new(BlockId([0x01; 32]), TxIndex(2));
+    let dispatch = Dispatch::new(resource, 3);
+    let bytes = dispatch.as_bytes();
+    assert_eq!(bytes.len(), 34 + 8);
+    let decoded = Dispatch::from_bytes(bytes.clone()).unwrap();
+    assert_eq!(decoded.get_resource(), resource);
+    assert_eq!(decoded.get_amount(), 3);
+    assert!(Dispatch::from_bytes(bytes[1..].to_vec()).is_err());
+
+    let reconciliation = InventoryReconciliation::new([0x02; 32], 0);
+    let bytes = reconciliation.as_bytes();
+    assert_eq!(bytes.len(), 32 + 8);
+    let decoded = InventoryReconciliation::from_bytes(bytes.clone()).unwrap();
+    assert_eq!(decoded.get_product_type(), [0x02; 32]);
+    assert_eq!(decoded.get_amount(), 0);
+    assert!(InventoryReconciliation::from_bytes([&bytes[..], &[0x00][..]].concat()).is_err());
+
+}
+
+#[test]
+fn test_inventory(){
+
+    use std::cell::RefCell;
+    use blockchain::errors::VerificationErrorReason;
+    use blockchain::txtypes::allocation::WorkloadAllocation;
+    use blockchain::txtypes::production::ProductionOutput;
+    use blockchain::txtypes::resource::OwnershipTransfer;
+    use blockchain::txtypes::resource::WriteOff;
+    use blockchain::txtypes::trust::TrustEstablish;
+    use blockchain::params::ConsensusParams;
+    use blockchain::txtypes::TestChain;
+    use blockchain::txtypes::signed_transaction;
+    use blockchain::txtypes::register_collective;
+    use blockchain::txtypes::register_c2i_collective;
+    use blockchain::txtypes::define_product_type;
+    use blockchain::txtypes::start_production;
+    use blockchain::txtypes::keypair;
+
+    let (secret_a, pubkey_a) = keypair(0x0A);
+    let (secret_b, pubkey_b) = keypair(0x0B);
+
+    // write-offs only need to be less than the
+    // workloads of the period
+
+    let trust = TrustEstablish::new(pubkey_a, pubkey_b);
+    let params = ConsensusParams{write_off_limit_divisor: 1, ..ConsensusParams::default()};
+    let mut chain = TestChain::with_params(params);
+    chain.append(1, vec![register_c2i_collective(&secret_a),
+                         register_collective(&secret_b),
+                         signed_transaction(TxPayload::TrustEstablish(trust),
+                                            &[&secret_a, &secret_b])]).unwrap();
+    let (bread, definition) = define_product_type(&secret_a, "bread");
+    chain.append(2, vec![definition]).unwrap();
+
+    let notifications = Rc::new(RefCell::new(vec![]));
+    let subscriber = notifications.clone();
+    chain.indexes.inventory.subscribe(Rc::new(move |product_type, price| {
+        subscriber.borrow_mut().push((product_type, price));
+    }));
+
+    let produce = |chain: &mut TestChain, secret_key: &[u8; 64], timestamp, amount, value| {
+        let production = start_production(chain, secret_key, timestamp);
+        let payload = WorkloadAllocation::new(production, [0x01; 32], value, false);
+        chain.append(timestamp + 3, vec![signed_transaction(TxPayload::WorkloadAllocation(payload),
+                                                            &[secret_key])]).unwrap();
+        let payload = ProductionOutput::new(production, bread, amount, value, false);
+        chain.append(timestamp + 4, vec![signed_transaction(TxPayload::ProductionOutput(payload),
+                                                            &[secret_key])]).unwrap()[0]
+    };
+    let transfer = |signers: &[&[u8]], previous, receiver| {
+        let payload = OwnershipTransfer::new(previous, receiver);
+        signed_transaction(TxPayload::OwnershipTransfer(payload), signers)
+    };
+
+    // only resources of C2I collectives are in the inventory
+
+    let output_b = produce(&mut chain, &secret_b, 10, 10, 400);
+    assert_eq!(chain.indexes.inventory.appropriation_price(bread), None);
+    assert!(notifications.borrow().is_empty());
+
+    let output_a = produce(&mut chain, &secret_a, 20, 2, 60);
+    assert_eq!(chain.indexes.inventory.get_stock(pubkey_a, bread), Stock::new(2, 60));
+    assert_eq!(chain.indexes.inventory.appropriation_price(bread), Some(Rational::from_integer(30)));
+    assert_eq!(*notifications.borrow(), vec![(bread, Some(Rational::from_integer(30)))]);
+
+    // transfers to and from C2I collectives move the stock
+
+    let transfer_id = chain.append(30, vec![transfer(&[&secret_b, &secret_a], output_b, pubkey_a)])
+                           .unwrap()[0];
+    assert_eq!(chain.indexes.inventory.get_total(bread), Stock::new(12, 460));
+    assert_eq!(chain.indexes.inventory.appropriation_price(bread), Some(Rational::new(115, 3)));
+
+    let payload = WriteOff::new(output_a, String::from("stale"));
+    chain.append(31, vec![signed_transaction(TxPayload::WriteOff(payload.clone()), &[&secret_a])]).unwrap();
+    assert_eq!(chain.indexes.inventory.appropriation_price(bread), Some(Rational::from_integer(40)));
+
+    // subscribers aren't notified of rejected blocks
+
+    notifications.borrow_mut().clear();
+    let write_off = signed_transaction(TxPayload::WriteOff(payload), &[&secret_a]);
+    match chain.append(32, vec![transfer(&[&secret_a, &secret_b], transfer_id, pubkey_b), write_off]){
+        Err(VerificationErrorReason::ResourceInUse) => {},
+        result => panic!("Unexpected result {:?}", result)
+    }
+    assert!(notifications.borrow().is_empty());
+
+    chain.append(32, vec![transfer(&[&secret_a, &secret_b], transfer_id, pubkey_b)]).unwrap();
+    assert_eq!(chain.indexes.inventory.get_stock(pubkey_a, bread), Stock::new(0, 0));
+    assert_eq!(chain.indexes.inventory.appropriation_price(bread), None);
+    assert_eq!(*notifications.borrow(), vec![(bread, None)]);
+
+}
+
+#[test]
+fn test_dispatch(){
+
+    use blockchain::errors::VerificationErrorReason;
+    use blockchain::txtypes::allocation::WorkloadAllocation;
+    use blockchain::txtypes::production::ProductionOutput;
+    use blockchain::txtypes::resource::Collectivization;
+    use blockchain::txtypes::resource::OwnershipTransfer;
+    use blockchain::txtypes::resource::WriteOff;
+    use blockchain::txtypes::trust::TrustEstablish;
+    use blockchain::txtypes::TestChain;
+    use blockchain::txtypes::signed_transaction;
+    use blockchain::txtypes::register_collective;
+    use blockchain::txtypes::register_c2i_collective;
+    use blockchain::txtypes::define_product_type;
+    use blockchain::txtypes::start_production;
+    use blockchain::txtypes::keypair;
+
+    let (secret_a, pubkey_a) = keypair(0x0A);
+    let (secret_b, pubkey_b) = keypair(0x0B);
+    let (secret_c, _) = keypair(0x0C);
+
+    let trust = TrustEstablish::new(pubkey_a, pubkey_b);
+    let mut chain = TestChain::new();
+    chain.append(1, vec![register_c2i_collective(&secret_a),
+                         register_collective(&secret_b),
+                         signed_transaction(TxPayload::TrustEstablish(trust),
+                                            &[&secret_a, &secret_b])]).unwrap();
+    let (bread, definition) = define_product_type(&secret_a, "bread");
+    chain.append(2, vec![definition]).unwrap();
+
+    let production = start_production(&mut chain, &secret_a, 10);
+    let payload = WorkloadAllocation::new(production, [0x01; 32], 180, false);
+    chain.append(13, vec![signed_transaction(TxPayload::WorkloadAllocation(payload), &[&secret_a])]).unwrap();
+    let payload = ProductionOutput::new(production, bread, 4, 180, false);
+    let output = chain.append(14, vec![signed_transaction(TxPayload::ProductionOutput(payload),
+                                                          &[&secret_a])]).unwrap()[0];
+    let payload = Collectivization::new(bread, 2);
+    let collectivized = chain.append(15, vec![signed_transaction(TxPayload::Collectivization(payload),
+                                                                 &[&secret_a])]).unwrap()[0];
+    assert_eq!(chain.indexes.inventory.appropriation_price(bread), Some(Rational::from_integer(30)));
+
+    let dispatch = |secret_key: &[u8; 64], amount| {
+        let payload = Dispatch::new(output, amount);
+        signed_transaction(TxPayload::Dispatch(payload), &[secret_key])
+    };
+    let write_off = || {
+        let payload = WriteOff::new(output, String::from("stale"));
+        signed_transaction(TxPayload::WriteOff(payload), &[&secret_a])
+    };
+    let transfer = || {
+        let payload = OwnershipTransfer::new(output, pubkey_b);
+        signed_transaction(TxPayload::OwnershipTransfer(payload), &[&secret_a, &secret_b])
+    };
+    let reconciliation = |secret_key: &[u8; 64], amount| {
+        let payload = InventoryReconciliation::new(bread, amount);
+        signed_transaction(TxPayload::InventoryReconciliation(payload), &[secret_key])
+    };
+
+    // only C2I collectives dispatch their own resources
+
+    match chain.append(20, vec![dispatch(&secret_c, 1)]){
+        Err(VerificationErrorReason::UnknownCollective) => {},
+        result => panic!("Unexpected result {:?}", result)
+    }
+    match chain.append(20, vec![dispatch(&secret_b, 1)]){
+        Err(VerificationErrorReason::NotC2I) => {},
+        result => panic!("Unexpected result {:?}", result)
+    }
+    match chain.append(20, vec![dispatch(&secret_a, 0)]){
+        Err(VerificationErrorReason::InvalidAmount) => {},
+        result => panic!("Unexpected result {:?}", result)
+    }
+    match chain.append(20, vec![dispatch(&secret_a, 5)]){
+        Err(VerificationErrorReason::InsufficientStock) => {},
+        result => panic!("Unexpected result {:?}", result)
+    }
+    match chain.append(20, vec![dispatch(&secret_a, 4), dispatch(&secret_a, 1)]){
+        Err(VerificationErrorReason::InsufficientStock) => {},
+        result => panic!("Unexpected result {:?}", result)
+    }
+
+    // dispatched units are removed at the value of the
+    // resource, which can't be used otherwise anymore
+
+    chain.append(20, vec![dispatch(&secret_a, 3)]).unwrap();
+    assert_eq!(chain.indexes.inventory.get_stock(pubkey_a, bread), Stock::new(3, 45));
+    assert_eq!(chain.indexes.inventory.get_remaining(output), Stock::new(1, 45));
+    assert_eq!(chain.indexes.inventory.appropriation_price(bread), Some(Rational::from_integer(15)));
+
+    match chain.append(21, vec![write_off()]){
+        Err(VerificationErrorReason::ResourceInUse) => {},
+        result => panic!("Unexpected result {:?}", result)
+    }
+
+    // the dispatch of the last unit retires the resource,
+    // so it can't bring its value back into the stocks
+
+    chain.append(30, vec![dispatch(&secret_a, 1)]).unwrap();
+    assert_eq!(chain.indexes.inventory.get_stock(pubkey_a, bread), Stock::new(2, 0));
+
+    match chain.append(31, vec![transfer()]){
+        Err(VerificationErrorReason::ResourceInUse) => {},
+        result => panic!("Unexpected result {:?}", result)
+    }
+    match chain.append(31, vec![write_off()]){
+        Err(VerificationErrorReason::ResourceInUse) => {},
+        result => panic!("Unexpected result {:?}", result)
+    }
+    match chain.append(31, vec![dispatch(&secret_a, 1)]){
+        Err(VerificationErrorReason::InsufficientStock) => {},
+        result => panic!("Unexpected result {:?}", result)
+    }
+    assert_eq!(chain.indexes.inventory.get_total(bread), Stock::new(2, 0));
+
+    // surplus units are added without value, missing units
+    // are taken from them first, then from the resources
+
+    match chain.append(40, vec![reconciliation(&secret_b, 1)]){
+        Err(VerificationErrorReason::NotC2I) => {},
+        result => panic!("Unexpected result {:?}", result)
+    }
+
+    chain.append(40, vec![reconciliation(&secret_a, 3)]).unwrap();
+    assert_eq!(chain.indexes.inventory.get_stock(pubkey_a, bread), Stock::new(3, 0));
+    assert_eq!(chain.indexes.inventory.get_remaining(collectivized), Stock::new(2, 0));
+
+    chain.append(41, vec![reconciliation(&secret_a, 1)]).unwrap();
+    assert_eq!(chain.indexes.inventory.get_stock(pubkey_a, bread), Stock::new(1, 0));
+    assert_eq!(chain.indexes.inventory.get_remaining(collectivized), Stock::new(1, 0));
+    assert!(chain.indexes.inventory.is_dispatched(collectivized));
+}
+
+#[test]
+fn test_reconciliation_transfer_back(){
+
+    use blockchain::errors::VerificationErrorReason;
+    use blockchain::txtypes::allocation::WorkloadAllocation;
+    use blockchain::txtypes::production::ProductionOutput;
+    use blockchain::txtypes::resource::OwnershipTransfer;
+    use blockchain::txtypes::trust::TrustEstablish;
+    use blockchain::txtypes::TestChain;
+    use blockchain::txtypes::signed_transaction;
+    use blockchain::txtypes::register_collective;
+    use blockchain::txtypes::register_c2i_collective;
+    use blockchain::txtypes::define_product_type;
+    use blockchain::txtypes::start_production;
+    use blockchain::txtypes::keypair;
+
+    let (secret_a, pubkey_a) = keypair(0x0A);
+    let (secret_b, pubkey_b) = keypair(0x0B);
+
+    let trust = TrustEstablish::new(pubkey_a, pubkey_b);
+    let mut chain = TestChain::new();
+    chain.append(1, vec![register_c2i_collective(&secret_a),
+                         register_collective(&secret_b),
+                         signed_transaction(TxPayload::TrustEstablish(trust),
+                                            &[&secret_a, &secret_b])]).unwrap();
+    let (bread, definition) = define_product_type(&secret_a, "bread");
+    chain.append(2, vec![definition]).unwrap();
+
+    let produce = |chain: &mut TestChain, timestamp, amount, value| {
+        let production = start_production(chain, &secret_a, timestamp);
+        let payload = WorkloadAllocation::new(production, [0x01; 32], value, false);
+        chain.append(timestamp + 3, vec![signed_transaction(TxPayload::WorkloadAllocation(payload),
+                                                            &[&secret_a])]).unwrap();
+        let payload = ProductionOutput::new(production, bread, amount, value, false);
+        chain.append(timestamp + 4, vec![signed_transaction(TxPayload::ProductionOutput(payload),
+                                                            &[&secret_a])]).unwrap()[0]
+    };
+    let transfer = |signers: &[&[u8]], previous, receiver| {
+        let payload = OwnershipTransfer::new(previous, receiver);
+        signed_transaction(TxPayload::OwnershipTransfer(payload), signers)
+    };
+    let reconciliation = |amount| {
+        let payload = InventoryReconciliation::new(bread, amount);
+        signed_transaction(TxPayload::InventoryReconciliation(payload), &[&secret_a])
+    };
+
+    let output_1 = produce(&mut chain, 10, 4, 120);
+    let output_2 = produce(&mut chain, 20, 2, 60);
+    assert_eq!(chain.indexes.inventory.get_stock(pubkey_a, bread), Stock::new(6, 180));
+
+    // the missing unit is taken from the first
+    // resource, which can't be transferred anymore
+
+    chain.append(30, vec![reconciliation(5)]).unwrap();
+    assert_eq!(chain.indexes.inventory.get_stock(pubkey_a, bread), Stock::new(5, 150));
+    assert_eq!(chain.indexes.inventory.get_remaining(output_1), Stock::new(3, 90));
+
+    match chain.append(31, vec![transfer(&[&secret_a, &secret_b], output_1, pubkey_b)]){
+        Err(VerificationErrorReason::ResourceInUse) => {},
+        result => panic!("Unexpected result {:?}", result)
+    }
+
+    // transferring the other resource out and back
+    // doesn't bring the missing unit back
+
+    let transfer_id = chain.append(31, vec![transfer(&[&secret_a, &secret_b], output_2, pubkey_b)])
+                           .unwrap()[0];
+    assert_eq!(chain.indexes.inventory.get_stock(pubkey_a, bread), Stock::new(3, 90));
+
+    chain.append(32, vec![transfer(&[&secret_b, &secret_a], transfer_id, pubkey_a)]).unwrap();
+    assert_eq!(chain.indexes.inventory.get_stock(pubkey_a, bread), Stock::new(5, 150));
+    assert_eq!(chain.indexes.inventory.get_total(bread), Stock::new(5, 150));
+
+    // the resources are taken in the order they were received
+
+    chain.append(33, vec![reconciliation(1)]).unwrap();
+    assert_eq!(chain.indexes.inventory.get_remaining(output_1), Stock::new(0, 0));
+    assert_eq!(chain.indexes.inventory.get_remaining(output_2), Stock::new(1, 30));
+    assert_eq!(chain.indexes.inventory.get_stock(pubkey_a, bread), Stock::new(1, 30));
+
+}
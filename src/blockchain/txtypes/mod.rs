@@ -0,0 +1,697 @@
+//
+//    Stachanov
+//    Copyright (C) 2017 Stachanov Developer Collective
+//
+//    This file is part of Stachanov.
+//
+//    This program is free software: you can redistribute it and/or
+//    modify it under the terms of the GNU Affero General Public
+//    License, version 3, as published by the Free Software Foundation.
+//
+//    This program is distributed in the hope that it will be useful,
+//    but WITHOUT ANY WARRANTY; without even the implied warranty of
+//    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//    GNU Affero General Public License for more details.
+//
+//    You should have received a copy of the
+//               GNU Affero General Public License
+//    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+// This module holds the transaction types of the economic layer.
+// Every submodule defines the payload of one or more transaction
+// types, the rules to verify them against the current chain and
+// the indexes derived from them.
+
+pub mod collective;
+pub mod trust;
+pub mod production;
+pub mod allocation;
+pub mod product;
+pub mod revision;
+pub mod coupon;
+pub mod resource;
+pub mod inventory;
+pub mod settlement;
+pub mod vote;
+pub mod governance;
+
+#[cfg(test)]
+extern crate crypto;
+#[cfg(test)]
+use blockchain::block::Block;
+#[cfg(test)]
+use blockchain::block::BlockError;
+#[cfg(test)]
+use blockchain::block::BlockErrorReason;
+#[cfg(test)]
+use blockchain::transactions::TxIndex;
+#[cfg(test)]
+use blockchain::storages::memory::MemoryChainStorage;
+#[cfg(test)]
+use blockchain::processor::apply_block;
+use std::collections::HashMap;
+use blockchain::header::BlockHeader;
+use blockchain::traits::ChainStorage;
+use blockchain::transactions::Transaction;
+use blockchain::transactions::TxPayload;
+use blockchain::transactions::TxId;
+use blockchain::transactions::TxRel;
+use blockchain::transactions::TxRelId;
+use blockchain::transactions::TxState;
+use blockchain::transactions::TxTotalRelState;
+use blockchain::transactions::BadClaimReason;
+use blockchain::traits::BinFormat;
+use blockchain::transactions::read_tx_id;
+use blockchain::errors::BinFormatError;
+use blockchain::errors::BinFormatErrorReason;
+use blockchain::errors::VerificationError;
+use blockchain::errors::VerificationErrorReason;
+use blockchain::processor::ChainIndexes;
+use blockchain::params::ConsensusParams;
+use blockchain::utils::u16_to_u8le;
+use blockchain::utils::BinReader;
+
+/// `TxContext` bundles everything a transaction needs to be
+/// verified: the chain it is going to be appended to, the
+/// indexes including all preceding transactions of the same
+/// block, the header of the block holding it and the states
+/// of transactions that were claimed within the block so far.
+///
+/// Transactions can only refer to transactions in preceding
+/// blocks, so referenced transactions are always fetched from
+/// the storage.
+
+pub struct TxContext<'a, C: 'a>{
+    storage: &'a C,
+    indexes: &'a ChainIndexes,
+    header: &'a BlockHeader,
+    tx_id: TxId,
+    claimed_states: &'a mut HashMap<TxId, TxState>
+}
+
+impl<'a, C> TxContext<'a, C> where C: ChainStorage{
+
+    /// Creates a new `TxContext`
+    ///
+    /// # Arguments
+    /// * `storage`: The storage holding the chain
+    /// * `indexes`: The indexes of the chain (including all
+    ///              preceding transactions of the block)
+    /// * `header`: The header of the block holding the transaction
+    /// * `tx_id`: The id of the transaction that is verified
+    /// * `claimed_states`: States modified within the block so far
+
+    pub fn new(storage: &'a C,
+               indexes: &'a ChainIndexes,
+               header: &'a BlockHeader,
+               tx_id: TxId,
+               claimed_states: &'a mut HashMap<TxId, TxState>) -> TxContext<'a, C>{
+
+        TxContext{
+            storage: storage,
+            indexes: indexes,
+            header: header,
+            tx_id: tx_id,
+            claimed_states: claimed_states
+        }
+
+    }
+
+    /// Gets the id of the transaction that is verified
+
+    pub fn get_tx_id(&self) -> TxId{
+        self.tx_id
+    }
+
+    /// Gets a reference to the chain indexes
+
+    pub fn get_indexes(&self) -> &ChainIndexes{
+        self.indexes
+    }
+
+    /// Gets the consensus parameters of the chain
+
+    pub fn get_params(&self) -> &ConsensusParams{
+        &self.indexes.params
+    }
+
+    /// Gets a reference to the header of the block
+    /// holding the transaction
+
+    pub fn get_header(&self) -> &BlockHeader{
+        self.header
+    }
+
+    /// Gets a reference to the storage holding the chain
+
+    pub fn get_storage(&self) -> &'a C{
+        self.storage
+    }
+
+    /// Fetches a transaction from a preceding block.
+    ///
+    /// Returns a VerificationError with reason InvalidTxRef,
+    /// if the transaction doesn't exist
+    ///
+    /// # Arguments
+    /// * `tx_id`: The id of the referenced transaction
+
+    pub fn get_transaction(&self, tx_id: TxId) -> Result<Transaction, VerificationError>{
+
+        match self.storage.get_transaction(tx_id){
+            Some(transaction) => Ok(transaction),
+            None => {
+                let reason = VerificationErrorReason::InvalidTxRef(tx_id);
+                Err(VerificationError::new(reason))
+            }
+        }
+
+    }
+
+    /// Fetches the timestamp of the block holding a
+    /// transaction in a preceding block.
+    ///
+    /// Returns a VerificationError with reason InvalidTxRef,
+    /// if the block doesn't exist
+    ///
+    /// # Arguments
+    /// * `tx_id`: The id of the referenced transaction
+
+    pub fn get_timestamp(&self, tx_id: TxId) -> Result<u64, VerificationError>{
+
+        match self.storage.get_header(tx_id.block_id){
+            Some(header) => Ok(header.get_timestamp()),
+            None => {
+                let reason = VerificationErrorReason::InvalidTxRef(tx_id);
+                Err(VerificationError::new(reason))
+            }
+        }
+
+    }
+
+    /// Fetches the current state of a transaction,
+    /// including claims of the same block
+    ///
+    /// # Arguments
+    /// * `tx_id`: The id of the referenced transaction
+
+    pub fn get_tx_state(&self, tx_id: TxId) -> Option<TxState>{
+
+        if let Some(tx_state) = self.claimed_states.get(&tx_id){
+            return Some(tx_state.clone())
+        }
+        self.storage.get_transaction_state(tx_id)
+
+    }
+
+    /// Checks if a 1:1 relationship of a transaction
+    /// was claimed, including claims of the same block
+    ///
+    /// # Arguments
+    /// * `tx_id`: The id of the referenced transaction
+    /// * `tx_rel_id`: The relationship in question
+
+    pub fn is_rel_claimed(&self, tx_id: TxId, tx_rel_id: TxRelId) -> bool{
+        self.get_tx_state(tx_id).is_some_and(|tx_state| {
+            matches!(tx_state.get_rel(tx_rel_id), Ok(&TxRel::OneToOne(Some(_))))
+        })
+    }
+
+    /// Fetches the ids of all transactions that claimed a
+    /// relationship of a transaction, including claims of
+    /// the same block
+    ///
+    /// # Arguments
+    /// * `tx_id`: The id of the referenced transaction
+    /// * `tx_rel_id`: The relationship in question
+
+    pub fn get_claimers(&self, tx_id: TxId, tx_rel_id: TxRelId) -> Vec<TxId>{
+
+        let tx_state = match self.get_tx_state(tx_id){
+            Some(tx_state) => tx_state,
+            None => return vec![]
+        };
+
+        match tx_state.get_rel(tx_rel_id){
+            Ok(&TxRel::OneToOne(Some(claimer_tx_id))) => vec![claimer_tx_id],
+            Ok(TxRel::OneToMany(claimer_tx_ids)) => claimer_tx_ids.clone(),
+            _ => vec![]
+        }
+
+    }
+
+    /// Claims a relationship of a transaction in a preceding
+    /// block for the transaction that is verified.
+    ///
+    /// Returns a VerificationError with reason InvalidTxRef if
+    /// the transaction doesn't exist and BadClaim if the
+    /// relationship can't be claimed
+    ///
+    /// # Arguments
+    /// * `tx_id`: The id of the referenced transaction
+    /// * `tx_rel_id`: The relationship that should be claimed
+
+    pub fn claim_rel(&mut self,
+                     tx_id: TxId,
+                     tx_rel_id: TxRelId) -> Result<(), VerificationError>{
+
+        let mut tx_state = match self.get_tx_state(tx_id){
+            Some(tx_state) => tx_state,
+            None => {
+                let reason = VerificationErrorReason::InvalidTxRef(tx_id);
+                return Err(VerificationError::new(reason))
+            }
+        };
+
+        if let Err(bad_claim) = tx_state.claim_rel(tx_rel_id, self.tx_id){
+            let reason = VerificationErrorReason::BadClaim(bad_claim.reason);
+            return Err(VerificationError::new(reason))
+        }
+
+        self.claimed_states.insert(tx_id, tx_state);
+        Ok(())
+
+    }
+
+    /// Finalizes a transaction in a preceding block by the
+    /// transaction that is verified, so none of its
+    /// relationships can be claimed anymore.
+    ///
+    /// Returns a VerificationError with reason InvalidTxRef if
+    /// the transaction doesn't exist and BadClaim if it is
+    /// unclaimable or already finalized
+    ///
+    /// # Arguments
+    /// * `tx_id`: The id of the referenced transaction
+
+    pub fn finalize(&mut self, tx_id: TxId) -> Result<(), VerificationError>{
+
+        let mut tx_state = match self.get_tx_state(tx_id){
+            Some(tx_state) => tx_state,
+            None => {
+                let reason = VerificationErrorReason::InvalidTxRef(tx_id);
+                return Err(VerificationError::new(reason))
+            }
+        };
+
+        let bad_claim_reason = match *tx_state.get_total_rel_state(){
+            TxTotalRelState::Claimable => None,
+            TxTotalRelState::Unclaimable => Some(BadClaimReason::TxUnclaimable),
+            TxTotalRelState::Finalized(fin_tx_id) => Some(BadClaimReason::TxFinalized(fin_tx_id))
+        };
+
+        if let Some(bad_claim_reason) = bad_claim_reason{
+            let reason = VerificationErrorReason::BadClaim(bad_claim_reason);
+            return Err(VerificationError::new(reason))
+        }
+
+        tx_state.set_total_rel_state(TxTotalRelState::Finalized(self.tx_id));
+        self.claimed_states.insert(tx_id, tx_state);
+        Ok(())
+
+    }
+
+}
+
+/// Checks that a transaction is signed by exactly the
+/// supplied public keys (in the supplied order).
+///
+/// Returns a VerificationError with reason InvalidTxSigners
+/// if the signers differ. Note that this doesn't check the
+/// signatures themselves.
+///
+/// # Arguments
+/// * `transaction`: The transaction to check
+/// * `signer_pubkeys`: The expected public keys
+
+pub fn verify_signers(transaction: &Transaction,
+                      signer_pubkeys: &[[u8; 32]]) -> Result<(), VerificationError>{
+
+    if &transaction.get_signer_pubkeys()[..] != signer_pubkeys{
+        let err = VerificationError::new(VerificationErrorReason::InvalidTxSigners);
+        return Err(err)
+    }
+    Ok(())
+
+}
+
+/// Verifies the payload of a transaction against the chain.
+/// Relationships of preceding transactions are claimed in
+/// the context.
+///
+/// # Arguments
+/// * `transaction`: The transaction that is verified
+/// * `context`: The verification context
+
+pub fn verify_payload<C>(transaction: &Transaction,
+                         context: &mut TxContext<C>)
+                         -> Result<(), VerificationError> where C: ChainStorage{
+
+    match *transaction.get_payload(){
+        TxPayload::Dummy => Ok(()),
+        TxPayload::CollectiveRegistration(ref payload) => {
+            payload.verify(transaction, context)
+        },
+        TxPayload::TrustEstablish(ref payload) => payload.verify(transaction, context),
+        TxPayload::TrustCancel(ref payload) => payload.verify(transaction, context),
+        TxPayload::Order(ref payload) => payload.verify(transaction, context),
+        TxPayload::OrderRejection(ref payload) => payload.verify(transaction, context),
+        TxPayload::ValueEstimation(ref payload) => payload.verify(transaction, context),
+        TxPayload::ProductionStart(ref payload) => payload.verify(transaction, context),
+        TxPayload::WorkloadAllocation(ref payload) => payload.verify(transaction, context),
+        TxPayload::ConsumptiveResourceAllocation(ref payload) => {
+            payload.verify(transaction, context)
+        },
+        TxPayload::MopAllocation(ref payload) => payload.verify(transaction, context),
+        TxPayload::MopDeallocation(ref payload) => payload.verify(transaction, context),
+        TxPayload::ProductionOutput(ref payload) => payload.verify(transaction, context),
+        TxPayload::ProductTypeDefinition(ref payload) => payload.verify(transaction, context),
+        TxPayload::FinalRevision(ref payload) => payload.verify(transaction, context),
+        TxPayload::CouponClaim(ref payload) => payload.verify(transaction, context),
+        TxPayload::Appropriation(ref payload) => payload.verify(transaction, context),
+        TxPayload::Collectivization(ref payload) => payload.verify(transaction, context),
+        TxPayload::OwnershipTransfer(ref payload) => payload.verify(transaction, context),
+        TxPayload::WriteOff(ref payload) => payload.verify(transaction, context),
+        TxPayload::Dispatch(ref payload) => payload.verify(transaction, context),
+        TxPayload::InventoryReconciliation(ref payload) => payload.verify(transaction, context),
+        TxPayload::Settlement(ref payload) => payload.verify(transaction, context),
+        TxPayload::ProductionVote(ref payload) => payload.verify(transaction, context),
+        TxPayload::Ballot(ref payload) => payload.verify(transaction, context),
+        TxPayload::ClassificationProposal(ref payload) => payload.verify(transaction, context)
+    }
+
+}
+
+/// Returns the initial state of a new transaction, that
+/// is the relationships other transactions can claim
+///
+/// # Arguments
+/// * `transaction`: The new transaction
+
+pub fn initial_state(transaction: &Transaction) -> TxState{
+
+    match *transaction.get_payload(){
+        TxPayload::Dummy => TxState::new(TxTotalRelState::Unclaimable),
+        TxPayload::CollectiveRegistration(ref payload) => payload.initial_state(),
+        TxPayload::TrustEstablish(ref payload) => payload.initial_state(),
+        TxPayload::TrustCancel(_) => TxState::new(TxTotalRelState::Unclaimable),
+        TxPayload::Order(ref payload) => payload.initial_state(),
+        TxPayload::OrderRejection(_) => TxState::new(TxTotalRelState::Unclaimable),
+        TxPayload::ValueEstimation(ref payload) => payload.initial_state(),
+        TxPayload::ProductionStart(ref payload) => payload.initial_state(),
+        TxPayload::WorkloadAllocation(ref payload) => payload.initial_state(),
+        TxPayload::ConsumptiveResourceAllocation(_) => TxState::new(TxTotalRelState::Unclaimable),
+        TxPayload::MopAllocation(ref payload) => payload.initial_state(),
+        TxPayload::MopDeallocation(_) => TxState::new(TxTotalRelState::Unclaimable),
+        TxPayload::ProductionOutput(ref payload) => payload.initial_state(),
+        TxPayload::ProductTypeDefinition(_) => TxState::new(TxTotalRelState::Unclaimable),
+        TxPayload::FinalRevision(_) => TxState::new(TxTotalRelState::Unclaimable),
+        TxPayload::CouponClaim(_) => TxState::new(TxTotalRelState::Unclaimable),
+        TxPayload::Appropriation(_) => TxState::new(TxTotalRelState::Unclaimable),
+        TxPayload::Collectivization(ref payload) => payload.initial_state(),
+        TxPayload::OwnershipTransfer(ref payload) => payload.initial_state(),
+        TxPayload::WriteOff(_) => TxState::new(TxTotalRelState::Unclaimable),
+        TxPayload::Dispatch(_) => TxState::new(TxTotalRelState::Unclaimable),
+        TxPayload::InventoryReconciliation(_) => TxState::new(TxTotalRelState::Unclaimable),
+        TxPayload::Settlement(_) => TxState::new(TxTotalRelState::Unclaimable),
+        TxPayload::ProductionVote(ref payload) => payload.initial_state(),
+        TxPayload::Ballot(_) => TxState::new(TxTotalRelState::Unclaimable),
+        TxPayload::ClassificationProposal(ref payload) => payload.initial_state()
+    }
+
+}
+
+// ------------------------------------------------------------------------
+
+// The following helpers implement the field formats shared by
+// the payloads of different transaction types:
+
+//  string:           len (2) | UTF-8 encoded string
+//  optional TxId:    0x00 | 0x01 followed by a TxId
+//  bool:             0x00 (false) | 0x01 (true)
+
+/// Returns a string as length prefixed u8 vector
+
+pub fn string_as_bytes(string: &str) -> Vec<u8>{
+    [&u16_to_u8le(string.len() as u16)[..], string.as_bytes()].concat()
+}
+
+/// Reads a length prefixed string from a `BinReader`
+///
+/// # Arguments
+/// * `reader`: The reader
+/// * `field_name`: The name of the field (used for errors)
+
+pub fn read_string(reader: &mut BinReader, field_name: &str) -> Result<String, BinFormatError>{
+
+    let len = reader.read_u16()? as usize;
+
+    match String::from_utf8(reader.read_bytes(len)?.to_vec()){
+        Ok(string) => Ok(string),
+        Err(_) => {
+            let reason = BinFormatErrorReason::InvalidFieldData(String::from(field_name));
+            Err(BinFormatError::new(reason))
+        }
+    }
+
+}
+
+/// Returns an optional transaction id as u8 vector
+
+pub fn optional_tx_id_as_bytes(tx_id: Option<TxId>) -> Vec<u8>{
+
+    match tx_id{
+        Some(tx_id) => [&[0x01][..], &tx_id.as_bytes()[..]].concat(),
+        None => vec![0x00]
+    }
+
+}
+
+/// Reads an optional transaction id from a `BinReader`
+///
+/// # Arguments
+/// * `reader`: The reader
+/// * `field_name`: The name of the field (used for errors)
+
+pub fn read_optional_tx_id(reader: &mut BinReader,
+                           field_name: &str) -> Result<Option<TxId>, BinFormatError>{
+
+    match reader.read_u8()?{
+        0x00 => Ok(None),
+        0x01 => Ok(Some(read_tx_id(reader)?)),
+        _ => {
+            let reason = BinFormatErrorReason::InvalidFieldData(String::from(field_name));
+            Err(BinFormatError::new(reason))
+        }
+    }
+
+}
+
+/// Reads a bool from a `BinReader`
+///
+/// # Arguments
+/// * `reader`: The reader
+/// * `field_name`: The name of the field (used for errors)
+
+pub fn read_bool(reader: &mut BinReader, field_name: &str) -> Result<bool, BinFormatError>{
+
+    match reader.read_u8()?{
+        0x00 => Ok(false),
+        0x01 => Ok(true),
+        _ => {
+            let reason = BinFormatErrorReason::InvalidFieldData(String::from(field_name));
+            Err(BinFormatError::new(reason))
+        }
+    }
+
+}
+
+/// Creates a deterministic ed25519 keypair
+/// (secret key, public key) from a seed byte
+
+#[cfg(test)]
+pub fn keypair(seed: u8) -> ([u8; 64], [u8; 32]){
+    self::crypto::ed25519::keypair(&[seed; 32])
+}
+
+/// Creates a transaction signed by all supplied secret keys
+/// (the public keys are taken from the non-detached keys)
+
+#[cfg(test)]
+pub fn signed_transaction(payload: TxPayload, secret_keys: &[&[u8]]) -> Transaction{
+
+    let signer_pubkeys = secret_keys.iter().map(|secret_key| {
+        let mut pubkey = [0; 32];
+        pubkey.copy_from_slice(&secret_key[32..]);
+        pubkey
+    }).collect();
+
+    let mut transaction = Transaction::new(payload, signer_pubkeys);
+    for secret_key in secret_keys{
+        transaction.sign(secret_key);
+    }
+    transaction
+
+}
+
+/// Creates a signed registration (without parent)
+/// of the collective owning the secret key
+
+#[cfg(test)]
+pub fn register_collective(secret_key: &[u8]) -> Transaction{
+    registration(secret_key, false)
+}
+
+/// Creates the registration (without parent) of a C2I
+/// collective owning the secret key
+
+#[cfg(test)]
+pub fn register_c2i_collective(secret_key: &[u8]) -> Transaction{
+    registration(secret_key, true)
+}
+
+#[cfg(test)]
+fn registration(secret_key: &[u8], c2i: bool) -> Transaction{
+
+    let mut pubkey = [0; 32];
+    pubkey.copy_from_slice(&secret_key[32..]);
+
+    let registration = collective::CollectiveRegistration::new(pubkey,
+                                                               String::from("collective"),
+                                                               vec![],
+                                                               None,
+                                                               c2i);
+    signed_transaction(TxPayload::CollectiveRegistration(registration), &[secret_key])
+
+}
+
+/// Creates a signed definition of a simple product type
+/// (lasting 1000 minutes with a value of 60 minutes) by the
+/// collective owning the secret key
+///
+/// Returns the product type and the definition
+///
+/// # Arguments
+/// * `secret_key`: The secret key of the defining collective
+/// * `name`: The name of the product type
+
+#[cfg(test)]
+pub fn define_product_type(secret_key: &[u8], name: &str) -> ([u8; 32], Transaction){
+
+    let definition = product::ProductTypeDefinition::new(String::from(name), 1000, 60, vec![]);
+    let product_type = definition.get_product_type();
+    (product_type, signed_transaction(TxPayload::ProductTypeDefinition(definition), &[secret_key]))
+
+}
+
+/// `TestChain` appends blocks to a chain in memory,
+/// keeping the indexes up to date
+
+#[cfg(test)]
+pub struct TestChain{
+    pub storage: MemoryChainStorage,
+    pub indexes: ChainIndexes,
+    tail: Option<Block>
+}
+
+#[cfg(test)]
+impl TestChain{
+
+    /// Creates a new empty `TestChain`
+
+    pub fn new() -> TestChain{
+        TestChain::with_params(ConsensusParams::default())
+    }
+
+    /// Creates a new empty `TestChain` verifying
+    /// transactions against the supplied parameters
+
+    pub fn with_params(params: ConsensusParams) -> TestChain{
+        TestChain{
+            storage: MemoryChainStorage::new(),
+            indexes: ChainIndexes::with_params(params),
+            tail: None
+        }
+    }
+
+    /// Appends a block holding the supplied transactions.
+    ///
+    /// Returns the ids of the transactions or the reason
+    /// the first invalid transaction was rejected for. The
+    /// chain is left unchanged in that case.
+    ///
+    /// # Arguments
+    /// * `timestamp`: The timestamp of the block
+    /// * `transactions`: The transactions of the block
+
+    pub fn append(&mut self,
+                  timestamp: u64,
+                  transactions: Vec<Transaction>)
+                  -> Result<Vec<TxId>, VerificationErrorReason>{
+
+        let block = Block::new([0; 32], self.tail.as_ref(), timestamp, transactions);
+        let block_id = block.get_id();
+        let tx_count = block.get_transaction_count();
+
+        match apply_block(&mut self.storage, &mut self.indexes, block.clone()){
+            Ok(()) => {},
+            Err(BlockError{reason: BlockErrorReason::InvalidTransaction(_, err)}) => {
+                return Err(err.reason)
+            },
+            Err(err) => panic!("Block could not be applied: {}", err)
+        }
+
+        self.tail = Some(block);
+        Ok((0..tx_count).map(|index| TxId::new(block_id, TxIndex(index as u16))).collect())
+
+    }
+
+}
+
+/// Starts a self-appointed exclusive production of the
+/// (registered) collective owning the secret key. The order,
+/// estimation and production start are appended in three
+/// blocks with increasing timestamps.
+///
+/// Returns the id of the production start
+
+#[cfg(test)]
+pub fn start_production(chain: &mut TestChain, secret_key: &[u8], timestamp: u64) -> TxId{
+    start_labeled_production(chain, secret_key, timestamp, false)
+}
+
+/// Starts a self-appointed production like `start_production`,
+/// which is labeled as commons production if `common` is set
+///
+/// Returns the id of the production start
+
+#[cfg(test)]
+pub fn start_labeled_production(chain: &mut TestChain,
+                                secret_key: &[u8],
+                                timestamp: u64,
+                                common: bool) -> TxId{
+
+    use blockchain::txtypes::production::Order;
+    use blockchain::txtypes::production::ValueEstimation;
+    use blockchain::txtypes::production::ProductionStart;
+
+    let mut pubkey = [0; 32];
+    pubkey.copy_from_slice(&secret_key[32..]);
+
+    let order = Order::new([0xFF; 32], 1, pubkey, pubkey, timestamp, None, common);
+    let order_id = chain.append(timestamp,
+                                vec![signed_transaction(TxPayload::Order(order),
+                                                        &[secret_key])]).unwrap()[0];
+
+    let estimation = ValueEstimation::new(order_id, 60);
+    let estimation_id = chain.append(timestamp + 1,
+                                     vec![signed_transaction(TxPayload::ValueEstimation(estimation),
+                                                             &[secret_key])]).unwrap()[0];
+
+    let start = ProductionStart::new(estimation_id, common);
+    chain.append(timestamp + 2,
+                 vec![signed_transaction(TxPayload::ProductionStart(start),
+                                         &[secret_key])]).unwrap()[0]
+
+}
@@ -0,0 +1,652 @@
+//
+//    Stachanov
+//    Copyright (C) 2017 Stachanov Developer Collective
+//
+//    This file is part of Stachanov.
+//
+//    This program is free software: you can redistribute it and/or
+//    modify it under the terms of the GNU Affero General Public
+//    License, version 3, as published by the Free Software Foundation.
+//
+//    This program is distributed in the hope that it will be useful,
+//    but WITHOUT ANY WARRANTY; without even the implied warranty of
+//    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//    GNU Affero General Public License for more details.
+//
+//    You should have received a copy of the
+//               GNU Affero General Public License
+//    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+// This module implements the registry of product types. Product
+// types form a directed acyclic graph, in which an edge (t1, t2)
+// means that t1 needs t2 as an input resource and can be
+// separated, so that t2 is available as a single product again.
+// Simple products have no children.
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+use blockchain::traits::BinFormat;
+use blockchain::traits::ChainStorage;
+use blockchain::transactions::Transaction;
+use blockchain::transactions::TxPayload;
+use blockchain::transactions::TxId;
+use blockchain::errors::BinFormatError;
+use blockchain::errors::VerificationError;
+use blockchain::errors::VerificationErrorReason;
+use blockchain::txtypes::TxContext;
+use blockchain::txtypes::string_as_bytes;
+use blockchain::txtypes::read_string;
+use blockchain::journal::Journal;
+use blockchain::journal::Journaled;
+use blockchain::utils::u64_to_u8le;
+use blockchain::utils::sha3_256;
+use blockchain::utils::BinReader;
+
+/// The maximum number of direct children of a product type
+
+pub const MAX_CHILDREN: usize = 0xFF;
+
+/// `ProductTypeDefinition` registers a product type. The id of
+/// the product type is the sha3 hash of the definition, so
+/// product types are content addressed. Its children (the
+/// direct subelements c(t)) must be registered in preceding
+/// blocks, which keeps the product graph acyclic.
+///
+/// Simple products declare their durability d(t) in minutes.
+/// Composite products must declare a durability of 0, since
+/// their durability is the minimum durability of their
+/// children. The value v(t) is the simple value of the product
+/// element in minutes (e.g. the work needed for the assembly).
+///
+/// A definition is signed by the registered collective
+/// defining it.
+
+#[derive(Clone)]
+#[derive(Debug)]
+pub struct ProductTypeDefinition{
+    name: String,
+    durability: u64,
+    value: u64,
+    children: Vec<[u8; 32]>
+}
+
+impl ProductTypeDefinition{
+
+    /// Creates a new `ProductTypeDefinition`
+    ///
+    /// # Arguments
+    /// * `name`: The name of the product type
+    /// * `durability`: The durability in minutes (0 for composite products)
+    /// * `value`: The simple value in minutes
+    /// * `children`: The ids of the direct subelements
+
+    pub fn new(name: String,
+               durability: u64,
+               value: u64,
+               children: Vec<[u8; 32]>) -> ProductTypeDefinition{
+
+        assert!(name.len() <= 0xFFFF, "Product type name exceeds the maximum length");
+        assert!(children.len() <= MAX_CHILDREN, "Product type has too many children");
+
+        ProductTypeDefinition{
+            name: name,
+            durability: durability,
+            value: value,
+            children: children
+        }
+
+    }
+
+    /// Gets the id of the defined product type
+
+    pub fn get_product_type(&self) -> [u8; 32]{
+        sha3_256(&self.as_bytes())
+    }
+
+    /// Gets the name of the product type
+
+    pub fn get_name(&self) -> &str{
+        &self.name
+    }
+
+    /// Gets the declared durability in minutes
+
+    pub fn get_durability(&self) -> u64{
+        self.durability
+    }
+
+    /// Gets the simple value in minutes
+
+    pub fn get_value(&self) -> u64{
+        self.value
+    }
+
+    /// Gets the ids of the direct subelements
+
+    pub fn get_children(&self) -> &Vec<[u8; 32]>{
+        &self.children
+    }
+
+    /// Verifies the definition against the chain
+    ///
+    /// # Arguments
+    /// * `transaction`: The transaction holding the definition
+    /// * `context`: The verification context
+
+    pub fn verify<C>(&self,
+                     transaction: &Transaction,
+                     context: &mut TxContext<C>)
+                     -> Result<(), VerificationError> where C: ChainStorage{
+
+        let signer_pubkeys = transaction.get_signer_pubkeys();
+        if signer_pubkeys.len() != 1{
+            let reason = VerificationErrorReason::InvalidTxSigners;
+            return Err(VerificationError::new(reason))
+        }
+
+        let indexes = context.get_indexes();
+
+        if indexes.collectives.get_registration(signer_pubkeys[0]).is_none(){
+            let reason = VerificationErrorReason::UnknownCollective;
+            return Err(VerificationError::new(reason))
+        }
+
+        if indexes.products.get_definition_id(self.get_product_type()).is_some(){
+            let reason = VerificationErrorReason::DuplicateProductType;
+            return Err(VerificationError::new(reason))
+        }
+
+        // simple products declare their durability, the
+        // durability of composite products is derived
+
+        let distinct_children: HashSet<&[u8; 32]> = self.children.iter().collect();
+
+        if (self.children.is_empty() && self.durability == 0) ||
+           (!self.children.is_empty() && self.durability != 0) ||
+           distinct_children.len() != self.children.len(){
+            let reason = VerificationErrorReason::InvalidProductType;
+            return Err(VerificationError::new(reason))
+        }
+
+        for child in &self.children{
+            if indexes.products.get_definition_id(*child).is_none(){
+                let reason = VerificationErrorReason::UnknownProductType;
+                return Err(VerificationError::new(reason))
+            }
+        }
+
+        if indexes.products.derive(self).is_none(){
+            let reason = VerificationErrorReason::InvalidAmount;
+            return Err(VerificationError::new(reason))
+        }
+
+        Ok(())
+
+    }
+
+}
+
+impl BinFormat<ProductTypeDefinition> for ProductTypeDefinition{
+
+    // The byte format of ProductTypeDefinition is:
+
+    //    field            length
+    //  .------------------------.
+    //  | name            | 2+n  |  (string)
+    //  |------------------------|
+    //  | durability      | 8    |
+    //  |------------------------|
+    //  | value           | 8    |
+    //  |------------------------|
+    //  | child_count     | 1    |
+    //  |------------------------|
+    //  | children        | 32*n |
+    //  '------------------------'
+
+    /// Returns the definition as an u8 vector
+
+    fn as_bytes(&self) -> Vec<u8>{
+
+        let mut bytes = [&string_as_bytes(&self.name)[..],
+                         &u64_to_u8le(self.durability)[..],
+                         &u64_to_u8le(self.value)[..],
+                         &[self.children.len() as u8][..]].concat();
+
+        for child in &self.children{
+            bytes.extend_from_slice(child);
+        }
+        bytes
+
+    }
+
+    /// Creates a new definition from a byte vector.
+    ///
+    /// * `bytes`: A byte vector
+
+    fn from_bytes(bytes: Vec<u8>) -> Result<ProductTypeDefinition, BinFormatError>{
+
+        let mut reader = BinReader::new(&bytes);
+
+        let name = read_string(&mut reader, "name")?;
+        let durability = reader.read_u64()?;
+        let value = reader.read_u64()?;
+
+        let child_count = reader.read_u8()?;
+        let mut children = vec![];
+        for _ in 0..child_count{
+            children.push(reader.read_hash()?);
+        }
+
+        reader.finish()?;
+
+        Ok(ProductTypeDefinition::new(name, durability, value, children))
+
+    }
+
+}
+
+// ------------------------------------------------------------------------
+
+/// `ProductTypeEntry` holds the derived properties of a
+/// registered product type, which are computed once when
+/// its definition is applied
+
+#[derive(Clone)]
+#[derive(Debug)]
+pub struct ProductTypeEntry{
+    pub definition: TxId,
+    pub children: Vec<[u8; 32]>,
+    pub durability: u64,
+    pub value: u64,
+    pub aggregated_value: u64
+}
+
+/// `ProductTypeIndex` maps the ids of all registered product
+/// types to the ids of their definitions and their derived
+/// durability and aggregated value. The children of a product
+/// type are registered before it, so both are computed from
+/// the entries of its subelements. It is updated with every
+/// applied block.
+
+pub struct ProductTypeIndex{
+    entries: HashMap<[u8; 32], ProductTypeEntry>,
+    journal: Journal<ProductTypeIndex>
+}
+
+impl ProductTypeIndex{
+
+    /// Creates a new empty `ProductTypeIndex`
+
+    pub fn new() -> ProductTypeIndex{
+        ProductTypeIndex{
+            entries: HashMap::new(),
+            journal: Journal::new()
+        }
+    }
+
+    /// Fetches the id of the definition of a product type
+    ///
+    /// # Arguments
+    /// * `product_type`: The id of the product type
+
+    pub fn get_definition_id(&self, product_type: [u8; 32]) -> Option<TxId>{
+        self.entries.get(&product_type).map(|entry| entry.definition)
+    }
+
+    /// Fetches the entry of a registered product type
+    ///
+    /// # Arguments
+    /// * `product_type`: The id of the product type
+
+    pub fn get_entry(&self, product_type: [u8; 32]) -> Option<&ProductTypeEntry>{
+        self.entries.get(&product_type)
+    }
+
+    /// Returns the supplied children and all their
+    /// subelements, that is s(t) of a product type
+    /// with these children
+    ///
+    /// # Arguments
+    /// * `children`: The ids of the children
+
+    pub fn get_subelements(&self, children: &[[u8; 32]]) -> Option<HashSet<[u8; 32]>>{
+
+        let mut subelements = HashSet::new();
+        let mut pending = children.to_vec();
+
+        while let Some(child) = pending.pop(){
+            if subelements.insert(child){
+                pending.extend(self.entries.get(&child)?.children.iter().cloned());
+            }
+        }
+        Some(subelements)
+
+    }
+
+    /// Computes the durability d(t) and the aggregated value
+    /// v*(t) of a product type that isn't registered yet.
+    ///
+    /// Returns None if a child is unknown or the aggregated
+    /// value overflows
+    ///
+    /// # Arguments
+    /// * `definition`: The definition of the product type
+
+    pub fn derive(&self, definition: &ProductTypeDefinition) -> Option<(u64, u64)>{
+
+        let mut durability = if definition.children.is_empty(){
+            definition.durability
+        }else{
+            u64::MAX
+        };
+        for child in &definition.children{
+            durability = durability.min(self.entries.get(child)?.durability);
+        }
+
+        let mut aggregated_value = definition.value;
+        for subelement in self.get_subelements(&definition.children)?{
+            aggregated_value = aggregated_value.checked_add(self.entries.get(&subelement)?.value)?;
+        }
+
+        Some((durability, aggregated_value))
+
+    }
+
+    /// Updates the index with a verified transaction
+    ///
+    /// # Arguments
+    /// * `tx_id`: The id of the transaction
+    /// * `transaction`: The verified transaction
+
+    pub fn apply_transaction(&mut self, tx_id: TxId, transaction: &Transaction){
+        if let TxPayload::ProductTypeDefinition(ref payload) = *transaction.get_payload(){
+
+            // verified definitions have registered
+            // children and don't overflow
+
+            if let Some((durability, aggregated_value)) = self.derive(payload){
+                let entry = ProductTypeEntry{
+                    definition: tx_id,
+                    children: payload.children.clone(),
+                    durability: durability,
+                    value: payload.value,
+                    aggregated_value: aggregated_value
+                };
+                let product_type = payload.get_product_type();
+                let previous = self.entries.insert(product_type, entry);
+                self.journal.record_entry(|index| &mut index.entries, product_type, previous);
+            }
+
+        }
+    }
+
+}
+
+impl Journaled for ProductTypeIndex{
+    fn get_journal(&mut self) -> &mut Journal<ProductTypeIndex>{
+        &mut self.journal
+    }
+}
+
+/// `ProductRegistry` answers queries about registered product
+/// types. Definitions are read from the chain, the derived
+/// properties are taken from the product type index. All
+/// queries return None for unknown product types.
+
+pub struct ProductRegistry<'a, C: 'a>{
+    storage: &'a C,
+    index: &'a ProductTypeIndex
+}
+
+impl<'a, C> ProductRegistry<'a, C> where C: ChainStorage{
+
+    /// Creates a new `ProductRegistry`
+    ///
+    /// # Arguments
+    /// * `storage`: The storage holding the chain
+    /// * `index`: The product type index of the chain
+
+    pub fn new(storage: &'a C, index: &'a ProductTypeIndex) -> ProductRegistry<'a, C>{
+        ProductRegistry{storage: storage, index: index}
+    }
+
+    /// Fetches the definition of a product type
+    ///
+    /// # Arguments
+    /// * `product_type`: The id of the product type
+
+    pub fn get_definition(&self, product_type: [u8; 32]) -> Option<ProductTypeDefinition>{
+
+        let tx_id = self.index.get_definition_id(product_type)?;
+        match *self.storage.get_transaction(tx_id)?.get_payload(){
+            TxPayload::ProductTypeDefinition(ref definition) => Some(definition.clone()),
+            _ => None
+        }
+
+    }
+
+    /// Returns the direct subelements c(t) of a product type
+    ///
+    /// # Arguments
+    /// * `product_type`: The id of the product type
+
+    pub fn get_children(&self, product_type: [u8; 32]) -> Option<Vec<[u8; 32]>>{
+        self.index.get_entry(product_type).map(|entry| entry.children.clone())
+    }
+
+    /// Returns all subelements s(t) of a product type
+    ///
+    /// # Arguments
+    /// * `product_type`: The id of the product type
+
+    pub fn get_subelements(&self, product_type: [u8; 32]) -> Option<HashSet<[u8; 32]>>{
+        self.index.get_subelements(&self.index.get_entry(product_type)?.children)
+    }
+
+    /// Returns the durability d(t) of a product type in
+    /// minutes, that is the declared durability of simple
+    /// products and the minimum durability of the children
+    /// of composite products
+    ///
+    /// # Arguments
+    /// * `product_type`: The id of the product type
+
+    pub fn get_durability(&self, product_type: [u8; 32]) -> Option<u64>{
+        self.index.get_entry(product_type).map(|entry| entry.durability)
+    }
+
+    /// Returns the simple value v(t) of a product type in minutes
+    ///
+    /// # Arguments
+    /// * `product_type`: The id of the product type
+
+    pub fn get_value(&self, product_type: [u8; 32]) -> Option<u64>{
+        self.index.get_entry(product_type).map(|entry| entry.value)
+    }
+
+    /// Returns the aggregated value v*(t) of a product type in
+    /// minutes, that is the simple value of the product type
+    /// plus the simple values of all its subelements
+    ///
+    /// # Arguments
+    /// * `product_type`: The id of the product type
+
+    pub fn get_aggregated_value(&self, product_type: [u8; 32]) -> Option<u64>{
+        self.index.get_entry(product_type).map(|entry| entry.aggregated_value)
+    }
+
+}
+
+// ------------------------------------------------------------------------
+
+#[test]
+fn test_product_type_definition_bytes(){
+
+    let definition = ProductTypeDefinition::new(String::from("computer"),
+                                                0,
+                                                15,
+                                                vec![[0x01; 32], [0x02; 32]]);
+    let bytes = definition.as_bytes();
+    assert_eq!(bytes.len(), 2 + 8 + 8 + 8 + 1 + 2 * 32);
+
+    let decoded = ProductTypeDefinition::from_bytes(bytes.clone()).unwrap();
+    assert_eq!(decoded.get_name(), "computer");
+    assert_eq!(decoded.get_durability(), 0);
+    assert_eq!(decoded.get_value(), 15);
+    assert_eq!(decoded.get_children(), &vec![[0x01; 32], [0x02; 32]]);
+    assert_eq!(decoded.get_product_type(), definition.get_product_type());
+
+    let mut missing_child = bytes.clone();
+    missing_child.truncate(bytes.len() - 32);
+    assert!(ProductTypeDefinition::from_bytes(missing_child).is_err());
+
+}
+
+#[test]
+fn test_product_registry(){
+
+    use blockchain::txtypes::TestChain;
+    use blockchain::txtypes::signed_transaction;
+    use blockchain::txtypes::register_collective;
+    use blockchain::txtypes::keypair;
+
+    let (secret_a, _) = keypair(0x0A);
+    let (secret_x, _) = keypair(0x0F);
+
+    let mut chain = TestChain::new();
+    chain.append(1, vec![register_collective(&secret_a)]).unwrap();
+
+    // the computer assembly of the proposal
+
+    const Y: u64 = 365 * 24 * 60;
+
+    let mut timestamp = 1;
+    let mut define = |name: &str, durability, value, children| {
+        let definition = ProductTypeDefinition::new(String::from(name), durability, value, children);
+        let product_type = definition.get_product_type();
+        let transaction = signed_transaction(TxPayload::ProductTypeDefinition(definition),
+                                             &[&secret_a]);
+        timestamp += 1;
+        chain.append(timestamp, vec![transaction]).map(|_| product_type)
+    };
+
+    let c = define("case", 12 * Y, 200, vec![]).unwrap();
+    let m = define("mainboard", 12 * Y, 600, vec![]).unwrap();
+    let p = define("CPU", 9 * Y, 2500, vec![]).unwrap();
+    let r = define("RAM module", 8 * Y, 700, vec![]).unwrap();
+    let h = define("hard drive", 5 * Y, 400, vec![]).unwrap();
+    let x = define("heat paste", 5 * Y, 30, vec![]).unwrap();
+    let y = define("hard disk cable", 10 * Y, 10, vec![]).unwrap();
+    let m_p = define("mainboard with CPU", 0, 10, vec![x, p, m]).unwrap();
+    let m_r = define("mainboard with RAM", 0, 1, vec![m, r]).unwrap();
+    let c_h = define("case with harddrive", 0, 2, vec![c, h]).unwrap();
+    let m_r_p = define("board with CPU / RAM", 0, 0, vec![m_p, m_r]).unwrap();
+    let computer = define("computer", 0, 15, vec![m_r_p, c_h, y]).unwrap();
+
+    // invalid definitions
+
+    match define("case", 12 * Y, 200, vec![]){
+        Err(VerificationErrorReason::DuplicateProductType) => {},
+        result => panic!("Unexpected result {:?}", result)
+    }
+    match define("screen", 0, 100, vec![]){
+        Err(VerificationErrorReason::InvalidProductType) => {},
+        result => panic!("Unexpected result {:?}", result)
+    }
+    match define("screen", 5 * Y, 100, vec![m, r]){
+        Err(VerificationErrorReason::InvalidProductType) => {},
+        result => panic!("Unexpected result {:?}", result)
+    }
+    match define("double RAM", 0, 1, vec![r, r]){
+        Err(VerificationErrorReason::InvalidProductType) => {},
+        result => panic!("Unexpected result {:?}", result)
+    }
+    match define("laptop", 0, 1, vec![[0x01; 32]]){
+        Err(VerificationErrorReason::UnknownProductType) => {},
+        result => panic!("Unexpected result {:?}", result)
+    }
+    let gold = define("gold bar", Y, u64::MAX, vec![]).unwrap();
+    match define("golden case", 0, 1, vec![gold]){
+        Err(VerificationErrorReason::InvalidAmount) => {},
+        result => panic!("Unexpected result {:?}", result)
+    }
+
+    let definition = ProductTypeDefinition::new(String::from("screen"), Y, 100, vec![]);
+    let unregistered = signed_transaction(TxPayload::ProductTypeDefinition(definition),
+                                          &[&secret_x]);
+    match chain.append(100, vec![unregistered]){
+        Err(VerificationErrorReason::UnknownCollective) => {},
+        result => panic!("Unexpected result {:?}", result)
+    }
+
+    // compare c(t), d(t), v(t) and v*(t) with the tables
+
+    let registry = ProductRegistry::new(&chain.storage, &chain.indexes.products);
+
+    let expected = [(c, 12 * Y, 200, 200),
+                    (m, 12 * Y, 600, 600),
+                    (p, 9 * Y, 2500, 2500),
+                    (r, 8 * Y, 700, 700),
+                    (h, 5 * Y, 400, 400),
+                    (x, 5 * Y, 30, 30),
+                    (y, 10 * Y, 10, 10),
+                    (m_p, 5 * Y, 10, 3140),
+                    (m_r, 8 * Y, 1, 1301),
+                    (c_h, 5 * Y, 2, 602),
+                    (m_r_p, 5 * Y, 0, 3841),
+                    (computer, 5 * Y, 15, 4468)];
+
+    for &(product_type, durability, value, aggregated_value) in expected.iter(){
+        assert_eq!(registry.get_durability(product_type), Some(durability));
+        assert_eq!(registry.get_value(product_type), Some(value));
+        assert_eq!(registry.get_aggregated_value(product_type), Some(aggregated_value));
+    }
+
+    assert_eq!(registry.get_children(m_r_p), Some(vec![m_p, m_r]));
+    assert_eq!(registry.get_subelements(c).map(|s| s.len()), Some(0));
+    assert_eq!(registry.get_subelements(m_r_p).map(|s| s.len()), Some(6));
+    assert_eq!(registry.get_subelements(computer).map(|s| s.len()), Some(11));
+
+    assert_eq!(registry.get_durability([0x01; 32]), None);
+    assert_eq!(registry.get_aggregated_value([0x01; 32]), None);
+
+}
+
+#[test]
+fn test_product_registry_shared_children(){
+
+    use blockchain::txtypes::TestChain;
+    use blockchain::txtypes::signed_transaction;
+    use blockchain::txtypes::register_collective;
+    use blockchain::txtypes::keypair;
+
+    let (secret_a, _) = keypair(0x0A);
+
+    let mut chain = TestChain::new();
+    chain.append(1, vec![register_collective(&secret_a)]).unwrap();
+
+    let mut define = |timestamp, durability, children| {
+        let definition = ProductTypeDefinition::new(format!("part {}", timestamp), durability, 1, children);
+        let product_type = definition.get_product_type();
+        let transaction = signed_transaction(TxPayload::ProductTypeDefinition(definition),
+                                             &[&secret_a]);
+        chain.append(timestamp, vec![transaction]).unwrap();
+        product_type
+    };
+
+    // every part t_n consists of the parts t_n-1 and t_n-2,
+    // so naively walking the children takes exponential time
+
+    let mut parts = vec![define(2, 100, vec![]), define(3, 50, vec![])];
+    for n in 2..100{
+        let children = vec![parts[n - 1], parts[n - 2]];
+        parts.push(define(n as u64 + 2, 0, children));
+    }
+
+    let registry = ProductRegistry::new(&chain.storage, &chain.indexes.products);
+    assert_eq!(registry.get_durability(parts[99]), Some(50));
+    assert_eq!(registry.get_aggregated_value(parts[99]), Some(100));
+    assert_eq!(registry.get_subelements(parts[99]).map(|s| s.len()), Some(99));
+
+}
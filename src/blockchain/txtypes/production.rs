@@ -0,0 +1,1381 @@
+//
+//    Stachanov
+//    Copyright (C) 2017 Stachanov Developer Collective
+//
+//    This file is part of Stachanov.
+//
+//    This program is free software: you can redistribute it and/or
+//    modify it under the terms of the GNU Affero General Public
+//    License, version 3, as published by the Free Software Foundation.
+//
+//    This program is distributed in the hope that it will be useful,
+//    but WITHOUT ANY WARRANTY; without even the implied warranty of
+//    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//    GNU Affero General Public License for more details.
+//
+//    You should have received a copy of the
+//               GNU Affero General Public License
+//    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+// This module implements the transaction types of a production
+// cycle. A cycle starts with an order of one collective to
+// another (or to itself), which the producing collective
+// either rejects or answers with a value estimation. The
+// estimated order is produced between a production start and
+// a production output (see the allocation module for the
+// transactions allocating labor and resources in between).
+//
+// Orders, production starts and outputs carry a label, which
+// marks them as part of commons or exclusive production. All
+// transactions of a production cycle carry the same label,
+// so commons and exclusive production are kept separate.
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+use blockchain::traits::BinFormat;
+use blockchain::traits::ChainStorage;
+use blockchain::transactions::Transaction;
+use blockchain::transactions::TxPayload;
+use blockchain::transactions::TxId;
+use blockchain::transactions::TxRelId;
+use blockchain::transactions::TxState;
+use blockchain::transactions::TxTotalRelState;
+use blockchain::transactions::read_tx_id;
+use blockchain::errors::BinFormatError;
+use blockchain::errors::VerificationError;
+use blockchain::errors::VerificationErrorReason;
+use blockchain::txtypes::TxContext;
+use blockchain::txtypes::verify_signers;
+use blockchain::txtypes::string_as_bytes;
+use blockchain::txtypes::read_string;
+use blockchain::txtypes::optional_tx_id_as_bytes;
+use blockchain::txtypes::read_optional_tx_id;
+use blockchain::txtypes::read_bool;
+use blockchain::txtypes::allocation::production_value;
+use blockchain::txtypes::governance::verify_classification;
+use blockchain::journal::Journal;
+use blockchain::journal::Journaled;
+use blockchain::utils::u64_to_u8le;
+use blockchain::utils::BinReader;
+
+/// `Order` is the first transaction of every production cycle.
+/// The ordering collective orders an amount of a product type
+/// from the producing collective until a delivery time. Orders
+/// can only be issued between collectives which established
+/// strong trust, or by a collective to itself (self-appointed
+/// orders).
+///
+/// An order can refer to a parent order, if the ordering
+/// collective needs the product to fulfill an order it
+/// received itself. Sub-orders carry the label of their
+/// parent order. The label must follow the classification
+/// of the product type in force for the producing collective
+/// (see `governance`).
+///
+/// Orders hold the following relationships:
+///
+/// * `OrderEstimation` (1:1): Claimed by the value estimation
+///         of the producing collective
+/// * `OrderRejection` (1:1): Claimed by the rejection of the
+///         producing collective
+/// * `OrderSubOrders` (1:n): Claimed by orders referring to
+///         this order as their parent
+
+#[derive(Clone)]
+#[derive(Debug)]
+pub struct Order{
+    product_type: [u8; 32],
+    amount: u64,
+    orderer: [u8; 32],
+    producer: [u8; 32],
+    delivery_time: u64,
+    parent_order: Option<TxId>,
+    common: bool
+}
+
+impl Order{
+
+    /// Creates a new `Order`
+    ///
+    /// # Arguments
+    /// * `product_type`: The id of the ordered product type
+    /// * `amount`: The ordered amount
+    /// * `orderer`: The public key of the ordering collective
+    /// * `producer`: The public key of the producing collective
+    /// * `delivery_time`: The unix timestamp of the delivery
+    /// * `parent_order`: The order this order serves or None
+    /// * `common`: Whether the order is part of commons production
+
+    pub fn new(product_type: [u8; 32],
+               amount: u64,
+               orderer: [u8; 32],
+               producer: [u8; 32],
+               delivery_time: u64,
+               parent_order: Option<TxId>,
+               common: bool) -> Order{
+
+        Order{
+            product_type: product_type,
+            amount: amount,
+            orderer: orderer,
+            producer: producer,
+            delivery_time: delivery_time,
+            parent_order: parent_order,
+            common: common
+        }
+
+    }
+
+    /// Gets the id of the ordered product type
+
+    pub fn get_product_type(&self) -> [u8; 32]{
+        self.product_type
+    }
+
+    /// Gets the ordered amount
+
+    pub fn get_amount(&self) -> u64{
+        self.amount
+    }
+
+    /// Gets the public key of the ordering collective
+
+    pub fn get_orderer(&self) -> [u8; 32]{
+        self.orderer
+    }
+
+    /// Gets the public key of the producing collective
+
+    pub fn get_producer(&self) -> [u8; 32]{
+        self.producer
+    }
+
+    /// Gets the unix timestamp of the delivery
+
+    pub fn get_delivery_time(&self) -> u64{
+        self.delivery_time
+    }
+
+    /// Gets the id of the parent order
+
+    pub fn get_parent_order(&self) -> Option<TxId>{
+        self.parent_order
+    }
+
+    /// Checks if the order is part of commons production
+
+    pub fn is_common(&self) -> bool{
+        self.common
+    }
+
+    /// Verifies the order against the chain
+    ///
+    /// # Arguments
+    /// * `transaction`: The transaction holding the order
+    /// * `context`: The verification context
+
+    pub fn verify<C>(&self,
+                     transaction: &Transaction,
+                     context: &mut TxContext<C>)
+                     -> Result<(), VerificationError> where C: ChainStorage{
+
+        verify_signers(transaction, &[self.orderer])?;
+
+        if self.amount == 0{
+            let reason = VerificationErrorReason::InvalidAmount;
+            return Err(VerificationError::new(reason))
+        }
+
+        {
+            let indexes = context.get_indexes();
+
+            if indexes.collectives.get_registration(self.orderer).is_none() ||
+               indexes.collectives.get_registration(self.producer).is_none(){
+                let reason = VerificationErrorReason::UnknownCollective;
+                return Err(VerificationError::new(reason))
+            }
+
+            if self.orderer != self.producer &&
+               !indexes.trust.strong_trust(self.orderer, self.producer){
+                let reason = VerificationErrorReason::MissingTrust;
+                return Err(VerificationError::new(reason))
+            }
+        }
+
+        verify_classification(context, self.product_type, self.producer, self.common)?;
+
+        // sub orders can only be issued by the producer
+        // of the parent order as long as it isn't rejected
+
+        if let Some(parent_order_id) = self.parent_order{
+
+            let parent_order = get_order(context, parent_order_id)?;
+
+            if parent_order.producer != self.orderer{
+                let reason = VerificationErrorReason::InvalidTxRef(parent_order_id);
+                return Err(VerificationError::new(reason))
+            }
+
+            if parent_order.common != self.common{
+                let reason = VerificationErrorReason::InvalidLabel;
+                return Err(VerificationError::new(reason))
+            }
+
+            verify_not_rejected(context, parent_order_id)?;
+            context.claim_rel(parent_order_id, TxRelId::OrderSubOrders)?;
+
+        }
+
+        Ok(())
+
+    }
+
+    /// Returns the initial state of the order
+
+    pub fn initial_state(&self) -> TxState{
+
+        let mut tx_state = TxState::new(TxTotalRelState::Claimable);
+        tx_state.add_one_to_one_rel(TxRelId::OrderEstimation)
+                .and_then(|_| tx_state.add_one_to_one_rel(TxRelId::OrderRejection))
+                .and_then(|_| tx_state.add_one_to_many_rel(TxRelId::OrderSubOrders))
+                .expect("Fresh TxState already had a relationship");
+        tx_state
+
+    }
+
+}
+
+impl BinFormat<Order> for Order{
+
+    // The byte format of Order is (see the txtypes
+    // module for the shared field formats):
+
+    //    field            length
+    //  .------------------------.
+    //  | product_type    | 32   |
+    //  |------------------------|
+    //  | amount          | 8    |
+    //  |------------------------|
+    //  | orderer         | 32   |
+    //  |------------------------|
+    //  | producer        | 32   |
+    //  |------------------------|
+    //  | delivery_time   | 8    |
+    //  |------------------------|
+    //  | parent_order    | 1/35 |  (optional TxId)
+    //  |------------------------|
+    //  | common          | 1    |  (bool)
+    //  '------------------------'
+
+    /// Returns the order as an u8 vector
+
+    fn as_bytes(&self) -> Vec<u8>{
+
+        [&self.product_type[..],
+         &u64_to_u8le(self.amount)[..],
+         &self.orderer[..],
+         &self.producer[..],
+         &u64_to_u8le(self.delivery_time)[..],
+         &optional_tx_id_as_bytes(self.parent_order)[..],
+         &[self.common as u8][..]].concat()
+
+    }
+
+    /// Creates a new order from a byte vector.
+    ///
+    /// * `bytes`: A byte vector
+
+    fn from_bytes(bytes: Vec<u8>) -> Result<Order, BinFormatError>{
+
+        let mut reader = BinReader::new(&bytes);
+
+        let product_type = reader.read_hash()?;
+        let amount = reader.read_u64()?;
+        let orderer = reader.read_hash()?;
+        let producer = reader.read_hash()?;
+        let delivery_time = reader.read_u64()?;
+        let parent_order = read_optional_tx_id(&mut reader, "parent_order")?;
+        let common = read_bool(&mut reader, "common")?;
+
+        reader.finish()?;
+
+        Ok(Order::new(product_type, amount, orderer, producer, delivery_time, parent_order, common))
+
+    }
+
+}
+
+/// `OrderRejection` rejects an order. Orders can be rejected
+/// by the producing collective at any time, even after they
+/// were estimated. The rejection carries a reason (such as
+/// over-capacity) and claims the `OrderRejection`
+/// relationship of the order.
+
+#[derive(Clone)]
+#[derive(Debug)]
+pub struct OrderRejection{
+    order: TxId,
+    reason: String
+}
+
+impl OrderRejection{
+
+    /// Creates a new `OrderRejection`
+    ///
+    /// # Arguments
+    /// * `order`: The id of the rejected order
+    /// * `reason`: The reason for the rejection
+
+    pub fn new(order: TxId, reason: String) -> OrderRejection{
+        assert!(reason.len() <= 0xFFFF, "Rejection reason exceeds the maximum length");
+        OrderRejection{order: order, reason: reason}
+    }
+
+    /// Gets the id of the rejected order
+
+    pub fn get_order(&self) -> TxId{
+        self.order
+    }
+
+    /// Gets the reason for the rejection
+
+    pub fn get_reason(&self) -> &str{
+        &self.reason
+    }
+
+    /// Verifies the rejection against the chain
+    ///
+    /// # Arguments
+    /// * `transaction`: The transaction holding the rejection
+    /// * `context`: The verification context
+
+    pub fn verify<C>(&self,
+                     transaction: &Transaction,
+                     context: &mut TxContext<C>)
+                     -> Result<(), VerificationError> where C: ChainStorage{
+
+        let order = get_order(context, self.order)?;
+        verify_signers(transaction, &[order.producer])?;
+        context.claim_rel(self.order, TxRelId::OrderRejection)
+
+    }
+
+}
+
+impl BinFormat<OrderRejection> for OrderRejection{
+
+    // The byte format of OrderRejection is:
+
+    //    field            length
+    //  .------------------------.
+    //  | order           | 34   |
+    //  |------------------------|
+    //  | reason          | 2+n  |  (string)
+    //  '------------------------'
+
+    /// Returns the rejection as an u8 vector
+
+    fn as_bytes(&self) -> Vec<u8>{
+        [self.order.as_bytes(), string_as_bytes(&self.reason)].concat()
+    }
+
+    /// Creates a new rejection from a byte vector.
+    ///
+    /// * `bytes`: A byte vector
+
+    fn from_bytes(bytes: Vec<u8>) -> Result<OrderRejection, BinFormatError>{
+
+        let mut reader = BinReader::new(&bytes);
+        let order = read_tx_id(&mut reader)?;
+        let reason = read_string(&mut reader, "reason")?;
+        reader.finish()?;
+
+        Ok(OrderRejection::new(order, reason))
+
+    }
+
+}
+
+/// `ValueEstimation` accepts an order. The producing collective
+/// estimates the value (in minutes) of the ordered products and
+/// claims the `OrderEstimation` relationship of the order, so
+/// every order is estimated at most once. Rejected orders
+/// can't be estimated.
+///
+/// Estimations hold the following relationships:
+///
+/// * `EstimationProductionStart` (1:1): Claimed by the
+///         production start
+/// * `EstimationVote` (1:1): Claimed by the production vote
+///         of large estimations
+
+#[derive(Clone)]
+#[derive(Debug)]
+pub struct ValueEstimation{
+    order: TxId,
+    value: u64
+}
+
+impl ValueEstimation{
+
+    /// Creates a new `ValueEstimation`
+    ///
+    /// # Arguments
+    /// * `order`: The id of the estimated order
+    /// * `value`: The estimated value in minutes
+
+    pub fn new(order: TxId, value: u64) -> ValueEstimation{
+        ValueEstimation{order: order, value: value}
+    }
+
+    /// Gets the id of the estimated order
+
+    pub fn get_order(&self) -> TxId{
+        self.order
+    }
+
+    /// Gets the estimated value in minutes
+
+    pub fn get_value(&self) -> u64{
+        self.value
+    }
+
+    /// Verifies the estimation against the chain
+    ///
+    /// # Arguments
+    /// * `transaction`: The transaction holding the estimation
+    /// * `context`: The verification context
+
+    pub fn verify<C>(&self,
+                     transaction: &Transaction,
+                     context: &mut TxContext<C>)
+                     -> Result<(), VerificationError> where C: ChainStorage{
+
+        let order = get_order(context, self.order)?;
+        verify_signers(transaction, &[order.producer])?;
+
+        if self.value == 0{
+            let reason = VerificationErrorReason::InvalidAmount;
+            return Err(VerificationError::new(reason))
+        }
+
+        verify_not_rejected(context, self.order)?;
+        context.claim_rel(self.order, TxRelId::OrderEstimation)
+
+    }
+
+    /// Returns the initial state of the estimation
+
+    pub fn initial_state(&self) -> TxState{
+
+        let mut tx_state = TxState::new(TxTotalRelState::Claimable);
+        tx_state.add_one_to_one_rel(TxRelId::EstimationProductionStart)
+                .and_then(|_| tx_state.add_one_to_one_rel(TxRelId::EstimationVote))
+                .expect("Fresh TxState already had a relationship");
+        tx_state
+
+    }
+
+}
+
+impl BinFormat<ValueEstimation> for ValueEstimation{
+
+    // The byte format of ValueEstimation is:
+
+    //    field            length
+    //  .------------------------.
+    //  | order           | 34   |
+    //  |------------------------|
+    //  | value           | 8    |
+    //  '------------------------'
+
+    /// Returns the estimation as an u8 vector
+
+    fn as_bytes(&self) -> Vec<u8>{
+        [&self.order.as_bytes()[..], &u64_to_u8le(self.value)[..]].concat()
+    }
+
+    /// Creates a new estimation from a byte vector.
+    ///
+    /// * `bytes`: A byte vector
+
+    fn from_bytes(bytes: Vec<u8>) -> Result<ValueEstimation, BinFormatError>{
+
+        let mut reader = BinReader::new(&bytes);
+        let order = read_tx_id(&mut reader)?;
+        let value = reader.read_u64()?;
+        reader.finish()?;
+
+        Ok(ValueEstimation::new(order, value))
+
+    }
+
+}
+
+/// `ProductionStart` starts the production of an estimated
+/// order. It claims the `EstimationProductionStart`
+/// relationship of the value estimation, so every estimation
+/// is produced at most once. Until the production is
+/// finalized by its output, the producing collective can
+/// allocate workloads, consumptive resources and means of
+/// production to it. The production start carries the label
+/// of the order. Estimations above the vote threshold of the
+/// consensus parameters can only be produced after a passed
+/// production vote, which is closed by the production start.
+///
+/// Production starts hold the following relationships:
+///
+/// * `ProductionWorkloads` (1:n): Claimed by workload allocations
+/// * `ProductionResources` (1:n): Claimed by consumptive
+///         resource allocations
+/// * `ProductionMopAllocations` (1:n): Claimed by allocations
+///         of means of production
+/// * `ProductionMopDeallocations` (1:n): Claimed by
+///         deallocations of means of production
+
+#[derive(Clone)]
+#[derive(Debug)]
+pub struct ProductionStart{
+    estimation: TxId,
+    common: bool
+}
+
+impl ProductionStart{
+
+    /// Creates a new `ProductionStart`
+    ///
+    /// # Arguments
+    /// * `estimation`: The id of the value estimation
+    /// * `common`: Whether the production is commons production
+
+    pub fn new(estimation: TxId, common: bool) -> ProductionStart{
+        ProductionStart{estimation: estimation, common: common}
+    }
+
+    /// Gets the id of the value estimation
+
+    pub fn get_estimation(&self) -> TxId{
+        self.estimation
+    }
+
+    /// Checks if the production is commons production
+
+    pub fn is_common(&self) -> bool{
+        self.common
+    }
+
+    /// Verifies the production start against the chain
+    ///
+    /// # Arguments
+    /// * `transaction`: The transaction holding the production start
+    /// * `context`: The verification context
+
+    pub fn verify<C>(&self,
+                     transaction: &Transaction,
+                     context: &mut TxContext<C>)
+                     -> Result<(), VerificationError> where C: ChainStorage{
+
+        let estimation = get_estimation(context, self.estimation)?;
+        let order = get_order(context, estimation.order)?;
+        verify_signers(transaction, &[order.producer])?;
+
+        if order.common != self.common{
+            let reason = VerificationErrorReason::InvalidLabel;
+            return Err(VerificationError::new(reason))
+        }
+
+        // large projects must be legitimized by
+        // a production vote of the subsystem
+
+        let params = context.get_params();
+        if estimation.value > params.vote_threshold &&
+           !context.get_indexes().votes.is_passed(self.estimation, params){
+            let reason = VerificationErrorReason::VoteNotPassed;
+            return Err(VerificationError::new(reason))
+        }
+
+        verify_not_rejected(context, estimation.order)?;
+        context.claim_rel(self.estimation, TxRelId::EstimationProductionStart)
+
+    }
+
+    /// Returns the initial state of the production start
+
+    pub fn initial_state(&self) -> TxState{
+
+        let mut tx_state = TxState::new(TxTotalRelState::Claimable);
+        tx_state.add_one_to_many_rel(TxRelId::ProductionWorkloads)
+                .and_then(|_| tx_state.add_one_to_many_rel(TxRelId::ProductionResources))
+                .and_then(|_| tx_state.add_one_to_many_rel(TxRelId::ProductionMopAllocations))
+                .and_then(|_| tx_state.add_one_to_many_rel(TxRelId::ProductionMopDeallocations))
+                .expect("Fresh TxState already had a relationship");
+        tx_state
+
+    }
+
+}
+
+impl BinFormat<ProductionStart> for ProductionStart{
+
+    // The byte format of ProductionStart is:
+
+    //    field            length
+    //  .------------------------.
+    //  | estimation      | 34   |
+    //  |------------------------|
+    //  | common          | 1    |  (bool)
+    //  '------------------------'
+
+    /// Returns the production start as an u8 vector
+
+    fn as_bytes(&self) -> Vec<u8>{
+        [&self.estimation.as_bytes()[..], &[self.common as u8][..]].concat()
+    }
+
+    /// Creates a new production start from a byte vector.
+    ///
+    /// * `bytes`: A byte vector
+
+    fn from_bytes(bytes: Vec<u8>) -> Result<ProductionStart, BinFormatError>{
+
+        let mut reader = BinReader::new(&bytes);
+        let estimation = read_tx_id(&mut reader)?;
+        let common = read_bool(&mut reader, "common")?;
+        reader.finish()?;
+
+        Ok(ProductionStart::new(estimation, common))
+
+    }
+
+}
+
+/// `ProductionOutput` finishes a production. It declares the
+/// produced amount of a product type and its value (in
+/// minutes) and finalizes the production start, so no further
+/// allocations can be made. The output is a resource owned
+/// by the producing collective, which can be consumed by
+/// other productions. The output carries the label of the
+/// production, which must follow the classification of the
+/// output's product type in force for the producing collective.
+///
+/// The declared value must equal the value transferred by
+/// the allocations of the production (see `production_value`)
+/// and all means of production must be deallocated.
+///
+/// Production outputs hold the following relationships:
+///
+/// * `ResourceConsumption` (1:1): Claimed by the consumptive
+///         allocation of the resource
+/// * `ResourceTransfer` (1:1): Claimed by the first ownership
+///         transfer of the resource
+
+#[derive(Clone)]
+#[derive(Debug)]
+pub struct ProductionOutput{
+    production_start: TxId,
+    product_type: [u8; 32],
+    amount: u64,
+    value: u64,
+    common: bool
+}
+
+impl ProductionOutput{
+
+    /// Creates a new `ProductionOutput`
+    ///
+    /// # Arguments
+    /// * `production_start`: The id of the finished production
+    /// * `product_type`: The id of the produced product type
+    /// * `amount`: The produced amount
+    /// * `value`: The value of the produced amount in minutes
+    /// * `common`: Whether the output is a commons product
+
+    pub fn new(production_start: TxId,
+               product_type: [u8; 32],
+               amount: u64,
+               value: u64,
+               common: bool) -> ProductionOutput{
+
+        ProductionOutput{
+            production_start: production_start,
+            product_type: product_type,
+            amount: amount,
+            value: value,
+            common: common
+        }
+
+    }
+
+    /// Gets the id of the finished production
+
+    pub fn get_production_start(&self) -> TxId{
+        self.production_start
+    }
+
+    /// Gets the id of the produced product type
+
+    pub fn get_product_type(&self) -> [u8; 32]{
+        self.product_type
+    }
+
+    /// Gets the produced amount
+
+    pub fn get_amount(&self) -> u64{
+        self.amount
+    }
+
+    /// Gets the value of the produced amount in minutes
+
+    pub fn get_value(&self) -> u64{
+        self.value
+    }
+
+    /// Checks if the output is a commons product
+
+    pub fn is_common(&self) -> bool{
+        self.common
+    }
+
+    /// Verifies the production output against the chain
+    ///
+    /// # Arguments
+    /// * `transaction`: The transaction holding the output
+    /// * `context`: The verification context
+
+    pub fn verify<C>(&self,
+                     transaction: &Transaction,
+                     context: &mut TxContext<C>)
+                     -> Result<(), VerificationError> where C: ChainStorage{
+
+        let collective_pubkey = get_production_collective(context, self.production_start)?;
+        verify_signers(transaction, &[collective_pubkey])?;
+        verify_label(context, self.production_start, self.common)?;
+
+        // the value transfer of the output as mean of
+        // production is only known for registered types
+
+        if context.get_indexes().products.get_definition_id(self.product_type).is_none(){
+            let reason = VerificationErrorReason::UnknownProductType;
+            return Err(VerificationError::new(reason))
+        }
+
+        verify_classification(context, self.product_type, collective_pubkey, self.common)?;
+
+        if self.amount == 0{
+            let reason = VerificationErrorReason::InvalidAmount;
+            return Err(VerificationError::new(reason))
+        }
+
+        // the value transfer of means of production is only
+        // known after they were deallocated
+
+        let indexes = context.get_indexes();
+        if !indexes.production.get_open_allocations(self.production_start).is_empty(){
+            let reason = VerificationErrorReason::MopAllocated;
+            return Err(VerificationError::new(reason))
+        }
+
+        let transferred_value = production_value(context, self.production_start)?;
+        if self.value != transferred_value{
+            let reason = VerificationErrorReason::ValueImbalance(self.value, transferred_value);
+            return Err(VerificationError::new(reason))
+        }
+
+        context.finalize(self.production_start)
+
+    }
+
+    /// Returns the initial state of the production output
+
+    pub fn initial_state(&self) -> TxState{
+
+        let mut tx_state = TxState::new(TxTotalRelState::Claimable);
+        tx_state.add_one_to_one_rel(TxRelId::ResourceConsumption)
+                .and_then(|_| tx_state.add_one_to_one_rel(TxRelId::ResourceTransfer))
+                .expect("Fresh TxState already had a relationship");
+        tx_state
+
+    }
+
+}
+
+impl BinFormat<ProductionOutput> for ProductionOutput{
+
+    // The byte format of ProductionOutput is:
+
+    //    field              length
+    //  .--------------------------.
+    //  | production_start  | 34   |
+    //  |--------------------------|
+    //  | product_type      | 32   |
+    //  |--------------------------|
+    //  | amount            | 8    |
+    //  |--------------------------|
+    //  | value             | 8    |
+    //  |--------------------------|
+    //  | common            | 1    |  (bool)
+    //  '--------------------------'
+
+    /// Returns the production output as an u8 vector
+
+    fn as_bytes(&self) -> Vec<u8>{
+
+        [&self.production_start.as_bytes()[..],
+         &self.product_type[..],
+         &u64_to_u8le(self.amount)[..],
+         &u64_to_u8le(self.value)[..],
+         &[self.common as u8][..]].concat()
+
+    }
+
+    /// Creates a new production output from a byte vector.
+    ///
+    /// * `bytes`: A byte vector
+
+    fn from_bytes(bytes: Vec<u8>) -> Result<ProductionOutput, BinFormatError>{
+
+        let mut reader = BinReader::new(&bytes);
+
+        let production_start = read_tx_id(&mut reader)?;
+        let product_type = reader.read_hash()?;
+        let amount = reader.read_u64()?;
+        let value = reader.read_u64()?;
+        let common = read_bool(&mut reader, "common")?;
+
+        reader.finish()?;
+
+        Ok(ProductionOutput::new(production_start, product_type, amount, value, common))
+
+    }
+
+}
+
+// ------------------------------------------------------------------------
+
+/// `ProductionIndex` keeps track of all productions, their
+/// producing collectives and labels and the means of
+/// production that are currently allocated. It is updated
+/// with every applied block.
+
+pub struct ProductionIndex{
+    collectives: HashMap<TxId, [u8; 32]>,
+    commons: HashSet<TxId>,
+    mop_allocations: HashMap<TxId, (TxId, TxId)>,
+    open_mops: HashMap<TxId, TxId>,
+    open_allocations: HashMap<TxId, HashSet<TxId>>,
+    journal: Journal<ProductionIndex>
+}
+
+impl ProductionIndex{
+
+    /// Creates a new empty `ProductionIndex`
+
+    pub fn new() -> ProductionIndex{
+        ProductionIndex{
+            collectives: HashMap::new(),
+            commons: HashSet::new(),
+            mop_allocations: HashMap::new(),
+            open_mops: HashMap::new(),
+            open_allocations: HashMap::new(),
+            journal: Journal::new()
+        }
+    }
+
+    /// Fetches the public key of the collective running
+    /// a production
+    ///
+    /// # Arguments
+    /// * `production_start`: The id of the production start
+
+    pub fn get_collective(&self, production_start: TxId) -> Option<[u8; 32]>{
+        self.collectives.get(&production_start).cloned()
+    }
+
+    /// Checks if a production is commons production
+    ///
+    /// # Arguments
+    /// * `production_start`: The id of the production start
+
+    pub fn is_common(&self, production_start: TxId) -> bool{
+        self.commons.contains(&production_start)
+    }
+
+    /// Fetches the production start and the allocated resource
+    /// of an allocation of a mean of production
+    ///
+    /// # Arguments
+    /// * `allocation`: The id of the allocation
+
+    pub fn get_mop_allocation(&self, allocation: TxId) -> Option<(TxId, TxId)>{
+        self.mop_allocations.get(&allocation).cloned()
+    }
+
+    /// Fetches the id of the open allocation of a mean of
+    /// production or None, if it isn't allocated
+    ///
+    /// # Arguments
+    /// * `mop`: The id of the resource
+
+    pub fn get_open_allocation(&self, mop: TxId) -> Option<TxId>{
+        self.open_mops.get(&mop).cloned()
+    }
+
+    /// Fetches the ids of all allocations of means of
+    /// production of a production that weren't deallocated
+    ///
+    /// # Arguments
+    /// * `production_start`: The id of the production start
+
+    pub fn get_open_allocations(&self, production_start: TxId) -> HashSet<TxId>{
+        self.open_allocations.get(&production_start).cloned().unwrap_or_default()
+    }
+
+    /// Updates the index with a verified transaction
+    ///
+    /// # Arguments
+    /// * `tx_id`: The id of the transaction
+    /// * `transaction`: The verified transaction
+
+    pub fn apply_transaction(&mut self, tx_id: TxId, transaction: &Transaction){
+
+        match *transaction.get_payload(){
+
+            // the signer of a production start
+            // is the producing collective
+
+            TxPayload::ProductionStart(ref payload) => {
+                let collective_pubkey = transaction.get_signer_pubkeys()[0];
+                let previous = self.collectives.insert(tx_id, collective_pubkey);
+                self.journal.record_entry(|index| &mut index.collectives, tx_id, previous);
+                if payload.is_common(){
+                    let was_member = !self.commons.insert(tx_id);
+                    self.journal.record_member(|index| &mut index.commons, tx_id, was_member);
+                }
+            },
+
+            TxPayload::MopAllocation(ref payload) => {
+                let production_start = payload.get_production_start();
+                let mop = payload.get_mop();
+                let previous = self.mop_allocations.insert(tx_id, (production_start, mop));
+                self.journal.record_entry(|index| &mut index.mop_allocations, tx_id, previous);
+                let previous = self.open_mops.insert(mop, tx_id);
+                self.journal.record_entry(|index| &mut index.open_mops, mop, previous);
+                let previous = self.open_allocations.get(&production_start).cloned();
+                self.journal.record_entry(|index| &mut index.open_allocations, production_start, previous);
+                self.open_allocations.entry(production_start)
+                                     .or_default()
+                                     .insert(tx_id);
+            },
+
+            TxPayload::MopDeallocation(ref payload) => {
+                let allocation = payload.get_allocation();
+                if let Some((production_start, mop)) = self.get_mop_allocation(allocation){
+                    let previous = self.open_mops.remove(&mop);
+                    self.journal.record_entry(|index| &mut index.open_mops, mop, previous);
+                    let previous = self.open_allocations.get(&production_start).cloned();
+                    self.journal.record_entry(|index| &mut index.open_allocations, production_start, previous);
+                    if let Some(allocations) = self.open_allocations.get_mut(&production_start){
+                        allocations.remove(&allocation);
+                    }
+                }
+            },
+
+            _ => {}
+
+        }
+
+    }
+
+}
+
+impl Journaled for ProductionIndex{
+    fn get_journal(&mut self) -> &mut Journal<ProductionIndex>{
+        &mut self.journal
+    }
+}
+
+// ------------------------------------------------------------------------
+
+/// Fetches the order with the supplied id.
+///
+/// Returns a VerificationError with reason InvalidTxRef
+/// if the transaction doesn't exist or isn't an order
+
+pub fn get_order<C>(context: &TxContext<C>, tx_id: TxId)
+                    -> Result<Order, VerificationError> where C: ChainStorage{
+
+    match *context.get_transaction(tx_id)?.get_payload(){
+        TxPayload::Order(ref order) => Ok(order.clone()),
+        _ => {
+            let reason = VerificationErrorReason::InvalidTxRef(tx_id);
+            Err(VerificationError::new(reason))
+        }
+    }
+
+}
+
+/// Fetches the value estimation with the supplied id.
+///
+/// Returns a VerificationError with reason InvalidTxRef
+/// if the transaction doesn't exist or isn't an estimation
+
+pub fn get_estimation<C>(context: &TxContext<C>, tx_id: TxId)
+                         -> Result<ValueEstimation, VerificationError> where C: ChainStorage{
+
+    match *context.get_transaction(tx_id)?.get_payload(){
+        TxPayload::ValueEstimation(ref estimation) => Ok(estimation.clone()),
+        _ => {
+            let reason = VerificationErrorReason::InvalidTxRef(tx_id);
+            Err(VerificationError::new(reason))
+        }
+    }
+
+}
+
+/// Checks that an order wasn't rejected.
+///
+/// Returns a VerificationError with reason OrderRejected
+/// if the rejection relationship of the order is claimed
+
+fn verify_not_rejected<C>(context: &TxContext<C>, order_id: TxId)
+                          -> Result<(), VerificationError> where C: ChainStorage{
+
+    if context.is_rel_claimed(order_id, TxRelId::OrderRejection){
+        let reason = VerificationErrorReason::OrderRejected;
+        return Err(VerificationError::new(reason))
+    }
+    Ok(())
+
+}
+
+/// Fetches the public key of the collective running
+/// a production.
+///
+/// Returns a VerificationError with reason InvalidTxRef
+/// if the transaction isn't a production start
+
+pub fn get_production_collective<C>(context: &TxContext<C>, production_start: TxId)
+                                    -> Result<[u8; 32], VerificationError> where C: ChainStorage{
+
+    match context.get_indexes().production.get_collective(production_start){
+        Some(collective_pubkey) => Ok(collective_pubkey),
+        None => {
+            let reason = VerificationErrorReason::InvalidTxRef(production_start);
+            Err(VerificationError::new(reason))
+        }
+    }
+
+}
+
+/// Checks that the label of a transaction matches the
+/// label of the production it refers to.
+///
+/// Returns a VerificationError with reason InvalidLabel
+/// otherwise
+///
+/// # Arguments
+/// * `context`: The verification context
+/// * `production_start`: The id of the production start
+/// * `common`: The label of the transaction
+
+pub fn verify_label<C>(context: &TxContext<C>,
+                       production_start: TxId,
+                       common: bool) -> Result<(), VerificationError> where C: ChainStorage{
+
+    if context.get_indexes().production.is_common(production_start) != common{
+        let reason = VerificationErrorReason::InvalidLabel;
+        return Err(VerificationError::new(reason))
+    }
+    Ok(())
+
+}
+
+// ------------------------------------------------------------------------
+
+#[test]
+fn test_production_bytes(){
+
+    use blockchain::block::BlockId;
+    use blockchain::transactions::TxIndex;
+    use blockchain::errors::BinFormatErrorReason;
+
+    let order_id = TxId::new(BlockId([0x01; 32]), TxIndex(3));
+
+    let order = Order::new([0x02; 32], 10, [0x03; 32], [0x04; 32], 1500000000, Some(order_id), true);
+    let bytes = order.as_bytes();
+    assert_eq!(bytes.len(), 32 + 8 + 32 + 32 + 8 + 35 + 1);
+
+    let decoded = Order::from_bytes(bytes.clone()).unwrap();
+    assert_eq!(decoded.get_product_type(), [0x02; 32]);
+    assert_eq!(decoded.get_amount(), 10);
+    assert_eq!(decoded.get_orderer(), [0x03; 32]);
+    assert_eq!(decoded.get_producer(), [0x04; 32]);
+    assert_eq!(decoded.get_delivery_time(), 1500000000);
+    assert_eq!(decoded.get_parent_order(), Some(order_id));
+    assert!(decoded.is_common());
+
+    let mut truncated = bytes.clone();
+    truncated.pop();
+    assert!(Order::from_bytes(truncated).is_err());
+
+    let mut invalid_flag = bytes.clone();
+    invalid_flag[112] = 0x02;
+    match Order::from_bytes(invalid_flag){
+        Err(err) => match err.reason{
+            BinFormatErrorReason::InvalidFieldData(ref field) if field == "parent_order" => {},
+            reason => panic!("Unexpected reason {:?}", reason)
+        },
+        Ok(_) => panic!("Invalid parent flag was accepted")
+    }
+
+    let mut invalid_label = bytes.clone();
+    invalid_label[147] = 0x02;
+    match Order::from_bytes(invalid_label){
+        Err(err) => match err.reason{
+            BinFormatErrorReason::InvalidFieldData(ref field) if field == "common" => {},
+            reason => panic!("Unexpected reason {:?}", reason)
+        },
+        Ok(_) => panic!("Invalid label was accepted")
+    }
+
+    let rejection = OrderRejection::new(order_id, String::from("over capacity"));
+    let decoded = OrderRejection::from_bytes(rejection.as_bytes()).unwrap();
+    assert_eq!(decoded.get_order(), order_id);
+    assert_eq!(decoded.get_reason(), "over capacity");
+
+    let estimation = ValueEstimation::new(order_id, 530);
+    let bytes = estimation.as_bytes();
+    assert_eq!(bytes.len(), 34 + 8);
+    let decoded = ValueEstimation::from_bytes(bytes).unwrap();
+    assert_eq!(decoded.get_order(), order_id);
+    assert_eq!(decoded.get_value(), 530);
+
+    let start = ProductionStart::new(order_id, true);
+    let bytes = start.as_bytes();
+    assert_eq!(bytes.len(), 34 + 1);
+    let decoded = ProductionStart::from_bytes(bytes).unwrap();
+    assert_eq!(decoded.get_estimation(), order_id);
+    assert!(decoded.is_common());
+
+    let output = ProductionOutput::new(order_id, [0x05; 32], 3, 240, false);
+    let bytes = output.as_bytes();
+    assert_eq!(bytes.len(), 34 + 32 + 8 + 8 + 1);
+    let decoded = ProductionOutput::from_bytes(bytes).unwrap();
+    assert_eq!(decoded.get_production_start(), order_id);
+    assert_eq!(decoded.get_product_type(), [0x05; 32]);
+    assert_eq!(decoded.get_amount(), 3);
+    assert_eq!(decoded.get_value(), 240);
+    assert!(!decoded.is_common());
+
+}
+
+#[test]
+fn test_order_flow(){
+
+    use blockchain::block::Block;
+    use blockchain::block::BlockError;
+    use blockchain::block::BlockErrorReason;
+    use blockchain::transactions::TxIndex;
+    use blockchain::transactions::TxRel;
+    use blockchain::transactions::BadClaimReason;
+    use blockchain::traits::ChainStorage;
+    use blockchain::storages::memory::MemoryChainStorage;
+    use blockchain::processor::ChainIndexes;
+    use blockchain::processor::apply_block;
+    use blockchain::txtypes::trust::TrustEstablish;
+    use blockchain::txtypes::signed_transaction;
+    use blockchain::txtypes::register_collective;
+    use blockchain::txtypes::keypair;
+
+    let (secret_a, pubkey_a) = keypair(0x0A);
+    let (secret_b, pubkey_b) = keypair(0x0B);
+    let (secret_c, pubkey_c) = keypair(0x0C);
+
+    let order = |secret_key: &[u8; 64], orderer, producer, parent| {
+        let payload = Order::new([0x01; 32], 5, orderer, producer, 1500000000, parent, false);
+        signed_transaction(TxPayload::Order(payload), &[secret_key])
+    };
+
+    let estimate = |secret_key: &[u8; 64], order_id, value| {
+        let payload = ValueEstimation::new(order_id, value);
+        signed_transaction(TxPayload::ValueEstimation(payload), &[secret_key])
+    };
+
+    let reject = |secret_key: &[u8; 64], order_id| {
+        let payload = OrderRejection::new(order_id, String::from("over capacity"));
+        signed_transaction(TxPayload::OrderRejection(payload), &[secret_key])
+    };
+
+    let verification_error = |result: Result<(), BlockError>| {
+        match result{
+            Err(BlockError{reason: BlockErrorReason::InvalidTransaction(_, err)}) => err.reason,
+            _ => panic!("Invalid production transaction was accepted")
+        }
+    };
+
+    let mut storage = MemoryChainStorage::new();
+    let mut indexes = ChainIndexes::new();
+
+    let trust = TrustEstablish::new(pubkey_a, pubkey_b);
+    let genesis = Block::new([0; 32], None, 1,
+                             vec![register_collective(&secret_a),
+                                  register_collective(&secret_b),
+                                  register_collective(&secret_c),
+                                  signed_transaction(TxPayload::TrustEstablish(trust),
+                                                     &[&secret_a, &secret_b])]);
+    apply_block(&mut storage, &mut indexes, genesis.clone()).unwrap();
+
+    // orders need strong trust unless they are self-appointed
+
+    let block = Block::new([0; 32], Some(&genesis), 2,
+                           vec![order(&secret_a, pubkey_a, pubkey_c, None)]);
+    match verification_error(apply_block(&mut storage, &mut indexes, block)){
+        VerificationErrorReason::MissingTrust => {},
+        reason => panic!("Unexpected reason {:?}", reason)
+    }
+
+    let block_2 = Block::new([0; 32], Some(&genesis), 2,
+                             vec![order(&secret_a, pubkey_a, pubkey_b, None),
+                                  order(&secret_c, pubkey_c, pubkey_c, None),
+                                  order(&secret_b, pubkey_b, pubkey_b, None)]);
+    let order_ab = TxId::new(block_2.get_id(), TxIndex(0));
+    let order_cc = TxId::new(block_2.get_id(), TxIndex(1));
+    let order_bb = TxId::new(block_2.get_id(), TxIndex(2));
+    apply_block(&mut storage, &mut indexes, block_2.clone()).unwrap();
+
+    // only the producer estimates and rejects
+
+    let block = Block::new([0; 32], Some(&block_2), 3,
+                           vec![estimate(&secret_a, order_ab, 530)]);
+    match verification_error(apply_block(&mut storage, &mut indexes, block)){
+        VerificationErrorReason::InvalidTxSigners => {},
+        reason => panic!("Unexpected reason {:?}", reason)
+    }
+
+    // sub orders are issued by the producer of the parent order
+
+    let block = Block::new([0; 32], Some(&block_2), 3,
+                           vec![order(&secret_c, pubkey_c, pubkey_c, Some(order_ab))]);
+    match verification_error(apply_block(&mut storage, &mut indexes, block)){
+        VerificationErrorReason::InvalidTxRef(tx_id) => assert_eq!(tx_id, order_ab),
+        reason => panic!("Unexpected reason {:?}", reason)
+    }
+
+    let block_3 = Block::new([0; 32], Some(&block_2), 3,
+                             vec![estimate(&secret_b, order_ab, 530),
+                                  order(&secret_b, pubkey_b, pubkey_b, Some(order_ab)),
+                                  order(&secret_b, pubkey_b, pubkey_b, Some(order_ab)),
+                                  reject(&secret_c, order_cc)]);
+    apply_block(&mut storage, &mut indexes, block_3.clone()).unwrap();
+
+    let tx_state = storage.get_transaction_state(order_ab).unwrap();
+    match tx_state.get_rel(TxRelId::OrderSubOrders){
+        Ok(TxRel::OneToMany(claims)) => assert_eq!(claims.len(), 2),
+        _ => panic!("Sub orders weren't claimed")
+    }
+
+    // orders are estimated at most once and rejected
+    // orders can't be estimated at all
+
+    let block = Block::new([0; 32], Some(&block_3), 4,
+                           vec![estimate(&secret_b, order_ab, 600)]);
+    match verification_error(apply_block(&mut storage, &mut indexes, block)){
+        VerificationErrorReason::BadClaim(BadClaimReason::RelClaimed(TxRelId::OrderEstimation, _)) => {},
+        reason => panic!("Unexpected reason {:?}", reason)
+    }
+
+    let block = Block::new([0; 32], Some(&block_3), 4,
+                           vec![estimate(&secret_c, order_cc, 530)]);
+    match verification_error(apply_block(&mut storage, &mut indexes, block)){
+        VerificationErrorReason::OrderRejected => {},
+        reason => panic!("Unexpected reason {:?}", reason)
+    }
+
+    let block = Block::new([0; 32], Some(&block_3), 4,
+                           vec![estimate(&secret_b, order_bb, 0)]);
+    match verification_error(apply_block(&mut storage, &mut indexes, block)){
+        VerificationErrorReason::InvalidAmount => {},
+        reason => panic!("Unexpected reason {:?}", reason)
+    }
+
+}
+
+#[test]
+fn test_value_conservation(){
+
+    use blockchain::txtypes::allocation::WorkloadAllocation;
+    use blockchain::txtypes::allocation::ConsumptiveResourceAllocation;
+    use blockchain::txtypes::allocation::MopAllocation;
+    use blockchain::txtypes::allocation::MopDeallocation;
+    use blockchain::txtypes::TestChain;
+    use blockchain::txtypes::signed_transaction;
+    use blockchain::txtypes::register_collective;
+    use blockchain::txtypes::define_product_type;
+    use blockchain::txtypes::start_production;
+    use blockchain::txtypes::keypair;
+
+    let (secret_a, _) = keypair(0x0A);
+
+    let mut chain = TestChain::new();
+    chain.append(1, vec![register_collective(&secret_a)]).unwrap();
+    let (product_type, definition) = define_product_type(&secret_a, "tool");
+    chain.append(2, vec![definition]).unwrap();
+
+    let sign = |payload| signed_transaction(payload, &[&secret_a]);
+    let workload = |production_start, minutes| {
+        sign(TxPayload::WorkloadAllocation(WorkloadAllocation::new(production_start,
+                                                                   [0x01; 32],
+                                                                   minutes,
+                                                                   false)))
+    };
+    let output = |production_start, value| {
+        sign(TxPayload::ProductionOutput(ProductionOutput::new(production_start,
+                                                               product_type,
+                                                               1,
+                                                               value,
+                                                               false)))
+    };
+
+    // two resources worth 100 and 50 minutes
+
+    let production_1 = start_production(&mut chain, &secret_a, 10);
+    let production_2 = start_production(&mut chain, &secret_a, 20);
+    chain.append(30, vec![workload(production_1, 100), workload(production_2, 50)]).unwrap();
+    let ids = chain.append(31, vec![output(production_1, 100), output(production_2, 50)]).unwrap();
+    let (resource_1, mop) = (ids[0], ids[1]);
+
+    // a production with workloads, a consumed
+    // resource and a mean of production
+
+    let production_3 = start_production(&mut chain, &secret_a, 40);
+    let payload = ConsumptiveResourceAllocation::new(production_3, resource_1);
+    let ids = chain.append(50, vec![workload(production_3, 30),
+                                    workload(production_3, 45),
+                                    sign(TxPayload::ConsumptiveResourceAllocation(payload)),
+                                    sign(TxPayload::MopAllocation(MopAllocation::new(production_3,
+                                                                                     mop,
+                                                                                     false,
+                                                                                     false)))])
+                   .unwrap();
+    let allocation = ids[3];
+
+    match chain.append(51, vec![output(production_3, 175)]){
+        Err(VerificationErrorReason::MopAllocated) => {},
+        result => panic!("Unexpected result {:?}", result)
+    }
+
+    chain.append(51, vec![sign(TxPayload::MopDeallocation(MopDeallocation::new(allocation)))])
+         .unwrap();
+
+    match chain.append(52, vec![output(production_3, 170)]){
+        Err(VerificationErrorReason::ValueImbalance(declared, transferred)) => {
+            assert_eq!(declared, 170);
+            assert_eq!(transferred, 175);
+        },
+        result => panic!("Unexpected result {:?}", result)
+    }
+
+    chain.append(52, vec![output(production_3, 175)]).unwrap();
+
+}
@@ -0,0 +1,690 @@
+//
+//    Stachanov
+//    Copyright (C) 2017 Stachanov Developer Collective
+//
+//    This file is part of Stachanov.
+//
+//    This program is free software: you can redistribute it and/or
+//    modify it under the terms of the GNU Affero General Public
+//    License, version 3, as published by the Free Software Foundation.
+//
+//    This program is distributed in the hope that it will be useful,
+//    but WITHOUT ANY WARRANTY; without even the implied warranty of
+//    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//    GNU Affero General Public License for more details.
+//
+//    You should have received a copy of the
+//               GNU Affero General Public License
+//    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+// This module implements the lifecycle of resources. Resources
+// are created by production outputs or enter the chain by being
+// collectivized. They change hands by ownership transfers and
+// leave the chain by being consumed or written off.
+//
+// Resources are always referenced by the id of the transaction
+// that created them. Transfers of a resource form a chain of
+// 1:1 `ResourceTransfer` relationships starting at that
+// transaction, so every state of ownership is transferred once.
+
+use std::collections::HashMap;
+use blockchain::traits::BinFormat;
+use blockchain::traits::ChainStorage;
+use blockchain::transactions::Transaction;
+use blockchain::transactions::TxPayload;
+use blockchain::transactions::TxId;
+use blockchain::transactions::TxRelId;
+use blockchain::transactions::TxState;
+use blockchain::transactions::TxTotalRelState;
+use blockchain::transactions::read_tx_id;
+use blockchain::errors::BinFormatError;
+use blockchain::errors::VerificationError;
+use blockchain::errors::VerificationErrorReason;
+use blockchain::txtypes::TxContext;
+use blockchain::txtypes::verify_signers;
+use blockchain::txtypes::string_as_bytes;
+use blockchain::txtypes::read_string;
+use blockchain::txtypes::allocation::get_resource;
+use blockchain::txtypes::revision::period_start;
+use blockchain::txtypes::revision::exceeds_write_off_limit;
+use blockchain::journal::Journal;
+use blockchain::journal::Journaled;
+use blockchain::utils::u64_to_u8le;
+use blockchain::utils::BinReader;
+
+/// `Collectivization` brings a resource that wasn't produced
+/// within the system into the possession of a collective.
+/// No labor was registered for collectivized resources, so
+/// they carry no value. The collectivization is signed by
+/// the collective.
+///
+/// Collectivizations hold the following relationships:
+///
+/// * `ResourceConsumption` (1:1): Claimed by the consumptive
+///         allocation of the resource
+/// * `ResourceTransfer` (1:1): Claimed by the first ownership
+///         transfer of the resource
+
+#[derive(Clone)]
+#[derive(Debug)]
+pub struct Collectivization{
+    product_type: [u8; 32],
+    amount: u64
+}
+
+impl Collectivization{
+
+    /// Creates a new `Collectivization`
+    ///
+    /// # Arguments
+    /// * `product_type`: The id of the product type
+    /// * `amount`: The collectivized amount
+
+    pub fn new(product_type: [u8; 32], amount: u64) -> Collectivization{
+        Collectivization{product_type: product_type, amount: amount}
+    }
+
+    /// Gets the id of the product type
+
+    pub fn get_product_type(&self) -> [u8; 32]{
+        self.product_type
+    }
+
+    /// Gets the collectivized amount
+
+    pub fn get_amount(&self) -> u64{
+        self.amount
+    }
+
+    /// Verifies the collectivization against the chain
+    ///
+    /// # Arguments
+    /// * `transaction`: The transaction holding the collectivization
+    /// * `context`: The verification context
+
+    pub fn verify<C>(&self,
+                     transaction: &Transaction,
+                     context: &mut TxContext<C>)
+                     -> Result<(), VerificationError> where C: ChainStorage{
+
+        let signer_pubkeys = transaction.get_signer_pubkeys();
+        if signer_pubkeys.len() != 1{
+            let reason = VerificationErrorReason::InvalidTxSigners;
+            return Err(VerificationError::new(reason))
+        }
+
+        if context.get_indexes().collectives.get_registration(signer_pubkeys[0]).is_none(){
+            let reason = VerificationErrorReason::UnknownCollective;
+            return Err(VerificationError::new(reason))
+        }
+
+        if self.amount == 0{
+            let reason = VerificationErrorReason::InvalidAmount;
+            return Err(VerificationError::new(reason))
+        }
+
+        Ok(())
+
+    }
+
+    /// Returns the initial state of the collectivization
+
+    pub fn initial_state(&self) -> TxState{
+
+        let mut tx_state = TxState::new(TxTotalRelState::Claimable);
+        tx_state.add_one_to_one_rel(TxRelId::ResourceConsumption)
+                .and_then(|_| tx_state.add_one_to_one_rel(TxRelId::ResourceTransfer))
+                .expect("Fresh TxState already had a relationship");
+        tx_state
+
+    }
+
+}
+
+impl BinFormat<Collectivization> for Collectivization{
+
+    // The byte format of Collectivization is:
+
+    //    field            length
+    //  .------------------------.
+    //  | product_type    | 32   |
+    //  |------------------------|
+    //  | amount          | 8    |
+    //  '------------------------'
+
+    /// Returns the collectivization as an u8 vector
+
+    fn as_bytes(&self) -> Vec<u8>{
+        [&self.product_type[..], &u64_to_u8le(self.amount)[..]].concat()
+    }
+
+    /// Creates a new collectivization from a byte vector.
+    ///
+    /// * `bytes`: A byte vector
+
+    fn from_bytes(bytes: Vec<u8>) -> Result<Collectivization, BinFormatError>{
+
+        let mut reader = BinReader::new(&bytes);
+
+        let product_type = reader.read_hash()?;
+        let amount = reader.read_u64()?;
+
+        reader.finish()?;
+
+        Ok(Collectivization::new(product_type, amount))
+
+    }
+
+}
+
+/// `OwnershipTransfer` passes a resource from its owner to
+/// another collective. Both collectives sign the transfer
+/// (owner first) and must have established strong trust.
+/// The transfer claims the `ResourceTransfer` relationship
+/// of the latest transfer of the resource (or of the resource
+/// itself, if it wasn't transferred before). Consumed, written
+/// off, allocated and dispatched resources can't be transferred.
+///
+/// Ownership transfers hold the following relationships:
+///
+/// * `ResourceTransfer` (1:1): Claimed by the next ownership
+///         transfer of the resource
+
+#[derive(Clone)]
+#[derive(Debug)]
+pub struct OwnershipTransfer{
+    previous: TxId,
+    receiver: [u8; 32]
+}
+
+impl OwnershipTransfer{
+
+    /// Creates a new `OwnershipTransfer`
+    ///
+    /// # Arguments
+    /// * `previous`: The id of the latest transfer of the
+    ///               resource (or of the resource itself)
+    /// * `receiver`: The public key of the receiving collective
+
+    pub fn new(previous: TxId, receiver: [u8; 32]) -> OwnershipTransfer{
+        OwnershipTransfer{previous: previous, receiver: receiver}
+    }
+
+    /// Gets the id of the latest transfer of the
+    /// resource (or of the resource itself)
+
+    pub fn get_previous(&self) -> TxId{
+        self.previous
+    }
+
+    /// Gets the public key of the receiving collective
+
+    pub fn get_receiver(&self) -> [u8; 32]{
+        self.receiver
+    }
+
+    /// Verifies the ownership transfer against the chain
+    ///
+    /// # Arguments
+    /// * `transaction`: The transaction holding the transfer
+    /// * `context`: The verification context
+
+    pub fn verify<C>(&self,
+                     transaction: &Transaction,
+                     context: &mut TxContext<C>)
+                     -> Result<(), VerificationError> where C: ChainStorage{
+
+        let resource_id = context.get_indexes().ownership.get_resource_id(self.previous);
+        let resource = get_resource(context, resource_id)?;
+        verify_signers(transaction, &[resource.owner, self.receiver])?;
+
+        if !context.get_indexes().trust.strong_trust(resource.owner, self.receiver){
+            let reason = VerificationErrorReason::MissingTrust;
+            return Err(VerificationError::new(reason))
+        }
+
+        verify_available(context, resource_id)?;
+        context.claim_rel(self.previous, TxRelId::ResourceTransfer)
+
+    }
+
+    /// Returns the initial state of the ownership transfer
+
+    pub fn initial_state(&self) -> TxState{
+
+        let mut tx_state = TxState::new(TxTotalRelState::Claimable);
+        tx_state.add_one_to_one_rel(TxRelId::ResourceTransfer)
+                .expect("Fresh TxState already had a relationship");
+        tx_state
+
+    }
+
+}
+
+impl BinFormat<OwnershipTransfer> for OwnershipTransfer{
+
+    // The byte format of OwnershipTransfer is:
+
+    //    field            length
+    //  .------------------------.
+    //  | previous        | 34   |
+    //  |------------------------|
+    //  | receiver        | 32   |
+    //  '------------------------'
+
+    /// Returns the ownership transfer as an u8 vector
+
+    fn as_bytes(&self) -> Vec<u8>{
+        [&self.previous.as_bytes()[..], &self.receiver[..]].concat()
+    }
+
+    /// Creates a new ownership transfer from a byte vector.
+    ///
+    /// * `bytes`: A byte vector
+
+    fn from_bytes(bytes: Vec<u8>) -> Result<OwnershipTransfer, BinFormatError>{
+
+        let mut reader = BinReader::new(&bytes);
+
+        let previous = read_tx_id(&mut reader)?;
+        let receiver = reader.read_hash()?;
+
+        reader.finish()?;
+
+        Ok(OwnershipTransfer::new(previous, receiver))
+
+    }
+
+}
+
+/// `WriteOff` removes a resource that got lost or broke from
+/// the chain. It is signed by the owner of the resource and
+/// states the reason of the write-off. The write-off finalizes
+/// the resource, so it can't be consumed, allocated or
+/// transferred anymore. The value of written off resources
+/// is accounted for in the final revision of the period, so
+/// exclusive write-offs are rejected once they would exceed
+/// the write-off limit of the period.
+
+#[derive(Clone)]
+#[derive(Debug)]
+pub struct WriteOff{
+    resource: TxId,
+    reason: String
+}
+
+impl WriteOff{
+
+    /// Creates a new `WriteOff`
+    ///
+    /// # Arguments
+    /// * `resource`: The id of the written off resource
+    /// * `reason`: The reason of the write-off
+
+    pub fn new(resource: TxId, reason: String) -> WriteOff{
+        assert!(reason.len() <= 0xFFFF, "Write-off reason exceeds the maximum length");
+        WriteOff{resource: resource, reason: reason}
+    }
+
+    /// Gets the id of the written off resource
+
+    pub fn get_resource(&self) -> TxId{
+        self.resource
+    }
+
+    /// Gets the reason of the write-off
+
+    pub fn get_reason(&self) -> &str{
+        &self.reason
+    }
+
+    /// Verifies the write-off against the chain
+    ///
+    /// # Arguments
+    /// * `transaction`: The transaction holding the write-off
+    /// * `context`: The verification context
+
+    pub fn verify<C>(&self,
+                     transaction: &Transaction,
+                     context: &mut TxContext<C>)
+                     -> Result<(), VerificationError> where C: ChainStorage{
+
+        let resource = get_resource(context, self.resource)?;
+        verify_signers(transaction, &[resource.owner])?;
+
+        verify_available(context, self.resource)?;
+
+        // exclusive write-offs are limited by the
+        // workloads of the period so far
+
+        let revisions = &context.get_indexes().revisions;
+        let sums = revisions.get_sums(period_start(context.get_header().get_timestamp()));
+        let write_offs = sums.write_offs.saturating_add(revisions.get_exclusive_value(self.resource));
+        if write_offs > sums.write_offs &&
+           exceeds_write_off_limit(sums.workloads, write_offs, context.get_params()){
+            let reason = VerificationErrorReason::WriteOffLimitExceeded;
+            return Err(VerificationError::new(reason))
+        }
+
+        context.finalize(self.resource)
+
+    }
+
+}
+
+impl BinFormat<WriteOff> for WriteOff{
+
+    // The byte format of WriteOff is:
+
+    //    field            length
+    //  .------------------------.
+    //  | resource        | 34   |
+    //  |------------------------|
+    //  | reason          | 2+n  |  (string)
+    //  '------------------------'
+
+    /// Returns the write-off as an u8 vector
+
+    fn as_bytes(&self) -> Vec<u8>{
+        [self.resource.as_bytes(), string_as_bytes(&self.reason)].concat()
+    }
+
+    /// Creates a new write-off from a byte vector.
+    ///
+    /// * `bytes`: A byte vector
+
+    fn from_bytes(bytes: Vec<u8>) -> Result<WriteOff, BinFormatError>{
+
+        let mut reader = BinReader::new(&bytes);
+        let resource = read_tx_id(&mut reader)?;
+        let reason = read_string(&mut reader, "reason")?;
+        reader.finish()?;
+
+        Ok(WriteOff::new(resource, reason))
+
+    }
+
+}
+
+// ------------------------------------------------------------------------
+
+/// Checks that a resource is neither consumed, written off,
+/// allocated as a mean of production nor being dispatched.
+///
+/// Returns a VerificationError with reason ResourceInUse
+/// otherwise
+///
+/// # Arguments
+/// * `context`: The verification context
+/// * `resource`: The id of the resource
+
+pub fn verify_available<C>(context: &TxContext<C>, resource: TxId)
+                           -> Result<(), VerificationError> where C: ChainStorage{
+
+    let written_off = context.get_tx_state(resource).is_some_and(|tx_state| {
+        matches!(*tx_state.get_total_rel_state(), TxTotalRelState::Finalized(_))
+    });
+
+    if written_off ||
+       context.is_rel_claimed(resource, TxRelId::ResourceConsumption) ||
+       context.get_indexes().production.get_open_allocation(resource).is_some() ||
+       context.get_indexes().inventory.is_dispatched(resource){
+        let reason = VerificationErrorReason::ResourceInUse;
+        return Err(VerificationError::new(reason))
+    }
+
+    Ok(())
+
+}
+
+// ------------------------------------------------------------------------
+
+/// `OwnershipIndex` keeps track of the current owner of every
+/// resource by following its ownership transfers. It is
+/// updated with every applied block.
+
+pub struct OwnershipIndex{
+    owners: HashMap<TxId, [u8; 32]>,
+    transfers: HashMap<TxId, TxId>,
+    journal: Journal<OwnershipIndex>
+}
+
+impl OwnershipIndex{
+
+    /// Creates a new empty `OwnershipIndex`
+
+    pub fn new() -> OwnershipIndex{
+        OwnershipIndex{
+            owners: HashMap::new(),
+            transfers: HashMap::new(),
+            journal: Journal::new()
+        }
+    }
+
+    /// Fetches the current owner of a resource
+    ///
+    /// # Arguments
+    /// * `resource`: The id of the resource
+
+    pub fn get_owner(&self, resource: TxId) -> Option<[u8; 32]>{
+        self.owners.get(&resource).cloned()
+    }
+
+    /// Returns the id of the resource an ownership transfer
+    /// passed on. Ids of other transactions are returned as is.
+    ///
+    /// # Arguments
+    /// * `tx_id`: The id of an ownership transfer or resource
+
+    pub fn get_resource_id(&self, tx_id: TxId) -> TxId{
+        self.transfers.get(&tx_id).cloned().unwrap_or(tx_id)
+    }
+
+    /// Updates the index with a verified transaction
+    ///
+    /// # Arguments
+    /// * `tx_id`: The id of the transaction
+    /// * `transaction`: The verified transaction
+
+    pub fn apply_transaction(&mut self, tx_id: TxId, transaction: &Transaction){
+
+        match *transaction.get_payload(){
+
+            // new resources are owned by the collective
+            // that signed them
+
+            TxPayload::ProductionOutput(_) | TxPayload::Collectivization(_) => {
+                let previous = self.owners.insert(tx_id, transaction.get_signer_pubkeys()[0]);
+                self.journal.record_entry(|index| &mut index.owners, tx_id, previous);
+            },
+
+            TxPayload::OwnershipTransfer(ref payload) => {
+                let resource = self.get_resource_id(payload.get_previous());
+                let previous = self.transfers.insert(tx_id, resource);
+                self.journal.record_entry(|index| &mut index.transfers, tx_id, previous);
+                let previous = self.owners.insert(resource, payload.get_receiver());
+                self.journal.record_entry(|index| &mut index.owners, resource, previous);
+            },
+
+            _ => {}
+
+        }
+
+    }
+
+}
+
+impl Journaled for OwnershipIndex{
+    fn get_journal(&mut self) -> &mut Journal<OwnershipIndex>{
+        &mut self.journal
+    }
+}
+
+// ------------------------------------------------------------------------
+
+#[test]
+fn test_resource_bytes(){
+
+    use blockchain::block::BlockId;
+    use blockchain::transactions::TxIndex;
+
+    let resource = TxId::new(BlockId([0x01; 32]), TxIndex(1));
+
+    let collectivization = Collectivization::new([0x02; 32], 3);
+    let bytes = collectivization.as_bytes();
+    assert_eq!(bytes.len(), 32 + 8);
+    let decoded = Collectivization::from_bytes(bytes.clone()).unwrap();
+    assert_eq!(decoded.get_product_type(), [0x02; 32]);
+    assert_eq!(decoded.get_amount(), 3);
+    assert!(Collectivization::from_bytes(bytes[1..].to_vec()).is_err());
+
+    let transfer = OwnershipTransfer::new(resource, [0x03; 32]);
+    let bytes = transfer.as_bytes();
+    assert_eq!(bytes.len(), 34 + 32);
+    let decoded = OwnershipTransfer::from_bytes(bytes).unwrap();
+    assert_eq!(decoded.get_previous(), resource);
+    assert_eq!(decoded.get_receiver(), [0x03; 32]);
+
+    let write_off = WriteOff::new(resource, String::from("broken"));
+    let bytes = write_off.as_bytes();
+    assert_eq!(bytes.len(), 34 + 2 + 6);
+    let decoded = WriteOff::from_bytes(bytes.clone()).unwrap();
+    assert_eq!(decoded.get_resource(), resource);
+    assert_eq!(decoded.get_reason(), "broken");
+    assert!(WriteOff::from_bytes([&bytes[..], &[0x00][..]].concat()).is_err());
+
+}
+
+#[test]
+fn test_resource_lifecycle(){
+
+    use blockchain::transactions::BadClaimReason;
+    use blockchain::traits::ChainStorage;
+    use blockchain::txtypes::allocation::MopAllocation;
+    use blockchain::txtypes::trust::TrustEstablish;
+    use blockchain::txtypes::TestChain;
+    use blockchain::txtypes::signed_transaction;
+    use blockchain::txtypes::register_collective;
+    use blockchain::txtypes::start_production;
+    use blockchain::txtypes::keypair;
+
+    let (secret_a, pubkey_a) = keypair(0x0A);
+    let (secret_b, pubkey_b) = keypair(0x0B);
+    let (secret_c, pubkey_c) = keypair(0x0C);
+    let (secret_d, _) = keypair(0x0D);
+
+    let trust = TrustEstablish::new(pubkey_a, pubkey_b);
+    let mut chain = TestChain::new();
+    chain.append(1, vec![register_collective(&secret_a),
+                         register_collective(&secret_b),
+                         register_collective(&secret_c),
+                         signed_transaction(TxPayload::TrustEstablish(trust),
+                                            &[&secret_a, &secret_b])]).unwrap();
+
+    let collectivization = |secret_key: &[u8; 64], amount| {
+        let payload = Collectivization::new([0x01; 32], amount);
+        signed_transaction(TxPayload::Collectivization(payload), &[secret_key])
+    };
+    let transfer = |signers: &[&[u8]], previous, receiver| {
+        let payload = OwnershipTransfer::new(previous, receiver);
+        signed_transaction(TxPayload::OwnershipTransfer(payload), signers)
+    };
+    let write_off = |secret_key: &[u8; 64], resource| {
+        let payload = WriteOff::new(resource, String::from("broken"));
+        signed_transaction(TxPayload::WriteOff(payload), &[secret_key])
+    };
+
+    match chain.append(2, vec![collectivization(&secret_d, 2)]){
+        Err(VerificationErrorReason::UnknownCollective) => {},
+        result => panic!("Unexpected result {:?}", result)
+    }
+    match chain.append(2, vec![collectivization(&secret_a, 0)]){
+        Err(VerificationErrorReason::InvalidAmount) => {},
+        result => panic!("Unexpected result {:?}", result)
+    }
+
+    let resources = chain.append(2, vec![collectivization(&secret_a, 2),
+                                         collectivization(&secret_a, 1)]).unwrap();
+    assert_eq!(chain.indexes.ownership.get_owner(resources[0]), Some(pubkey_a));
+
+    // transfers are signed by both collectives, which
+    // must have established strong trust
+
+    match chain.append(3, vec![transfer(&[&secret_a], resources[0], pubkey_b)]){
+        Err(VerificationErrorReason::InvalidTxSigners) => {},
+        result => panic!("Unexpected result {:?}", result)
+    }
+    match chain.append(3, vec![transfer(&[&secret_a, &secret_c], resources[0], pubkey_c)]){
+        Err(VerificationErrorReason::MissingTrust) => {},
+        result => panic!("Unexpected result {:?}", result)
+    }
+
+    let transfers = chain.append(3, vec![transfer(&[&secret_a, &secret_b], resources[0], pubkey_b),
+                                         transfer(&[&secret_a, &secret_b], resources[1], pubkey_b)])
+                         .unwrap();
+    assert_eq!(chain.indexes.ownership.get_owner(resources[0]), Some(pubkey_b));
+    assert_eq!(chain.indexes.ownership.get_resource_id(transfers[0]), resources[0]);
+
+    // every state of ownership is transferred once
+
+    match chain.append(4, vec![transfer(&[&secret_b, &secret_a], resources[0], pubkey_a)]){
+        Err(VerificationErrorReason::BadClaim(BadClaimReason::RelClaimed(TxRelId::ResourceTransfer, _))) => {},
+        result => panic!("Unexpected result {:?}", result)
+    }
+
+    let transfer_back = chain.append(4, vec![transfer(&[&secret_b, &secret_a],
+                                                      transfers[0],
+                                                      pubkey_a)]).unwrap()[0];
+    assert_eq!(chain.indexes.ownership.get_owner(resources[0]), Some(pubkey_a));
+
+    // only the current owner allocates the resource and
+    // allocated resources can't be transferred
+
+    let production_a = start_production(&mut chain, &secret_a, 10);
+    let production_b = start_production(&mut chain, &secret_b, 20);
+
+    let payload = MopAllocation::new(production_a, resources[1], false, false);
+    match chain.append(30, vec![signed_transaction(TxPayload::MopAllocation(payload), &[&secret_a])]){
+        Err(VerificationErrorReason::InvalidOwner) => {},
+        result => panic!("Unexpected result {:?}", result)
+    }
+
+    let payload = MopAllocation::new(production_b, resources[1], false, false);
+    chain.append(30, vec![signed_transaction(TxPayload::MopAllocation(payload), &[&secret_b])]).unwrap();
+
+    match chain.append(31, vec![transfer(&[&secret_b, &secret_a], transfers[1], pubkey_a)]){
+        Err(VerificationErrorReason::ResourceInUse) => {},
+        result => panic!("Unexpected result {:?}", result)
+    }
+
+    // write-offs are signed by the current owner and
+    // finalize the resource
+
+    match chain.append(32, vec![write_off(&secret_b, resources[0])]){
+        Err(VerificationErrorReason::InvalidTxSigners) => {},
+        result => panic!("Unexpected result {:?}", result)
+    }
+
+    let write_off_id = chain.append(32, vec![write_off(&secret_a, resources[0])]).unwrap()[0];
+    match *chain.storage.get_transaction_state(resources[0]).unwrap().get_total_rel_state(){
+        TxTotalRelState::Finalized(tx_id) => assert_eq!(tx_id, write_off_id),
+        ref state => panic!("Unexpected state {:?}", state)
+    }
+
+    match chain.append(33, vec![transfer(&[&secret_a, &secret_b], transfer_back, pubkey_b)]){
+        Err(VerificationErrorReason::ResourceInUse) => {},
+        result => panic!("Unexpected result {:?}", result)
+    }
+    match chain.append(33, vec![write_off(&secret_a, resources[0])]){
+        Err(VerificationErrorReason::ResourceInUse) => {},
+        result => panic!("Unexpected result {:?}", result)
+    }
+
+    let payload = MopAllocation::new(production_a, resources[0], false, false);
+    match chain.append(33, vec![signed_transaction(TxPayload::MopAllocation(payload), &[&secret_a])]){
+        Err(VerificationErrorReason::ResourceInUse) => {},
+        result => panic!("Unexpected result {:?}", result)
+    }
+
+}
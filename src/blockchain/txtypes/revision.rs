@@ -0,0 +1,724 @@
+//
+//    Stachanov
+//    Copyright (C) 2017 Stachanov Developer Collective
+//
+//    This file is part of Stachanov.
+//
+//    This program is free software: you can redistribute it and/or
+//    modify it under the terms of the GNU Affero General Public
+//    License, version 3, as published by the Free Software Foundation.
+//
+//    This program is distributed in the hope that it will be useful,
+//    but WITHOUT ANY WARRANTY; without even the implied warranty of
+//    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//    GNU Affero General Public License for more details.
+//
+//    You should have received a copy of the
+//               GNU Affero General Public License
+//    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+// This module implements the final revision of a period. Before
+// the workloads of a period can be transformed into labor
+// coupons, a final revision fixes the tax rate (c+w)/a of the
+// period, where a is the sum of all workloads, c the sum of
+// all workloads used for commons production and w the value
+// of all exclusive write-offs of the period.
+//
+// The write-offs of a period are limited relative to its
+// workloads. The limit is enforced when a write-off is
+// verified, so a period can always be revised.
+
+use std::collections::HashMap;
+use blockchain::traits::BinFormat;
+use blockchain::traits::ChainStorage;
+use blockchain::transactions::Transaction;
+use blockchain::transactions::TxPayload;
+use blockchain::transactions::TxId;
+use blockchain::transactions::TxIndex;
+use blockchain::errors::BinFormatError;
+use blockchain::errors::VerificationError;
+use blockchain::errors::VerificationErrorReason;
+use blockchain::txtypes::TxContext;
+use blockchain::txtypes::verify_signers;
+use blockchain::txtypes::allocation::created_resource;
+use blockchain::txtypes::allocation::deallocation_value;
+use blockchain::txtypes::product::ProductTypeIndex;
+use blockchain::depreciation::Rational;
+use blockchain::params::ConsensusParams;
+use blockchain::journal::Journal;
+use blockchain::journal::Journaled;
+use blockchain::utils::u64_to_u8le;
+use blockchain::utils::BinReader;
+
+/// The length of a revision period in seconds (one week).
+/// Periods start at multiples of the period length.
+
+pub const REVISION_PERIOD: u64 = 7 * 24 * 60 * 60;
+
+/// `FinalRevision` fixes the tax rate of the period ]k1, k2]
+/// (timestamps in seconds). Final revisions can be issued by
+/// anyone and are unsigned, since the sums they declare are
+/// recomputed from the chain by every node. They are only
+/// accepted after the period ended, for aligned periods and
+/// once per period.
+
+#[derive(Clone)]
+#[derive(Debug)]
+pub struct FinalRevision{
+    period_start: u64,
+    period_end: u64,
+    workloads: u64,
+    commons: u64,
+    write_offs: u64
+}
+
+impl FinalRevision{
+
+    /// Creates a new `FinalRevision`
+    ///
+    /// # Arguments
+    /// * `period_start`: The (excluded) start k1 of the period
+    /// * `period_end`: The (included) end k2 of the period
+    /// * `workloads`: The sum a of all workloads in minutes
+    /// * `commons`: The sum c of all commons workloads in minutes
+    /// * `write_offs`: The value w of all exclusive write-offs in minutes
+
+    pub fn new(period_start: u64,
+               period_end: u64,
+               workloads: u64,
+               commons: u64,
+               write_offs: u64) -> FinalRevision{
+
+        FinalRevision{
+            period_start: period_start,
+            period_end: period_end,
+            workloads: workloads,
+            commons: commons,
+            write_offs: write_offs
+        }
+
+    }
+
+    /// Computes the revision of a period from the chain. The
+    /// commons sum c includes the value repurposed means of
+    /// production transferred to commons productions.
+    ///
+    /// Returns None if one of the sums overflows
+    ///
+    /// # Arguments
+    /// * `storage`: The storage holding the chain
+    /// * `products`: The product type index of the chain
+    /// * `period_start`: The (excluded) start k1 of the period
+    /// * `period_end`: The (included) end k2 of the period
+
+    pub fn from_chain<C>(storage: &C,
+                         products: &ProductTypeIndex,
+                         period_start: u64,
+                         period_end: u64) -> Option<FinalRevision> where C: ChainStorage{
+
+        let mut revision = FinalRevision::new(period_start, period_end, 0, 0, 0);
+
+        let mut current = storage.get_after_timestamp(period_start);
+        while let Some(block) = current{
+
+            if block.get_header_ref().get_timestamp() > period_end{
+                break
+            }
+
+            for index in 0..block.get_transaction_count(){
+                let tx_index = TxIndex(index as u16);
+                let transaction = block.get_transaction(tx_index)?;
+                match *transaction.get_payload(){
+                    TxPayload::WorkloadAllocation(ref workload) => {
+                        revision.workloads = revision.workloads.checked_add(workload.get_minutes())?;
+                        if workload.is_common(){
+                            revision.commons = revision.commons.checked_add(workload.get_minutes())?;
+                        }
+                    },
+                    TxPayload::MopDeallocation(ref deallocation) => {
+                        let allocation = storage.get_transaction(deallocation.get_allocation())?;
+                        if let TxPayload::MopAllocation(ref allocation) = *allocation.get_payload(){
+                            if allocation.is_repurposed(){
+                                let tx_id = TxId::new(block.get_id(), tx_index);
+                                let value = deallocation_value(storage, products, tx_id)?;
+                                revision.commons = revision.commons.checked_add(value)?;
+                            }
+                        }
+                    },
+                    TxPayload::WriteOff(ref write_off) => {
+                        let resource = created_resource(&storage.get_transaction(write_off.get_resource())?)?;
+                        if !resource.common{
+                            revision.write_offs = revision.write_offs.checked_add(resource.value)?;
+                        }
+                    },
+                    _ => {}
+                }
+            }
+
+            current = storage.get_after(block.get_id());
+
+        }
+
+        Some(revision)
+
+    }
+
+    /// Gets the (excluded) start k1 of the period
+
+    pub fn get_period_start(&self) -> u64{
+        self.period_start
+    }
+
+    /// Gets the (included) end k2 of the period
+
+    pub fn get_period_end(&self) -> u64{
+        self.period_end
+    }
+
+    /// Gets the sum a of all workloads in minutes
+
+    pub fn get_workloads(&self) -> u64{
+        self.workloads
+    }
+
+    /// Gets the sum c of all commons workloads in minutes
+
+    pub fn get_commons(&self) -> u64{
+        self.commons
+    }
+
+    /// Gets the value w of all exclusive write-offs in minutes
+
+    pub fn get_write_offs(&self) -> u64{
+        self.write_offs
+    }
+
+    /// Returns the tax rate (c+w)/a of the period
+    /// (0 for periods without workloads)
+
+    pub fn get_tax_rate(&self) -> Rational{
+
+        if self.workloads == 0{
+            return Rational::from_integer(0)
+        }
+
+        let taxed = self.commons as u128 + self.write_offs as u128;
+        Rational::new(taxed, self.workloads as u128)
+
+    }
+
+    /// Returns the value (1 - (c+w)/a) * l in minutes of the
+    /// coupons for a workload l of the period, rounded down
+    ///
+    /// # Arguments
+    /// * `minutes`: The workload l in minutes
+
+    pub fn get_coupon_value(&self, minutes: u64) -> u64{
+
+        if self.workloads == 0{
+            return minutes
+        }
+
+        let taxed = self.commons as u128 + self.write_offs as u128;
+        let untaxed = (self.workloads as u128).saturating_sub(taxed);
+        (minutes as u128 * untaxed / self.workloads as u128) as u64
+
+    }
+
+    /// Verifies the revision against the chain
+    ///
+    /// # Arguments
+    /// * `transaction`: The transaction holding the revision
+    /// * `context`: The verification context
+
+    pub fn verify<C>(&self,
+                     transaction: &Transaction,
+                     context: &mut TxContext<C>)
+                     -> Result<(), VerificationError> where C: ChainStorage{
+
+        verify_signers(transaction, &[])?;
+
+        // periods must be aligned and must have ended, so
+        // all of their blocks are part of the chain
+
+        if !self.period_start.is_multiple_of(REVISION_PERIOD) ||
+           self.period_end != self.period_start.saturating_add(REVISION_PERIOD) ||
+           self.period_end >= context.get_header().get_timestamp(){
+            let reason = VerificationErrorReason::InvalidRevisionPeriod;
+            return Err(VerificationError::new(reason))
+        }
+
+        if context.get_indexes().revisions.get_revision(self.period_start).is_some(){
+            let reason = VerificationErrorReason::OverlappingRevision;
+            return Err(VerificationError::new(reason))
+        }
+
+        let expected = FinalRevision::from_chain(context.get_storage(),
+                                                 &context.get_indexes().products,
+                                                 self.period_start,
+                                                 self.period_end);
+
+        let matches = expected.is_some_and(|expected| {
+            expected.workloads == self.workloads &&
+            expected.commons == self.commons &&
+            expected.write_offs == self.write_offs
+        });
+
+        if !matches{
+            let reason = VerificationErrorReason::InvalidRevision;
+            return Err(VerificationError::new(reason))
+        }
+
+        // write-offs were limited when they were verified
+
+        if exceeds_write_off_limit(self.workloads, self.write_offs, context.get_params()){
+            let reason = VerificationErrorReason::WriteOffLimitExceeded;
+            return Err(VerificationError::new(reason))
+        }
+
+        Ok(())
+
+    }
+
+}
+
+impl BinFormat<FinalRevision> for FinalRevision{
+
+    // The byte format of FinalRevision is:
+
+    //    field            length
+    //  .------------------------.
+    //  | period_start    | 8    |
+    //  |------------------------|
+    //  | period_end      | 8    |
+    //  |------------------------|
+    //  | workloads       | 8    |
+    //  |------------------------|
+    //  | commons         | 8    |
+    //  |------------------------|
+    //  | write_offs      | 8    |
+    //  '------------------------'
+
+    /// Returns the revision as an u8 vector
+
+    fn as_bytes(&self) -> Vec<u8>{
+
+        [u64_to_u8le(self.period_start),
+         u64_to_u8le(self.period_end),
+         u64_to_u8le(self.workloads),
+         u64_to_u8le(self.commons),
+         u64_to_u8le(self.write_offs)].concat()
+
+    }
+
+    /// Creates a new revision from a byte vector.
+    ///
+    /// * `bytes`: A byte vector
+
+    fn from_bytes(bytes: Vec<u8>) -> Result<FinalRevision, BinFormatError>{
+
+        let mut reader = BinReader::new(&bytes);
+
+        let period_start = reader.read_u64()?;
+        let period_end = reader.read_u64()?;
+        let workloads = reader.read_u64()?;
+        let commons = reader.read_u64()?;
+        let write_offs = reader.read_u64()?;
+
+        reader.finish()?;
+
+        Ok(FinalRevision::new(period_start, period_end, workloads, commons, write_offs))
+
+    }
+
+}
+
+/// Returns the (excluded) start of the revision period
+/// holding a timestamp
+///
+/// # Arguments
+/// * `timestamp`: The timestamp in seconds
+
+pub fn period_start(timestamp: u64) -> u64{
+    timestamp.saturating_sub(1) / REVISION_PERIOD * REVISION_PERIOD
+}
+
+/// Checks if exclusive write-offs w exceed the limit relative
+/// to the workloads a of a period, that is w >= a / divisor
+/// (periods without write-offs never exceed the limit)
+///
+/// # Arguments
+/// * `workloads`: The sum a of the workloads in minutes
+/// * `write_offs`: The value w of the write-offs in minutes
+/// * `params`: The consensus parameters holding the divisor
+
+pub fn exceeds_write_off_limit(workloads: u64, write_offs: u64, params: &ConsensusParams) -> bool{
+    write_offs > 0 &&
+    write_offs as u128 * params.write_off_limit_divisor as u128 >= workloads as u128
+}
+
+// ------------------------------------------------------------------------
+
+/// `PeriodSums` holds the sums of a revision period that
+/// are known so far
+
+#[derive(Clone)]
+#[derive(Copy)]
+#[derive(Debug)]
+#[derive(Default)]
+#[derive(PartialEq)]
+#[derive(Eq)]
+pub struct PeriodSums{
+    pub workloads: u64,
+    pub write_offs: u64
+}
+
+/// `RevisionIndex` maps the starts of all revised periods to
+/// the ids of their final revisions. It keeps the running sums
+/// of the workloads and exclusive write-offs of every period,
+/// so write-offs can be checked against the limit, and the
+/// values of all exclusive resources. It is updated with every
+/// applied block.
+
+pub struct RevisionIndex{
+    revisions: HashMap<u64, TxId>,
+    sums: HashMap<u64, PeriodSums>,
+    exclusive_values: HashMap<TxId, u64>,
+    journal: Journal<RevisionIndex>
+}
+
+impl RevisionIndex{
+
+    /// Creates a new empty `RevisionIndex`
+
+    pub fn new() -> RevisionIndex{
+        RevisionIndex{
+            revisions: HashMap::new(),
+            sums: HashMap::new(),
+            exclusive_values: HashMap::new(),
+            journal: Journal::new()
+        }
+    }
+
+    /// Returns the sums of a period known so far
+    ///
+    /// # Arguments
+    /// * `period_start`: The (excluded) start of the period
+
+    pub fn get_sums(&self, period_start: u64) -> PeriodSums{
+        self.sums.get(&period_start).cloned().unwrap_or_default()
+    }
+
+    /// Returns the value of a resource that is accounted for
+    /// when it is written off (0 for commons resources)
+    ///
+    /// # Arguments
+    /// * `resource`: The id of the resource
+
+    pub fn get_exclusive_value(&self, resource: TxId) -> u64{
+        self.exclusive_values.get(&resource).cloned().unwrap_or(0)
+    }
+
+    /// Fetches the id of the final revision of a period
+    ///
+    /// # Arguments
+    /// * `period_start`: The (excluded) start of the period
+
+    pub fn get_revision(&self, period_start: u64) -> Option<TxId>{
+        self.revisions.get(&period_start).cloned()
+    }
+
+    /// Updates the index with a verified transaction
+    ///
+    /// # Arguments
+    /// * `tx_id`: The id of the transaction
+    /// * `transaction`: The verified transaction
+    /// * `timestamp`: The timestamp of the block holding the transaction
+
+    pub fn apply_transaction(&mut self, tx_id: TxId, transaction: &Transaction, timestamp: u64){
+
+        match *transaction.get_payload(){
+
+            TxPayload::FinalRevision(ref payload) => {
+                let period_start = payload.get_period_start();
+                let previous = self.revisions.insert(period_start, tx_id);
+                self.journal.record_entry(|index| &mut index.revisions, period_start, previous);
+            },
+
+            TxPayload::ProductionOutput(ref payload) if !payload.is_common() => {
+                let previous = self.exclusive_values.insert(tx_id, payload.get_value());
+                self.journal.record_entry(|index| &mut index.exclusive_values, tx_id, previous);
+            },
+
+            TxPayload::WorkloadAllocation(ref payload) => {
+                let sums = self.update_sums(period_start(timestamp));
+                sums.workloads = sums.workloads.saturating_add(payload.get_minutes());
+            },
+
+            TxPayload::WriteOff(ref payload) => {
+                let resource = payload.get_resource();
+                let previous = self.exclusive_values.remove(&resource);
+                self.journal.record_entry(|index| &mut index.exclusive_values, resource, previous);
+                let sums = self.update_sums(period_start(timestamp));
+                sums.write_offs = sums.write_offs.saturating_add(previous.unwrap_or(0));
+            },
+
+            _ => {}
+
+        }
+
+    }
+
+    /// Journals the sums of a period and
+    /// returns them, so they can be updated
+
+    fn update_sums(&mut self, period: u64) -> &mut PeriodSums{
+        let previous = self.sums.get(&period).cloned();
+        self.journal.record_entry(|index| &mut index.sums, period, previous);
+        self.sums.entry(period).or_default()
+    }
+
+}
+
+impl Journaled for RevisionIndex{
+    fn get_journal(&mut self) -> &mut Journal<RevisionIndex>{
+        &mut self.journal
+    }
+}
+
+// ------------------------------------------------------------------------
+
+#[test]
+fn test_final_revision_bytes(){
+
+    let revision = FinalRevision::new(REVISION_PERIOD, 2 * REVISION_PERIOD, 1000, 150, 50);
+    let bytes = revision.as_bytes();
+    assert_eq!(bytes.len(), 5 * 8);
+
+    let decoded = FinalRevision::from_bytes(bytes.clone()).unwrap();
+    assert_eq!(decoded.get_period_start(), REVISION_PERIOD);
+    assert_eq!(decoded.get_period_end(), 2 * REVISION_PERIOD);
+    assert_eq!(decoded.get_workloads(), 1000);
+    assert_eq!(decoded.get_commons(), 150);
+    assert_eq!(decoded.get_write_offs(), 50);
+    assert!(FinalRevision::from_bytes(bytes[1..].to_vec()).is_err());
+
+    assert_eq!(decoded.get_tax_rate(), Rational::new(1, 5));
+    assert_eq!(decoded.get_coupon_value(480), 384);
+    assert_eq!(decoded.get_coupon_value(7), 5);
+    assert_eq!(FinalRevision::new(0, REVISION_PERIOD, 0, 0, 0).get_tax_rate(),
+               Rational::from_integer(0));
+
+}
+
+#[test]
+fn test_final_revision(){
+
+    use blockchain::transactions::Transaction;
+    use blockchain::txtypes::allocation::WorkloadAllocation;
+    use blockchain::txtypes::TestChain;
+    use blockchain::txtypes::signed_transaction;
+    use blockchain::txtypes::register_collective;
+    use blockchain::txtypes::start_production;
+    use blockchain::txtypes::keypair;
+
+    const P: u64 = REVISION_PERIOD;
+
+    let (secret_a, _) = keypair(0x0A);
+
+    let mut chain = TestChain::new();
+    chain.append(1, vec![register_collective(&secret_a)]).unwrap();
+
+    let production_start = start_production(&mut chain, &secret_a, 10);
+    let workload = |minutes| {
+        let payload = WorkloadAllocation::new(production_start, [0x01; 32], minutes, false);
+        signed_transaction(TxPayload::WorkloadAllocation(payload), &[&secret_a])
+    };
+    let revision = |period_start, period_end, workloads| {
+        let payload = FinalRevision::new(period_start, period_end, workloads, 0, 0);
+        Transaction::new(TxPayload::FinalRevision(payload), vec![])
+    };
+
+    // workloads at the borders of the period ]P, 2P]
+
+    chain.append(P, vec![workload(10)]).unwrap();
+    chain.append(P + 1, vec![workload(20), workload(30)]).unwrap();
+    chain.append(2 * P, vec![workload(40)]).unwrap();
+
+    // the period hasn't ended yet
+
+    match chain.append(2 * P, vec![revision(P, 2 * P, 90)]){
+        Err(VerificationErrorReason::InvalidRevisionPeriod) => {},
+        result => panic!("Unexpected result {:?}", result)
+    }
+
+    chain.append(2 * P + 1, vec![workload(50)]).unwrap();
+
+    assert_eq!(FinalRevision::from_chain(&chain.storage, &chain.indexes.products, P, 2 * P).unwrap().get_workloads(), 90);
+    assert_eq!(FinalRevision::from_chain(&chain.storage, &chain.indexes.products, 0, P).unwrap().get_workloads(), 10);
+
+    for transaction in [revision(P + 1, 2 * P + 1, 90), revision(P, 3 * P, 90)]{
+        match chain.append(2 * P + 2, vec![transaction]){
+            Err(VerificationErrorReason::InvalidRevisionPeriod) => {},
+            result => panic!("Unexpected result {:?}", result)
+        }
+    }
+
+    match chain.append(2 * P + 2, vec![revision(P, 2 * P, 100)]){
+        Err(VerificationErrorReason::InvalidRevision) => {},
+        result => panic!("Unexpected result {:?}", result)
+    }
+
+    // revisions are unsigned
+
+    let payload = FinalRevision::new(P, 2 * P, 90, 0, 0);
+    let signed = signed_transaction(TxPayload::FinalRevision(payload), &[&secret_a]);
+    match chain.append(2 * P + 2, vec![signed]){
+        Err(VerificationErrorReason::InvalidTxSigners) => {},
+        result => panic!("Unexpected result {:?}", result)
+    }
+
+    let revision_id = chain.append(2 * P + 2, vec![revision(P, 2 * P, 90),
+                                                   revision(0, P, 10)]).unwrap()[0];
+    assert_eq!(chain.indexes.revisions.get_revision(P), Some(revision_id));
+
+    match chain.append(2 * P + 3, vec![revision(P, 2 * P, 90)]){
+        Err(VerificationErrorReason::OverlappingRevision) => {},
+        result => panic!("Unexpected result {:?}", result)
+    }
+
+}
+
+#[test]
+fn test_final_revision_write_offs(){
+
+    use blockchain::transactions::Transaction;
+    use blockchain::txtypes::allocation::WorkloadAllocation;
+    use blockchain::txtypes::production::ProductionOutput;
+    use blockchain::txtypes::resource::Collectivization;
+    use blockchain::txtypes::resource::WriteOff;
+    use blockchain::txtypes::TestChain;
+    use blockchain::txtypes::signed_transaction;
+    use blockchain::txtypes::register_collective;
+    use blockchain::txtypes::define_product_type;
+    use blockchain::txtypes::start_production;
+    use blockchain::txtypes::keypair;
+
+    const P: u64 = REVISION_PERIOD;
+
+    let (secret_a, _) = keypair(0x0A);
+
+    let mut chain = TestChain::new();
+    chain.append(1, vec![register_collective(&secret_a)]).unwrap();
+    let (product_type, definition) = define_product_type(&secret_a, "bread");
+    chain.append(2, vec![definition]).unwrap();
+
+    let production_a = start_production(&mut chain, &secret_a, 10);
+    let production_b = start_production(&mut chain, &secret_a, 20);
+
+    let sign = |payload| signed_transaction(payload, &[&secret_a]);
+    let workload = |production_start, minutes| {
+        sign(TxPayload::WorkloadAllocation(WorkloadAllocation::new(production_start, [0x01; 32], minutes, false)))
+    };
+    let output = |production_start, value| {
+        sign(TxPayload::ProductionOutput(ProductionOutput::new(production_start, product_type, 1, value, false)))
+    };
+    let write_off = |resource| {
+        sign(TxPayload::WriteOff(WriteOff::new(resource, String::from("lost"))))
+    };
+    let revision = |period_start, period_end, workloads, write_offs| {
+        let payload = FinalRevision::new(period_start, period_end, workloads, 0, write_offs);
+        Transaction::new(TxPayload::FinalRevision(payload), vec![])
+    };
+
+    // ]P, 2P]: the written off output and the collectivized
+    // resource (without value) are below the limit
+
+    chain.append(P + 1, vec![workload(production_a, 50), workload(production_b, 1000)]).unwrap();
+    let resources = chain.append(P + 2, vec![output(production_a, 50),
+                                             sign(TxPayload::Collectivization(Collectivization::new([0x03; 32], 1)))])
+                         .unwrap();
+    chain.append(P + 3, vec![write_off(resources[0]), write_off(resources[1])]).unwrap();
+
+    let computed = FinalRevision::from_chain(&chain.storage, &chain.indexes.products, P, 2 * P).unwrap();
+    assert_eq!(computed.get_workloads(), 1050);
+    assert_eq!(computed.get_write_offs(), 50);
+
+    match chain.append(2 * P + 1, vec![revision(P, 2 * P, 1050, 0)]){
+        Err(VerificationErrorReason::InvalidRevision) => {},
+        result => panic!("Unexpected result {:?}", result)
+    }
+
+    // ]2P, 3P]: writing off the output would exceed a tenth
+    // of the workloads, so the period can still be revised
+
+    chain.append(2 * P + 1, vec![revision(P, 2 * P, 1050, 50),
+                                 workload(production_b, 200)]).unwrap();
+    let resource = chain.append(2 * P + 2, vec![output(production_b, 1200)]).unwrap()[0];
+
+    match chain.append(2 * P + 3, vec![write_off(resource)]){
+        Err(VerificationErrorReason::WriteOffLimitExceeded) => {},
+        result => panic!("Unexpected result {:?}", result)
+    }
+    assert_eq!(chain.indexes.revisions.get_sums(2 * P), PeriodSums{workloads: 200, write_offs: 0});
+
+    chain.append(3 * P + 1, vec![revision(2 * P, 3 * P, 200, 0)]).unwrap();
+
+    // ]3P, 4P]: the output can be written off once the
+    // workloads of the period are large enough
+
+    let production_c = start_production(&mut chain, &secret_a, 3 * P + 2);
+    chain.append(3 * P + 5, vec![workload(production_c, 12000)]).unwrap();
+    match chain.append(3 * P + 6, vec![write_off(resource)]){
+        Err(VerificationErrorReason::WriteOffLimitExceeded) => {},
+        result => panic!("Unexpected result {:?}", result)
+    }
+    chain.append(3 * P + 6, vec![workload(production_c, 1), workload(production_c, 1), write_off(resource)])
+         .unwrap();
+    chain.append(4 * P + 1, vec![revision(3 * P, 4 * P, 12002, 1200)]).unwrap();
+
+}
+
+#[test]
+fn test_write_off_limit_params(){
+
+    use blockchain::params::ConsensusParams;
+    use blockchain::txtypes::allocation::WorkloadAllocation;
+    use blockchain::txtypes::production::ProductionOutput;
+    use blockchain::txtypes::resource::WriteOff;
+    use blockchain::txtypes::TestChain;
+    use blockchain::txtypes::signed_transaction;
+    use blockchain::txtypes::register_collective;
+    use blockchain::txtypes::define_product_type;
+    use blockchain::txtypes::start_production;
+    use blockchain::txtypes::keypair;
+
+    let (secret_a, _) = keypair(0x0A);
+    let sign = |payload| signed_transaction(payload, &[&secret_a]);
+
+    // write-offs only need to be less than the workloads
+
+    let params = ConsensusParams{write_off_limit_divisor: 1, ..ConsensusParams::default()};
+    let mut chain = TestChain::with_params(params);
+    chain.append(1, vec![register_collective(&secret_a)]).unwrap();
+    let (product_type, definition) = define_product_type(&secret_a, "bread");
+    chain.append(2, vec![definition]).unwrap();
+
+    let production = start_production(&mut chain, &secret_a, 10);
+    let payload = WorkloadAllocation::new(production, [0x01; 32], 100, false);
+    chain.append(20, vec![sign(TxPayload::WorkloadAllocation(payload))]).unwrap();
+    let payload = ProductionOutput::new(production, product_type, 1, 100, false);
+    let resource = chain.append(30, vec![sign(TxPayload::ProductionOutput(payload))]).unwrap()[0];
+
+    let write_off = sign(TxPayload::WriteOff(WriteOff::new(resource, String::from("lost"))));
+    match chain.append(40, vec![write_off.clone()]){
+        Err(VerificationErrorReason::WriteOffLimitExceeded) => {},
+        result => panic!("Unexpected result {:?}", result)
+    }
+
+    let production = start_production(&mut chain, &secret_a, 40);
+    let payload = WorkloadAllocation::new(production, [0x01; 32], 1, false);
+    chain.append(50, vec![sign(TxPayload::WorkloadAllocation(payload)), write_off]).unwrap();
+
+}
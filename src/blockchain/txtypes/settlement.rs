@@ -0,0 +1,463 @@
+//
+//    Stachanov
+//    Copyright (C) 2017 Stachanov Developer Collective
+//
+//    This file is part of Stachanov.
+//
+//    This program is free software: you can redistribute it and/or
+//    modify it under the terms of the GNU Affero General Public
+//    License, version 3, as published by the Free Software Foundation.
+//
+//    This program is distributed in the hope that it will be useful,
+//    but WITHOUT ANY WARRANTY; without even the implied warranty of
+//    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//    GNU Affero General Public License for more details.
+//
+//    You should have received a copy of the
+//               GNU Affero General Public License
+//    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+// This module implements the settlement between subsystems.
+// When the cancellation of strong trust splits an economic
+// subsystem into two, the coupons issued on one side might
+// have been backed by products of the other side. The split
+// opens a pending settlement, which lists the outstanding
+// coupons and the values of the C2I stocks on both sides at
+// the time of the split. Appropriations with coupons of one
+// side at dispatchers of the other side are rejected until
+// both collectives of the cancelled trust accepted the
+// settlement, even if the subsystems got connected again.
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+use blockchain::traits::BinFormat;
+use blockchain::traits::ChainStorage;
+use blockchain::transactions::Transaction;
+use blockchain::transactions::TxPayload;
+use blockchain::transactions::TxId;
+use blockchain::transactions::read_tx_id;
+use blockchain::errors::BinFormatError;
+use blockchain::errors::VerificationError;
+use blockchain::errors::VerificationErrorReason;
+use blockchain::txtypes::TxContext;
+use blockchain::txtypes::verify_signers;
+use blockchain::txtypes::trust::TrustGraph;
+use blockchain::txtypes::coupon::WalletIndex;
+use blockchain::txtypes::inventory::InventoryIndex;
+use blockchain::journal::Journal;
+use blockchain::journal::Journaled;
+use blockchain::utils::BinReader;
+
+/// `Settlement` accepts the pending settlement opened by a
+/// `TrustCancel` transaction, that split a subsystem. It must
+/// be signed by both collectives of the cancelled trust (in
+/// the order of the establishment).
+
+#[derive(Clone)]
+#[derive(Debug)]
+pub struct Settlement{
+    cancellation: TxId
+}
+
+impl Settlement{
+
+    /// Creates a new `Settlement`
+    ///
+    /// # Arguments
+    /// * `cancellation`: The id of the `TrustCancel` transaction
+
+    pub fn new(cancellation: TxId) -> Settlement{
+        Settlement{cancellation: cancellation}
+    }
+
+    /// Gets the id of the settled cancellation
+
+    pub fn get_cancellation(&self) -> TxId{
+        self.cancellation
+    }
+
+    /// Verifies the settlement against the chain
+    ///
+    /// # Arguments
+    /// * `transaction`: The transaction holding the settlement
+    /// * `context`: The verification context
+
+    pub fn verify<C>(&self,
+                     transaction: &Transaction,
+                     context: &mut TxContext<C>)
+                     -> Result<(), VerificationError> where C: ChainStorage{
+
+        let collectives = match context.get_indexes().settlements.get_pending(self.cancellation){
+            Some(pending) => [pending.get_side_a().collective, pending.get_side_b().collective],
+            None => {
+                let reason = VerificationErrorReason::InvalidTxRef(self.cancellation);
+                return Err(VerificationError::new(reason))
+            }
+        };
+
+        verify_signers(transaction, &collectives)
+
+    }
+
+}
+
+impl BinFormat<Settlement> for Settlement{
+
+    // The byte format of Settlement is:
+
+    //    field            length
+    //  .------------------------.
+    //  | cancellation    | 34   |
+    //  '------------------------'
+
+    /// Returns the settlement as an u8 vector
+
+    fn as_bytes(&self) -> Vec<u8>{
+        self.cancellation.as_bytes()
+    }
+
+    /// Creates a new settlement from a byte vector.
+    ///
+    /// * `bytes`: A byte vector
+
+    fn from_bytes(bytes: Vec<u8>) -> Result<Settlement, BinFormatError>{
+
+        let mut reader = BinReader::new(&bytes);
+        let cancellation = read_tx_id(&mut reader)?;
+        reader.finish()?;
+
+        Ok(Settlement::new(cancellation))
+
+    }
+
+}
+
+// ------------------------------------------------------------------------
+
+/// `SettlementSide` is one of the two subsystems of a split
+
+#[derive(Clone)]
+#[derive(Debug)]
+pub struct SettlementSide{
+
+    /// The collective of the cancelled trust on this side
+    pub collective: [u8; 32],
+
+    /// All collectives of the subsystem
+    pub subsystem: HashSet<[u8; 32]>,
+
+    /// The coupons in minutes held by wallets, that
+    /// were issued by collectives of the subsystem
+    pub coupons: u64,
+
+    /// The value in minutes of the stocks held by
+    /// C2I collectives of the subsystem
+    pub value: u64
+
+}
+
+impl SettlementSide{
+
+    /// Creates the side of a split subsystem
+    ///
+    /// # Arguments
+    /// * `collective`: The collective of the cancelled trust
+    /// * `trust`: The trust graph after the cancellation
+    /// * `wallets`: The wallet index of the chain
+    /// * `inventory`: The inventory index of the chain
+
+    fn new(collective: [u8; 32],
+           trust: &TrustGraph,
+           wallets: &WalletIndex,
+           inventory: &InventoryIndex) -> SettlementSide{
+
+        let subsystem = trust.subsystem_of(collective);
+        SettlementSide{
+            collective: collective,
+            coupons: wallets.outstanding(&subsystem),
+            value: inventory.value_held_by(&subsystem),
+            subsystem: subsystem
+        }
+
+    }
+
+}
+
+/// `PendingSettlement` is opened, when the cancellation of
+/// strong trust splits a subsystem into two
+
+#[derive(Clone)]
+#[derive(Debug)]
+pub struct PendingSettlement{
+    side_a: SettlementSide,
+    side_b: SettlementSide
+}
+
+impl PendingSettlement{
+
+    /// Gets the side of the first collective of the cancelled trust
+
+    pub fn get_side_a(&self) -> &SettlementSide{
+        &self.side_a
+    }
+
+    /// Gets the side of the second collective of the cancelled trust
+
+    pub fn get_side_b(&self) -> &SettlementSide{
+        &self.side_b
+    }
+
+    /// Checks if two collectives ended up on different sides
+    ///
+    /// # Arguments
+    /// * `collective_a`: The public key of the first collective
+    /// * `collective_b`: The public key of the second collective
+
+    pub fn separates(&self, collective_a: [u8; 32], collective_b: [u8; 32]) -> bool{
+        (self.side_a.subsystem.contains(&collective_a) && self.side_b.subsystem.contains(&collective_b)) ||
+        (self.side_b.subsystem.contains(&collective_a) && self.side_a.subsystem.contains(&collective_b))
+    }
+
+}
+
+/// `SettlementIndex` keeps track of the pending settlements
+/// by the id of the cancellation that opened them. It must
+/// be updated after the trust graph.
+
+pub struct SettlementIndex{
+    pending: HashMap<TxId, PendingSettlement>,
+    journal: Journal<SettlementIndex>
+}
+
+impl SettlementIndex{
+
+    /// Creates a new empty `SettlementIndex`
+
+    pub fn new() -> SettlementIndex{
+        SettlementIndex{
+            pending: HashMap::new(),
+            journal: Journal::new()
+        }
+    }
+
+    /// Returns the pending settlement opened by a cancellation
+    ///
+    /// # Arguments
+    /// * `cancellation`: The id of the `TrustCancel` transaction
+
+    pub fn get_pending(&self, cancellation: TxId) -> Option<&PendingSettlement>{
+        self.pending.get(&cancellation)
+    }
+
+    /// Checks if a pending settlement separates two collectives
+    ///
+    /// # Arguments
+    /// * `collective_a`: The public key of the first collective
+    /// * `collective_b`: The public key of the second collective
+
+    pub fn is_pending(&self, collective_a: [u8; 32], collective_b: [u8; 32]) -> bool{
+        self.pending.values().any(|pending| pending.separates(collective_a, collective_b))
+    }
+
+    /// Updates the index with a verified transaction
+    ///
+    /// # Arguments
+    /// * `tx_id`: The id of the transaction
+    /// * `transaction`: The verified transaction
+    /// * `trust`: The trust graph of the chain
+    /// * `wallets`: The wallet index of the chain
+    /// * `inventory`: The inventory index of the chain
+
+    pub fn apply_transaction(&mut self,
+                             tx_id: TxId,
+                             transaction: &Transaction,
+                             trust: &TrustGraph,
+                             wallets: &WalletIndex,
+                             inventory: &InventoryIndex){
+
+        match *transaction.get_payload(){
+
+            TxPayload::TrustCancel(_) => {
+                if let Some((collective_a, collective_b)) = trust.get_split(tx_id){
+                    let pending = PendingSettlement{
+                        side_a: SettlementSide::new(collective_a, trust, wallets, inventory),
+                        side_b: SettlementSide::new(collective_b, trust, wallets, inventory)
+                    };
+                    let previous = self.pending.insert(tx_id, pending);
+                    self.journal.record_entry(|index| &mut index.pending, tx_id, previous);
+                }
+            },
+
+            TxPayload::Settlement(ref payload) => {
+                let cancellation = payload.get_cancellation();
+                let previous = self.pending.remove(&cancellation);
+                self.journal.record_entry(|index| &mut index.pending, cancellation, previous);
+            },
+
+            _ => {}
+
+        }
+
+    }
+
+}
+
+impl Journaled for SettlementIndex{
+    fn get_journal(&mut self) -> &mut Journal<SettlementIndex>{
+        &mut self.journal
+    }
+}
+
+#[test]
+fn test_settlement_bytes(){
+
+    use blockchain::block::BlockId;
+    use blockchain::transactions::TxIndex;
+
+    let cancellation = TxId::new(BlockId([0x01; 32]), TxIndex(2));
+    let settlement = Settlement::new(cancellation);
+    let bytes = settlement.as_bytes();
+    assert_eq!(bytes.len(), 34);
+    let decoded = Settlement::from_bytes(bytes.clone()).unwrap();
+    assert_eq!(decoded.get_cancellation(), cancellation);
+    assert!(Settlement::from_bytes(bytes[1..].to_vec()).is_err());
+
+}
+
+#[test]
+fn test_settlement(){
+
+    use blockchain::txtypes::allocation::WorkloadAllocation;
+    use blockchain::txtypes::coupon::Appropriation;
+    use blockchain::txtypes::coupon::CouponClaim;
+    use blockchain::txtypes::production::ProductionOutput;
+    use blockchain::txtypes::resource::Collectivization;
+    use blockchain::txtypes::revision::FinalRevision;
+    use blockchain::txtypes::revision::REVISION_PERIOD;
+    use blockchain::txtypes::trust::TrustCancel;
+    use blockchain::txtypes::trust::TrustEstablish;
+    use blockchain::txtypes::TestChain;
+    use blockchain::txtypes::signed_transaction;
+    use blockchain::txtypes::register_c2i_collective;
+    use blockchain::txtypes::define_product_type;
+    use blockchain::txtypes::start_production;
+    use blockchain::txtypes::keypair;
+
+    const P: u64 = REVISION_PERIOD;
+
+    let (secret_a, pubkey_a) = keypair(0x0A);
+    let (secret_b, pubkey_b) = keypair(0x0B);
+    let (secret_c, pubkey_c) = keypair(0x0C);
+    let (secret_w, wallet) = keypair(0x01);
+
+    let establish = |secret_x: &[u8; 64], pubkey_x, secret_y: &[u8; 64], pubkey_y| {
+        let payload = TrustEstablish::new(pubkey_x, pubkey_y);
+        signed_transaction(TxPayload::TrustEstablish(payload), &[secret_x, secret_y])
+    };
+    let cancel = |secret_key: &[u8; 64], establishment| {
+        let payload = TrustCancel::new(establishment);
+        signed_transaction(TxPayload::TrustCancel(payload), &[secret_key])
+    };
+    let settle = |signers: &[&[u8]], cancellation| {
+        let payload = Settlement::new(cancellation);
+        signed_transaction(TxPayload::Settlement(payload), signers)
+    };
+    let appropriation = || {
+        let payload = Appropriation::new(pubkey_b, pubkey_a, 60);
+        signed_transaction(TxPayload::Appropriation(payload), &[&secret_w])
+    };
+
+    // a, b and c trust each other, the wallet holds
+    // coupons issued by a and b holds collectivized
+    // and produced stocks
+
+    let mut chain = TestChain::new();
+    let ids = chain.append(1, vec![register_c2i_collective(&secret_a),
+                                   register_c2i_collective(&secret_b),
+                                   register_c2i_collective(&secret_c),
+                                   establish(&secret_a, pubkey_a, &secret_b, pubkey_b),
+                                   establish(&secret_b, pubkey_b, &secret_c, pubkey_c),
+                                   establish(&secret_a, pubkey_a, &secret_c, pubkey_c)]).unwrap();
+    let (trust_ab, trust_ac) = (ids[3], ids[5]);
+    let (bread, definition) = define_product_type(&secret_b, "bread");
+    chain.append(2, vec![definition]).unwrap();
+
+    let production_start = start_production(&mut chain, &secret_a, 10);
+    let payload = WorkloadAllocation::new(production_start, wallet, 480, false);
+    let workload = chain.append(P + 1, vec![signed_transaction(TxPayload::WorkloadAllocation(payload),
+                                                               &[&secret_a])]).unwrap()[0];
+
+    let payload = FinalRevision::new(P, 2 * P, 480, 0, 0);
+    let revision = chain.append(2 * P + 1,
+                                vec![Transaction::new(TxPayload::FinalRevision(payload),
+                                                      vec![])]).unwrap()[0];
+
+    let payload = CouponClaim::new(workload, revision, 480);
+    let payload_b = Collectivization::new(bread, 5);
+    chain.append(2 * P + 2, vec![signed_transaction(TxPayload::CouponClaim(payload), &[&secret_w]),
+                                 signed_transaction(TxPayload::Collectivization(payload_b),
+                                                    &[&secret_b])]).unwrap();
+
+    let production_b = start_production(&mut chain, &secret_b, 2 * P + 3);
+    let payload = WorkloadAllocation::new(production_b, [0x02; 32], 90, false);
+    chain.append(2 * P + 6, vec![signed_transaction(TxPayload::WorkloadAllocation(payload),
+                                                    &[&secret_b])]).unwrap();
+    let payload = ProductionOutput::new(production_b, bread, 1, 90, false);
+    chain.append(2 * P + 7, vec![signed_transaction(TxPayload::ProductionOutput(payload),
+                                                    &[&secret_b])]).unwrap();
+
+    // cancellations that don't split a subsystem
+    // don't need a settlement
+
+    let cancellation = chain.append(2 * P + 8, vec![cancel(&secret_c, trust_ac)]).unwrap()[0];
+    assert!(chain.indexes.trust.get_split(cancellation).is_none());
+    assert!(chain.indexes.settlements.get_pending(cancellation).is_none());
+
+    let cancellation = chain.append(2 * P + 9, vec![cancel(&secret_a, trust_ab)]).unwrap()[0];
+    assert_eq!(chain.indexes.trust.get_split(cancellation), Some((pubkey_a, pubkey_b)));
+    {
+        let pending = chain.indexes.settlements.get_pending(cancellation).unwrap();
+        let (side_a, side_b) = (pending.get_side_a(), pending.get_side_b());
+        assert_eq!(side_a.subsystem.len(), 1);
+        assert_eq!((side_a.coupons, side_a.value), (480, 0));
+        assert!(side_b.subsystem.contains(&pubkey_c));
+        assert_eq!((side_b.coupons, side_b.value), (0, 90));
+    }
+
+    // reconnecting the subsystems doesn't resume
+    // appropriations across them
+
+    match chain.append(2 * P + 10, vec![appropriation()]){
+        Err(VerificationErrorReason::MissingTrust) => {},
+        result => panic!("Unexpected result {:?}", result)
+    }
+
+    chain.append(2 * P + 10, vec![establish(&secret_a, pubkey_a, &secret_c, pubkey_c)]).unwrap();
+    match chain.append(2 * P + 11, vec![appropriation()]){
+        Err(VerificationErrorReason::PendingSettlement) => {},
+        result => panic!("Unexpected result {:?}", result)
+    }
+
+    // both collectives of the cancelled trust accept
+    // the settlement
+
+    match chain.append(2 * P + 11, vec![settle(&[&secret_a], cancellation)]){
+        Err(VerificationErrorReason::InvalidTxSigners) => {},
+        result => panic!("Unexpected result {:?}", result)
+    }
+    match chain.append(2 * P + 11, vec![settle(&[&secret_a, &secret_b], trust_ab)]){
+        Err(VerificationErrorReason::InvalidTxRef(tx_id)) => assert_eq!(tx_id, trust_ab),
+        result => panic!("Unexpected result {:?}", result)
+    }
+
+    chain.append(2 * P + 11, vec![settle(&[&secret_a, &secret_b], cancellation),
+                                  appropriation()]).unwrap();
+    assert_eq!(chain.indexes.wallets.balance(wallet), 420);
+
+    match chain.append(2 * P + 12, vec![settle(&[&secret_a, &secret_b], cancellation)]){
+        Err(VerificationErrorReason::InvalidTxRef(_)) => {},
+        result => panic!("Unexpected result {:?}", result)
+    }
+
+}
@@ -0,0 +1,563 @@
+//
+//    Stachanov
+//    Copyright (C) 2017 Stachanov Developer Collective
+//
+//    This file is part of Stachanov.
+//
+//    This program is free software: you can redistribute it and/or
+//    modify it under the terms of the GNU Affero General Public
+//    License, version 3, as published by the Free Software Foundation.
+//
+//    This program is distributed in the hope that it will be useful,
+//    but WITHOUT ANY WARRANTY; without even the implied warranty of
+//    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//    GNU Affero General Public License for more details.
+//
+//    You should have received a copy of the
+//               GNU Affero General Public License
+//    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+use blockchain::traits::BinFormat;
+use blockchain::traits::ChainStorage;
+use blockchain::transactions::Transaction;
+use blockchain::transactions::TxPayload;
+use blockchain::transactions::TxId;
+use blockchain::transactions::TxRelId;
+use blockchain::transactions::TxState;
+use blockchain::transactions::TxTotalRelState;
+use blockchain::transactions::read_tx_id;
+use blockchain::errors::BinFormatError;
+use blockchain::errors::VerificationError;
+use blockchain::errors::VerificationErrorReason;
+use blockchain::txtypes::TxContext;
+use blockchain::txtypes::verify_signers;
+use blockchain::journal::Journal;
+use blockchain::journal::Journaled;
+use blockchain::utils::BinReader;
+
+/// `TrustEstablish` establishes strong trust between two
+/// registered collectives. Trust is symmetric, so both
+/// collectives must sign the transaction (in the order of
+/// the payload). The establishment holds the 1:1
+/// `TrustCancellation` relationship, which is claimed
+/// when the trust gets cancelled.
+
+#[derive(Clone)]
+#[derive(Debug)]
+pub struct TrustEstablish{
+    collective_a: [u8; 32],
+    collective_b: [u8; 32]
+}
+
+impl TrustEstablish{
+
+    /// Creates a new `TrustEstablish`
+    ///
+    /// # Arguments
+    /// * `collective_a`: The public key of the first collective
+    /// * `collective_b`: The public key of the second collective
+
+    pub fn new(collective_a: [u8; 32], collective_b: [u8; 32]) -> TrustEstablish{
+        TrustEstablish{collective_a: collective_a, collective_b: collective_b}
+    }
+
+    /// Gets the public keys of both collectives
+
+    pub fn get_collectives(&self) -> ([u8; 32], [u8; 32]){
+        (self.collective_a, self.collective_b)
+    }
+
+    /// Verifies the establishment against the chain
+    ///
+    /// # Arguments
+    /// * `transaction`: The transaction holding the establishment
+    /// * `context`: The verification context
+
+    pub fn verify<C>(&self,
+                     transaction: &Transaction,
+                     context: &mut TxContext<C>)
+                     -> Result<(), VerificationError> where C: ChainStorage{
+
+        verify_signers(transaction, &[self.collective_a, self.collective_b])?;
+
+        let indexes = context.get_indexes();
+
+        if indexes.collectives.get_registration(self.collective_a).is_none() ||
+           indexes.collectives.get_registration(self.collective_b).is_none(){
+            let reason = VerificationErrorReason::UnknownCollective;
+            return Err(VerificationError::new(reason))
+        }
+
+        if self.collective_a == self.collective_b ||
+           indexes.trust.strong_trust(self.collective_a, self.collective_b){
+            let reason = VerificationErrorReason::InvalidTrust;
+            return Err(VerificationError::new(reason))
+        }
+
+        Ok(())
+
+    }
+
+    /// Returns the initial state of the establishment
+
+    pub fn initial_state(&self) -> TxState{
+
+        let mut tx_state = TxState::new(TxTotalRelState::Claimable);
+        tx_state.add_one_to_one_rel(TxRelId::TrustCancellation)
+                .expect("Fresh TxState already had a relationship");
+        tx_state
+
+    }
+
+}
+
+impl BinFormat<TrustEstablish> for TrustEstablish{
+
+    // The byte format of TrustEstablish is:
+
+    //    field            length
+    //  .------------------------.
+    //  | collective_a    | 32   |
+    //  |------------------------|
+    //  | collective_b    | 32   |
+    //  '------------------------'
+
+    /// Returns the establishment as an u8 vector
+
+    fn as_bytes(&self) -> Vec<u8>{
+        [&self.collective_a[..], &self.collective_b[..]].concat()
+    }
+
+    /// Creates a new establishment from a byte vector.
+    ///
+    /// * `bytes`: A byte vector
+
+    fn from_bytes(bytes: Vec<u8>) -> Result<TrustEstablish, BinFormatError>{
+
+        let mut reader = BinReader::new(&bytes);
+        let collective_a = reader.read_hash()?;
+        let collective_b = reader.read_hash()?;
+        reader.finish()?;
+
+        Ok(TrustEstablish::new(collective_a, collective_b))
+
+    }
+
+}
+
+/// `TrustCancel` cancels the strong trust created by a
+/// `TrustEstablish` transaction. It must be signed by
+/// one of the two collectives and claims the
+/// `TrustCancellation` relationship of the establishment,
+/// so trust can only be cancelled once.
+
+#[derive(Clone)]
+#[derive(Debug)]
+pub struct TrustCancel{
+    establishment: TxId
+}
+
+impl TrustCancel{
+
+    /// Creates a new `TrustCancel`
+    ///
+    /// # Arguments
+    /// * `establishment`: The id of the `TrustEstablish` transaction
+
+    pub fn new(establishment: TxId) -> TrustCancel{
+        TrustCancel{establishment: establishment}
+    }
+
+    /// Gets the id of the cancelled establishment
+
+    pub fn get_establishment(&self) -> TxId{
+        self.establishment
+    }
+
+    /// Verifies the cancellation against the chain
+    ///
+    /// # Arguments
+    /// * `transaction`: The transaction holding the cancellation
+    /// * `context`: The verification context
+
+    pub fn verify<C>(&self,
+                     transaction: &Transaction,
+                     context: &mut TxContext<C>)
+                     -> Result<(), VerificationError> where C: ChainStorage{
+
+        let establishment = context.get_transaction(self.establishment)?;
+
+        let (collective_a, collective_b) = match *establishment.get_payload(){
+            TxPayload::TrustEstablish(ref payload) => payload.get_collectives(),
+            _ => {
+                let reason = VerificationErrorReason::InvalidTxRef(self.establishment);
+                return Err(VerificationError::new(reason))
+            }
+        };
+
+        if verify_signers(transaction, &[collective_a]).is_err(){
+            verify_signers(transaction, &[collective_b])?;
+        }
+
+        context.claim_rel(self.establishment, TxRelId::TrustCancellation)
+
+    }
+
+}
+
+impl BinFormat<TrustCancel> for TrustCancel{
+
+    // The byte format of TrustCancel is:
+
+    //    field            length
+    //  .------------------------.
+    //  | establishment   | 34   |
+    //  '------------------------'
+
+    /// Returns the cancellation as an u8 vector
+
+    fn as_bytes(&self) -> Vec<u8>{
+        self.establishment.as_bytes()
+    }
+
+    /// Creates a new cancellation from a byte vector.
+    ///
+    /// * `bytes`: A byte vector
+
+    fn from_bytes(bytes: Vec<u8>) -> Result<TrustCancel, BinFormatError>{
+
+        let mut reader = BinReader::new(&bytes);
+        let establishment = read_tx_id(&mut reader)?;
+        reader.finish()?;
+
+        Ok(TrustCancel::new(establishment))
+
+    }
+
+}
+
+// ------------------------------------------------------------------------
+
+/// `TrustGraph` is the undirected graph of strong trust
+/// between collectives. It is updated with every applied
+/// block. Two collectives established *strong trust* when
+/// they are connected by an edge and *weak trust* when
+/// there is a path between them. Every connected component
+/// of the graph forms an economic subsystem.
+///
+/// The graph also records the cancellations that split a
+/// subsystem into two, as the split subsystems must reach
+/// a settlement.
+
+pub struct TrustGraph{
+    edges: HashMap<[u8; 32], HashSet<[u8; 32]>>,
+    establishments: HashMap<TxId, ([u8; 32], [u8; 32])>,
+    splits: HashMap<TxId, ([u8; 32], [u8; 32])>,
+    journal: Journal<TrustGraph>
+}
+
+impl TrustGraph{
+
+    /// Creates a new empty `TrustGraph`
+
+    pub fn new() -> TrustGraph{
+        TrustGraph{
+            edges: HashMap::new(),
+            establishments: HashMap::new(),
+            splits: HashMap::new(),
+            journal: Journal::new()
+        }
+    }
+
+    /// Checks if two collectives established strong trust
+    ///
+    /// # Arguments
+    /// * `collective_a`: The public key of the first collective
+    /// * `collective_b`: The public key of the second collective
+
+    pub fn strong_trust(&self, collective_a: [u8; 32], collective_b: [u8; 32]) -> bool{
+
+        match self.edges.get(&collective_a){
+            Some(neighbours) => neighbours.contains(&collective_b),
+            None => false
+        }
+
+    }
+
+    /// Checks if two collectives established weak trust,
+    /// that is if they belong to the same subsystem
+    ///
+    /// # Arguments
+    /// * `collective_a`: The public key of the first collective
+    /// * `collective_b`: The public key of the second collective
+
+    pub fn weak_trust(&self, collective_a: [u8; 32], collective_b: [u8; 32]) -> bool{
+        self.subsystem_of(collective_a).contains(&collective_b)
+    }
+
+    /// Returns the economic subsystem of a collective, that
+    /// is all collectives (including the collective itself)
+    /// it established weak trust with
+    ///
+    /// # Arguments
+    /// * `collective`: The public key of the collective
+
+    pub fn subsystem_of(&self, collective: [u8; 32]) -> HashSet<[u8; 32]>{
+
+        let mut subsystem = HashSet::new();
+        let mut pending = vec![collective];
+        subsystem.insert(collective);
+
+        while let Some(current) = pending.pop(){
+            if let Some(neighbours) = self.edges.get(&current){
+                for neighbour in neighbours{
+                    if subsystem.insert(*neighbour){
+                        pending.push(*neighbour);
+                    }
+                }
+            }
+        }
+
+        subsystem
+
+    }
+
+    /// Returns the two collectives of the cancelled trust, if
+    /// the cancellation split their subsystem into two
+    ///
+    /// # Arguments
+    /// * `cancellation`: The id of the `TrustCancel` transaction
+
+    pub fn get_split(&self, cancellation: TxId) -> Option<([u8; 32], [u8; 32])>{
+        self.splits.get(&cancellation).cloned()
+    }
+
+    /// Updates the graph with a verified transaction
+    ///
+    /// # Arguments
+    /// * `tx_id`: The id of the transaction
+    /// * `transaction`: The verified transaction
+
+    pub fn apply_transaction(&mut self, tx_id: TxId, transaction: &Transaction){
+
+        match *transaction.get_payload(){
+
+            TxPayload::TrustEstablish(ref payload) => {
+                let (collective_a, collective_b) = payload.get_collectives();
+                self.record_edges(collective_a, collective_b);
+                self.edges.entry(collective_a).or_default().insert(collective_b);
+                self.edges.entry(collective_b).or_default().insert(collective_a);
+                let previous = self.establishments.insert(tx_id, (collective_a, collective_b));
+                self.journal.record_entry(|index| &mut index.establishments, tx_id, previous);
+            },
+
+            TxPayload::TrustCancel(ref payload) => {
+                let establishment = payload.get_establishment();
+                let previous = self.establishments.remove(&establishment);
+                self.journal.record_entry(|index| &mut index.establishments, establishment, previous);
+                if let Some((collective_a, collective_b)) = previous{
+                    self.record_edges(collective_a, collective_b);
+                    self.remove_edge(collective_a, collective_b);
+                    self.remove_edge(collective_b, collective_a);
+                    if !self.weak_trust(collective_a, collective_b){
+                        let previous = self.splits.insert(tx_id, (collective_a, collective_b));
+                        self.journal.record_entry(|index| &mut index.splits, tx_id, previous);
+                    }
+                }
+            },
+
+            _ => {}
+
+        }
+
+    }
+
+    /// Journals the neighbours of two collectives
+    /// before the edge between them changes
+
+    fn record_edges(&mut self, collective_a: [u8; 32], collective_b: [u8; 32]){
+        for collective in [collective_a, collective_b]{
+            let previous = self.edges.get(&collective).cloned();
+            self.journal.record_entry(|index| &mut index.edges, collective, previous);
+        }
+    }
+
+    /// Removes the directed half of an edge
+
+    fn remove_edge(&mut self, from: [u8; 32], to: [u8; 32]){
+
+        let is_empty = match self.edges.get_mut(&from){
+            Some(neighbours) => {
+                neighbours.remove(&to);
+                neighbours.is_empty()
+            },
+            None => false
+        };
+
+        if is_empty{
+            self.edges.remove(&from);
+        }
+
+    }
+
+}
+
+impl Journaled for TrustGraph{
+    fn get_journal(&mut self) -> &mut Journal<TrustGraph>{
+        &mut self.journal
+    }
+}
+
+#[test]
+fn test_trust_to_bytes_from_bytes(){
+
+    use blockchain::block::BlockId;
+    use blockchain::transactions::TxIndex;
+
+    let establish = TrustEstablish::new([0x01; 32], [0x02; 32]);
+    let rebuild = TrustEstablish::from_bytes(establish.as_bytes()).unwrap();
+    assert_eq!(rebuild.get_collectives(), ([0x01; 32], [0x02; 32]));
+
+    let establishment = TxId::new(BlockId([0x0A; 32]), TxIndex(1));
+    let cancel = TrustCancel::new(establishment);
+    let rebuild = TrustCancel::from_bytes(cancel.as_bytes()).unwrap();
+    assert_eq!(rebuild.get_establishment(), establishment);
+
+    assert!(TrustEstablish::from_bytes(vec![0x01; 63]).is_err(),
+            "Truncated TrustEstablish was accepted");
+    assert!(TrustCancel::from_bytes(vec![0x01; 35]).is_err(),
+            "TrustCancel with trailing data was accepted");
+
+}
+
+#[test]
+fn test_trust_graph(){
+
+    use blockchain::block::Block;
+    use blockchain::block::BlockError;
+    use blockchain::block::BlockErrorReason;
+    use blockchain::transactions::TxIndex;
+    use blockchain::transactions::BadClaimReason;
+    use blockchain::storages::memory::MemoryChainStorage;
+    use blockchain::processor::ChainIndexes;
+    use blockchain::processor::apply_block;
+    use blockchain::txtypes::signed_transaction;
+    use blockchain::txtypes::register_collective;
+    use blockchain::txtypes::keypair;
+
+    let (secret_a, pubkey_a) = keypair(0x0A);
+    let (secret_b, pubkey_b) = keypair(0x0B);
+    let (secret_c, pubkey_c) = keypair(0x0C);
+    let (secret_d, pubkey_d) = keypair(0x0D);
+
+    let establish = |a: &[u8; 64], b: &[u8; 64], pubkey_a, pubkey_b| {
+        let payload = TrustEstablish::new(pubkey_a, pubkey_b);
+        signed_transaction(TxPayload::TrustEstablish(payload), &[a, b])
+    };
+
+    let verification_error = |result: Result<(), BlockError>| {
+        match result{
+            Err(BlockError{reason: BlockErrorReason::InvalidTransaction(_, err)}) => err.reason,
+            _ => panic!("Invalid trust transaction was accepted")
+        }
+    };
+
+    let mut storage = MemoryChainStorage::new();
+    let mut indexes = ChainIndexes::new();
+
+    let genesis = Block::new([0; 32], None, 1,
+                             vec![register_collective(&secret_a),
+                                  register_collective(&secret_b),
+                                  register_collective(&secret_c),
+                                  register_collective(&secret_d)]);
+    apply_block(&mut storage, &mut indexes, genesis.clone()).unwrap();
+
+    // a - b - c    d
+
+    let block_2 = Block::new([0; 32], Some(&genesis), 2,
+                             vec![establish(&secret_a, &secret_b, pubkey_a, pubkey_b),
+                                  establish(&secret_b, &secret_c, pubkey_b, pubkey_c)]);
+    let establishment_ab = TxId::new(block_2.get_id(), TxIndex(0));
+    apply_block(&mut storage, &mut indexes, block_2.clone()).unwrap();
+
+    let trust = &indexes.trust;
+    assert!(trust.strong_trust(pubkey_a, pubkey_b));
+    assert!(trust.strong_trust(pubkey_b, pubkey_a));
+    assert!(!trust.strong_trust(pubkey_a, pubkey_c));
+    assert!(trust.weak_trust(pubkey_a, pubkey_c));
+    assert!(!trust.weak_trust(pubkey_a, pubkey_d));
+    assert_eq!(trust.subsystem_of(pubkey_c).len(), 3);
+    assert_eq!(trust.subsystem_of(pubkey_d).len(), 1);
+
+    // trust needs both signatures, registered collectives
+    // and can't be established twice
+
+    let one_signer = signed_transaction(TxPayload::TrustEstablish(
+                                            TrustEstablish::new(pubkey_a, pubkey_d)),
+                                        &[&secret_a]);
+    let block = Block::new([0; 32], Some(&block_2), 3, vec![one_signer]);
+    match verification_error(apply_block(&mut storage, &mut indexes, block)){
+        VerificationErrorReason::InvalidTxSigners => {},
+        reason => panic!("Unexpected reason {:?}", reason)
+    }
+
+    let (secret_x, pubkey_x) = keypair(0x0F);
+    let block = Block::new([0; 32], Some(&block_2), 3,
+                           vec![establish(&secret_a, &secret_x, pubkey_a, pubkey_x)]);
+    match verification_error(apply_block(&mut storage, &mut indexes, block)){
+        VerificationErrorReason::UnknownCollective => {},
+        reason => panic!("Unexpected reason {:?}", reason)
+    }
+
+    let block = Block::new([0; 32], Some(&block_2), 3,
+                           vec![establish(&secret_b, &secret_a, pubkey_b, pubkey_a)]);
+    match verification_error(apply_block(&mut storage, &mut indexes, block)){
+        VerificationErrorReason::InvalidTrust => {},
+        reason => panic!("Unexpected reason {:?}", reason)
+    }
+
+    // cancellation by an outsider is rejected
+
+    let cancel = |secret_key: &[u8; 64]| {
+        let payload = TrustCancel::new(establishment_ab);
+        signed_transaction(TxPayload::TrustCancel(payload), &[secret_key])
+    };
+
+    let block = Block::new([0; 32], Some(&block_2), 3, vec![cancel(&secret_c)]);
+    match verification_error(apply_block(&mut storage, &mut indexes, block)){
+        VerificationErrorReason::InvalidTxSigners => {},
+        reason => panic!("Unexpected reason {:?}", reason)
+    }
+
+    // b cancels the trust to a, which splits the graph
+
+    let block_3 = Block::new([0; 32], Some(&block_2), 3, vec![cancel(&secret_b)]);
+    apply_block(&mut storage, &mut indexes, block_3.clone()).unwrap();
+
+    assert!(!indexes.trust.strong_trust(pubkey_a, pubkey_b));
+    assert!(!indexes.trust.weak_trust(pubkey_a, pubkey_c));
+    assert!(indexes.trust.weak_trust(pubkey_b, pubkey_c));
+
+    // trust can only be cancelled once
+
+    let block = Block::new([0; 32], Some(&block_3), 4, vec![cancel(&secret_a)]);
+    match verification_error(apply_block(&mut storage, &mut indexes, block)){
+        VerificationErrorReason::BadClaim(BadClaimReason::RelClaimed(..)) => {},
+        reason => panic!("Unexpected reason {:?}", reason)
+    }
+
+    // ... but can be established again
+
+    let block_4 = Block::new([0; 32], Some(&block_3), 4,
+                             vec![establish(&secret_a, &secret_b, pubkey_a, pubkey_b)]);
+    apply_block(&mut storage, &mut indexes, block_4).unwrap();
+    assert!(indexes.trust.weak_trust(pubkey_a, pubkey_c));
+
+    let rebuilt = ChainIndexes::rebuild(&storage, indexes.params);
+    assert!(rebuilt.trust.strong_trust(pubkey_a, pubkey_b));
+    assert!(!rebuilt.trust.weak_trust(pubkey_a, pubkey_d));
+
+}
@@ -0,0 +1,668 @@
+//
+//    Stachanov
+//    Copyright (C) 2017 Stachanov Developer Collective
+//
+//    This file is part of Stachanov.
+//
+//    This program is free software: you can redistribute it and/or
+//    modify it under the terms of the GNU Affero General Public
+//    License, version 3, as published by the Free Software Foundation.
+//
+//    This program is distributed in the hope that it will be useful,
+//    but WITHOUT ANY WARRANTY; without even the implied warranty of
+//    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//    GNU Affero General Public License for more details.
+//
+//    You should have received a copy of the
+//               GNU Affero General Public License
+//    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+// This module implements production votes. Large projects
+// bind a lot of labor of a subsystem, so they need to be
+// legitimized democratically: when the value estimation of
+// an order exceeds the vote threshold of the consensus
+// parameters, the producing collective opens a production
+// vote between the estimation and the production start. Every
+// collective of the producer's subsystem can cast one ballot
+// until the production starts. The vote passes, when enough
+// collectives took part (the vote quorum) and enough of the
+// ballots approve it (the vote approval). Ballots are also
+// cast on classification proposals (see `governance`), which
+// are counted by the same rules.
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+use blockchain::traits::BinFormat;
+use blockchain::traits::ChainStorage;
+use blockchain::transactions::Transaction;
+use blockchain::transactions::TxPayload;
+use blockchain::transactions::TxId;
+use blockchain::transactions::TxRelId;
+use blockchain::transactions::TxState;
+use blockchain::transactions::TxTotalRelState;
+use blockchain::transactions::read_tx_id;
+use blockchain::errors::BinFormatError;
+use blockchain::errors::VerificationError;
+use blockchain::errors::VerificationErrorReason;
+use blockchain::txtypes::TxContext;
+use blockchain::txtypes::verify_signers;
+use blockchain::txtypes::read_bool;
+use blockchain::txtypes::production::get_order;
+use blockchain::txtypes::production::get_estimation;
+use blockchain::txtypes::trust::TrustGraph;
+use blockchain::params::ConsensusParams;
+use blockchain::journal::Journal;
+use blockchain::journal::Journaled;
+use blockchain::utils::BinReader;
+
+/// `ProductionVote` opens the vote on a value estimation
+/// above the vote threshold. It is signed by the producing
+/// collective and claims the `EstimationVote` relationship
+/// of the estimation, so every estimation is voted on at
+/// most once. The electorate is the subsystem of the
+/// producing collective when the vote is opened. The vote
+/// is closed once the production of the estimation starts.
+///
+/// Production votes hold the following relationships:
+///
+/// * `VoteBallots` (1:n): Claimed by the ballots
+
+#[derive(Clone)]
+#[derive(Debug)]
+pub struct ProductionVote{
+    estimation: TxId
+}
+
+impl ProductionVote{
+
+    /// Creates a new `ProductionVote`
+    ///
+    /// # Arguments
+    /// * `estimation`: The id of the value estimation
+
+    pub fn new(estimation: TxId) -> ProductionVote{
+        ProductionVote{estimation: estimation}
+    }
+
+    /// Gets the id of the value estimation
+
+    pub fn get_estimation(&self) -> TxId{
+        self.estimation
+    }
+
+    /// Verifies the production vote against the chain
+    ///
+    /// # Arguments
+    /// * `transaction`: The transaction holding the production vote
+    /// * `context`: The verification context
+
+    pub fn verify<C>(&self,
+                     transaction: &Transaction,
+                     context: &mut TxContext<C>)
+                     -> Result<(), VerificationError> where C: ChainStorage{
+
+        let estimation = get_estimation(context, self.estimation)?;
+        let order = get_order(context, estimation.get_order())?;
+        verify_signers(transaction, &[order.get_producer()])?;
+
+        if estimation.get_value() <= context.get_params().vote_threshold{
+            let reason = VerificationErrorReason::InvalidAmount;
+            return Err(VerificationError::new(reason))
+        }
+
+        context.claim_rel(self.estimation, TxRelId::EstimationVote)
+
+    }
+
+    /// Returns the initial state of the production vote
+
+    pub fn initial_state(&self) -> TxState{
+
+        let mut tx_state = TxState::new(TxTotalRelState::Claimable);
+        tx_state.add_one_to_many_rel(TxRelId::VoteBallots)
+                .expect("Fresh TxState already had a relationship");
+        tx_state
+
+    }
+
+}
+
+impl BinFormat<ProductionVote> for ProductionVote{
+
+    // The byte format of ProductionVote is:
+
+    //    field            length
+    //  .------------------------.
+    //  | estimation      | 34   |
+    //  '------------------------'
+
+    /// Returns the production vote as an u8 vector
+
+    fn as_bytes(&self) -> Vec<u8>{
+        self.estimation.as_bytes()
+    }
+
+    /// Creates a new production vote from a byte vector.
+    ///
+    /// * `bytes`: A byte vector
+
+    fn from_bytes(bytes: Vec<u8>) -> Result<ProductionVote, BinFormatError>{
+
+        let mut reader = BinReader::new(&bytes);
+        let estimation = read_tx_id(&mut reader)?;
+        reader.finish()?;
+
+        Ok(ProductionVote::new(estimation))
+
+    }
+
+}
+
+/// `Ballot` approves or rejects a production vote or a
+/// classification proposal. It is signed by a collective of
+/// the electorate and claims the `VoteBallots` relationship
+/// of the vote. Every collective casts at most one ballot
+/// per vote.
+
+#[derive(Clone)]
+#[derive(Debug)]
+pub struct Ballot{
+    vote: TxId,
+    approve: bool
+}
+
+impl Ballot{
+
+    /// Creates a new `Ballot`
+    ///
+    /// # Arguments
+    /// * `vote`: The id of the production vote or the
+    ///         classification proposal
+    /// * `approve`: Whether the ballot approves the vote
+
+    pub fn new(vote: TxId, approve: bool) -> Ballot{
+        Ballot{vote: vote, approve: approve}
+    }
+
+    /// Gets the id of the production vote
+    /// or the classification proposal
+
+    pub fn get_vote(&self) -> TxId{
+        self.vote
+    }
+
+    /// Checks if the ballot approves the production
+    /// or the classification
+
+    pub fn approves(&self) -> bool{
+        self.approve
+    }
+
+    /// Verifies the ballot against the chain
+    ///
+    /// # Arguments
+    /// * `transaction`: The transaction holding the ballot
+    /// * `context`: The verification context
+
+    pub fn verify<C>(&self,
+                     transaction: &Transaction,
+                     context: &mut TxContext<C>)
+                     -> Result<(), VerificationError> where C: ChainStorage{
+
+        let signer_pubkeys = transaction.get_signer_pubkeys();
+        if signer_pubkeys.len() != 1{
+            let reason = VerificationErrorReason::InvalidTxSigners;
+            return Err(VerificationError::new(reason))
+        }
+
+        match context.get_indexes().votes.get_vote(self.vote){
+            Some(vote) => {
+                if vote.closed ||
+                   vote.deadline.is_some_and(|deadline| context.get_header().get_index() >= deadline){
+                    let reason = VerificationErrorReason::VotingClosed;
+                    return Err(VerificationError::new(reason))
+                }
+                if !vote.electorate.contains(&signer_pubkeys[0]){
+                    let reason = VerificationErrorReason::MissingTrust;
+                    return Err(VerificationError::new(reason))
+                }
+                if vote.voters.contains(&signer_pubkeys[0]){
+                    let reason = VerificationErrorReason::DuplicateBallot;
+                    return Err(VerificationError::new(reason))
+                }
+            },
+            None => {
+                let reason = VerificationErrorReason::InvalidTxRef(self.vote);
+                return Err(VerificationError::new(reason))
+            }
+        }
+
+        context.claim_rel(self.vote, TxRelId::VoteBallots)
+
+    }
+
+}
+
+impl BinFormat<Ballot> for Ballot{
+
+    // The byte format of Ballot is:
+
+    //    field            length
+    //  .------------------------.
+    //  | vote            | 34   |
+    //  |------------------------|
+    //  | approve         | 1    |  (bool)
+    //  '------------------------'
+
+    /// Returns the ballot as an u8 vector
+
+    fn as_bytes(&self) -> Vec<u8>{
+        [&self.vote.as_bytes()[..], &[self.approve as u8][..]].concat()
+    }
+
+    /// Creates a new ballot from a byte vector.
+    ///
+    /// * `bytes`: A byte vector
+
+    fn from_bytes(bytes: Vec<u8>) -> Result<Ballot, BinFormatError>{
+
+        let mut reader = BinReader::new(&bytes);
+        let vote = read_tx_id(&mut reader)?;
+        let approve = read_bool(&mut reader, "approve")?;
+        reader.finish()?;
+
+        Ok(Ballot::new(vote, approve))
+
+    }
+
+}
+
+// ------------------------------------------------------------------------
+
+/// `VoteState` is the state of a production vote or
+/// of the vote on a classification proposal
+
+#[derive(Clone)]
+#[derive(Debug)]
+pub struct VoteState{
+
+    /// The collectives allowed to cast a ballot
+    pub electorate: HashSet<[u8; 32]>,
+
+    /// The collectives that cast a ballot
+    pub voters: HashSet<[u8; 32]>,
+
+    /// The number of approving ballots
+    pub approvals: u64,
+
+    /// The block index from which on no more
+    /// ballots are accepted
+    pub deadline: Option<u64>,
+
+    /// Whether the vote was closed by the production start
+    pub closed: bool
+
+}
+
+impl VoteState{
+
+    /// Checks if the vote reached the quorum and the
+    /// ballots approve the production or the classification
+    ///
+    /// # Arguments
+    /// * `params`: The consensus parameters of the chain
+
+    pub fn is_passed(&self, params: &ConsensusParams) -> bool{
+
+        let electorate = self.electorate.len() as u64;
+        let ballots = self.voters.len() as u64;
+
+        ballots.saturating_mul(100) >= electorate.saturating_mul(params.vote_quorum_percent) &&
+        self.approvals.saturating_mul(100) > ballots.saturating_mul(params.vote_approval_percent)
+
+    }
+
+}
+
+/// `VoteIndex` keeps track of the production votes, the
+/// classification proposals and their ballots. It is updated
+/// with every applied block and must be updated after the
+/// trust graph.
+
+pub struct VoteIndex{
+    votes: HashMap<TxId, VoteState>,
+    estimations: HashMap<TxId, TxId>,
+    journal: Journal<VoteIndex>
+}
+
+impl VoteIndex{
+
+    /// Creates a new empty `VoteIndex`
+
+    pub fn new() -> VoteIndex{
+        VoteIndex{
+            votes: HashMap::new(),
+            estimations: HashMap::new(),
+            journal: Journal::new()
+        }
+    }
+
+    /// Returns the state of a production vote or of the
+    /// vote on a classification proposal
+    ///
+    /// # Arguments
+    /// * `vote`: The id of the production vote or the
+    ///         classification proposal
+
+    pub fn get_vote(&self, vote: TxId) -> Option<&VoteState>{
+        self.votes.get(&vote)
+    }
+
+    /// Checks if the production of an estimation was approved
+    /// by a production vote
+    ///
+    /// # Arguments
+    /// * `estimation`: The id of the value estimation
+    /// * `params`: The consensus parameters of the chain
+
+    pub fn is_passed(&self, estimation: TxId, params: &ConsensusParams) -> bool{
+        self.estimations.get(&estimation)
+                        .and_then(|vote| self.votes.get(vote))
+                        .is_some_and(|vote| vote.is_passed(params))
+    }
+
+    /// Updates the index with a verified transaction
+    ///
+    /// # Arguments
+    /// * `tx_id`: The id of the transaction
+    /// * `transaction`: The verified transaction
+    /// * `trust`: The trust graph of the chain
+
+    pub fn apply_transaction(&mut self, tx_id: TxId, transaction: &Transaction, trust: &TrustGraph){
+
+        match *transaction.get_payload(){
+
+            TxPayload::ProductionVote(ref payload) => {
+                let producer = transaction.get_signer_pubkeys()[0];
+                let vote = VoteState{
+                    electorate: trust.subsystem_of(producer),
+                    voters: HashSet::new(),
+                    approvals: 0,
+                    deadline: None,
+                    closed: false
+                };
+                let previous = self.votes.insert(tx_id, vote);
+                self.journal.record_entry(|index| &mut index.votes, tx_id, previous);
+                let estimation = payload.get_estimation();
+                let previous = self.estimations.insert(estimation, tx_id);
+                self.journal.record_entry(|index| &mut index.estimations, estimation, previous);
+            },
+
+            TxPayload::ClassificationProposal(ref payload) => {
+                let proposer = transaction.get_signer_pubkeys()[0];
+                let vote = VoteState{
+                    electorate: trust.subsystem_of(proposer),
+                    voters: HashSet::new(),
+                    approvals: 0,
+                    deadline: Some(payload.get_activation()),
+                    closed: false
+                };
+                let previous = self.votes.insert(tx_id, vote);
+                self.journal.record_entry(|index| &mut index.votes, tx_id, previous);
+            },
+
+            // the production start closes the vote,
+            // so the result can't change afterwards
+
+            TxPayload::ProductionStart(ref payload) => {
+                let vote_id = self.estimations.get(&payload.get_estimation()).cloned();
+                if let Some(vote) = vote_id.and_then(|vote_id| self.votes.get_mut(&vote_id)){
+                    let was_closed = vote.closed;
+                    vote.closed = true;
+                    self.journal.record(move |index: &mut VoteIndex| {
+                        if let Some(vote) = vote_id.and_then(|vote_id| index.votes.get_mut(&vote_id)){
+                            vote.closed = was_closed;
+                        }
+                    });
+                }
+            },
+
+            TxPayload::Ballot(ref payload) => {
+                let vote_id = payload.get_vote();
+                let voter = transaction.get_signer_pubkeys()[0];
+                let approves = payload.approves();
+                if let Some(vote) = self.votes.get_mut(&vote_id){
+
+                    // the electorate can be large, so the
+                    // ballot is undone instead of copying the vote
+
+                    let is_new_voter = vote.voters.insert(voter);
+                    if approves{
+                        vote.approvals += 1;
+                    }
+                    self.journal.record(move |index: &mut VoteIndex| {
+                        if let Some(vote) = index.votes.get_mut(&vote_id){
+                            if is_new_voter{
+                                vote.voters.remove(&voter);
+                            }
+                            if approves{
+                                vote.approvals -= 1;
+                            }
+                        }
+                    });
+                }
+            },
+
+            _ => {}
+
+        }
+
+    }
+
+}
+
+impl Journaled for VoteIndex{
+    fn get_journal(&mut self) -> &mut Journal<VoteIndex>{
+        &mut self.journal
+    }
+}
+
+#[test]
+fn test_vote_bytes(){
+
+    use blockchain::block::BlockId;
+    use blockchain::transactions::TxIndex;
+
+    let tx_id = TxId::new(BlockId([0x01; 32]), TxIndex(2));
+
+    let vote = ProductionVote::new(tx_id);
+    let bytes = vote.as_bytes();
+    assert_eq!(bytes.len(), 34);
+    let decoded = ProductionVote::from_bytes(bytes.clone()).unwrap();
+    assert_eq!(decoded.get_estimation(), tx_id);
+    assert!(ProductionVote::from_bytes(bytes[1..].to_vec()).is_err());
+
+    let ballot = Ballot::new(tx_id, true);
+    let mut bytes = ballot.as_bytes();
+    assert_eq!(bytes.len(), 34 + 1);
+    let decoded = Ballot::from_bytes(bytes.clone()).unwrap();
+    assert_eq!(decoded.get_vote(), tx_id);
+    assert!(decoded.approves());
+
+    bytes[34] = 0x02;
+    assert!(Ballot::from_bytes(bytes).is_err());
+
+}
+
+#[test]
+fn test_production_vote(){
+
+    use blockchain::traits::BlockStorage;
+    use blockchain::transactions::BadClaimReason;
+    use blockchain::txtypes::production::Order;
+    use blockchain::txtypes::production::ValueEstimation;
+    use blockchain::txtypes::production::ProductionStart;
+    use blockchain::txtypes::trust::TrustEstablish;
+    use blockchain::txtypes::TestChain;
+    use blockchain::txtypes::signed_transaction;
+    use blockchain::txtypes::register_collective;
+    use blockchain::txtypes::start_production;
+    use blockchain::txtypes::keypair;
+
+    let (secret_a, pubkey_a) = keypair(0x0A);
+    let (secret_b, pubkey_b) = keypair(0x0B);
+    let (secret_c, pubkey_c) = keypair(0x0C);
+    let (secret_d, _) = keypair(0x0D);
+    let sign = |payload, secret_key: &[u8; 64]| signed_transaction(payload, &[secret_key]);
+
+    // a, b and c form a subsystem, d is on its own
+
+    let trust_ab = TrustEstablish::new(pubkey_a, pubkey_b);
+    let trust_bc = TrustEstablish::new(pubkey_b, pubkey_c);
+    let mut chain = TestChain::new();
+    chain.append(1, vec![register_collective(&secret_a),
+                         register_collective(&secret_b),
+                         register_collective(&secret_c),
+                         register_collective(&secret_d),
+                         signed_transaction(TxPayload::TrustEstablish(trust_ab),
+                                            &[&secret_a, &secret_b]),
+                         signed_transaction(TxPayload::TrustEstablish(trust_bc),
+                                            &[&secret_b, &secret_c])]).unwrap();
+
+    let order = Order::new([0xFF; 32], 1, pubkey_a, pubkey_a, 2, None, false);
+    let order = chain.append(2, vec![sign(TxPayload::Order(order), &secret_a)]).unwrap()[0];
+    let estimation = ValueEstimation::new(order, ConsensusParams::default().vote_threshold + 1);
+    let estimation = chain.append(3, vec![sign(TxPayload::ValueEstimation(estimation), &secret_a)])
+                          .unwrap()[0];
+    let start = ProductionStart::new(estimation, false);
+    let start = sign(TxPayload::ProductionStart(start), &secret_a);
+
+    // large estimations can't be produced without a
+    // vote and small ones can't be voted on
+
+    match chain.append(4, vec![start.clone()]){
+        Err(VerificationErrorReason::VoteNotPassed) => {},
+        result => panic!("Unexpected result {:?}", result)
+    }
+
+    let small_start = start_production(&mut chain, &secret_a, 4);
+    let small_estimation = match *chain.storage.get_transaction(small_start).unwrap().get_payload(){
+        TxPayload::ProductionStart(ref payload) => payload.get_estimation(),
+        ref payload => panic!("Unexpected payload {:?}", payload)
+    };
+    let vote = ProductionVote::new(small_estimation);
+    match chain.append(10, vec![sign(TxPayload::ProductionVote(vote), &secret_a)]){
+        Err(VerificationErrorReason::InvalidAmount) => {},
+        result => panic!("Unexpected result {:?}", result)
+    }
+
+    let vote = ProductionVote::new(estimation);
+    match chain.append(10, vec![sign(TxPayload::ProductionVote(vote.clone()), &secret_b)]){
+        Err(VerificationErrorReason::InvalidTxSigners) => {},
+        result => panic!("Unexpected result {:?}", result)
+    }
+    match chain.append(10, vec![sign(TxPayload::ProductionVote(vote.clone()), &secret_a),
+                                sign(TxPayload::ProductionVote(vote.clone()), &secret_a)]){
+        Err(VerificationErrorReason::BadClaim(BadClaimReason::RelClaimed(TxRelId::EstimationVote, _))) => {},
+        result => panic!("Unexpected result {:?}", result)
+    }
+
+    let vote = chain.append(10, vec![sign(TxPayload::ProductionVote(vote), &secret_a)]).unwrap()[0];
+    assert_eq!(chain.indexes.votes.get_vote(vote).unwrap().electorate.len(), 3);
+
+    let ballot = |secret_key: &[u8; 64], approve| {
+        sign(TxPayload::Ballot(Ballot::new(vote, approve)), secret_key)
+    };
+
+    // only the subsystem votes and every collective once
+
+    match chain.append(11, vec![ballot(&secret_d, true)]){
+        Err(VerificationErrorReason::MissingTrust) => {},
+        result => panic!("Unexpected result {:?}", result)
+    }
+    match chain.append(11, vec![ballot(&secret_a, true), ballot(&secret_a, true)]){
+        Err(VerificationErrorReason::DuplicateBallot) => {},
+        result => panic!("Unexpected result {:?}", result)
+    }
+
+    // one ballot misses the quorum, a tie misses the approval
+
+    chain.append(11, vec![ballot(&secret_a, true)]).unwrap();
+    assert!(!chain.indexes.votes.is_passed(estimation, &chain.indexes.params));
+    chain.append(12, vec![ballot(&secret_b, false)]).unwrap();
+    assert!(!chain.indexes.votes.is_passed(estimation, &chain.indexes.params));
+
+    match chain.append(13, vec![start.clone()]){
+        Err(VerificationErrorReason::VoteNotPassed) => {},
+        result => panic!("Unexpected result {:?}", result)
+    }
+
+    chain.append(13, vec![ballot(&secret_c, true), start]).unwrap();
+    assert!(chain.indexes.votes.is_passed(estimation, &chain.indexes.params));
+
+    // the production start closed the vote
+
+    match chain.append(14, vec![ballot(&secret_a, false)]){
+        Err(VerificationErrorReason::VotingClosed) => {},
+        result => panic!("Unexpected result {:?}", result)
+    }
+
+}
+
+#[test]
+fn test_vote_params(){
+
+    use blockchain::txtypes::production::Order;
+    use blockchain::txtypes::production::ValueEstimation;
+    use blockchain::txtypes::production::ProductionStart;
+    use blockchain::txtypes::trust::TrustEstablish;
+    use blockchain::txtypes::TestChain;
+    use blockchain::txtypes::signed_transaction;
+    use blockchain::txtypes::register_collective;
+    use blockchain::txtypes::keypair;
+
+    let (secret_a, pubkey_a) = keypair(0x0A);
+    let (secret_b, pubkey_b) = keypair(0x0B);
+    let sign = |payload, secret_key: &[u8; 64]| signed_transaction(payload, &[secret_key]);
+
+    // small estimations need a vote, every collective must
+    // cast a ballot and one approval is enough
+
+    let params = ConsensusParams{vote_threshold: 100,
+                                 vote_quorum_percent: 100,
+                                 vote_approval_percent: 0,
+                                 ..ConsensusParams::default()};
+    let trust = TrustEstablish::new(pubkey_a, pubkey_b);
+    let mut chain = TestChain::with_params(params);
+    chain.append(1, vec![register_collective(&secret_a),
+                         register_collective(&secret_b),
+                         signed_transaction(TxPayload::TrustEstablish(trust),
+                                            &[&secret_a, &secret_b])]).unwrap();
+
+    let order = Order::new([0xFF; 32], 1, pubkey_a, pubkey_a, 2, None, false);
+    let order = chain.append(2, vec![sign(TxPayload::Order(order), &secret_a)]).unwrap()[0];
+    let estimation = ValueEstimation::new(order, 101);
+    let estimation = chain.append(3, vec![sign(TxPayload::ValueEstimation(estimation), &secret_a)])
+                          .unwrap()[0];
+    let start = ProductionStart::new(estimation, false);
+    let start = sign(TxPayload::ProductionStart(start), &secret_a);
+
+    match chain.append(4, vec![start.clone()]){
+        Err(VerificationErrorReason::VoteNotPassed) => {},
+        result => panic!("Unexpected result {:?}", result)
+    }
+
+    let vote = ProductionVote::new(estimation);
+    let vote = chain.append(4, vec![sign(TxPayload::ProductionVote(vote), &secret_a)]).unwrap()[0];
+    let ballot = |secret_key: &[u8; 64], approve| {
+        sign(TxPayload::Ballot(Ballot::new(vote, approve)), secret_key)
+    };
+
+    chain.append(5, vec![ballot(&secret_a, true)]).unwrap();
+    assert!(!chain.indexes.votes.is_passed(estimation, &params));
+
+    chain.append(6, vec![ballot(&secret_b, false), start]).unwrap();
+    assert!(chain.indexes.votes.is_passed(estimation, &params));
+
+}
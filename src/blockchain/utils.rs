@@ -21,6 +21,8 @@
 extern crate crypto;
 use self::crypto::sha3::Sha3;
 use self::crypto::digest::Digest;
+use blockchain::errors::BinFormatError;
+use blockchain::errors::BinFormatErrorReason;
 
 pub fn u16_to_u8le(input: u16) -> [u8; 2]{
 
@@ -45,6 +47,29 @@ pub fn u8le_to_u16(input: [u8; 2]) -> u16{
 
 }
 
+pub fn u32_to_u8le(input: u32) -> [u8; 4]{
+
+    let mut output = [0;4];
+    for i in 0..4{
+        output[i] = (input >> i*8) as u8
+    }
+    output
+
+}
+
+pub fn u8le_to_u32(input: [u8; 4]) -> u32{
+
+    let mut output: u32 = 0;
+    let mut i = 0;
+
+    while i < 4{
+        output += (input[i] as u32) << (8*i);
+        i += 1;
+    }
+    output
+
+}
+
 pub fn u64_to_u8le(input: u64) -> [u8; 8]{
 
     let mut output = [0;8];
@@ -78,3 +103,87 @@ pub fn sha3_256(input: &[u8]) -> [u8; 32]{
     output
 
 }
+
+/// `BinReader` reads fields from a byte slice in the little
+/// endian format used by all `BinFormat` implementations.
+/// Reading beyond the end of the slice fails with a
+/// `BinFormatError` with reason InvalidDataSize.
+
+pub struct BinReader<'a>{
+    bytes: &'a [u8],
+    offset: usize
+}
+
+impl<'a> BinReader<'a>{
+
+    /// Creates a new `BinReader` positioned at the
+    /// start of the supplied bytes
+
+    pub fn new(bytes: &'a [u8]) -> BinReader<'a>{
+        BinReader{bytes: bytes, offset: 0}
+    }
+
+    /// Reads the next `len` bytes
+
+    pub fn read_bytes(&mut self, len: usize) -> Result<&'a [u8], BinFormatError>{
+
+        if self.bytes.len() - self.offset < len{
+            let reason = BinFormatErrorReason::InvalidDataSize;
+            return Err(BinFormatError::new(reason))
+        }
+
+        let slice = &self.bytes[self.offset..self.offset + len];
+        self.offset += len;
+        Ok(slice)
+
+    }
+
+    pub fn read_u8(&mut self) -> Result<u8, BinFormatError>{
+        Ok(self.read_bytes(1)?[0])
+    }
+
+    pub fn read_u16(&mut self) -> Result<u16, BinFormatError>{
+        let mut value_u8le = [0; 2];
+        value_u8le.copy_from_slice(self.read_bytes(2)?);
+        Ok(u8le_to_u16(value_u8le))
+    }
+
+    pub fn read_u32(&mut self) -> Result<u32, BinFormatError>{
+        let mut value_u8le = [0; 4];
+        value_u8le.copy_from_slice(self.read_bytes(4)?);
+        Ok(u8le_to_u32(value_u8le))
+    }
+
+    pub fn read_u64(&mut self) -> Result<u64, BinFormatError>{
+        let mut value_u8le = [0; 8];
+        value_u8le.copy_from_slice(self.read_bytes(8)?);
+        Ok(u8le_to_u64(value_u8le))
+    }
+
+    pub fn read_hash(&mut self) -> Result<[u8; 32], BinFormatError>{
+        let mut hash = [0; 32];
+        hash.copy_from_slice(self.read_bytes(32)?);
+        Ok(hash)
+    }
+
+    pub fn read_signature(&mut self) -> Result<[u8; 64], BinFormatError>{
+        let mut signature = [0; 64];
+        signature.copy_from_slice(self.read_bytes(64)?);
+        Ok(signature)
+    }
+
+    /// Checks that all bytes were read. Returns a
+    /// `BinFormatError` with reason InvalidDataSize
+    /// if there are trailing bytes
+
+    pub fn finish(&self) -> Result<(), BinFormatError>{
+
+        if self.offset != self.bytes.len(){
+            let reason = BinFormatErrorReason::InvalidDataSize;
+            return Err(BinFormatError::new(reason))
+        }
+        Ok(())
+
+    }
+
+}
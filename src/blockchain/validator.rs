@@ -0,0 +1,322 @@
+//
+//    Stachanov
+//    Copyright (C) 2017 Stachanov Developer Collective
+//
+//    This file is part of Stachanov.
+//
+//    This program is free software: you can redistribute it and/or
+//    modify it under the terms of the GNU Affero General Public
+//    License, version 3, as published by the Free Software Foundation.
+//
+//    This program is distributed in the hope that it will be useful,
+//    but WITHOUT ANY WARRANTY; without even the implied warranty of
+//    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//    GNU Affero General Public License for more details.
+//
+//    You should have received a copy of the
+//               GNU Affero General Public License
+//    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+use blockchain::block::Block;
+use blockchain::block::BlockId;
+use blockchain::traits::ChainStorage;
+use blockchain::errors::VerificationError;
+use blockchain::errors::VerificationErrorReason;
+
+/// `ValidationFailure` describes a single problem
+/// found by the `ChainValidator`
+///
+/// * `block_id`: The id of the offending block
+/// * `reason`: What is wrong with the block
+
+#[derive(Debug)]
+pub struct ValidationFailure{
+    pub block_id: BlockId,
+    pub reason: VerificationErrorReason
+}
+
+/// `ChainValidator` verifies a stored chain end to end. It walks
+/// the chain from the genesis block to the tail (using
+/// `get_after`) and checks every block for:
+///
+/// * a valid issuer signature
+/// * a content hash matching the merkle root of the transactions
+/// * valid transaction signatures
+/// * a correct link to the preceding block
+/// * a timestamp greater than the one of the preceding block
+/// * an index following the one of the preceding block
+///
+/// If the tail block can't be reached from the genesis block,
+/// the tail is reported with an invalid chain link.
+///
+/// In contrast to the verification of single blocks, the
+/// validator doesn't stop at the first problem, but reports
+/// every failure it finds.
+
+pub struct ChainValidator<'a, C: 'a>{
+    storage: &'a C
+}
+
+impl<'a, C> ChainValidator<'a, C> where C: ChainStorage{
+
+    /// Creates a new `ChainValidator`
+    ///
+    /// # Arguments
+    /// * `storage`: The storage holding the chain
+
+    pub fn new(storage: &'a C) -> ChainValidator<'a, C>{
+        ChainValidator{storage: storage}
+    }
+
+    /// Validates the whole chain and returns all failures
+    /// in chain order. An empty vector means the chain is
+    /// valid.
+
+    pub fn validate(&self) -> Vec<ValidationFailure>{
+
+        let mut failures = vec![];
+
+        let mut current = self.storage.get_first_block();
+        let mut previous: Option<Block> = None;
+
+        while let Some(block) = current{
+
+            let block_id = block.get_id();
+            let mut report = |result: Result<(), VerificationError>|{
+                if let Err(err) = result{
+                    failures.push(ValidationFailure{block_id: block_id, reason: err.reason});
+                }
+            };
+
+            report(block.get_header_ref().verify_signature());
+            report(block.verify_content_hash());
+            report(block.verify_tx_signatures());
+
+            for reason in link_failures(previous.as_ref(), &block){
+                report(Err(VerificationError::new(reason)));
+            }
+
+            current = self.storage.get_after(block_id);
+            previous = Some(block);
+
+        }
+
+        // the walk must end at the tail, otherwise
+        // the storage lost a link of the chain
+
+        if let Some(tail) = self.storage.get_tail_block(){
+            if previous.map(|block| block.get_id()) != Some(tail.get_id()){
+                let reason = VerificationErrorReason::InvalidChainLink;
+                failures.push(ValidationFailure{block_id: tail.get_id(), reason: reason});
+            }
+        }
+
+        failures
+
+    }
+
+}
+
+/// Checks how a block is linked to its predecessor
+/// (or if it is a valid first block)
+
+fn link_failures(previous: Option<&Block>, block: &Block) -> Vec<VerificationErrorReason>{
+
+    let mut failures = vec![];
+
+    let previous = match previous{
+        Some(previous) => previous,
+        None => {
+            if block.get_index() != 0{
+                failures.push(VerificationErrorReason::InvalidIndex);
+            }
+            return failures
+        }
+    };
+
+    if block.get_previous_id() != Some(previous.get_id()){
+        failures.push(VerificationErrorReason::InvalidChainLink);
+    }
+
+    if block.get_timestamp() <= previous.get_timestamp(){
+        failures.push(VerificationErrorReason::InvalidTimestamp);
+    }
+
+    if block.get_index() != previous.get_index() + 1{
+        failures.push(VerificationErrorReason::InvalidIndex);
+    }
+
+    failures
+
+}
+
+#[test]
+fn test_chain_validator(){
+
+    use blockchain::traits::BinFormat;
+    use blockchain::traits::BlockStorage;
+    use blockchain::transactions::Transaction;
+    use blockchain::transactions::TxPayload;
+    use blockchain::storages::memory::MemoryChainStorage;
+    use blockchain::txtypes::keypair;
+
+    let (secret_key, public_key) = keypair(0x01);
+
+    // an empty chain is valid
+
+    let mut storage = MemoryChainStorage::new();
+    assert!(ChainValidator::new(&storage).validate().is_empty());
+
+    // a correctly signed and linked chain is valid
+
+    let mut blocks: Vec<Block> = vec![];
+    for i in 0..4{
+        let mut block = Block::new(public_key, blocks.last(), i + 1, vec![Transaction::dummy()]);
+        block.sign(&secret_key);
+        storage.append_verified_block(block.clone()).unwrap();
+        blocks.push(block);
+    }
+
+    assert!(ChainValidator::new(&storage).validate().is_empty(),
+            "Valid chain was reported as invalid");
+
+    // append some faulty blocks:
+    //
+    //  * an unsigned block
+    //  * a block with the same timestamp as its predecessor
+    //  * a block with a wrong index
+    //  * a block with a wrong content hash and an unsigned
+    //    transaction
+
+    let unsigned = Block::new(public_key, blocks.last(), 5, vec![]);
+
+    let mut same_timestamp = Block::new(public_key, Some(&unsigned), 5, vec![]);
+    same_timestamp.sign(&secret_key);
+
+    let mut bytes = Block::new(public_key, Some(&same_timestamp), 6, vec![]).as_bytes();
+    bytes[66] = 0xFF;
+    let mut wrong_index = Block::from_bytes(bytes).unwrap();
+    wrong_index.sign(&secret_key);
+
+    let unsigned_tx = Transaction::new(TxPayload::Dummy, vec![public_key]);
+    let mut bytes = Block::new(public_key, Some(&wrong_index), 7, vec![unsigned_tx]).as_bytes();
+    bytes[82] ^= 0xFF;
+    let mut wrong_content = Block::from_bytes(bytes).unwrap();
+    wrong_content.sign(&secret_key);
+
+    let faulty = vec![unsigned, same_timestamp, wrong_index, wrong_content];
+    for block in &faulty{
+        storage.append_verified_block(block.clone()).unwrap();
+    }
+
+    // every failure is reported
+
+    let failures = ChainValidator::new(&storage).validate();
+    let failures: Vec<(BlockId, String)> =
+        failures.into_iter()
+                .map(|failure| (failure.block_id, format!("{:?}", failure.reason)))
+                .collect();
+
+    assert_eq!(failures, vec![
+        (faulty[0].get_id(), String::from("InvalidIssuerSignature")),
+        (faulty[1].get_id(), String::from("InvalidTimestamp")),
+        (faulty[2].get_id(), String::from("InvalidIndex")),
+        (faulty[3].get_id(), String::from("InvalidContentHash")),
+        (faulty[3].get_id(), String::from("InvalidTxSignature"))
+    ]);
+
+}
+
+#[test]
+fn test_chain_validator_missing_link(){
+
+    use blockchain::block::BlockError;
+    use blockchain::header::BlockHeader;
+    use blockchain::traits::BlockStorage;
+    use blockchain::transactions::Transaction;
+    use blockchain::transactions::TxId;
+    use blockchain::transactions::TxState;
+    use blockchain::transactions::TxProgError;
+    use blockchain::storages::memory::MemoryChainStorage;
+    use blockchain::txtypes::keypair;
+
+    // a storage that lost the successor link
+    // of one of its blocks
+
+    struct BrokenStorage{
+        inner: MemoryChainStorage,
+        broken: Option<BlockId>
+    }
+
+    impl BlockStorage for BrokenStorage{
+        fn get_block(&self, block_id: BlockId) -> Option<Block>{
+            self.inner.get_block(block_id)
+        }
+        fn get_header(&self, block_id: BlockId) -> Option<BlockHeader>{
+            self.inner.get_header(block_id)
+        }
+        fn append_verified_block(&mut self, block: Block) -> Result<(), BlockError>{
+            self.inner.append_verified_block(block)
+        }
+        fn get_transaction(&self, tx_id: TxId) -> Option<Transaction>{
+            self.inner.get_transaction(tx_id)
+        }
+        fn reset(&mut self){
+            self.inner.reset()
+        }
+    }
+
+    impl ChainStorage for BrokenStorage{
+        fn get_after(&self, block_id: BlockId) -> Option<Block>{
+            if self.broken == Some(block_id){
+                return None
+            }
+            self.inner.get_after(block_id)
+        }
+        fn get_after_timestamp(&self, timestamp: u64) -> Option<Block>{
+            self.inner.get_after_timestamp(timestamp)
+        }
+        fn get_first_block(&self) -> Option<Block>{
+            self.inner.get_first_block()
+        }
+        fn get_tail_block(&self) -> Option<Block>{
+            self.inner.get_tail_block()
+        }
+        fn get_transaction_state(&self, tx_id: TxId) -> Option<TxState>{
+            self.inner.get_transaction_state(tx_id)
+        }
+        fn set_transaction_state(&mut self, tx_id: TxId, tx_state: TxState) -> Result<(), TxProgError>{
+            self.inner.set_transaction_state(tx_id, tx_state)
+        }
+        fn rollback_to(&mut self, block_id: BlockId) -> Result<(), BlockError>{
+            self.inner.rollback_to(block_id)
+        }
+    }
+
+    let (secret_key, public_key) = keypair(0x01);
+    let mut storage = BrokenStorage{inner: MemoryChainStorage::new(), broken: None};
+
+    let mut blocks: Vec<Block> = vec![];
+    for i in 0..4{
+        let mut block = Block::new(public_key, blocks.last(), i + 1, vec![]);
+        block.sign(&secret_key);
+        storage.append_verified_block(block.clone()).unwrap();
+        blocks.push(block);
+    }
+
+    assert!(ChainValidator::new(&storage).validate().is_empty());
+
+    // the tail can't be reached from the genesis block
+
+    storage.broken = Some(blocks[1].get_id());
+
+    let failures = ChainValidator::new(&storage).validate();
+    assert_eq!(failures.len(), 1);
+    assert_eq!(failures[0].block_id, blocks[3].get_id());
+    match failures[0].reason{
+        VerificationErrorReason::InvalidChainLink => {},
+        ref reason => panic!("Unexpected reason {:?}", reason)
+    }
+
+}